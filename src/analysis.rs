@@ -0,0 +1,118 @@
+use crate::ast::ASTNode;
+use crate::builtin::register_builtins;
+use crate::environment::{Env, ValueType};
+use crate::parsers::parse_error::ParseError;
+use crate::parsers::symbol::Symbol;
+use crate::parsers::Parser;
+use crate::token::{Token, TokenKind};
+use crate::tokenizer::token_spans;
+/// 実行せずに読んだ結果。プレイグラウンド、フォーマッタ、エディタ連携で使う
+pub struct Analysis {
+    // エラーの前までに読めた文
+    pub nodes: Vec<ASTNode>,
+    // 最初の構文エラーか型エラー。そこから先は読まない
+    pub error: Option<ParseError>,
+    pub parser: Parser,
+    // トークンと、その終わりの列 (含まない)
+    pub spans: Vec<(Token, usize)>,
+}
+
+/// ソースを文ごとに読み、構文と型を検査する
+pub fn analyze(source: &str) -> Analysis {
+    let spans = token_spans(source);
+    let tokens = spans.iter().map(|(token, _)| token.clone()).collect();
+    let mut env = Env::new();
+    let builtins = register_builtins(&mut env);
+    let mut parser = Parser::new(tokens, builtins);
+    parser.record_symbols();
+    parser.record_layout();
+    let mut nodes = vec![];
+    let mut error = None;
+    while let Some(result) = parser.parse_next() {
+        match result {
+            Ok(node) => nodes.push(node),
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        }
+    }
+    Analysis {
+        nodes,
+        error,
+        parser,
+        spans,
+    }
+}
+
+impl Analysis {
+    pub fn symbols(&self) -> &[Symbol] {
+        self.parser.symbols()
+    }
+
+    /// 位置にある名前かリテラルの型。行と列は 1 始まり
+    pub fn type_at(&self, line: usize, column: usize) -> Option<ValueType> {
+        let contains = |start: usize, len: usize| (start..start + len).contains(&column);
+        let symbol = self
+            .symbols()
+            .iter()
+            .rev()
+            .find(|s| s.line == line && contains(s.column, s.name.chars().count()));
+        if let Some(symbol) = symbol {
+            return symbol.value_type.clone();
+        }
+        let (token, _) = self
+            .spans
+            .iter()
+            .find(|(token, end)| token.line == line && (token.column..*end).contains(&column))?;
+        match token.kind {
+            TokenKind::Number(_) => Some(ValueType::Number),
+            TokenKind::String(_) => Some(ValueType::String),
+            TokenKind::True | TokenKind::False => Some(ValueType::Bool),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_type_at() {
+        let source = "val name = \"sag\"\nfun twice(n: number): number {\n    return n * 2\n}\nval x = twice(len(name))\n";
+        let analysis = analyze(source);
+        assert!(analysis.error.is_none());
+        assert_eq!(analysis.nodes.len(), 3);
+        assert_eq!(analysis.type_at(1, 5), Some(ValueType::String));
+        assert_eq!(analysis.type_at(3, 12), Some(ValueType::Number));
+        assert_eq!(analysis.type_at(5, 10), Some(ValueType::Number));
+        assert_eq!(analysis.type_at(5, 20), Some(ValueType::String));
+        assert_eq!(analysis.type_at(1, 13), Some(ValueType::String));
+        assert_eq!(analysis.type_at(1, 9), None);
+
+        let analysis = analyze("val x = 1\nval y: string = x\nval z = 2\n");
+        assert_eq!(analysis.error.as_ref().map(|e| e.line), Some(2));
+        assert_eq!(analysis.type_at(1, 5), Some(ValueType::Number));
+    }
+
+    #[test]
+    fn test_invalid_input_becomes_error() {
+        let analysis = analyze("val x = 1\nimpl 1 {\n}\n");
+        assert_eq!(analysis.nodes.len(), 1);
+        assert_eq!(
+            analysis.error.map(|e| e.message),
+            Some("Expected struct name after impl".to_string())
+        );
+
+        let analysis = analyze("val x: foo = 1\n");
+        assert_eq!(
+            analysis.error.map(|e| (e.message, e.line)),
+            Some(("undefined type: \"foo\"".to_string(), 1))
+        );
+
+        for source in ["struct point {\n}\n", "val p = q.x\n", "val x: List< = 1\n", "val x = if (true) { 1 }\n"] {
+            assert!(analyze(source).error.is_some(), "{}", source);
+        }
+    }
+}
@@ -100,6 +100,8 @@ pub enum ASTNode {
     Lambda {
         arguments: Vec<ASTNode>,
         body: Box<ASTNode>,
+        // 本体に出てくる引数以外の名前。作るときに外側のローカル変数から捕捉する
+        captures: Vec<String>,
         line: usize,
         column: usize,
     },
@@ -157,6 +159,7 @@ pub enum ASTNode {
     StructField {
         value_type: ValueType,
         is_public: bool,
+        default_value: Option<Box<ASTNode>>,
         line: usize,
         column: usize,
     },
@@ -179,9 +182,23 @@ pub enum ASTNode {
         line: usize,
         column: usize,
     },
+    Tuple {
+        elements: Vec<ASTNode>,
+        line: usize,
+        column: usize,
+    },
+    // val (a, b) = ... / val Point { x, y } = ...
+    Destructure {
+        pattern: Box<ASTNode>, // Variable, Tuple, StructInstance
+        value: Box<ASTNode>,
+        variable_type: EnvVariableType,
+        line: usize,
+        column: usize,
+    },
     Impl {
         base_struct: Box<ValueType>,
         methods: Vec<ASTNode>,
+        constants: Vec<ASTNode>, // Assign (name: "Struct::NAME")
         line: usize,
         column: usize,
     },
@@ -200,6 +217,14 @@ pub enum ASTNode {
     Import {
         module_name: String,
         symbols: Vec<String>,
+        aliases: Vec<String>,      // symbols と同じ順番の、取り込む側での名前
+        namespace: Option<String>, // import math from math
+        line: usize,
+        column: usize,
+    },
+    ModuleAccess {
+        module: String,
+        member: Box<ASTNode>, // Variable, FunctionCall, StructInstance, ModuleAccess
         line: usize,
         column: usize,
     },
@@ -227,6 +252,12 @@ pub enum ASTNode {
         line: usize,
         column: usize,
     },
+    // expr? : Fail/None なら関数から早期リターンする
+    Try {
+        expr: Box<ASTNode>,
+        line: usize,
+        column: usize,
+    },
     Match {
         expression: Box<ASTNode>,
         cases: Vec<(ASTNode, ASTNode)>,
@@ -260,3 +291,56 @@ pub enum ASTNode {
         column: usize,
     },
 }
+
+impl ASTNode {
+    /// ノードの位置 (line, column)
+    pub fn position(&self) -> (usize, usize) {
+        match self {
+            ASTNode::Literal { line, column, .. }
+            | ASTNode::Variable { line, column, .. }
+            | ASTNode::Block { line, column, .. }
+            | ASTNode::PrefixOp { line, column, .. }
+            | ASTNode::BinaryOp { line, column, .. }
+            | ASTNode::Assign { line, column, .. }
+            | ASTNode::Function { line, column, .. }
+            | ASTNode::Method { line, column, .. }
+            | ASTNode::MethodCall { line, column, .. }
+            | ASTNode::FunctionCall { line, column, .. }
+            | ASTNode::FunctionCallArgs { line, column, .. }
+            | ASTNode::Return { line, column, .. }
+            | ASTNode::Break { line, column, .. }
+            | ASTNode::Continue { line, column, .. }
+            | ASTNode::Lambda { line, column, .. }
+            | ASTNode::LambdaCall { line, column, .. }
+            | ASTNode::Eq { line, column, .. }
+            | ASTNode::Gte { line, column, .. }
+            | ASTNode::Gt { line, column, .. }
+            | ASTNode::Lte { line, column, .. }
+            | ASTNode::Lt { line, column, .. }
+            | ASTNode::If { line, column, .. }
+            | ASTNode::Struct { line, column, .. }
+            | ASTNode::StructField { line, column, .. }
+            | ASTNode::StructFieldAccess { line, column, .. }
+            | ASTNode::StructFieldAssign { line, column, .. }
+            | ASTNode::StructInstance { line, column, .. }
+            | ASTNode::Tuple { line, column, .. }
+            | ASTNode::Destructure { line, column, .. }
+            | ASTNode::Impl { line, column, .. }
+            | ASTNode::CommentBlock { line, column, .. }
+            | ASTNode::For { line, column, .. }
+            | ASTNode::Import { line, column, .. }
+            | ASTNode::ModuleAccess { line, column, .. }
+            | ASTNode::Public { line, column, .. }
+            | ASTNode::OptionSome { line, column, .. }
+            | ASTNode::OptionNone { line, column, .. }
+            | ASTNode::ResultSuccess { line, column, .. }
+            | ASTNode::ResultFailure { line, column, .. }
+            | ASTNode::Try { line, column, .. }
+            | ASTNode::Match { line, column, .. }
+            | ASTNode::DictKeyAccess { line, column, .. }
+            | ASTNode::DictAssign { line, column, .. }
+            | ASTNode::ListIndexAccess { line, column, .. }
+            | ASTNode::ListIndexAssign { line, column, .. } => (*line, *column),
+        }
+    }
+}
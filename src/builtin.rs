@@ -1,125 +1,381 @@
 use crate::environment::Env;
 use crate::environment::ValueType;
+use crate::evals::assertion::ASSERTIONS;
 use crate::value::Value;
 use fraction::Fraction;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
-#[cfg(not(target_arch = "wasm32"))]
-pub fn register_builtins(env: &mut Env) -> HashMap<(String, String), ValueType> {
-    let mut builtins = HashMap::new();
-    env.register_builtin("print".to_string(), |args: Vec<Value>| {
-        for arg in args {
-            print!("{} ", arg);
+/// スクリプトに許す機能。許していない機能の builtin は登録しない
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// print
+    pub io: bool,
+    /// read_file、write_file
+    pub fs: bool,
+    /// now
+    pub clock: bool,
+    /// random
+    pub random: bool,
+    /// env_var
+    pub env: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities::all()
+    }
+}
+
+impl Capabilities {
+    pub fn all() -> Self {
+        Capabilities {
+            io: true,
+            fs: true,
+            clock: true,
+            random: true,
+            env: true,
         }
-        println!();
-        Value::Void
-    });
-    builtins.insert(("global".into(), "print".to_string()), ValueType::Void);
-
-    env.register_builtin("len".to_string(), |args: Vec<Value>| {
-        if args.len() != 1 {
-            panic!("len function takes exactly one argument");
+    }
+
+    pub fn none() -> Self {
+        Capabilities {
+            io: false,
+            fs: false,
+            clock: false,
+            random: false,
+            env: false,
         }
-        match &args[0] {
-            Value::List(l) => Value::Number(l.len().into()),
-            Value::String(s) => Value::Number(s.len().into()),
-            _ => panic!("len function takes a list as an argument"),
+    }
+
+    /// この builtin を呼んでよいか。機能に属さない builtin はいつでも呼べる
+    pub fn allows(&self, builtin: &str) -> bool {
+        match builtin {
+            "print" => self.io,
+            "read_file" | "write_file" => self.fs,
+            "now" => self.clock,
+            "random" => self.random,
+            "env_var" => self.env,
+            _ => true,
         }
-    });
-    builtins.insert(("global".into(), "len".to_string()), ValueType::Number);
-
-    env.register_builtin("range".to_string(), |args: Vec<Value>| {
-        if let [Value::Number(start), Value::Number(end)] = args.as_slice() {
-            Value::List(
-                ((*start.numer().unwrap() as i64)..(*end.numer().unwrap() as i64))
-                    .map(|x| Value::Number(Fraction::from(x)))
-                    .collect(),
-            )
-        } else if let [Value::Number(end)] = args.as_slice() {
-            Value::List(
-                (0..(*end.numer().unwrap() as i64))
-                    .map(|x| Value::Number(Fraction::from(x)))
-                    .collect(),
-            )
-        } else if let [Value::Number(start), Value::Number(end), Value::Number(step)] =
-            args.as_slice()
-        {
-            Value::List(
-                ((*start.numer().unwrap() as i64..*end.numer().unwrap() as i64)
-                    .step_by(*step.numer().unwrap() as usize))
+    }
+}
+
+/// print の出力先。1 回の print につき 1 行を受け取る
+pub type OutputSink = Rc<dyn Fn(&str)>;
+
+thread_local! {
+    // `sag run script.sag -- a b` で渡された引数
+    static SCRIPT_ARGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    // print の出力先。None なら標準出力に書く
+    static OUTPUT_SINK: RefCell<Option<OutputSink>> = const { RefCell::new(None) };
+}
+
+/// f を実行する間だけ print の出力を sink に送る
+pub fn with_output_sink<T>(sink: OutputSink, f: impl FnOnce() -> T) -> T {
+    let previous = OUTPUT_SINK.with(|output| output.borrow_mut().replace(sink));
+    let result = f();
+    OUTPUT_SINK.with(|output| *output.borrow_mut() = previous);
+    result
+}
+
+/// print の 1 行を書く。どのエンジンの print もここを通る
+pub fn write_output(line: &str) {
+    match OUTPUT_SINK.with(|output| output.borrow().clone()) {
+        Some(sink) => sink(line),
+        None => println!("{}", line),
+    }
+}
+
+pub fn set_script_args(args: Vec<String>) {
+    SCRIPT_ARGS.with(|script_args| *script_args.borrow_mut() = args);
+}
+
+/// args() の戻り値。`fun main(args: List<string>)` にも渡す
+pub fn script_args() -> Value {
+    SCRIPT_ARGS.with(|script_args| {
+        Value::List(
+            script_args
+                .borrow()
+                .iter()
+                .map(|arg| Value::String(arg.clone()))
+                .collect(),
+        )
+    })
+}
+
+/// env_var(name) の戻り値。設定されていなければ None
+pub fn env_var(name: &str) -> Value {
+    Value::Option(
+        std::env::var(name)
+            .ok()
+            .map(|value| Box::new(Value::String(value))),
+    )
+}
+
+// 引数を空白区切りで 1 行にする
+fn print(args: Vec<Value>) -> Value {
+    let line = args
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    write_output(&line);
+    Value::Void
+}
+
+fn len(args: Vec<Value>) -> Value {
+    if args.len() != 1 {
+        panic!("len function takes exactly one argument");
+    }
+    match &args[0] {
+        Value::List(l) => Value::Number(l.len().into()),
+        Value::String(s) => Value::Number(s.len().into()),
+        _ => panic!("len function takes a list as an argument"),
+    }
+}
+
+fn range(args: Vec<Value>) -> Value {
+    if let [Value::Number(start), Value::Number(end)] = args.as_slice() {
+        Value::List(
+            ((*start.numer().unwrap() as i64)..(*end.numer().unwrap() as i64))
                 .map(|x| Value::Number(Fraction::from(x)))
                 .collect(),
-            )
-        } else {
-            panic!("range function takes 1, 2 or 3 arguments")
+        )
+    } else if let [Value::Number(end)] = args.as_slice() {
+        Value::List(
+            (0..(*end.numer().unwrap() as i64))
+                .map(|x| Value::Number(Fraction::from(x)))
+                .collect(),
+        )
+    } else if let [Value::Number(start), Value::Number(end), Value::Number(step)] =
+        args.as_slice()
+    {
+        Value::List(
+            ((*start.numer().unwrap() as i64..*end.numer().unwrap() as i64)
+                .step_by(*step.numer().unwrap() as usize))
+            .map(|x| Value::Number(Fraction::from(x)))
+            .collect(),
+        )
+    } else {
+        panic!("range function takes 1, 2 or 3 arguments")
+    }
+}
+
+fn io_result(result: std::io::Result<Value>) -> Value {
+    Value::Result(
+        result
+            .map(Box::new)
+            .map_err(|e| Box::new(Value::String(e.to_string()))),
+    )
+}
+
+pub fn io_result_type(success: ValueType) -> ValueType {
+    ValueType::ResultType {
+        success: Box::new(success),
+        failure: Box::new(ValueType::String),
+    }
+}
+
+fn read_file(args: Vec<Value>) -> Value {
+    match args.as_slice() {
+        [Value::String(path)] => io_result(std::fs::read_to_string(path).map(Value::String)),
+        _ => panic!("read_file function takes a path"),
+    }
+}
+
+fn write_file(args: Vec<Value>) -> Value {
+    match args.as_slice() {
+        [Value::String(path), Value::String(contents)] => {
+            io_result(std::fs::write(path, contents).map(|_| Value::Void))
         }
-    });
-    builtins.insert(("global".into(), "range".to_string()), ValueType::Number);
-    builtins
+        _ => panic!("write_file function takes a path and a string"),
+    }
+}
+
+/// UNIX エポックからのミリ秒
+fn now(_: Vec<Value>) -> Value {
+    Value::Number(Fraction::from(now_millis()))
+}
+
+/// 0 以上 1 未満の数
+fn random(_: Vec<Value>) -> Value {
+    Value::Number(Fraction::new(random_u64() >> 11, 1u64 << 53))
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+// RandomState は OS の乱数で鍵を作るので、外部の crate を使わずに乱数が得られる
+#[cfg(not(target_arch = "wasm32"))]
+fn random_u64() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish()
+}
+
+// wasm では時刻も乱数もブラウザから借りる
 #[cfg(target_arch = "wasm32")]
-pub fn register_builtins(env: &mut Env) -> HashMap<(String, String), ValueType> {
-    use crate::wasm::CONSOLE_OUTPUT;
+#[wasm_bindgen::prelude::wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Date, js_name = now)]
+    fn date_now() -> f64;
+    #[wasm_bindgen(js_namespace = Math, js_name = random)]
+    fn math_random() -> f64;
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now_millis() -> u64 {
+    date_now() as u64
+}
+
+#[cfg(target_arch = "wasm32")]
+fn random_u64() -> u64 {
+    ((math_random() * (1u64 << 53) as f64) as u64) << 11
+}
+
+/// builtin の実装は引数の型が正しい前提で書いているので、呼び出す前に確かめてエラーにする。
+/// 引数の型は検査する関数のときだけ求める
+pub fn check_builtin_arguments<T>(
+    name: &str,
+    args: &[T],
+    value_type: impl Fn(&T) -> ValueType,
+) -> Result<(), String> {
+    let (expected, message): (&[ValueType], &str) = match name {
+        "env_var" => (&[ValueType::String], "env_var() takes a variable name"),
+        "read_file" => (&[ValueType::String], "read_file() takes a path"),
+        "write_file" => (
+            &[ValueType::String, ValueType::String],
+            "write_file() takes a path and a string",
+        ),
+        _ => return Ok(()),
+    };
+    if args.iter().map(value_type).eq(expected.iter().cloned()) {
+        Ok(())
+    } else {
+        Err(message.to_string())
+    }
+}
+
+type Builtin = (&'static str, fn(Vec<Value>) -> Value, ValueType);
+
+fn builtin_table() -> Vec<Builtin> {
+    vec![
+        ("print", print, ValueType::Void),
+        ("len", len, ValueType::Number),
+        ("range", range, ValueType::Number),
+        (
+            "args",
+            |_| script_args(),
+            ValueType::List(Box::new(ValueType::String)),
+        ),
+        (
+            "env_var",
+            |args| match args.as_slice() {
+                [Value::String(name)] => env_var(name),
+                _ => panic!("env_var function takes a variable name"),
+            },
+            ValueType::OptionType(Box::new(ValueType::String)),
+        ),
+        ("read_file", read_file, io_result_type(ValueType::String)),
+        ("write_file", write_file, io_result_type(ValueType::Void)),
+        ("now", now, ValueType::Number),
+        ("random", random, ValueType::Number),
+    ]
+}
+
+/// 名前から builtin の実装を探す。vm は型検査を済ませたコードを実行するのでこれで呼ぶ
+pub fn builtin_function(name: &str) -> Option<fn(Vec<Value>) -> Value> {
+    builtin_table()
+        .into_iter()
+        .find(|(builtin, _, _)| *builtin == name)
+        .map(|(_, function, _)| function)
+}
 
+/// env に許された機能の builtin を登録し、パーサーに渡す型を返す
+pub fn register_builtins(env: &mut Env) -> HashMap<(String, String), ValueType> {
+    let capabilities = env.capabilities();
     let mut builtins = HashMap::new();
-    env.register_builtin("print".to_string(), |args: Vec<Value>| {
-        let output = args
-            .iter()
-            .map(|arg| format!("{}", arg))
-            .collect::<Vec<_>>()
-            .join(" ");
-
-        CONSOLE_OUTPUT.with(|console| {
-            let mut console = console.borrow_mut();
-            if !console.is_empty() {
-                console.push('\n');
-            }
-            console.push_str(&output);
-        });
+    for (name, function, value_type) in builtin_table() {
+        if capabilities.allows(name) {
+            env.register_builtin(name.to_string(), function);
+            builtins.insert(("global".into(), name.to_string()), value_type);
+        }
+    }
+    // アサーションと exit は評価器が直接処理するので、型だけ登録する
+    for name in ASSERTIONS.iter().chain(&["exit"]) {
+        builtins.insert(("global".into(), name.to_string()), ValueType::Void);
+    }
+    builtins
+}
 
-        Value::Void
-    });
-    builtins.insert(("global".into(), "print".to_string()), ValueType::Void);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repl_session::{EngineKind, EvalError, ReplSession};
 
-    env.register_builtin("len".to_string(), |args: Vec<Value>| {
-        if args.len() != 1 {
-            panic!("len function takes exactly one argument");
+    #[test]
+    fn test_fs_clock_and_random() {
+        let path = std::env::temp_dir().join(format!("sag_builtin_{}.txt", std::process::id()));
+        let path = path.to_string_lossy();
+        for engine in [EngineKind::Tree, EngineKind::Rc, EngineKind::Vm] {
+            let mut session = ReplSession::new(engine);
+            let source = format!("write_file(\"{}\", \"hi {}\")\n", path, engine);
+            assert_eq!(
+                session.run(&source).unwrap(),
+                Value::Result(Ok(Box::new(Value::Void)))
+            );
+            assert_eq!(
+                session.run(&format!("read_file(\"{}\")", path)).unwrap(),
+                Value::Result(Ok(Box::new(Value::String(format!("hi {}", engine)))))
+            );
+            assert!(matches!(
+                session.run("read_file(\"/nonexistent/sag\")").unwrap(),
+                Value::Result(Err(_))
+            ));
+            assert_eq!(session.run("now() > 0").unwrap(), Value::Bool(true));
+            assert_eq!(
+                session.run("val r = random()\nr >= 0 && r < 1").unwrap(),
+                Value::Bool(true)
+            );
         }
-        match &args[0] {
-            Value::List(l) => Value::Number(l.len().into()),
-            Value::String(s) => Value::Number(s.len().into()),
-            _ => panic!("len function takes a list as an argument"),
+        let _ = std::fs::remove_file(&*path);
+    }
+
+    #[test]
+    fn test_bad_arguments_are_runtime_errors() {
+        for engine in [EngineKind::Tree, EngineKind::Rc, EngineKind::Vm] {
+            for (source, message) in [
+                ("env_var(1)", "env_var() takes a variable name"),
+                ("read_file(1)", "read_file() takes a path"),
+                ("read_file()", "read_file() takes a path"),
+                ("write_file(\"x\", 1)", "write_file() takes a path and a string"),
+            ] {
+                let mut session = ReplSession::new(engine);
+                match session.run(source) {
+                    Err(EvalError::Runtime(e)) => assert_eq!(e.message, message, "{}", engine),
+                    other => panic!("{}: expected runtime error, got {:?}", engine, other),
+                }
+            }
         }
-    });
-    builtins.insert(("global".into(), "len".to_string()), ValueType::Number);
-
-    env.register_builtin("range".to_string(), |args: Vec<Value>| {
-        if let [Value::Number(start), Value::Number(end)] = args.as_slice() {
-            Value::List(
-                ((*start.numer().unwrap() as i64)..(*end.numer().unwrap() as i64))
-                    .map(|x| Value::Number(Fraction::from(x)))
-                    .collect(),
-            )
-        } else if let [Value::Number(end)] = args.as_slice() {
-            Value::List(
-                (0..(*end.numer().unwrap() as i64))
-                    .map(|x| Value::Number(Fraction::from(x)))
-                    .collect(),
-            )
-        } else if let [Value::Number(start), Value::Number(end), Value::Number(step)] =
-            args.as_slice()
-        {
-            Value::List(
-                ((*start.numer().unwrap() as i64..*end.numer().unwrap() as i64)
-                    .step_by(*step.numer().unwrap() as usize))
-                .map(|x| Value::Number(Fraction::from(x)))
-                .collect(),
-            )
-        } else {
-            panic!("range function takes 1, 2 or 3 arguments")
+    }
+
+    #[test]
+    fn test_print_failure_value() {
+        for engine in [EngineKind::Tree, EngineKind::Rc, EngineKind::Vm] {
+            let output = Rc::new(RefCell::new(String::new()));
+            let sink = output.clone();
+            let sink: OutputSink = Rc::new(move |text| sink.borrow_mut().push_str(text));
+            let mut session = ReplSession::new(engine);
+            let result = with_output_sink(sink, || session.run("print(Fail(\"x\"))"));
+            assert!(matches!(result, Ok(Value::Void)), "{}: {:?}", engine, result);
+            assert_eq!(output.borrow().trim_end(), "Fail(x)", "{}", engine);
         }
-    });
-    builtins.insert(("global".into(), "range".to_string()), ValueType::Number);
-    builtins
+    }
 }
@@ -1,12 +1,25 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs;
+use std::rc::Rc;
 
 use fraction::Fraction;
 
 use crate::ast::ASTNode;
-use crate::builtin::register_builtins;
-use crate::environment::Env;
+use crate::builtin::{
+    builtin_function, check_builtin_arguments, env_var, register_builtins, script_args,
+    write_output, Capabilities,
+};
+use crate::environment::{
+    Captures, Env, EnvVariableType, EnvVariableValueInfo, ValueType, VariableCell,
+};
+use crate::evals::assertion::{call_assertion, is_assertion};
+use crate::evals::option_result_method::call_option_result_method;
+use crate::exit_status::{
+    exit_code_argument, exit_status, ExitError, IO_ERROR, PARSE_ERROR, RUNTIME_ERROR,
+};
 use crate::parsers::Parser as SagParser;
+use crate::sandbox::{Limit, Limits, Sandbox};
 use crate::token::TokenKind;
 use crate::tokenizer::tokenize;
 use crate::value::Value;
@@ -42,6 +55,15 @@ enum Instr {
     Neg,
     Not,
     MakeList(usize),
+    MakeSome,
+    PushNone,
+    MakeSuc,
+    MakeFail,
+    PushLambda(String),
+    CallMethod {
+        name: String,
+        argc: usize,
+    },
     Pop,
     Call {
         name: String,
@@ -99,24 +121,37 @@ impl CompileContext {
     }
 }
 
-pub fn compile_file(input_path: &str, output_path: Option<&str>) -> Result<String, String> {
-    let source = fs::read_to_string(input_path).map_err(|e| e.to_string())?;
+pub fn compile_file(input_path: &str, output_path: Option<&str>) -> Result<String, ExitError> {
+    let source = fs::read_to_string(input_path).map_err(|e| ExitError::io(input_path, e))?;
     let program = compile_source(&source)?;
     let output_path = output_path
         .map(|s| s.to_string())
         .unwrap_or_else(|| format!("{}.sagc", input_path));
-    fs::write(&output_path, serialize_program(&program)).map_err(|e| e.to_string())?;
+    fs::write(&output_path, serialize_program(&program)).map_err(|e| {
+        ExitError::new(
+            IO_ERROR,
+            format!("Error: cannot write {}: {}", output_path, e),
+        )
+    })?;
     Ok(output_path)
 }
 
-pub fn run_compiled_file(path: &str) -> Result<(), String> {
-    let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    let program = parse_program(&source)?;
+/// 実行して終了コードを返す
+pub fn run_compiled_file(path: &str, limits: Limits) -> Result<i32, ExitError> {
+    let source = fs::read_to_string(path).map_err(|e| ExitError::io(path, e))?;
+    let program = parse_program(&source)
+        .map_err(|e| ExitError::new(PARSE_ERROR, format!("Error: {}: {}", path, e)))?;
     let mut vm = Vm::new(program);
-    vm.run()
+    vm.sandbox = Rc::new(Sandbox::new(limits));
+    let result = vm.run();
+    match (vm.exit_code, result) {
+        (Some(code), _) => Ok(code),
+        (None, Ok(value)) => exit_status(Ok(value), &source),
+        (None, Err(e)) => Err(ExitError::new(RUNTIME_ERROR, format!("Error: {}", e))),
+    }
 }
 
-fn compile_source(source: &str) -> Result<Program, String> {
+fn compile_source(source: &str) -> Result<Program, ExitError> {
     let source_owned = source.to_string();
     let tokens = tokenize(&source_owned);
     let mut env = Env::new();
@@ -124,10 +159,12 @@ fn compile_source(source: &str) -> Result<Program, String> {
     let mut parser = SagParser::new(tokens.to_vec(), builtins);
     let asts = parser
         .parse_lines()
-        .map_err(|e| e.message_with_source(source))?;
+        .map_err(|e| ExitError::parse(&e, source))?;
 
+    // コンパイラが対応していない構文も、ソースを受け付けられないという意味で構文エラーとして扱う
+    let compile_error = |e: String| ExitError::new(PARSE_ERROR, format!("Error: {}", e));
     let mut ctx = CompileContext::new();
-    let mut entry = compile_sequence(&asts, &mut ctx)?;
+    let mut entry = compile_sequence(&asts, &mut ctx).map_err(compile_error)?;
     entry.push(Instr::Halt);
     Ok(Program {
         entry,
@@ -312,6 +349,65 @@ fn compile_node(node: &ASTNode, ctx: &mut CompileContext) -> Result<Vec<Instr>,
                 .ok_or_else(|| "continue used outside of loop".to_string())?;
             Ok(vec![Instr::Jump(labels.continue_label.clone())])
         }
+        ASTNode::OptionSome { value, .. } => {
+            let mut code = compile_node(value, ctx)?;
+            code.push(Instr::MakeSome);
+            Ok(code)
+        }
+        ASTNode::OptionNone { .. } => Ok(vec![Instr::PushNone]),
+        ASTNode::ResultSuccess { value, .. } => {
+            let mut code = compile_node(value, ctx)?;
+            code.push(Instr::MakeSuc);
+            Ok(code)
+        }
+        ASTNode::ResultFailure { value, .. } => {
+            let mut code = compile_node(value, ctx)?;
+            code.push(Instr::MakeFail);
+            Ok(code)
+        }
+        ASTNode::Lambda {
+            arguments, body, ..
+        } => {
+            // ラムダは名前付きの関数としてコンパイルし、その参照を積む
+            let name = ctx.fresh_label("lambda");
+            let mut params = Vec::new();
+            for arg in arguments {
+                match arg {
+                    ASTNode::Variable { name, .. } => params.push(name.clone()),
+                    _ => return Err(format!("unsupported lambda parameter: {:?}", arg)),
+                }
+            }
+            let mut body_code = compile_node(body, ctx)?;
+            body_code.push(Instr::Return);
+            ctx.functions.insert(
+                name.clone(),
+                CompiledFunction {
+                    params,
+                    code: body_code,
+                },
+            );
+            Ok(vec![Instr::PushLambda(name)])
+        }
+        ASTNode::MethodCall {
+            method_name,
+            caller,
+            arguments,
+            ..
+        } => {
+            let args = match arguments.as_ref() {
+                ASTNode::FunctionCallArgs { args, .. } => args,
+                _ => return Err("illegal method arguments".into()),
+            };
+            let mut code = compile_node(caller, ctx)?;
+            for arg in args {
+                code.extend(compile_node(arg, ctx)?);
+            }
+            code.push(Instr::CallMethod {
+                name: method_name.clone(),
+                argc: args.len(),
+            });
+            Ok(code)
+        }
         ASTNode::CommentBlock { .. } => Ok(vec![Instr::PushVoid]),
         _ => Err(format!("unsupported node in compiler: {:?}", node)),
     }
@@ -405,6 +501,14 @@ fn serialize_instr(instr: &Instr, out: &mut String) {
         Instr::Neg => out.push_str("NEG\n"),
         Instr::Not => out.push_str("NOT\n"),
         Instr::MakeList(len) => out.push_str(&format!("MAKE_LIST {}\n", len)),
+        Instr::MakeSome => out.push_str("MAKE_SOME\n"),
+        Instr::PushNone => out.push_str("PUSH_NONE\n"),
+        Instr::MakeSuc => out.push_str("MAKE_SUC\n"),
+        Instr::MakeFail => out.push_str("MAKE_FAIL\n"),
+        Instr::PushLambda(name) => out.push_str(&format!("PUSH_LAMBDA {}\n", name)),
+        Instr::CallMethod { name, argc } => {
+            out.push_str(&format!("CALL_METHOD {} {}\n", name, argc))
+        }
         Instr::Pop => out.push_str("POP\n"),
         Instr::Call { name, argc } => out.push_str(&format!("CALL {} {}\n", name, argc)),
         Instr::Jump(label) => out.push_str(&format!("JUMP {}\n", label)),
@@ -504,6 +608,24 @@ fn parse_instr(line: &str) -> Result<Instr, String> {
             rest.parse::<usize>().map_err(|e| e.to_string())?,
         ));
     }
+    if let Some(rest) = line.strip_prefix("PUSH_LAMBDA ") {
+        return Ok(Instr::PushLambda(rest.to_string()));
+    }
+    if let Some(rest) = line.strip_prefix("CALL_METHOD ") {
+        let mut parts = rest.split_whitespace();
+        let name = parts
+            .next()
+            .ok_or_else(|| "missing method name".to_string())?;
+        let argc = parts
+            .next()
+            .ok_or_else(|| "missing method argc".to_string())?
+            .parse::<usize>()
+            .map_err(|e| e.to_string())?;
+        return Ok(Instr::CallMethod {
+            name: name.to_string(),
+            argc,
+        });
+    }
     if let Some(rest) = line.strip_prefix("CALL ") {
         let mut parts = rest.split_whitespace();
         let name = parts
@@ -563,6 +685,10 @@ fn parse_instr(line: &str) -> Result<Instr, String> {
         "XOR" => Ok(Instr::Xor),
         "NEG" => Ok(Instr::Neg),
         "NOT" => Ok(Instr::Not),
+        "MAKE_SOME" => Ok(Instr::MakeSome),
+        "PUSH_NONE" => Ok(Instr::PushNone),
+        "MAKE_SUC" => Ok(Instr::MakeSuc),
+        "MAKE_FAIL" => Ok(Instr::MakeFail),
         "POP" => Ok(Instr::Pop),
         "RETURN" => Ok(Instr::Return),
         "HALT" => Ok(Instr::Halt),
@@ -596,8 +722,9 @@ fn unescape_string(input: &str) -> Result<String, String> {
     Ok(out)
 }
 
+// ローカル変数はセルに入れ、ラムダが捕捉したときに定義元と同じ変数を読み書きする
 struct Frame {
-    scopes: Vec<HashMap<String, Value>>,
+    scopes: Vec<HashMap<String, VariableCell>>,
     loop_states: HashMap<String, (Vec<Value>, usize)>,
     write_globals: bool,
 }
@@ -619,36 +746,51 @@ impl Frame {
         }
     }
 
+    fn cell(&self, name: &str) -> Option<&VariableCell> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
     fn get(&self, name: &str) -> Option<Value> {
-        for scope in self.scopes.iter().rev() {
-            if let Some(value) = scope.get(name) {
-                return Some(value.clone());
-            }
-        }
-        None
+        self.cell(name).map(|cell| cell.borrow().value.clone())
     }
 
-    fn put_new(&mut self, name: &str, value: Value) {
+    fn put_cell(&mut self, name: &str, cell: VariableCell) {
         self.scopes
             .last_mut()
             .expect("frame always has one scope")
-            .insert(name.to_string(), value);
+            .insert(name.to_string(), cell);
+    }
+
+    fn put_new(&mut self, name: &str, value: Value) {
+        // VM は変数の型を検査しないので、セルの型は Any にする
+        let info = EnvVariableValueInfo {
+            value,
+            variable_type: EnvVariableType::Mutable,
+            value_type: ValueType::Any,
+        };
+        self.put_cell(name, Rc::new(RefCell::new(info)));
     }
 
     fn put_existing(&mut self, name: &str, value: Value) -> bool {
-        for scope in self.scopes.iter_mut().rev() {
-            if scope.contains_key(name) {
-                scope.insert(name.to_string(), value);
-                return true;
+        match self.cell(name) {
+            Some(cell) => {
+                cell.borrow_mut().value = value;
+                true
             }
+            None => false,
         }
-        false
     }
 }
 
 struct Vm {
     program: Program,
     globals: HashMap<String, Value>,
+    // exit(code) が呼ばれたときの終了コード
+    exit_code: Option<i32>,
+    sandbox: Rc<Sandbox>,
+    // 実行制限を超えて打ち切ったときの上限
+    limit: Option<Limit>,
+    capabilities: Capabilities,
 }
 
 impl Vm {
@@ -656,14 +798,36 @@ impl Vm {
         Self {
             program,
             globals: HashMap::new(),
+            exit_code: None,
+            sandbox: Rc::new(Sandbox::default()),
+            limit: None,
+            capabilities: Capabilities::all(),
+        }
+    }
+
+    fn exceeded(&mut self, limit: Limit) -> String {
+        self.limit = Some(limit);
+        limit.to_string()
+    }
+
+    // 作った値が大きすぎないか確かめる
+    fn checked(&mut self, value: Value) -> Result<Value, String> {
+        match self.sandbox.check_value(&value) {
+            Ok(()) => Ok(value),
+            Err(limit) => Err(self.exceeded(limit)),
         }
     }
 
-    fn run(&mut self) -> Result<(), String> {
+    // トップレベルを実行し、`fun main()` があればその戻り値を返す
+    fn run(&mut self) -> Result<Value, String> {
         let mut frame = Frame::root();
         let entry = self.program.entry.clone();
         self.execute(&entry, &mut frame)?;
-        Ok(())
+        match self.program.functions.get("main") {
+            Some(main) if main.params.len() == 1 => self.call("main", vec![script_args()]),
+            Some(_) => self.call("main", vec![]),
+            None => Ok(Value::Void),
+        }
     }
 
     fn execute(&mut self, code: &[Instr], frame: &mut Frame) -> Result<Value, String> {
@@ -672,6 +836,9 @@ impl Vm {
         let mut ip = 0usize;
 
         while ip < code.len() {
+            if let Err(limit) = self.sandbox.step() {
+                return Err(self.exceeded(limit));
+            }
             match &code[ip] {
                 Instr::PushNum(n) => stack.push(Value::Number(n.clone())),
                 Instr::PushString(s) => stack.push(Value::String(s.clone())),
@@ -708,7 +875,13 @@ impl Vm {
                 }
                 Instr::Add => {
                     let (left, right) = pop2(&mut stack)?;
-                    stack.push(binary_op(left, right, "add")?);
+                    if let (Value::String(l), Value::String(r)) = (&left, &right)
+                        && let Err(limit) = self.sandbox.check_concat(l, r)
+                    {
+                        return Err(self.exceeded(limit));
+                    }
+                    let value = binary_op(left, right, "add")?;
+                    stack.push(self.checked(value)?);
                 }
                 Instr::Sub => {
                     let (left, right) = pop2(&mut stack)?;
@@ -786,7 +959,50 @@ impl Vm {
                         items.push(stack.pop().ok_or_else(|| "stack underflow".to_string())?);
                     }
                     items.reverse();
-                    stack.push(Value::List(items));
+                    stack.push(self.checked(Value::List(items))?);
+                }
+                Instr::MakeSome => {
+                    let value = stack.pop().ok_or_else(|| "stack underflow".to_string())?;
+                    stack.push(Value::Option(Some(Box::new(value))));
+                }
+                Instr::PushNone => stack.push(Value::Option(None)),
+                Instr::MakeSuc => {
+                    let value = stack.pop().ok_or_else(|| "stack underflow".to_string())?;
+                    stack.push(Value::Result(Ok(Box::new(value))));
+                }
+                Instr::MakeFail => {
+                    let value = stack.pop().ok_or_else(|| "stack underflow".to_string())?;
+                    stack.push(Value::Result(Err(Box::new(value))));
+                }
+                Instr::PushLambda(name) => {
+                    let function = self
+                        .program
+                        .functions
+                        .get(name)
+                        .ok_or_else(|| format!("missing compiled function: {}", name))?;
+                    // 本体が使うローカル変数はセルごと捕捉し、定義元と同じ変数を読み書きする。
+                    // トップレベルの変数は呼び出し時にグローバル変数として読む
+                    let captures = self
+                        .free_variables(name)
+                        .into_iter()
+                        .filter(|var| !(frame.write_globals && self.globals.contains_key(var)))
+                        .filter_map(|var| frame.cell(&var).cloned().map(|cell| (var, cell)))
+                        .collect::<Vec<_>>();
+                    stack.push(compiled_lambda(
+                        name,
+                        &function.params,
+                        Captures::from_cells(captures),
+                    ));
+                }
+                Instr::CallMethod { name, argc } => {
+                    let mut args = Vec::with_capacity(*argc);
+                    for _ in 0..*argc {
+                        args.push(stack.pop().ok_or_else(|| "stack underflow".to_string())?);
+                    }
+                    args.reverse();
+                    let receiver = stack.pop().ok_or_else(|| "stack underflow".to_string())?;
+                    let value = self.call_method(receiver, name, args)?;
+                    stack.push(self.checked(value)?);
                 }
                 Instr::Pop => {
                     let _ = stack.pop();
@@ -797,7 +1013,20 @@ impl Vm {
                         args.push(stack.pop().ok_or_else(|| "stack underflow".to_string())?);
                     }
                     args.reverse();
-                    stack.push(self.call(name, args)?);
+                    // 関数として定義されていなければ、ラムダを入れた変数を呼ぶ
+                    let lambda = if self.program.functions.contains_key(name) {
+                        None
+                    } else {
+                        frame
+                            .get(name)
+                            .or_else(|| self.globals.get(name).cloned())
+                            .filter(|value| compiled_lambda_name(value).is_some())
+                    };
+                    let value = match lambda {
+                        Some(lambda) => self.call_lambda(lambda, args)?,
+                        None => self.call(name, args)?,
+                    };
+                    stack.push(self.checked(value)?);
                 }
                 Instr::Jump(label) => {
                     ip = *labels
@@ -864,6 +1093,14 @@ impl Vm {
     }
 
     fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        // 許されていない機能の builtin は、ほかのエンジンと同じく存在しない関数として扱う
+        let builtin = !self.program.functions.contains_key(name);
+        if builtin && !self.capabilities.allows(name) {
+            return Err(format!("missing compiled function: {}", name));
+        }
+        if builtin {
+            check_builtin_arguments(name, &args, Value::value_type)?;
+        }
         match name {
             "print" => {
                 let output = args
@@ -871,7 +1108,7 @@ impl Vm {
                     .map(|v| format!("{}", v))
                     .collect::<Vec<_>>()
                     .join(" ");
-                println!("{}", output);
+                write_output(&output);
                 Ok(Value::Void)
             }
             "len" => {
@@ -884,26 +1121,228 @@ impl Vm {
                     _ => Err("len() requires list or string".into()),
                 }
             }
-            "range" => builtin_range(args),
-            _ => {
-                let function = self
-                    .program
-                    .functions
-                    .get(name)
-                    .cloned()
-                    .ok_or_else(|| format!("missing compiled function: {}", name))?;
-                if function.params.len() != args.len() {
-                    return Err(format!("argument length mismatch for {}", name));
-                }
-                let mut frame = Frame::local();
-                for (param, arg) in function.params.iter().zip(args) {
-                    frame.put_new(param, arg);
-                }
-                let code = function.code.clone();
-                self.execute(&code, &mut frame)
+            "range" => {
+                let numbers: Vec<Fraction> = args
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        Value::Number(n) => Some(*n),
+                        _ => None,
+                    })
+                    .collect();
+                if let Err(limit) = self.sandbox.check_range(&numbers) {
+                    return Err(self.exceeded(limit));
+                }
+                builtin_range(args)
+            }
+            "args" => Ok(script_args()),
+            "env_var" => match args.as_slice() {
+                [Value::String(name)] => Ok(env_var(name)),
+                _ => Err("env_var() takes a variable name".into()),
+            },
+            "exit" if !self.program.functions.contains_key("exit") => {
+                self.exit_code = exit_code_argument(&args);
+                match self.exit_code {
+                    // エラーとして実行を打ち切り、run_compiled_file で終了コードに戻す
+                    Some(code) => Err(format!("exit({}) called", code)),
+                    None => Err("exit(code?) takes an integer exit code from 0 to 255".into()),
+                }
+            }
+            name if is_assertion(name) && builtin => call_assertion(
+                name,
+                args,
+                &mut |lambda, lambda_args| {
+                    let result = self.call_lambda(lambda, lambda_args);
+                    // exit や実行制限は終了コードや上限を記録してあるので、そのまま伝える
+                    if self.exit_code.is_some() || self.limit.is_some() {
+                        result.map(Ok)
+                    } else {
+                        Ok(result)
+                    }
+                },
+                &|message| message,
+            ),
+            "read_file" | "write_file" | "now" | "random" if builtin => {
+                let function = builtin_function(name).expect("builtin is registered");
+                Ok(function(args))
             }
+            _ => self.call_compiled(name, args, Captures::default()),
         }
     }
+
+    // 捕捉した変数を入れたフレームでコンパイル済みの関数を実行する
+    fn call_compiled(
+        &mut self,
+        name: &str,
+        args: Vec<Value>,
+        captures: Captures,
+    ) -> Result<Value, String> {
+        let function = self
+            .program
+            .functions
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("missing compiled function: {}", name))?;
+        if function.params.len() != args.len() {
+            return Err(format!("argument length mismatch for {}", name));
+        }
+        let mut frame = Frame::local();
+        for (var, cell) in captures.iter() {
+            frame.put_cell(var, cell.clone());
+        }
+        for (param, arg) in function.params.iter().zip(args) {
+            frame.put_new(param, arg);
+        }
+        let code = function.code.clone();
+        let sandbox = self.sandbox.clone();
+        match sandbox.call(|| self.execute(&code, &mut frame)) {
+            Ok(result) => result,
+            Err(limit) => Err(self.exceeded(limit)),
+        }
+    }
+
+    // 関数の本体と、その中で作るラムダの本体が読む変数 (引数を除く)
+    fn free_variables(&self, name: &str) -> Vec<String> {
+        let Some(function) = self.program.functions.get(name) else {
+            return vec![];
+        };
+        let mut names = vec![];
+        for instr in &function.code {
+            let found = match instr {
+                Instr::LoadVar(var) => vec![var.clone()],
+                Instr::PushLambda(inner) => self.free_variables(inner),
+                _ => continue,
+            };
+            for var in found {
+                if !function.params.contains(&var) && !names.contains(&var) {
+                    names.push(var);
+                }
+            }
+        }
+        names
+    }
+
+    fn call_method(
+        &mut self,
+        receiver: Value,
+        name: &str,
+        args: Vec<Value>,
+    ) -> Result<Value, String> {
+        match receiver {
+            Value::Option(_) | Value::Result(_) => call_option_result_method(
+                receiver,
+                name,
+                args,
+                &mut |lambda, lambda_args| self.call_lambda(lambda, lambda_args),
+                &|message| message,
+            ),
+            _ => Err(format!("unsupported method in compiler: {}", name)),
+        }
+    }
+
+    fn call_lambda(&mut self, lambda: Value, args: Vec<Value>) -> Result<Value, String> {
+        let name = compiled_lambda_name(&lambda)
+            .ok_or_else(|| format!("expected a lambda, got {}", lambda))?;
+        let captures = match &lambda {
+            Value::Lambda { captures, .. } => captures.clone(),
+            _ => Captures::default(),
+        };
+        self.call_compiled(&name, args, captures)
+    }
+}
+
+/// REPL で使う VM。入力ごとにコンパイルして同じ VM で実行するので、関数とグローバル変数が残る
+pub struct VmSession {
+    vm: Vm,
+    frame: Frame,
+    // ラムダやラベルの名前が前の入力と重ならないようにする
+    next_label: usize,
+}
+
+impl VmSession {
+    pub fn new() -> Self {
+        Self {
+            vm: Vm::new(Program {
+                entry: vec![],
+                functions: HashMap::new(),
+            }),
+            frame: Frame::root(),
+            next_label: 0,
+        }
+    }
+
+    pub fn eval(&mut self, node: &ASTNode) -> Result<Value, String> {
+        let mut ctx = CompileContext::new();
+        ctx.next_label = self.next_label;
+        let code = compile_node(node, &mut ctx)?;
+        self.next_label = ctx.next_label;
+        self.vm.program.functions.extend(ctx.functions);
+        self.vm.exit_code = None;
+        self.vm.limit = None;
+        self.vm.execute(&code, &mut self.frame)
+    }
+
+    /// 直前の eval で exit(code) が呼ばれていればその終了コード
+    pub fn exit_code(&self) -> Option<i32> {
+        self.vm.exit_code
+    }
+
+    /// 直前の eval が実行制限で打ち切られていればその上限
+    pub fn limit(&self) -> Option<Limit> {
+        self.vm.limit
+    }
+
+    pub fn sandbox(&self) -> &Sandbox {
+        &self.vm.sandbox
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.vm.sandbox = Rc::new(Sandbox::new(limits));
+    }
+
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.vm.capabilities = capabilities;
+    }
+
+    pub fn globals(&self) -> &HashMap<String, Value> {
+        &self.vm.globals
+    }
+}
+
+// コンパイル済みラムダは `\|x| => lambda_N(x)` という形の Value::Lambda で表す
+fn compiled_lambda(name: &str, params: &[String], captures: Captures) -> Value {
+    let arguments = params
+        .iter()
+        .map(|param| ASTNode::Variable {
+            name: param.clone(),
+            value_type: None,
+            line: 0,
+            column: 0,
+        })
+        .collect::<Vec<_>>();
+    Value::Lambda {
+        arguments: arguments.clone(),
+        body: Box::new(ASTNode::FunctionCall {
+            name: name.to_string(),
+            arguments: Box::new(ASTNode::FunctionCallArgs {
+                args: arguments,
+                line: 0,
+                column: 0,
+            }),
+            line: 0,
+            column: 0,
+        }),
+        captures,
+    }
+}
+
+fn compiled_lambda_name(value: &Value) -> Option<String> {
+    match value {
+        Value::Lambda { body, .. } => match body.as_ref() {
+            ASTNode::FunctionCall { name, .. } => Some(name.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
 }
 
 fn collect_labels(code: &[Instr]) -> HashMap<String, usize> {
@@ -1004,3 +1443,89 @@ fn builtin_range(args: Vec<Value>) -> Result<Value, String> {
 
     Ok(Value::List(values))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repl_session::{EngineKind, ReplSession};
+
+    fn run_source(source: &str) -> Result<Value, String> {
+        let program =
+            parse_program(&serialize_program(&compile_source(source).map_err(|e| e.message)?))?;
+        let mut vm = Vm::new(program);
+        let entry = vm.program.entry.clone();
+        vm.execute(&entry, &mut Frame::root())
+    }
+
+    #[test]
+    fn test_option_result_methods_in_vm() {
+        let source = r#"
+fun half(x: number): Option<number> {
+    return if (x % 2 == 0) {
+        Some(x / 2)
+    } else {
+        None
+    }
+}
+half(4).map(\|x: number| => x * 10).unwrap_or(0) + half(3).unwrap_or(1)
+"#;
+        assert_eq!(run_source(source), Ok(Value::Number(Fraction::from(21))));
+        let source = r#"
+val r: Result<number, string> = Fail("bad")
+r.map_err(\|e: string| => e + "!")
+"#;
+        assert_eq!(
+            run_source(source),
+            Ok(Value::Result(Err(Box::new(Value::String("bad!".into())))))
+        );
+    }
+
+    #[test]
+    fn test_lambda_captures_in_vm() {
+        let source = r#"
+fun f(k: number): number {
+    return Some(1).map(\|x: number| => x + k).unwrap()
+}
+fun adder(n: number): number {
+    val add = \|x: number| => Some(x).map(\|y: number| => y + n).unwrap()
+    return add(10)
+}
+val inc = \|x: number| => x + 1
+f(41) + adder(5) + inc(2)
+"#;
+        assert_eq!(run_source(source), Ok(Value::Number(Fraction::from(60))));
+    }
+
+    #[test]
+    fn test_lambda_shares_captured_variables() {
+        let source = r#"
+fun make_counter(): number {
+    val mut count = 0
+    val inc = \|| => {
+        count = count + 1
+        count
+    }
+    inc()
+    inc()
+    inc()
+    return count
+}
+fun failed(): bool {
+    val x = Fail("bad")
+    val f = \|| => { x.is_fail() }
+    return f()
+}
+make_counter()
+"#;
+        for engine in [EngineKind::Tree, EngineKind::Rc, EngineKind::Vm] {
+            let mut session = ReplSession::new(engine);
+            assert_eq!(
+                session.run(source).unwrap(),
+                Value::Number(Fraction::from(3)),
+                "{}",
+                engine
+            );
+            assert_eq!(session.run("failed()").unwrap(), Value::Bool(true), "{}", engine);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use crate::value::Value;
+use fraction::{Fraction, ToPrimitive};
+use std::collections::HashMap;
+
+/// Rust の値を sag の値にする
+pub trait IntoSag {
+    fn into_sag(self) -> Value;
+}
+
+/// sag の値を Rust の値にする。型が合わなければ理由を返す
+pub trait FromSag: Sized {
+    fn from_sag(value: Value) -> Result<Self, String>;
+}
+
+fn mismatch<T>(expected: &str, value: &Value) -> Result<T, String> {
+    Err(format!("expected {}, got {}", expected, value))
+}
+
+impl IntoSag for Value {
+    fn into_sag(self) -> Value {
+        self
+    }
+}
+
+impl FromSag for Value {
+    fn from_sag(value: Value) -> Result<Self, String> {
+        Ok(value)
+    }
+}
+
+impl IntoSag for () {
+    fn into_sag(self) -> Value {
+        Value::Void
+    }
+}
+
+impl FromSag for () {
+    fn from_sag(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Void => Ok(()),
+            value => mismatch("void", &value),
+        }
+    }
+}
+
+impl IntoSag for bool {
+    fn into_sag(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl FromSag for bool {
+    fn from_sag(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Bool(b) => Ok(b),
+            value => mismatch("bool", &value),
+        }
+    }
+}
+
+impl IntoSag for String {
+    fn into_sag(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoSag for &str {
+    fn into_sag(self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl FromSag for String {
+    fn from_sag(value: Value) -> Result<Self, String> {
+        match value {
+            Value::String(s) => Ok(s),
+            value => mismatch("string", &value),
+        }
+    }
+}
+
+impl IntoSag for f64 {
+    fn into_sag(self) -> Value {
+        Value::Number(Fraction::from(self))
+    }
+}
+
+impl FromSag for f64 {
+    fn from_sag(value: Value) -> Result<Self, String> {
+        match &value {
+            Value::Number(n) => n.to_f64().map_or_else(|| mismatch("f64", &value), Ok),
+            _ => mismatch("number", &value),
+        }
+    }
+}
+
+// 整数は分母が 1 の数だけを受け付ける
+macro_rules! integer_conversion {
+    ($($ty:ty => $to:ident),*) => {
+        $(
+            impl IntoSag for $ty {
+                fn into_sag(self) -> Value {
+                    Value::Number(Fraction::from(self))
+                }
+            }
+
+            impl FromSag for $ty {
+                fn from_sag(value: Value) -> Result<Self, String> {
+                    match &value {
+                        Value::Number(n) if n.denom().is_some_and(|denom| *denom == 1) => {
+                            n.$to().map_or_else(|| mismatch(stringify!($ty), &value), Ok)
+                        }
+                        _ => mismatch(concat!("an integer (", stringify!($ty), ")"), &value),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+integer_conversion!(i32 => to_i32, i64 => to_i64, u32 => to_u32, u64 => to_u64, usize => to_usize);
+
+impl<T: IntoSag> IntoSag for Vec<T> {
+    fn into_sag(self) -> Value {
+        Value::List(self.into_iter().map(IntoSag::into_sag).collect())
+    }
+}
+
+impl<T: FromSag> FromSag for Vec<T> {
+    fn from_sag(value: Value) -> Result<Self, String> {
+        match value {
+            Value::List(values) => values.into_iter().map(T::from_sag).collect(),
+            value => mismatch("List", &value),
+        }
+    }
+}
+
+impl<T: IntoSag> IntoSag for Option<T> {
+    fn into_sag(self) -> Value {
+        Value::Option(self.map(|value| Box::new(value.into_sag())))
+    }
+}
+
+impl<T: FromSag> FromSag for Option<T> {
+    fn from_sag(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Option(value) => value.map(|value| T::from_sag(*value)).transpose(),
+            value => mismatch("Option", &value),
+        }
+    }
+}
+
+impl<T: IntoSag, E: IntoSag> IntoSag for Result<T, E> {
+    fn into_sag(self) -> Value {
+        Value::Result(match self {
+            Ok(value) => Ok(Box::new(value.into_sag())),
+            Err(error) => Err(Box::new(error.into_sag())),
+        })
+    }
+}
+
+impl<T: FromSag, E: FromSag> FromSag for Result<T, E> {
+    fn from_sag(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Result(Ok(value)) => Ok(Ok(T::from_sag(*value)?)),
+            Value::Result(Err(error)) => Ok(Err(E::from_sag(*error)?)),
+            value => mismatch("Result", &value),
+        }
+    }
+}
+
+impl<T: IntoSag> IntoSag for HashMap<String, T> {
+    fn into_sag(self) -> Value {
+        Value::Dict(
+            self.into_iter()
+                .map(|(key, value)| (key, value.into_sag()))
+                .collect(),
+        )
+    }
+}
+
+impl<T: FromSag> FromSag for HashMap<String, T> {
+    fn from_sag(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Dict(entries) => entries
+                .into_iter()
+                .map(|(key, value)| Ok((key, T::from_sag(value)?)))
+                .collect(),
+            value => mismatch("Dict", &value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(i64::from_sag(42i64.into_sag()), Ok(42));
+        assert_eq!(f64::from_sag(0.5f64.into_sag()), Ok(0.5));
+        assert_eq!(String::from_sag("hi".into_sag()), Ok("hi".to_string()));
+        assert_eq!(
+            Vec::<Option<bool>>::from_sag(vec![Some(true), None].into_sag()),
+            Ok(vec![Some(true), None])
+        );
+        assert_eq!(
+            Result::<u32, String>::from_sag(Err::<u32, _>("no").into_sag()),
+            Ok(Err("no".to_string()))
+        );
+        let dict = HashMap::from([("a".to_string(), 1i32)]);
+        assert_eq!(
+            HashMap::<String, i32>::from_sag(dict.clone().into_sag()),
+            Ok(dict)
+        );
+        assert!(i64::from_sag(0.5f64.into_sag()).is_err());
+        assert!(bool::from_sag(1i32.into_sag()).is_err());
+    }
+}
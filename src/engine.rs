@@ -0,0 +1,365 @@
+use crate::ast::ASTNode;
+use crate::builtin::{with_output_sink, Capabilities, OutputSink};
+use crate::conversion::FromSag;
+use crate::environment::{HostFunction, ValueType};
+use crate::parsers::parse_error::ParseErrorKind;
+use crate::repl_session::{EngineKind, EvalError, ReplSession, SessionEnv};
+use crate::sandbox::{Limit, Limits};
+use crate::value::Value;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+/// 埋め込み API のエラー。位置は 1 始まりで、分からなければ 0
+#[derive(Debug, Clone, PartialEq)]
+pub enum SagError {
+    Io(String),
+    Parse {
+        message: String,
+        line: usize,
+        column: usize,
+    },
+    Type {
+        message: String,
+        line: usize,
+        column: usize,
+    },
+    Runtime {
+        message: String,
+        line: usize,
+        column: usize,
+    },
+    // 実行制限を超えた
+    Limit {
+        limit: Limit,
+        line: usize,
+        column: usize,
+    },
+    // スクリプトが exit(code) を呼んだ
+    Exit(i32),
+    // FromSag で Rust の型にできなかった
+    Conversion(String),
+}
+
+impl fmt::Display for SagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SagError::Io(message) => write!(f, "io error: {}", message),
+            SagError::Parse {
+                message,
+                line,
+                column,
+            } => write!(f, "parse error at {}:{}: {}", line, column, message),
+            SagError::Type {
+                message,
+                line,
+                column,
+            } => write!(f, "type error at {}:{}: {}", line, column, message),
+            SagError::Runtime {
+                message,
+                line,
+                column,
+            } => write!(f, "runtime error at {}:{}: {}", line, column, message),
+            SagError::Limit {
+                limit,
+                line,
+                column,
+            } => write!(f, "{} at {}:{}", limit, line, column),
+            SagError::Exit(code) => write!(f, "exit({}) called", code),
+            SagError::Conversion(message) => write!(f, "conversion error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SagError {}
+
+impl From<EvalError> for SagError {
+    fn from(error: EvalError) -> Self {
+        match error {
+            EvalError::Parse(e) if e.kind == ParseErrorKind::Type => SagError::Type {
+                message: e.message,
+                line: e.line,
+                column: e.column,
+            },
+            EvalError::Parse(e) => SagError::Parse {
+                message: e.message,
+                line: e.line,
+                column: e.column,
+            },
+            EvalError::Runtime(e) => match (e.exit_code, e.limit) {
+                (Some(code), _) => SagError::Exit(code),
+                (None, Some(limit)) => SagError::Limit {
+                    limit,
+                    line: e.line,
+                    column: e.column,
+                },
+                (None, None) => SagError::Runtime {
+                    message: e.message,
+                    line: e.line,
+                    column: e.column,
+                },
+            },
+        }
+    }
+}
+
+/// Rust から sag を実行するための入口。定義は eval_str や eval_file をまたいで残る
+///
+/// ```
+/// use sag::{Engine, FromSag, IntoSag};
+///
+/// let mut engine = Engine::new();
+/// engine.register_fn("double", |args| {
+///     let n = i64::from_sag(args[0].clone())?;
+///     Ok((n * 2).into_sag())
+/// });
+/// engine.eval_str("fun add(a: number, b: number): number {\n    return double(a) + b\n}\n").unwrap();
+/// let sum: i64 = engine.call_function("add", vec![2.into_sag(), 1.into_sag()]).unwrap();
+/// assert_eq!(sum, 5);
+/// ```
+pub struct Engine {
+    session: ReplSession,
+    output: Option<OutputSink>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine::with_capabilities(Capabilities::all())
+    }
+
+    /// 許す機能を選んでエンジンを作る。許していない builtin はスクリプトから見えない
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
+        Engine {
+            session: ReplSession::with_capabilities(EngineKind::Tree, capabilities),
+            output: None,
+        }
+    }
+
+    /// 実行制限つきのエンジンを作る。ステップ数と時間は eval_str などの呼び出しごとに数える
+    pub fn with_limits(limits: Limits) -> Self {
+        let mut engine = Engine::new();
+        engine.set_limits(limits);
+        engine
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.session.set_limits(limits);
+    }
+
+    /// print の出力を標準出力の代わりに sink に送る。1 回の print につき 1 行を渡す
+    pub fn set_output(&mut self, sink: impl Fn(&str) + 'static) {
+        self.output = Some(Rc::new(sink));
+    }
+
+    // 出力先が設定されていれば、評価の間だけ差し替える
+    fn with_output<T>(&mut self, f: impl FnOnce(&mut ReplSession) -> T) -> T {
+        match self.output.clone() {
+            Some(sink) => with_output_sink(sink, || f(&mut self.session)),
+            None => f(&mut self.session),
+        }
+    }
+
+    /// ソースを評価し、最後の文の値を返す
+    pub fn eval_str(&mut self, source: &str) -> Result<Value, SagError> {
+        Ok(self.with_output(|session| session.run(source))?)
+    }
+
+    /// ファイルを評価する。import はファイルの場所から解決する
+    pub fn eval_file(&mut self, path: impl AsRef<Path>) -> Result<Value, SagError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|e| SagError::Io(format!("cannot read {}: {}", path.display(), e)))?;
+        self.session.set_file_path(path.to_path_buf());
+        self.eval_str(&source)
+    }
+
+    /// 定義済みの関数を呼び、戻り値を Rust の型にする
+    pub fn call_function<T: FromSag>(
+        &mut self,
+        name: &str,
+        args: Vec<Value>,
+    ) -> Result<T, SagError> {
+        let args = args
+            .into_iter()
+            .map(|value| ASTNode::Literal {
+                value,
+                line: 0,
+                column: 0,
+            })
+            .collect();
+        let call = ASTNode::FunctionCall {
+            name: name.to_string(),
+            arguments: Box::new(ASTNode::FunctionCallArgs {
+                args,
+                line: 0,
+                column: 0,
+            }),
+            line: 0,
+            column: 0,
+        };
+        self.session.restart_limits();
+        let value = self
+            .with_output(|session| session.eval_node(call))
+            .map_err(|e| SagError::from(EvalError::Runtime(e)))?;
+        T::from_sag(value).map_err(SagError::Conversion)
+    }
+
+    /// Rust の関数をスクリプトから呼べるようにする。状態はクロージャに持たせ、
+    /// 変更するなら Cell や RefCell を使う。Err はスクリプトの実行時エラーになる。
+    /// 戻り値の型は any として型検査する
+    pub fn register_fn<F>(&mut self, name: &str, function: F)
+    where
+        F: Fn(Vec<Value>) -> Result<Value, String> + 'static,
+    {
+        if let SessionEnv::Tree(env) = &mut self.session.env {
+            env.host_functions
+                .insert(name.to_string(), HostFunction(Rc::new(function)));
+        }
+        self.session.parser.register_function_type(
+            "global".into(),
+            name.to_string(),
+            ValueType::Any,
+        );
+    }
+
+    /// トップレベルの変数を Rust の型で読む
+    pub fn get_global<T: FromSag>(&self, name: &str) -> Result<T, SagError> {
+        let value = self
+            .session
+            .bindings()
+            .into_iter()
+            .find(|binding| binding.name == name)
+            .and_then(|binding| binding.value)
+            .ok_or_else(|| SagError::Conversion(format!("undefined variable: {}", name)))?;
+        T::from_sag(value).map_err(SagError::Conversion)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::IntoSag;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_host_functions_keep_state() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut engine = Engine::new();
+        let captured = log.clone();
+        engine.register_fn("record", move |args| {
+            captured
+                .borrow_mut()
+                .push(String::from_sag(args[0].clone())?);
+            Ok(Value::Void)
+        });
+        engine.register_fn("fail", |_| Err("host failure".to_string()));
+        engine
+            .eval_str("val greeting = \"hi\"\nrecord(greeting)\n")
+            .unwrap();
+        engine.eval_str("record(\"again\")\n").unwrap();
+        assert_eq!(*log.borrow(), vec!["hi".to_string(), "again".to_string()]);
+        assert!(matches!(
+            engine.eval_str("\nfail()\n"),
+            Err(SagError::Runtime { message, line: 2, .. }) if message == "host failure"
+        ));
+        assert_eq!(engine.get_global::<String>("greeting"), Ok("hi".into()));
+    }
+
+    #[test]
+    fn test_call_function_and_errors() {
+        let mut engine = Engine::new();
+        engine
+            .eval_str("fun scale(a: number, k: number): number {\n    return a * k\n}\n")
+            .unwrap();
+        let scaled: i64 = engine
+            .call_function("scale", vec![2.into_sag(), 3.into_sag()])
+            .unwrap();
+        assert_eq!(scaled, 6);
+        let list = engine.eval_str("[1, 2]").unwrap();
+        assert_eq!(Vec::<i64>::from_sag(list), Ok(vec![1, 2]));
+        assert!(matches!(
+            engine.call_function::<String>("scale", vec![1.into_sag(), 1.into_sag()]),
+            Err(SagError::Conversion(_))
+        ));
+        assert!(matches!(
+            engine.eval_str("val = 1"),
+            Err(SagError::Parse { line: 1, .. })
+        ));
+        assert_eq!(engine.eval_str("exit(4)"), Err(SagError::Exit(4)));
+        assert!(matches!(
+            engine.eval_file("/nonexistent.sag"),
+            Err(SagError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_limits_are_counted_per_call() {
+        let mut engine = Engine::with_limits(Limits {
+            max_steps: Some(50),
+            ..Limits::default()
+        });
+        engine
+            .eval_str("fun count(n: number): number {\n    for i in range(n) {\n        i\n    }\n    return n\n}\n")
+            .unwrap();
+        assert!(matches!(
+            engine.call_function::<i64>("count", vec![100.into_sag()]),
+            Err(SagError::Limit {
+                limit: Limit::Steps(50),
+                ..
+            })
+        ));
+        assert_eq!(
+            engine.call_function::<i64>("count", vec![3.into_sag()]),
+            Ok(3)
+        );
+    }
+
+    #[test]
+    fn test_output_sink_captures_print() {
+        let lines = Rc::new(RefCell::new(vec![]));
+        let mut engine = Engine::new();
+        let captured = lines.clone();
+        engine.set_output(move |line| captured.borrow_mut().push(line.to_string()));
+        engine
+            .eval_str(
+                "fun greet(name: string): void {\n    print(\"hello\", name)\n}\nprint(1 + 2)\n",
+            )
+            .unwrap();
+        engine
+            .call_function::<()>("greet", vec!["sag".into_sag()])
+            .unwrap();
+        assert_eq!(
+            *lines.borrow(),
+            vec!["3".to_string(), "hello sag".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_denied_capabilities_are_not_defined() {
+        let capabilities = Capabilities {
+            fs: false,
+            env: false,
+            ..Capabilities::all()
+        };
+        let mut engine = Engine::with_capabilities(capabilities);
+        assert!(matches!(
+            engine.eval_str("read_file(\"/etc/hostname\")"),
+            Err(SagError::Runtime { message, .. }) if message.contains("read_file")
+        ));
+        assert!(engine.eval_str("env_var(\"HOME\")").is_err());
+        assert!(engine.eval_str("now() > 0").is_ok());
+
+        for engine in [EngineKind::Tree, EngineKind::Rc, EngineKind::Vm] {
+            let mut session = ReplSession::with_capabilities(engine, Capabilities::none());
+            assert!(session.run("print(1)").is_err(), "{}", engine);
+            assert!(session.run("len([1, 2])").is_ok(), "{}", engine);
+        }
+    }
+}
@@ -1,24 +1,42 @@
 use crate::ast::ASTNode;
-use crate::builtin::register_builtins;
+use crate::builtin::{register_builtins, Capabilities};
 use crate::evals::evals;
 use crate::evals::runtime_error::RuntimeError;
+use crate::module_graph::ModuleGraph;
+use crate::module_resolver::{module_not_found_message, resolve_module};
 use crate::parsers::Parser;
+use crate::sandbox::{Limits, Sandbox};
 use crate::tokenizer::tokenize;
 use crate::value::Value;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, PartialEq)]
 pub struct Env {
-    variable_map: HashMap<VariableKeyInfo, EnvVariableValueInfo>,
+    variable_map: HashMap<VariableKeyInfo, VariableCell>,
     scope_stack: Vec<String>,
     functions: HashMap<String, FunctionInfo>,
     structs: HashMap<String, Value>,
     builtins: HashMap<String, FunctionInfo>,
+    // 埋め込み側の Rust から登録された関数。sag::Engine が登録する
+    pub(crate) host_functions: HashMap<String, HostFunction>,
     modules: HashMap<String, Env>,
     exported_symbols: HashMap<String, ExportedSymbolType>,
+    // 実行中のファイル。モジュールはこのファイルからの相対パスで探す
+    file_path: Option<PathBuf>,
+    module_graph: ModuleGraph,
+    // 実行制限。関数呼び出し用に複製した環境やモジュールとも共有する
+    sandbox: Rc<Sandbox>,
+    // register_builtins が登録する builtin。モジュールにも同じものを許す
+    capabilities: Capabilities,
+    // 関数やクロージャの呼び出し中に作ったローカル変数。呼び出しが終わったら variable_map から取り除く
+    frame_locals: Vec<VariableKeyInfo>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -26,6 +44,7 @@ pub enum ExportedSymbolType {
     Function,
     Variable,
     Struct,
+    Module,
 }
 
 #[derive(Debug, Clone)]
@@ -45,6 +64,23 @@ impl PartialEq for FunctionInfo {
     }
 }
 
+/// 埋め込み側から登録する関数。builtin と違って状態を持つクロージャでもよく、
+/// エラーは呼び出し位置の RuntimeError になる
+#[derive(Clone)]
+pub struct HostFunction(pub Rc<dyn Fn(Vec<Value>) -> Result<Value, String>>);
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HostFunction")
+    }
+}
+
+impl PartialEq for HostFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct MethodInfo {
     pub arguments: Vec<ASTNode>,
@@ -75,6 +111,7 @@ pub enum ValueType {
     SelfType,
     MutSelfType,
     List(Box<ValueType>),
+    Tuple(Vec<ValueType>),
     Dict(Box<ValueType>),
     Function,
     Lambda,
@@ -114,6 +151,7 @@ impl PartialEq for ValueType {
             (ValueType::SelfType, ValueType::SelfType) => true,
             (ValueType::MutSelfType, ValueType::MutSelfType) => true,
             (ValueType::List(a), ValueType::List(b)) => a == b,
+            (ValueType::Tuple(a), ValueType::Tuple(b)) => a == b,
             (ValueType::Dict(a), ValueType::Dict(b)) => a == b,
             (ValueType::Function, ValueType::Function) => true,
             (ValueType::Lambda, ValueType::Lambda) => true,
@@ -153,6 +191,42 @@ impl PartialEq for ValueType {
 }
 impl Eq for ValueType {}
 
+/// ソースでの型の書き方で表示する
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueType::Any => write!(f, "any"),
+            ValueType::Number => write!(f, "number"),
+            ValueType::String => write!(f, "string"),
+            ValueType::Bool => write!(f, "bool"),
+            ValueType::Void => write!(f, "void"),
+            ValueType::SelfType => write!(f, "self"),
+            ValueType::MutSelfType => write!(f, "mut self"),
+            ValueType::List(element) => write!(f, "List<{}>", element),
+            ValueType::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| element.to_string())
+                    .collect::<Vec<_>>();
+                write!(f, "({})", elements.join(", "))
+            }
+            ValueType::Dict(value) => write!(f, "Dict<{}>", value),
+            ValueType::Function => write!(f, "function"),
+            ValueType::Lambda => write!(f, "lambda"),
+            ValueType::Return => write!(f, "return"),
+            ValueType::Struct { name, .. } | ValueType::StructInstance { name, .. } => {
+                write!(f, "{}", name)
+            }
+            ValueType::StructField { value_type, .. } => write!(f, "{}", value_type),
+            ValueType::Impl { base_struct, .. } => write!(f, "impl {}", base_struct),
+            ValueType::OptionType(value) => write!(f, "Option<{}>", value),
+            ValueType::ResultType { success, failure } => {
+                write!(f, "Result<{}, {}>", success, failure)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct EnvVariableValueInfo {
     pub value: Value,
@@ -160,6 +234,61 @@ pub struct EnvVariableValueInfo {
     pub value_type: ValueType,
 }
 
+/// 変数の実体。クロージャと定義元のスコープで同じセルを共有する
+pub type VariableCell = Rc<RefCell<EnvVariableValueInfo>>;
+
+/// ラムダが生成時に捕捉したローカル変数
+#[derive(Clone, Default)]
+pub struct Captures(HashMap<String, VariableCell>);
+
+impl Captures {
+    pub fn get(&self, name: &str) -> Option<&VariableCell> {
+        self.0.get(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &VariableCell)> {
+        self.0.iter()
+    }
+
+    /// 変数のセルをそのまま捕捉する。VM がフレームのセルを共有するのに使う
+    pub fn from_cells(cells: impl IntoIterator<Item = (String, VariableCell)>) -> Self {
+        Captures(cells.into_iter().collect())
+    }
+}
+
+// 捕捉した値が自分自身を含むことがあるので名前だけを表示する
+impl fmt::Debug for Captures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = self.0.keys().collect::<Vec<_>>();
+        names.sort();
+        f.debug_tuple("Captures").field(&names).finish()
+    }
+}
+
+// 同じ変数を捕捉しているかどうかで比較する
+impl PartialEq for Captures {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self.0.iter().all(|(name, cell)| {
+                other
+                    .0
+                    .get(name)
+                    .is_some_and(|other_cell| Rc::ptr_eq(cell, other_cell))
+            })
+    }
+}
+
+// 関数やクロージャの呼び出しごとに一意なスコープ名を作る
+static FRAME_SCOPE_ID: AtomicUsize = AtomicUsize::new(0);
+
 impl Env {
     pub fn new() -> Self {
         Self {
@@ -168,16 +297,27 @@ impl Env {
             functions: HashMap::new(),
             structs: HashMap::new(),
             builtins: HashMap::new(),
+            host_functions: HashMap::new(),
             modules: HashMap::new(),
             exported_symbols: HashMap::new(),
+            file_path: None,
+            module_graph: ModuleGraph::default(),
+            sandbox: Rc::new(Sandbox::default()),
+            capabilities: Capabilities::all(),
+            frame_locals: vec![],
         }
     }
 
     // アクセサメソッド
-    pub fn get_variable_map(&self) -> &HashMap<VariableKeyInfo, EnvVariableValueInfo> {
+    pub fn get_variable_map(&self) -> &HashMap<VariableKeyInfo, VariableCell> {
         &self.variable_map
     }
 
+    /// 既存のセルをそのまま登録する
+    pub fn insert_cell(&mut self, key: VariableKeyInfo, cell: VariableCell) {
+        self.variable_map.insert(key, cell);
+    }
+
     pub fn get_functions(&self) -> &HashMap<String, FunctionInfo> {
         &self.functions
     }
@@ -202,6 +342,43 @@ impl Env {
         &self.modules
     }
 
+    pub fn get_file_path(&self) -> Option<&PathBuf> {
+        self.file_path.as_ref()
+    }
+
+    pub fn set_file_path(&mut self, file_path: PathBuf) {
+        self.file_path = Some(file_path);
+    }
+
+    pub fn get_module_graph(&self) -> &ModuleGraph {
+        &self.module_graph
+    }
+
+    pub fn set_module_graph(&mut self, module_graph: ModuleGraph) {
+        self.module_graph = module_graph;
+    }
+
+    pub fn sandbox(&self) -> &Rc<Sandbox> {
+        &self.sandbox
+    }
+
+    pub fn set_sandbox(&mut self, sandbox: Rc<Sandbox>) {
+        self.sandbox = sandbox;
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.sandbox = Rc::new(Sandbox::new(limits));
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// register_builtins より前に呼ぶ
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
     pub fn set_scope_stack(&mut self, scope_stack: Vec<String>) {
         self.scope_stack = scope_stack;
     }
@@ -210,41 +387,64 @@ impl Env {
         self.modules.insert(name, env);
     }
 
-    pub fn register_module(
-        &mut self,
-        module_name: &String,
-        module_path: &String,
-    ) -> Result<(), String> {
+    pub fn register_module(&mut self, module_name: &String) -> Result<(), String> {
         if self.modules.contains_key(module_name) {
             // 登録済
             return Ok(());
         }
 
-        let file_content = if !PathBuf::from(module_path).exists() {
-            let module_path = PathBuf::from(format!("./.sag_packages/{}", module_path));
-            if !module_path.exists() {
-                return Err("missing package".to_string());
-            }
-            std::fs::read_to_string(module_path).unwrap()
-        } else {
-            std::fs::read_to_string(module_path).unwrap()
-        };
+        let module_path = resolve_module(module_name, self.file_path.as_deref())
+            .map_err(|tried| module_not_found_message(module_name, &tried))?;
+
+        // 他のファイルが読み込み済みならそれを共有する
+        if let Some(module_env) = self.module_graph.get(&module_path) {
+            self.modules.insert(module_name.to_string(), module_env);
+            return Ok(());
+        }
+
+        let depth = self
+            .module_graph
+            .start_loading(&module_path, self.file_path.as_deref())?;
+        let result = self.load_module(&module_path);
+        self.module_graph.finish_loading(depth);
+        let module_env = result?;
+
+        self.module_graph.insert(&module_path, module_env.clone());
+        self.modules.insert(module_name.to_string(), module_env);
+        Ok(())
+    }
+
+    fn load_module(&mut self, module_path: &PathBuf) -> Result<Env, String> {
+        let file_content = std::fs::read_to_string(module_path)
+            .map_err(|e| format!("cannot read {}: {}", module_path.display(), e))?;
+
+        let mut module_env = Env::new();
+        // モジュールの中の import はモジュール自身の場所から探し、読み込み状態は共有する
+        module_env.set_file_path(module_path.clone());
+        module_env.module_graph = self.module_graph.clone();
+        module_env.sandbox = self.sandbox.clone();
+        module_env.capabilities = self.capabilities;
 
         let tokens = tokenize(&file_content);
-        let builtins = register_builtins(self);
+        let builtins = register_builtins(&mut module_env);
         let mut parser = Parser::new(tokens, builtins);
+        parser.set_file_path(module_path.clone());
         let ast_nodes = parser.parse_lines();
         if let Err(e) = ast_nodes {
             return Err(format!("Error: {:?}", e));
         }
 
-        let mut module_env = Env::new();
         let result = evals(ast_nodes.unwrap(), &mut module_env);
         if let Err(e) = result {
-            return Err(format!("Error: {:?}", e));
+            return Err(format!(
+                "{}:{}:{}: {}",
+                module_path.display(),
+                e.line,
+                e.column,
+                e.message
+            ));
         }
-        self.modules.insert(module_name.to_string(), module_env);
-        Ok(())
+        Ok(module_env)
     }
 
     pub fn get_module(&self, module_name: &String) -> Option<&Env> {
@@ -264,6 +464,9 @@ impl Env {
         } else if let Some(_) = self.get_struct(&name) {
             self.exported_symbols
                 .insert(name, ExportedSymbolType::Struct);
+        } else if self.modules.contains_key(&name) {
+            self.exported_symbols
+                .insert(name, ExportedSymbolType::Module);
         }
     }
 
@@ -289,6 +492,11 @@ impl Env {
         Ok(())
     }
 
+    // REPL で構造体を定義し直すときに、前の定義を消す
+    pub fn remove_struct(&mut self, name: &str) {
+        self.structs.remove(name);
+    }
+
     pub fn get_struct(&self, name: &String) -> Option<&Value> {
         self.structs.get(name)
     }
@@ -338,9 +546,20 @@ impl Env {
         self.builtins.get(name)
     }
 
+    pub fn get_host_function(&self, name: &str) -> Option<&HostFunction> {
+        self.host_functions.get(name)
+    }
+
     pub fn enter_scope(&mut self, scope: String) {
         self.scope_stack.push(scope);
     }
+    /// for や match のブロックのスコープに入る。呼び出し元と呼び出し先にある同じ名前のブロックが
+    /// 変数を共有しないように、今のスコープの名前をつなげる
+    pub fn enter_block_scope(&mut self, scope: &str) {
+        let scope = format!("{}/{}", self.get_current_scope(), scope);
+        self.scope_stack.push(scope);
+    }
+
     pub fn leave_scope(&mut self) {
         if self.scope_stack.len() == 1 && self.scope_stack[0] == "global".to_string() {
             return;
@@ -364,6 +583,73 @@ impl Env {
         self.functions.get(name)
     }
 
+    /// names のうち、現在のスコープから見えるローカル変数のセルを捕捉する。
+    /// グローバル変数は呼び出し時にそのまま参照するので捕捉しない
+    pub fn capture(&self, names: &[String]) -> Captures {
+        let mut captures = HashMap::new();
+        for name in names {
+            let cell = self
+                .scope_stack
+                .iter()
+                .rev()
+                .filter(|scope| *scope != "global")
+                .find_map(|scope| self.get_with_scope(name.clone(), scope.clone()));
+            if let Some(cell) = cell {
+                captures.insert(name.clone(), cell.clone());
+            }
+        }
+        Captures(captures)
+    }
+
+    /// クロージャ本体を評価するためのスコープを作る。
+    /// 呼び出し元のローカル変数は見えず、捕捉した変数とグローバル変数だけが見える
+    pub fn with_closure_scope<T>(
+        &mut self,
+        captures: &Captures,
+        f: impl FnOnce(&mut Env) -> T,
+    ) -> T {
+        self.with_frame("closure", |env| {
+            let scope = env.get_current_scope();
+            for (name, cell) in &captures.0 {
+                env.insert_local(
+                    VariableKeyInfo {
+                        name: name.clone(),
+                        scope: scope.clone(),
+                    },
+                    cell.clone(),
+                );
+            }
+            f(env)
+        })
+    }
+
+    /// 関数本体を評価するためのスコープを作る。環境は複製せず、呼び出し元のローカル変数は見えない。
+    /// 呼び出し中に作ったローカル変数は、終わったら取り除く
+    pub fn with_frame<T>(&mut self, name: &str, f: impl FnOnce(&mut Env) -> T) -> T {
+        let scope = format!(
+            "{}#{}",
+            name,
+            FRAME_SCOPE_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let locals = self.frame_locals.len();
+        let caller_scopes =
+            std::mem::replace(&mut self.scope_stack, vec!["global".to_string(), scope]);
+        let result = f(self);
+        self.scope_stack = caller_scopes;
+        for key in self.frame_locals.split_off(locals) {
+            self.variable_map.remove(&key);
+        }
+        result
+    }
+
+    // 変数のセルを入れる。新しいローカル変数は、呼び出しが終わったときに取り除けるように覚えておく
+    fn insert_local(&mut self, key: VariableKeyInfo, cell: VariableCell) {
+        let is_local = key.scope != "global";
+        if self.variable_map.insert(key.clone(), cell).is_none() && is_local {
+            self.frame_locals.push(key);
+        }
+    }
+
     pub fn update_global_env(&mut self, local_env: &Self) {
         for (local_key, local_value) in &local_env.variable_map {
             if local_key.scope == "global" && self.variable_map.contains_key(local_key) {
@@ -386,103 +672,69 @@ impl Env {
             None => return Err("Missing scope".into()),
         };
 
-        // 新規の場合はそのまま書き込み
+        let value_info = EnvVariableValueInfo {
+            value,
+            variable_type,
+            value_type,
+        };
+
+        // 新規の場合は新しいセルを作る
         if is_new {
-            self.variable_map.insert(
+            self.insert_local(
                 VariableKeyInfo {
                     name: name.clone(),
                     scope: latest_scope,
                 },
-                EnvVariableValueInfo {
-                    value,
-                    variable_type,
-                    value_type,
-                },
-            );
-            return Ok(());
-        }
-
-        // ローカルスコープの変数をチェックと存在したら更新
-        if let Some(value_info) = self.get_with_scope(name.clone(), latest_scope.clone()) {
-            if value_info.variable_type == EnvVariableType::Immutable {
-                return Err("Cannot reassign to immutable variable".into());
-            }
-            self.variable_map.insert(
-                VariableKeyInfo {
-                    name,
-                    scope: latest_scope,
-                },
-                EnvVariableValueInfo {
-                    value,
-                    variable_type,
-                    value_type,
-                },
+                Rc::new(RefCell::new(value_info)),
             );
             return Ok(());
         }
 
-        // グローバルスコープの変数をチェックと存在したら更新
-        if let Some(value_info) = self.get_with_scope(name.clone(), "global".to_string()) {
-            if value_info.variable_type == EnvVariableType::Immutable {
-                return Err("Cannot reassign to immutable variable".into());
+        // ローカルスコープ、グローバルスコープの順に探して既存のセルを書き換える。
+        // セルを共有しているクロージャからも更新後の値が見える
+        for scope in [latest_scope.clone(), "global".to_string()] {
+            if let Some(cell) = self.get_with_scope(name.clone(), scope) {
+                if cell.borrow().variable_type == EnvVariableType::Immutable {
+                    return Err("Cannot reassign to immutable variable".into());
+                }
+                *cell.borrow_mut() = value_info;
+                return Ok(());
             }
-            // グローバル変数を更新
-            self.variable_map.insert(
-                VariableKeyInfo {
-                    name,
-                    scope: "global".to_string(),
-                },
-                EnvVariableValueInfo {
-                    value,
-                    variable_type,
-                    value_type,
-                },
-            );
-            return Ok(());
         }
 
         // どこにも存在しないので新しい変数としてローカルスコープに追加
-        self.variable_map.insert(
+        self.insert_local(
             VariableKeyInfo {
                 name,
                 scope: latest_scope,
             },
-            EnvVariableValueInfo {
-                value,
-                variable_type,
-                value_type,
-            },
+            Rc::new(RefCell::new(value_info)),
         );
 
         Ok(())
     }
 
-    fn get_with_scope(&self, name: String, scope: String) -> Option<&EnvVariableValueInfo> {
-        if let Some(variable_key_info) = self.variable_map.get(&VariableKeyInfo {
-            name: name.to_string(),
-            scope: scope.clone(),
-        }) {
-            return Some(variable_key_info);
-        }
-        None
+    fn get_with_scope(&self, name: String, scope: String) -> Option<&VariableCell> {
+        self.variable_map.get(&VariableKeyInfo { name, scope })
     }
 
     pub fn get(
         &self,
         name: &String,
         value_type: Option<&ValueType>,
-    ) -> Option<&EnvVariableValueInfo> {
+    ) -> Option<EnvVariableValueInfo> {
         for scope in self.scope_stack.iter().rev() {
-            if let Some(variable_key_info) = self.variable_map.get(&VariableKeyInfo {
+            if let Some(cell) = self.variable_map.get(&VariableKeyInfo {
                 name: name.to_string(),
                 scope: scope.clone(),
             }) {
+                let variable_key_info = cell.borrow();
                 if value_type.is_some() {
                     if variable_key_info.value_type != *value_type.unwrap() {
                         continue;
                     }
                 }
-                return Some(variable_key_info);
+                return Some(variable_key_info.clone());
             }
         }
         None
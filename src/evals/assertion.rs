@@ -0,0 +1,116 @@
+use crate::value::Value;
+
+pub const ASSERTIONS: [&str; 3] = ["assert", "assert_eq", "assert_fail"];
+
+pub fn is_assertion(name: &str) -> bool {
+    ASSERTIONS.contains(&name)
+}
+
+/// `sag test` で使うアサーション。失敗はエンジンのエラーとして返すので、呼び出し位置が報告される。
+/// assert_fail はラムダを呼ぶので、呼び出し方を `call` で受け取る。
+/// `call` の外側の Err は exit や実行制限のように assert_fail でも止めずに伝えるエラー
+pub fn call_assertion<E>(
+    name: &str,
+    args: Vec<Value>,
+    call: &mut dyn FnMut(Value, Vec<Value>) -> Result<Result<Value, E>, E>,
+    error: &dyn Fn(String) -> E,
+) -> Result<Value, E> {
+    // 最後の引数はメッセージとして省略できる
+    let message = |index: usize| match args.get(index) {
+        Some(Value::String(message)) => format!(": {}", message),
+        Some(value) => format!(": {}", value),
+        None => String::new(),
+    };
+    match (name, args.as_slice()) {
+        ("assert", [Value::Bool(condition), ..]) if args.len() <= 2 => {
+            if *condition {
+                Ok(Value::Void)
+            } else {
+                Err(error(format!("assertion failed{}", message(1))))
+            }
+        }
+        ("assert", [value, ..]) if args.len() <= 2 => Err(error(format!(
+            "assert requires a bool condition, got {}",
+            value
+        ))),
+        ("assert_eq", [left, right, ..]) if args.len() <= 3 => {
+            if left == right {
+                Ok(Value::Void)
+            } else {
+                Err(error(format!(
+                    "assert_eq failed{}: {} != {}",
+                    message(2),
+                    left,
+                    right
+                )))
+            }
+        }
+        ("assert_fail", [lambda, ..]) if args.len() <= 2 => match call(lambda.clone(), vec![])? {
+            Err(_) | Ok(Value::Result(Err(_))) => Ok(Value::Void),
+            Ok(value) => Err(error(format!(
+                "assert_fail failed{}: expected an error, got {}",
+                message(1),
+                value
+            ))),
+        },
+        ("assert", _) => Err(error(
+            "assert(condition, message?) takes 1 or 2 arguments".into(),
+        )),
+        ("assert_eq", _) => Err(error(
+            "assert_eq(left, right, message?) takes 2 or 3 arguments".into(),
+        )),
+        ("assert_fail", _) => Err(error(
+            "assert_fail(lambda, message?) takes 1 or 2 arguments".into(),
+        )),
+        _ => Err(error(format!("{} is not an assertion", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assertion(name: &str, args: Vec<Value>) -> Result<Value, String> {
+        call_assertion(name, args, &mut |lambda, _| Ok(Ok(lambda)), &|message| message)
+    }
+
+    #[test]
+    fn test_assertion_messages() {
+        let number = |n: i64| Value::Number(n.into());
+        assert_eq!(
+            assertion("assert", vec![Value::Bool(true)]),
+            Ok(Value::Void)
+        );
+        assert_eq!(
+            assertion(
+                "assert",
+                vec![Value::Bool(false), Value::String("positive".into())]
+            ),
+            Err("assertion failed: positive".to_string())
+        );
+        assert_eq!(
+            assertion("assert_eq", vec![number(1), number(2)]),
+            Err("assert_eq failed: 1 != 2".to_string())
+        );
+        assert_eq!(
+            assertion("assert_fail", vec![Value::Result(Err(Box::new(number(0))))]),
+            Ok(Value::Void)
+        );
+        assert!(assertion("assert_fail", vec![number(1)]).is_err());
+        assert!(assertion("assert_eq", vec![number(1)]).is_err());
+    }
+
+    #[test]
+    fn test_assert_fail_passes_through_escaping_errors() {
+        let mut call = |_, _| Err("exit(2) called".to_string());
+        assert_eq!(
+            call_assertion("assert_fail", vec![Value::Void], &mut call, &|message| message),
+            Err("exit(2) called".to_string())
+        );
+        let mut call = |_, _| Ok(Err("index out of range".to_string()));
+        assert_eq!(
+            call_assertion("assert_fail", vec![Value::Void], &mut call, &|message| message),
+            Ok(Value::Void)
+        );
+    }
+}
@@ -6,7 +6,7 @@ use crate::value::Value;
 
 pub fn assign_node(
     name: String,
-    value: Box<ASTNode>,
+    value: ASTNode,
     value_type: ValueType,
     variable_type: EnvVariableType,
     is_new: bool,
@@ -14,7 +14,7 @@ pub fn assign_node(
     column: usize,
     env: &mut Env,
 ) -> Result<Value, RuntimeError> {
-    let value = eval(*value, env)?;
+    let value = eval(value, env)?;
     //let value_type = match value {
     //    Value::Number(_) => ValueType::Number,
     //    Value::String(_) => ValueType::String,
@@ -7,14 +7,19 @@ use crate::value::Value;
 
 pub fn binary_op(
     op: TokenKind,
-    left: Box<ASTNode>,
-    right: Box<ASTNode>,
+    left: ASTNode,
+    right: ASTNode,
     line: usize,
     column: usize,
     env: &mut Env,
 ) -> Result<Value, RuntimeError> {
-    let left_val = eval(*left, env)?;
-    let right_val = eval(*right, env)?;
+    let left_val = eval(left, env)?;
+    let right_val = eval(right, env)?;
+    if let (Value::String(l), Value::String(r), TokenKind::Plus) = (&left_val, &right_val, &op) {
+        env.sandbox()
+            .check_concat(l, r)
+            .map_err(|limit| RuntimeError::limit(limit, line, column))?;
+    }
 
     match (&left_val, &right_val, &op) {
         (Value::String(l), Value::String(r), TokenKind::Plus) => {
@@ -7,14 +7,14 @@ use crate::value::Value;
 
 pub fn comparison_op_node(
     op: TokenKind,
-    left: Box<ASTNode>,
-    right: Box<ASTNode>,
+    left: ASTNode,
+    right: ASTNode,
     line: usize,
     column: usize,
     env: &mut Env,
 ) -> Result<Value, RuntimeError> {
-    let left_value = eval(*left, env)?;
-    let right_value = eval(*right, env)?;
+    let left_value = eval(left, env)?;
+    let right_value = eval(right, env)?;
     match (left_value, right_value, op) {
         (Value::Number(l), Value::Number(r), TokenKind::Eq) => Ok(Value::Bool(l == r)),
         (Value::Number(l), Value::Number(r), TokenKind::Neq) => Ok(Value::Bool(l != r)),
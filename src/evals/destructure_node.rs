@@ -0,0 +1,231 @@
+use crate::ast::ASTNode;
+use crate::environment::{Env, EnvVariableType, ValueType};
+use crate::evals::eval;
+use crate::evals::runtime_error::RuntimeError;
+use crate::value::Value;
+
+pub fn tuple_node(
+    elements: Vec<ASTNode>,
+    _line: usize,
+    _column: usize,
+    env: &mut Env,
+) -> Result<Value, RuntimeError> {
+    let mut values = vec![];
+    for element in elements {
+        values.push(eval(element, env)?);
+    }
+    Ok(Value::Tuple(values))
+}
+
+pub fn destructure_node(
+    pattern: Box<ASTNode>,
+    value: ASTNode,
+    variable_type: EnvVariableType,
+    line: usize,
+    column: usize,
+    env: &mut Env,
+) -> Result<Value, RuntimeError> {
+    let value = eval(value, env)?;
+    bind_pattern(&pattern, value.clone(), &variable_type, line, column, env)?;
+    Ok(value)
+}
+
+/// 関数・ラムダの引数を束縛する。通常の引数に加えて分割代入のパターンも受け付ける
+pub fn bind_argument(
+    param: &ASTNode,
+    value: Value,
+    line: usize,
+    column: usize,
+    env: &mut Env,
+) -> Result<(), RuntimeError> {
+    match param {
+        ASTNode::Variable {
+            name, value_type, ..
+        } => {
+            let _ = env.set(
+                name.to_string(),
+                value,
+                EnvVariableType::Immutable,
+                value_type.clone().unwrap_or(ValueType::Any),
+                true,
+            );
+            Ok(())
+        }
+        _ => bind_pattern(param, value, &EnvVariableType::Immutable, line, column, env),
+    }
+}
+
+/// パターンに従って値を分解し、現在のスコープに変数として登録する
+pub fn bind_pattern(
+    pattern: &ASTNode,
+    value: Value,
+    variable_type: &EnvVariableType,
+    line: usize,
+    column: usize,
+    env: &mut Env,
+) -> Result<(), RuntimeError> {
+    match pattern {
+        ASTNode::Variable { name, .. } => {
+            if name == "_" {
+                return Ok(());
+            }
+            let value_type = value.value_type();
+            env.set(
+                name.to_string(),
+                value,
+                variable_type.clone(),
+                value_type,
+                true,
+            )
+            .map_err(|e| RuntimeError::new(e.as_str(), line, column))
+        }
+        ASTNode::Tuple { elements, .. } => {
+            let values = match value {
+                Value::Tuple(values) | Value::List(values) => values,
+                _ => {
+                    return Err(RuntimeError::new(
+                        format!("cannot destructure {} as a tuple", value).as_str(),
+                        line,
+                        column,
+                    ))
+                }
+            };
+            if values.len() != elements.len() {
+                return Err(RuntimeError::new(
+                    format!(
+                        "tuple pattern expects {} elements, got {}",
+                        elements.len(),
+                        values.len()
+                    )
+                    .as_str(),
+                    line,
+                    column,
+                ));
+            }
+            for (element, value) in elements.iter().zip(values) {
+                bind_pattern(element, value, variable_type, line, column, env)?;
+            }
+            Ok(())
+        }
+        ASTNode::StructInstance {
+            name: pattern_name,
+            fields: pattern_fields,
+            ..
+        } => {
+            let fields = match value {
+                Value::StructInstance {
+                    ref name,
+                    ref fields,
+                } if name == pattern_name => fields.clone(),
+                _ => {
+                    return Err(RuntimeError::new(
+                        format!("cannot destructure {} as {}", value, pattern_name).as_str(),
+                        line,
+                        column,
+                    ))
+                }
+            };
+            for (field_name, field_pattern) in pattern_fields {
+                let field_value = fields.get(field_name).cloned().ok_or_else(|| {
+                    RuntimeError::new(
+                        format!("Field not found: {:?}", field_name).as_str(),
+                        line,
+                        column,
+                    )
+                })?;
+                bind_pattern(field_pattern, field_value, variable_type, line, column, env)?;
+            }
+            Ok(())
+        }
+        _ => Err(RuntimeError::new(
+            format!("invalid destructuring pattern: {:?}", pattern).as_str(),
+            line,
+            column,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evals::run_source;
+    use fraction::Fraction;
+
+    #[test]
+    fn test_tuple_literal() {
+        let result = run_source("(1, \"a\", true)").unwrap();
+        assert_eq!(
+            result.last(),
+            Some(&Value::Tuple(vec![
+                Value::Number(Fraction::from(1)),
+                Value::String("a".into()),
+                Value::Bool(true),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_destructure_tuple() {
+        let input = r#"
+        fun divmod(a: number, b: number): (number, number) {
+            return ((a - a % b) / b, a % b)
+        }
+        val (q, r) = divmod(7, 2)
+        q * 10 + r
+        "#;
+        let result = run_source(input).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(31))));
+    }
+
+    #[test]
+    fn test_destructure_nested_tuple_and_wildcard() {
+        let input = r#"
+        val (a, (_, c)) = (1, (2, 3))
+        a + c
+        "#;
+        let result = run_source(input).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(4))));
+    }
+
+    #[test]
+    fn test_destructure_struct() {
+        let input = r#"
+        struct Point {
+            x: number,
+            y: number
+        }
+        val p = Point{x: 3, y: 4}
+        val Point { x, y: height } = p
+        x + height
+        "#;
+        let result = run_source(input).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(7))));
+    }
+
+    #[test]
+    fn test_destructure_for_and_parameters() {
+        let input = r#"
+        fun add((a, b): (number, number)): number {
+            return a + b
+        }
+        val mut pairs = []
+        pairs.push((1, 2))
+        pairs.push((3, 4))
+        val mut total = 0
+        for (k, v) in pairs {
+            total = total + add((k, v))
+        }
+        val swap = \|(a, b): (number, number)| => (b, a)
+        val (first, _) = swap((5, 6))
+        total + first
+        "#;
+        let result = run_source(input).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(16))));
+    }
+
+    #[test]
+    fn test_destructure_length_mismatch() {
+        let result = run_source("val (a, b) = (1, 2, 3)");
+        assert!(result.is_err());
+    }
+}
@@ -6,18 +6,18 @@ use crate::value::Value;
 
 pub fn for_node(
     variable: String,
-    iterable: Box<ASTNode>,
+    iterable: ASTNode,
     body: Box<ASTNode>,
     line: usize,
     column: usize,
     env: &mut Env,
 ) -> Result<Value, RuntimeError> {
-    let iterable = eval(*iterable, env)?;
+    let iterable = eval(iterable, env)?;
     match iterable {
         Value::List(values) => {
             let scope_name = format!("for-{}", variable.clone());
             for value in values {
-                env.enter_scope(scope_name.clone());
+                env.enter_block_scope(&scope_name);
                 let _ = env.set(
                     variable.clone(),
                     value.clone(),
@@ -138,4 +138,22 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_for_tuple_list_literal() {
+        let input = r#"
+        val mut sum = 0
+        for (a, b) in [(1, 2), (3, -4)] {
+            sum = sum + a * b
+        }
+        sum
+        "#;
+        let tokens = tokenize(&input.to_string());
+        let builtin = register_builtins(&mut Env::new());
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        let mut env = Env::new();
+        register_builtins(&mut env);
+        let result = evals(asts, &mut env).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(-10))));
+    }
 }
@@ -1,13 +1,19 @@
 use crate::ast::ASTNode;
-use crate::environment::{Env, EnvVariableType, FunctionInfo, ValueType};
+use crate::builtin::{check_builtin_arguments, script_args};
+use crate::environment::{Env, FunctionInfo, HostFunction, ValueType};
+use crate::evals::assertion::{call_assertion, is_assertion};
+use crate::evals::destructure_node::bind_argument;
 use crate::evals::eval;
+use crate::evals::lambda_node::call_lambda_value;
 use crate::evals::runtime_error::RuntimeError;
+use crate::exit_status::exit_error;
 use crate::value::Value;
+use fraction::Fraction;
 
 pub fn function_node(
     name: String,
     arguments: Vec<ASTNode>,
-    body: Box<ASTNode>,
+    body: ASTNode,
     return_type: ValueType,
     _line: usize,
     _column: usize,
@@ -15,7 +21,7 @@ pub fn function_node(
 ) -> Result<Value, RuntimeError> {
     let function_info = FunctionInfo {
         arguments,
-        body: Some(*body),
+        body: Some(body),
         return_type,
         builtin: None,
     };
@@ -23,6 +29,58 @@ pub fn function_node(
     Ok(Value::Function)
 }
 
+/// 関数本体を評価し、`return` と `?` による早期リターンを戻り値に変換する。
+/// line と column は呼び出し位置で、呼び出しが深すぎるときのエラーに使う
+pub fn eval_function_body(
+    body: ASTNode,
+    line: usize,
+    column: usize,
+    env: &mut Env,
+) -> Result<Value, RuntimeError> {
+    let sandbox = env.sandbox().clone();
+    let result = sandbox
+        .call(|| eval(body, env))
+        .map_err(|limit| RuntimeError::limit(limit, line, column))?;
+    match result {
+        Ok(Value::Return(value)) => Ok(*value),
+        Err(RuntimeError {
+            early_return: Some(value),
+            ..
+        }) => Ok(*value),
+        result => result,
+    }
+}
+
+/// `fun main()` が定義されていれば呼び出して戻り値を返す。なければ Void
+#[allow(dead_code)]
+pub fn call_main(env: &mut Env) -> Result<Value, RuntimeError> {
+    let name = "main".to_string();
+    let parameters = match env.get_function(&name) {
+        Some(function) => function.arguments.len(),
+        None => return Ok(Value::Void),
+    };
+    function_call_node(name, Box::new(main_arguments(parameters)), 0, 0, env)
+}
+
+/// `fun main(args: List<string>)` ならコマンドラインの引数を渡す
+#[allow(dead_code)]
+pub fn main_arguments(parameters: usize) -> ASTNode {
+    let args = if parameters == 1 {
+        vec![ASTNode::Literal {
+            value: script_args(),
+            line: 0,
+            column: 0,
+        }]
+    } else {
+        vec![]
+    };
+    ASTNode::FunctionCallArgs {
+        args,
+        line: 0,
+        column: 0,
+    }
+}
+
 pub fn block_node(
     statements: Vec<ASTNode>,
     _line: usize,
@@ -53,6 +111,46 @@ pub fn function_call_node(
     column: usize,
     env: &mut Env,
 ) -> Result<Value, RuntimeError> {
+    // 同じ名前のユーザー定義関数があればそちらを優先する
+    if (is_assertion(&name) || name == "exit") && env.get_function(&name).is_none() {
+        let args = match *arguments {
+            ASTNode::FunctionCallArgs { args, .. } => args,
+            _ => return Err(RuntimeError::new("illegal arguments", line, column)),
+        };
+        let args = args
+            .into_iter()
+            .map(|arg| eval(arg, env))
+            .collect::<Result<Vec<Value>, RuntimeError>>()?;
+        if name == "exit" {
+            return Err(exit_error(&args, line, column));
+        }
+        return call_assertion(
+            &name,
+            args,
+            &mut |lambda, lambda_args| {
+                match call_lambda_value(lambda, lambda_args, line, column, env) {
+                    Err(error) if error.stops_execution() => Err(error),
+                    result => Ok(result),
+                }
+            },
+            &|message| RuntimeError::new(&message, line, column),
+        );
+    }
+    // 埋め込み側が登録した関数。こちらもユーザー定義の関数を優先する
+    match env.get_host_function(&name).cloned() {
+        Some(HostFunction(function)) if env.get_function(&name).is_none() => {
+            let args = match *arguments {
+                ASTNode::FunctionCallArgs { args, .. } => args,
+                _ => return Err(RuntimeError::new("illegal arguments", line, column)),
+            };
+            let args = args
+                .into_iter()
+                .map(|arg| eval(arg, env))
+                .collect::<Result<Vec<Value>, RuntimeError>>()?;
+            return function(args).map_err(|message| RuntimeError::new(&message, line, column));
+        }
+        _ => (),
+    }
     if env.get_function(&name).is_some() || env.get_builtin(&name).is_some() {
         let function = match env.get_function(&name) {
             Some(function) => function.clone(),
@@ -69,22 +167,6 @@ pub fn function_call_node(
                 }
             }
         };
-        let mut params_vec = vec![];
-        for arg in &function.arguments {
-            params_vec.push(match arg {
-                ASTNode::Variable {
-                    name, value_type, ..
-                } => (name, value_type),
-                _ => {
-                    return Err(RuntimeError::new(
-                        format!("illigal param: {:?}", function.arguments).as_str(),
-                        line,
-                        column,
-                    ))
-                }
-            });
-        }
-
         let args_vec = match *arguments {
             ASTNode::FunctionCallArgs {
                 args: arguments, ..
@@ -99,13 +181,18 @@ pub fn function_call_node(
         };
 
         if let Some(func) = function.builtin {
-            let result = func(
-                args_vec
-                    .iter()
-                    .map(|arg| eval(arg.clone(), env))
-                    .collect::<Result<Vec<Value>, RuntimeError>>()?,
-            );
-            return Ok(result);
+            let args = args_vec
+                .iter()
+                .map(|arg| eval(arg.clone(), env))
+                .collect::<Result<Vec<Value>, RuntimeError>>()?;
+            check_builtin_arguments(&name, &args, Value::value_type)
+                .map_err(|message| RuntimeError::new(&message, line, column))?;
+            if name == "range" {
+                env.sandbox()
+                    .check_range(&numbers(&args))
+                    .map_err(|limit| RuntimeError::limit(limit, line, column))?;
+            }
+            return Ok(func(args));
         };
 
         if args_vec.len() != function.arguments.len() {
@@ -116,64 +203,18 @@ pub fn function_call_node(
             ));
         }
 
-        let mut local_env = env.clone();
-
-        local_env.enter_scope(name.to_string());
-
-        for (param, arg) in params_vec.iter().zip(&args_vec) {
-            let arg_value = eval(arg.clone(), env)?;
-            let name = param.0.to_string();
-            let value_type = param.1.clone();
-            let _ = local_env.set(
-                name,
-                arg_value,
-                EnvVariableType::Immutable,
-                value_type.unwrap_or(ValueType::Any),
-                true,
-            );
-        }
-
-        let result = eval(function.body.unwrap(), &mut local_env)?;
-        env.update_global_env(&local_env);
-
-        local_env.leave_scope();
-        if let Value::Return(v) = result {
-            Ok(*v)
-        } else {
-            Ok(result)
-        }
-    } else if env.get(&name, Some(&ValueType::Lambda)).is_some() {
-        let lambda = match env.get(&name, None).unwrap().value.clone() {
-            Value::Lambda {
-                arguments,
-                body,
-                env: lambda_env,
-            } => (arguments, body, lambda_env),
-            _ => {
-                return Err(RuntimeError::new(
-                    format!("Function is missing: {:?}", name).as_str(),
-                    line,
-                    column,
-                ))
+        let args = args_vec
+            .into_iter()
+            .map(|arg| eval(arg, env))
+            .collect::<Result<Vec<Value>, RuntimeError>>()?;
+        // 環境は複製せず、引数とグローバル変数だけが見えるスコープで評価する
+        env.with_frame(&name, |local_env| {
+            for (param, arg_value) in function.arguments.iter().zip(args) {
+                bind_argument(param, arg_value, line, column, local_env)?;
             }
-        };
-
-        let mut params_vec = vec![];
-        for arg in &lambda.0 {
-            params_vec.push(match arg {
-                ASTNode::Variable {
-                    name, value_type, ..
-                } => (name, value_type),
-                _ => {
-                    return Err(RuntimeError::new(
-                        format!("illigal param: {:?}", lambda.0).as_str(),
-                        line,
-                        column,
-                    ))
-                }
-            });
-        }
-
+            eval_function_body(function.body.unwrap(), line, column, local_env)
+        })
+    } else if let Some(lambda) = env.get(&name, Some(&ValueType::Lambda)) {
         let args_vec = match *arguments {
             ASTNode::FunctionCallArgs {
                 args: arguments, ..
@@ -186,38 +227,11 @@ pub fn function_call_node(
                 ))
             }
         };
-
-        if args_vec.len() != lambda.0.len() {
-            return Err(RuntimeError::new(
-                "does not match arguments length",
-                line,
-                column,
-            ));
-        }
-
-        let mut local_env = env.clone();
-
-        local_env.enter_scope(name.to_string());
-
-        for (param, arg) in params_vec.iter().zip(&args_vec) {
-            let arg_value = eval(arg.clone(), env)?;
-            let name = param.0.to_string();
-            let value_type = param.1.clone();
-            let _ = local_env.set(
-                name,
-                arg_value,
-                EnvVariableType::Immutable,
-                value_type.unwrap_or(ValueType::Any),
-                true,
-            );
-        }
-
-        let result = eval(*lambda.1, &mut local_env)?;
-
-        env.update_global_env(&local_env);
-
-        env.leave_scope();
-        Ok(result)
+        let args = args_vec
+            .into_iter()
+            .map(|arg| eval(arg, env))
+            .collect::<Result<Vec<Value>, RuntimeError>>()?;
+        call_lambda_value(lambda.value, args, line, column, env)
     } else {
         Err(RuntimeError::new(
             format!("Function is missing: {:?}", name).as_str(),
@@ -226,3 +240,13 @@ pub fn function_call_node(
         ))
     }
 }
+
+// 引数のうちの数値
+fn numbers(args: &[Value]) -> Vec<Fraction> {
+    args.iter()
+        .filter_map(|arg| match arg {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        })
+        .collect()
+}
@@ -5,17 +5,17 @@ use crate::evals::runtime_error::RuntimeError;
 use crate::value::Value;
 
 pub fn if_node(
-    condition: Box<ASTNode>,
+    condition: ASTNode,
     _is_statement: bool,
-    then: Box<ASTNode>,
+    then: ASTNode,
     else_: Option<Box<ASTNode>>,
     line: usize,
     column: usize,
     env: &mut Env,
 ) -> Result<Value, RuntimeError> {
-    let condition = eval(*condition, env)?;
+    let condition = eval(condition, env)?;
     match condition {
-        Value::Bool(true) => eval(*then, env),
+        Value::Bool(true) => eval(then, env),
         Value::Bool(false) => {
             if let Some(else_) = else_ {
                 eval(*else_, env)
@@ -4,67 +4,296 @@ use crate::environment::ExportedSymbolType;
 use crate::evals::eval;
 use crate::evals::runtime_error::RuntimeError;
 use crate::value::Value;
+use std::collections::HashMap;
 
 pub fn import_node(
     module_name: String,
     symbols: Vec<String>,
+    aliases: Vec<String>,
+    namespace: Option<String>,
     line: usize,
     column: usize,
     env: &mut Env,
 ) -> Result<Value, RuntimeError> {
-    let module_path = format!("{}.sag", module_name);
-    match env.register_module(&module_name, &module_path) {
+    match env.register_module(&module_name) {
         Ok(_) => {}
         Err(e) => {
             return Err(RuntimeError::new(
-                format!("Failed to import module {}: {:?}", module_name, e).as_str(),
+                format!("Failed to import module {}: {}", module_name, e).as_str(),
                 line,
                 column,
             ))
         }
     }
 
-    if let Some(module_env) = env.clone().get_module(&module_name) {
-        for symbol in symbols {
-            if let Some(exported_symbol_type) = module_env.get_exported_symbol(&symbol) {
-                match exported_symbol_type.clone() {
-                    ExportedSymbolType::Function => {
-                        match module_env.clone().get_function(&symbol) {
-                            Some(func) => {
-                                env.register_function(symbol.clone(), func.clone());
-                            }
-                            None => {}
-                        };
-                    }
-                    ExportedSymbolType::Struct => match module_env.clone().get_struct(&symbol) {
-                        Some(s) => {
-                            env.register_struct(s.clone())?;
-                        }
-                        None => {}
-                    },
-                    ExportedSymbolType::Variable => match module_env.get(&symbol, None) {
-                        Some(symbol_value) => {
-                            let _ = env.set(
-                                symbol.clone(),
-                                symbol_value.value.clone(),
-                                symbol_value.variable_type.clone(),
-                                symbol_value.value_type.clone(),
-                                true,
-                            );
-                        }
-                        None => {}
-                    },
-                };
-            } else {
-                return Err(RuntimeError::new(
-                    format!("Symbol {} not found in module {}", symbol, module_name).as_str(),
+    let Some(module_env) = env.get_module(&module_name).cloned() else {
+        return Ok(Value::Void);
+    };
+    if let Some(namespace) = namespace {
+        // 同じ名前のシンボルがあればそれを、なければモジュール全体を取り込む
+        if module_env.get_exported_symbol(&namespace).is_some() {
+            import_symbol(
+                &module_env,
+                &namespace,
+                &namespace,
+                &module_name,
+                line,
+                column,
+                env,
+            )?;
+        } else {
+            env.insert_module(namespace, module_env);
+        }
+        return Ok(Value::Void);
+    }
+    for (symbol, alias) in symbols.iter().zip(aliases.iter()) {
+        import_symbol(&module_env, symbol, alias, &module_name, line, column, env)?;
+    }
+    Ok(Value::Void)
+}
+
+fn import_symbol(
+    module_env: &Env,
+    symbol: &String,
+    alias: &str,
+    module_name: &String,
+    line: usize,
+    column: usize,
+    env: &mut Env,
+) -> Result<(), RuntimeError> {
+    let Some(exported_symbol_type) = module_env.get_exported_symbol(symbol) else {
+        return Err(RuntimeError::new(
+            format!("Symbol {} not found in module {}", symbol, module_name).as_str(),
+            line,
+            column,
+        ));
+    };
+    match exported_symbol_type {
+        ExportedSymbolType::Function => {
+            if let Some(func) = module_env.clone().get_function(symbol) {
+                env.register_function(alias.to_string(), func.clone());
+            }
+        }
+        ExportedSymbolType::Struct => {
+            if let Some(s) = module_env.get_struct(symbol) {
+                let mut s = s.clone();
+                if let Value::Struct { ref mut name, .. } = s {
+                    *name = alias.to_string();
+                }
+                env.register_struct(s)?;
+            }
+        }
+        ExportedSymbolType::Variable => {
+            if let Some(symbol_value) = module_env.get(symbol, None) {
+                let _ = env.set(
+                    alias.to_string(),
+                    symbol_value.value,
+                    symbol_value.variable_type,
+                    symbol_value.value_type,
+                    true,
+                );
+            }
+        }
+        ExportedSymbolType::Module => {
+            if let Some(module) = module_env.get_module(symbol) {
+                env.insert_module(alias.to_string(), module.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `math.add(1, 2)` のようなモジュールのメンバーへのアクセス。
+/// 引数やフィールドは呼び出し側で評価し、関数や構造体はモジュールの環境で評価する
+pub fn module_access_node(
+    module: String,
+    member: ASTNode,
+    line: usize,
+    column: usize,
+    env: &mut Env,
+) -> Result<Value, RuntimeError> {
+    let Some(mut module_env) = env.get_module(&module).cloned() else {
+        // モジュールではなく同じ名前で取り込んだ値 (import origin from geo; origin.x)
+        return value_access(module, member, line, column, env);
+    };
+    let mut module_name = module;
+    let mut member = member;
+    // lib.math.add(1, 2) は lib が再エクスポートした math を辿る
+    while let ASTNode::ModuleAccess {
+        module: inner,
+        member: inner_member,
+        ..
+    } = member
+    {
+        match module_env.get_exported_symbol(&inner) {
+            Some(ExportedSymbolType::Module) => {}
+            Some(_) => {
+                let value = exported_value(&module_env, &module_name, &inner, line, column)?;
+                return access_value(value, *inner_member, line, column, env);
+            }
+            None => return Err(not_exported(&inner, &module_name, line, column)),
+        }
+        module_env = module_env.get_module(&inner).cloned().unwrap();
+        module_name = inner;
+        member = *inner_member;
+    }
+
+    match member {
+        ASTNode::Variable { name, .. } => {
+            exported_value(&module_env, &module_name, &name, line, column)
+        }
+        ASTNode::FunctionCall {
+            name, arguments, ..
+        } => {
+            if module_env.get_exported_symbol(&name) != Some(&ExportedSymbolType::Function) {
+                return Err(not_exported(&name, &module_name, line, column));
+            }
+            let arguments = match *arguments {
+                ASTNode::FunctionCallArgs { args, line, column } => ASTNode::FunctionCallArgs {
+                    args: literals(args, line, column, env)?,
                     line,
                     column,
-                ));
+                },
+                arguments => arguments,
+            };
+            eval(
+                ASTNode::FunctionCall {
+                    name,
+                    arguments: Box::new(arguments),
+                    line,
+                    column,
+                },
+                &mut module_env,
+            )
+        }
+        ASTNode::StructInstance { name, fields, .. } => {
+            if module_env.get_exported_symbol(&name) != Some(&ExportedSymbolType::Struct) {
+                return Err(not_exported(&name, &module_name, line, column));
             }
+            let mut evaluated = HashMap::new();
+            for (field_name, field) in fields {
+                evaluated.insert(field_name, literal(eval(field, env)?, line, column));
+            }
+            // メソッドを呼べるように構造体の定義を呼び出し側にも登録する
+            if let (None, Some(s)) = (env.get_struct(&name), module_env.get_struct(&name).cloned())
+            {
+                env.register_struct(s)?;
+            }
+            eval(
+                ASTNode::StructInstance {
+                    name,
+                    fields: evaluated,
+                    line,
+                    column,
+                },
+                &mut module_env,
+            )
         }
+        _ => Err(RuntimeError::new(
+            format!("Invalid access to module {}", module_name).as_str(),
+            line,
+            column,
+        )),
     }
-    Ok(Value::Void)
+}
+
+fn exported_value(
+    module_env: &Env,
+    module_name: &String,
+    name: &String,
+    line: usize,
+    column: usize,
+) -> Result<Value, RuntimeError> {
+    if module_env.get_exported_symbol(name) != Some(&ExportedSymbolType::Variable) {
+        return Err(not_exported(name, module_name, line, column));
+    }
+    match module_env.get(name, None) {
+        Some(variable) => Ok(variable.value),
+        None => Err(not_exported(name, module_name, line, column)),
+    }
+}
+
+fn value_access(
+    name: String,
+    member: ASTNode,
+    line: usize,
+    column: usize,
+    env: &mut Env,
+) -> Result<Value, RuntimeError> {
+    match env.get(&name, None) {
+        Some(variable) => access_value(variable.value, member, line, column, env),
+        None => Err(RuntimeError::new(
+            format!("{} is not a module", name).as_str(),
+            line,
+            column,
+        )),
+    }
+}
+
+// モジュールから取り出した値へのフィールドアクセスとメソッド呼び出し
+fn access_value(
+    value: Value,
+    member: ASTNode,
+    line: usize,
+    column: usize,
+    env: &mut Env,
+) -> Result<Value, RuntimeError> {
+    let builtin = !matches!(value, Value::StructInstance { .. });
+    let caller = Box::new(literal(value, line, column));
+    match member {
+        ASTNode::Variable { name, .. } => eval(
+            ASTNode::StructFieldAccess {
+                instance: caller,
+                field_name: name,
+                line,
+                column,
+            },
+            env,
+        ),
+        ASTNode::FunctionCall {
+            name, arguments, ..
+        } => eval(
+            ASTNode::MethodCall {
+                method_name: name,
+                caller,
+                arguments,
+                builtin,
+                line,
+                column,
+            },
+            env,
+        ),
+        _ => Err(RuntimeError::new("Invalid member access", line, column)),
+    }
+}
+
+fn literals(
+    args: Vec<ASTNode>,
+    line: usize,
+    column: usize,
+    env: &mut Env,
+) -> Result<Vec<ASTNode>, RuntimeError> {
+    let mut values = vec![];
+    for arg in args {
+        values.push(literal(eval(arg, env)?, line, column));
+    }
+    Ok(values)
+}
+
+fn literal(value: Value, line: usize, column: usize) -> ASTNode {
+    ASTNode::Literal {
+        value,
+        line,
+        column,
+    }
+}
+
+fn not_exported(name: &String, module_name: &String, line: usize, column: usize) -> RuntimeError {
+    RuntimeError::new(
+        format!("{} is not exported from module {}", name, module_name).as_str(),
+        line,
+        column,
+    )
 }
 
 pub fn public_node(
@@ -86,9 +315,24 @@ pub fn public_node(
             eval(*node, env)?;
             env.register_exported_symbol(name);
         }
+        // 再エクスポート
+        ASTNode::Import {
+            ref aliases,
+            ref namespace,
+            ..
+        } => {
+            let names = match namespace {
+                Some(namespace) => vec![namespace.clone()],
+                None => aliases.clone(),
+            };
+            eval(*node, env)?;
+            for name in names {
+                env.register_exported_symbol(name);
+            }
+        }
         _ => {
             return Err(RuntimeError::new(
-                format!("Only variables, struct and functions can be exported").as_str(),
+                "Only variables, struct, functions and imports can be exported",
                 line,
                 column,
             ))
@@ -111,6 +355,8 @@ mod tests {
         let ast = ASTNode::Import {
             module_name: "test_foo".to_string(),
             symbols: vec!["a".to_string(), "f".to_string(), "Ham".to_string()],
+            aliases: vec!["a".to_string(), "f".to_string(), "Ham".to_string()],
+            namespace: None,
             line: 0,
             column: 0,
         };
@@ -139,4 +385,111 @@ mod tests {
         );
         let _ = std::fs::remove_file(file_path);
     }
+
+    #[test]
+    fn test_import_relative_to_importing_file() {
+        let dir = std::env::temp_dir().join("sag_import_relative");
+        std::fs::create_dir_all(dir.join("utils")).unwrap();
+        std::fs::write(
+            dir.join("utils/strings.sag"),
+            "import bang from helper\npub val greeting = \"hi\"",
+        )
+        .unwrap();
+        std::fs::write(dir.join("utils/helper.sag"), "pub val bang = \"!\"").unwrap();
+
+        let mut env = Env::new();
+        env.set_file_path(dir.join("main.sag"));
+        let ast = ASTNode::Import {
+            module_name: "utils/strings".to_string(),
+            symbols: vec!["greeting".to_string()],
+            aliases: vec!["greeting".to_string()],
+            namespace: None,
+            line: 0,
+            column: 0,
+        };
+        assert_eq!(Value::Void, eval(ast, &mut env).unwrap());
+        assert_eq!(
+            env.get(&"greeting".to_string(), None).unwrap().value,
+            Value::String("hi".to_string())
+        );
+
+        let ast = ASTNode::Import {
+            module_name: "missing".to_string(),
+            symbols: vec![],
+            aliases: vec![],
+            namespace: None,
+            line: 0,
+            column: 0,
+        };
+        let error = eval(ast, &mut env).unwrap_err();
+        assert!(error.message.contains("module 'missing' not found"));
+        assert!(error
+            .message
+            .contains(&dir.join("missing.sag").display().to_string()));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn run_in(dir: &std::path::Path, input: &str) -> Result<Vec<Value>, RuntimeError> {
+        let tokens = crate::tokenizer::tokenize(&input.to_string());
+        let mut env = Env::new();
+        env.set_file_path(dir.join("main.sag"));
+        let builtins = crate::builtin::register_builtins(&mut env);
+        let mut parser = crate::parsers::Parser::new(tokens, builtins);
+        parser.set_file_path(dir.join("main.sag"));
+        let asts = parser.parse_lines().unwrap();
+        crate::evals::evals(asts, &mut env)
+    }
+
+    #[test]
+    fn test_namespace_import_and_aliases() {
+        let dir = std::env::temp_dir().join("sag_import_namespace");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("math.sag"),
+            "pub val PI = 3\nval secret = 1\npub fun add(a: number, b: number): number {\nreturn a + b\n}\npub struct Vector {\nx: number\n}",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("lib.sag"),
+            "pub import math from math\npub import { add as plus } from math",
+        )
+        .unwrap();
+
+        let input = r#"
+        import math from math
+        val n = 2
+        math.add(n, 1)
+        math.PI
+        math.Vector { x: n }
+        import { add as plus, PI } from math
+        plus(PI, 1)
+        import lib from lib
+        lib.math.add(1, 1)
+        lib.plus(2, 2)
+        val v = math.Vector { x: 5 }
+        v.x
+        "#;
+        let result = run_in(&dir, input).unwrap();
+        let number = |n: i64| Value::Number(fraction::Fraction::from(n));
+        assert_eq!(result[2], number(3));
+        assert_eq!(result[3], number(3));
+        match &result[4] {
+            Value::StructInstance { name, fields } => {
+                assert_eq!(name, "Vector");
+                assert_eq!(fields.get("x"), Some(&number(2)));
+            }
+            other => panic!("Expected StructInstance, got {:?}", other),
+        }
+        assert_eq!(result[6], number(4));
+        assert_eq!(result[8], number(2));
+        assert_eq!(result[9], number(4));
+        assert_eq!(result[11], number(5));
+
+        let result = run_in(&dir, "import { Vector } from math\nval w = Vector { x: 6 }\nw.x").unwrap();
+        assert_eq!(result[2], number(6));
+
+        let error = run_in(&dir, "import math from math\nmath.secret").unwrap_err();
+        assert_eq!(error.message, "secret is not exported from module math");
+        let _ = std::fs::remove_dir_all(dir);
+    }
 }
@@ -1,6 +1,8 @@
 use crate::ast::ASTNode;
-use crate::environment::{Env, EnvVariableType, ValueType};
+use crate::environment::Env;
+use crate::evals::destructure_node::bind_argument;
 use crate::evals::eval;
+use crate::evals::function_node::eval_function_body;
 use crate::evals::runtime_error::RuntimeError;
 use crate::value::Value;
 
@@ -11,11 +13,13 @@ pub fn lambda_call_node(
     column: usize,
     env: &mut Env,
 ) -> Result<Value, RuntimeError> {
-    let mut params_vec = vec![];
     let lambda = match *lambda {
         ASTNode::Lambda {
-            arguments, body, ..
-        } => (arguments, body),
+            arguments,
+            body,
+            captures,
+            ..
+        } => (arguments, body, captures),
         _ => {
             return Err(RuntimeError::new(
                 format!("Unexpected value type: {:?}", lambda).as_str(),
@@ -24,20 +28,6 @@ pub fn lambda_call_node(
             ))
         }
     };
-    for arg in &lambda.0 {
-        params_vec.push(match arg {
-            ASTNode::Variable {
-                name, value_type, ..
-            } => (name, value_type),
-            _ => {
-                return Err(RuntimeError::new(
-                    format!("illigal param: {:?}", lambda.0).as_str(),
-                    line,
-                    column,
-                ))
-            }
-        });
-    }
 
     let mut args_vec = vec![];
 
@@ -53,12 +43,47 @@ pub fn lambda_call_node(
             }
         }
     }
-    if args_vec.len() != lambda.0.len() {
+    let args = args_vec
+        .into_iter()
+        .map(|arg| eval(arg, env))
+        .collect::<Result<Vec<Value>, RuntimeError>>()?;
+
+    let lambda = Value::Lambda {
+        arguments: lambda.0,
+        body: lambda.1,
+        captures: env.capture(&lambda.2),
+    };
+    call_lambda_value(lambda, args, line, column, env)
+}
+
+/// 値として渡されたラムダを呼び出す
+pub fn call_lambda_value(
+    lambda: Value,
+    args: Vec<Value>,
+    line: usize,
+    column: usize,
+    env: &mut Env,
+) -> Result<Value, RuntimeError> {
+    let (arguments, body, captures) = match lambda {
+        Value::Lambda {
+            arguments,
+            body,
+            captures,
+        } => (arguments, body, captures),
+        _ => {
+            return Err(RuntimeError::new(
+                format!("expected a lambda, got {}", lambda).as_str(),
+                line,
+                column,
+            ))
+        }
+    };
+    if args.len() != arguments.len() {
         return Err(RuntimeError::new(
             format!(
                 "does not match arguments length: expected {}, got {}",
-                lambda.0.len(),
-                args_vec.len()
+                arguments.len(),
+                args.len()
             )
             .as_str(),
             line,
@@ -66,37 +91,22 @@ pub fn lambda_call_node(
         ));
     }
 
-    let mut local_env = env.clone();
-
-    local_env.enter_scope("lambda".to_string());
-
-    for (param, arg) in params_vec.iter().zip(&args_vec) {
-        let arg_value = eval(arg.clone(), env)?;
-        let name = param.0.to_string();
-        let value_type = param.1.clone();
-        let _ = local_env.set(
-            name,
-            arg_value,
-            EnvVariableType::Immutable,
-            value_type.unwrap_or(ValueType::Any),
-            true,
-        );
-    }
-
-    let result = eval(*lambda.1, &mut local_env);
-
-    env.update_global_env(&local_env);
-
-    env.leave_scope();
-    result
+    // 環境は複製せず、捕捉した変数のセルだけを見せて評価する
+    env.with_closure_scope(&captures, |local_env| {
+        for (param, arg_value) in arguments.iter().zip(args) {
+            bind_argument(param, arg_value, line, column, local_env)?;
+        }
+        eval_function_body(*body, line, column, local_env)
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::builtin::register_builtins;
-    use crate::evals::evals;
+    use crate::evals::{evals, run_engines, run_source};
     use crate::parsers::Parser;
+    use crate::repl_session::EngineKind;
     use crate::tokenizer::tokenize;
     use fraction::Fraction;
 
@@ -112,4 +122,92 @@ mod tests {
         let result = evals(asts, &mut env).unwrap();
         assert_eq!(*result.last().unwrap(), Value::Number(Fraction::from(7)));
     }
+
+    #[test]
+    fn test_closure_counter() {
+        let input = r#"
+        fun make_counter(): lambda {
+            val mut n = 0
+            return \|| => {
+                n = n + 1
+                return n
+            }
+        }
+        val counter = make_counter()
+        val other = make_counter()
+        counter()
+        counter()
+        other()
+        "#;
+        let result = run_source(input).unwrap();
+        assert_eq!(
+            result[result.len() - 3..].to_vec(),
+            vec![
+                Value::Number(Fraction::from(1)),
+                Value::Number(Fraction::from(2)),
+                Value::Number(Fraction::from(1)),
+            ]
+        );
+        for (engine, result) in run_engines(input, &[EngineKind::Rc, EngineKind::Vm]) {
+            assert_eq!(result, Ok(Value::Number(Fraction::from(1))), "engine: {}", engine);
+        }
+    }
+
+    #[test]
+    fn test_closure_sees_later_mutation() {
+        let input = r#"
+        fun later(): number {
+            val mut x = 1
+            val get = \|| => x
+            x = 10
+            return get()
+        }
+        later()
+        "#;
+        let result = run_source(input).unwrap();
+        assert_eq!(*result.last().unwrap(), Value::Number(Fraction::from(10)));
+        for (engine, result) in run_engines(input, &[EngineKind::Rc, EngineKind::Vm]) {
+            assert_eq!(result, Ok(Value::Number(Fraction::from(10))), "engine: {}", engine);
+        }
+    }
+
+    #[test]
+    fn test_closure_captures_only_free_variables() {
+        let input = r#"
+        fun make(): lambda {
+            val a = 1
+            val b = 2
+            val c = 3
+            return \|x: number| => x + a + c
+        }
+        make()
+        "#;
+        let result = run_source(input).unwrap();
+        let Some(Value::Lambda { captures, .. }) = result.last() else {
+            panic!("expected a lambda: {:?}", result.last());
+        };
+        let mut names = captures.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_closure_does_not_see_caller_locals() {
+        let input = r#"
+        fun apply(f: lambda): number {
+            val x = 100
+            return f()
+        }
+        fun outer(): number {
+            val x = 1
+            return apply(\|| => x)
+        }
+        outer()
+        "#;
+        let result = run_source(input).unwrap();
+        assert_eq!(*result.last().unwrap(), Value::Number(Fraction::from(1)));
+        for (engine, result) in run_engines(input, &[EngineKind::Rc, EngineKind::Vm]) {
+            assert_eq!(result, Ok(Value::Number(Fraction::from(1))), "engine: {}", engine);
+        }
+    }
 }
@@ -14,7 +14,7 @@ pub fn match_node(
     let expression_value = eval(*expression.clone(), env)?;
     let mut count = 0;
     for (pattern, body) in cases.clone() {
-        env.enter_scope(format!("match-{:?}", count).to_string());
+        env.enter_block_scope(&format!("match-{:?}", count));
         count += 1;
         match pattern {
             ASTNode::Variable { name, .. } if name == "_" => {
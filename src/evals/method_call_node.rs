@@ -1,13 +1,17 @@
 use crate::ast::ASTNode;
 use crate::environment::{Env, EnvVariableType, ValueType};
 use crate::evals::eval;
+use crate::evals::function_node::eval_function_body;
+use crate::evals::lambda_node::call_lambda_value;
+use crate::evals::option_result_method::call_option_result_method;
 use crate::evals::runtime_error::RuntimeError;
+use crate::evals::struct_node::has_self_argument;
 use crate::value::Value;
 use fraction::Fraction;
 use std::collections::HashMap;
 
-fn extract_arguments(arguments: Box<ASTNode>) -> Vec<ASTNode> {
-    match *arguments {
+fn extract_arguments(arguments: ASTNode) -> Vec<ASTNode> {
+    match arguments {
         ASTNode::FunctionCallArgs { args, .. } => args,
         _ => vec![],
     }
@@ -56,6 +60,9 @@ fn call_builtin_method_on_list(
                 return Err(RuntimeError::new("push requires an argument", line, column));
             }
             let new_val = eval(args[0].clone(), env)?;
+            env.sandbox()
+                .check_len(list.len() + 1)
+                .map_err(|limit| RuntimeError::limit(limit, line, column))?;
             list.push(new_val);
 
             if let ASTNode::Variable {
@@ -520,6 +527,77 @@ fn call_builtin_method_on_string(
     }
 }
 
+/// 型ごとの builtin メソッドの名前。エディタの補完で使うので、上の match に足したらここにも足す
+#[allow(dead_code)]
+pub fn builtin_method_names(value_type: &ValueType) -> &'static [&'static str] {
+    match value_type {
+        ValueType::Number => &["to_string", "round", "sqrt"],
+        ValueType::String => &[
+            "len",
+            "is_empty",
+            "to_uppercase",
+            "to_lowercase",
+            "trim",
+            "contains",
+            "starts_with",
+            "ends_with",
+            "split",
+            "replace",
+        ],
+        ValueType::List(_) => &[
+            "to_string",
+            "push",
+            "pop",
+            "len",
+            "is_empty",
+            "first",
+            "last",
+            "clear",
+            "contains",
+            "reverse",
+        ],
+        ValueType::Dict(_) => &[
+            "get",
+            "insert",
+            "remove",
+            "contains_key",
+            "keys",
+            "values",
+            "len",
+            "is_empty",
+            "clear",
+            "update",
+            "entry",
+            "get_or_insert",
+        ],
+        ValueType::OptionType(_) => &[
+            "is_some",
+            "is_none",
+            "unwrap",
+            "expect",
+            "unwrap_or",
+            "unwrap_or_else",
+            "map",
+            "and_then",
+            "or_else",
+            "ok_or",
+        ],
+        ValueType::ResultType { .. } => &[
+            "is_suc",
+            "is_fail",
+            "unwrap",
+            "expect",
+            "unwrap_or",
+            "unwrap_or_else",
+            "map",
+            "map_err",
+            "and_then",
+            "or_else",
+        ],
+        _ => &[],
+    }
+}
+
 /// Valueに応じた builtin メソッドの呼び出し
 fn call_builtin_method(
     value: Value,
@@ -541,6 +619,21 @@ fn call_builtin_method(
         Value::String(string) => {
             call_builtin_method_on_string(string, method_name, args, env, line, column)
         }
+        Value::Option(_) | Value::Result(_) => {
+            let mut arg_values = vec![];
+            for arg in args {
+                arg_values.push(eval(arg.clone(), env)?);
+            }
+            call_option_result_method(
+                value,
+                method_name,
+                arg_values,
+                &mut |lambda, lambda_args| {
+                    call_lambda_value(lambda, lambda_args, line, column, env)
+                },
+                &|message| RuntimeError::new(message.as_str(), line, column),
+            )
+        }
         _ => Err(RuntimeError::new(
             format!("Method {} is not supported for this type", method_name).as_str(),
             line,
@@ -557,7 +650,7 @@ pub fn builtin_method_call_node(
     column: usize,
     env: &mut Env,
 ) -> Result<Value, RuntimeError> {
-    let args = extract_arguments(arguments);
+    let args = extract_arguments(*arguments);
     let value = eval(*caller.clone(), env)?;
     call_builtin_method(value, &method_name, &args, &caller, env, line, column)
 }
@@ -566,13 +659,13 @@ pub fn builtin_method_call_node(
 pub fn method_call_node(
     method_name: String,
     caller: Box<ASTNode>,
-    arguments: Box<ASTNode>,
+    arguments: ASTNode,
     line: usize,
     column: usize,
     env: &mut Env,
 ) -> Result<Value, RuntimeError> {
     // 引数リストの取り出し
-    let args_vec = match *arguments {
+    let args_vec = match arguments {
         ASTNode::FunctionCallArgs { args, .. } => args,
         _ => vec![],
     };
@@ -635,6 +728,22 @@ pub fn method_call_node(
         )
     })?;
 
+    if !has_self_argument(&method_info.arguments) {
+        let struct_name = match &struct_info {
+            Some(Value::Struct { name, .. }) => name.clone(),
+            _ => String::new(),
+        };
+        return Err(RuntimeError::new(
+            format!(
+                "{} is a static method and must be called as {}::{}",
+                method_name, struct_name, method_name
+            )
+            .as_str(),
+            line,
+            column,
+        ));
+    }
+
     // 変更可能な変数であることの確認
     if variable_info.variable_type == EnvVariableType::Immutable {
         return Err(RuntimeError::new(
@@ -729,12 +838,13 @@ pub fn method_call_node(
     }
 
     // メソッド本体の評価
-    let result = eval(method_info.body.clone().unwrap(), &mut local_env)?;
     // Returnに包まれている場合は中身を取り出す
-    let unwrapped_result = match result {
-        Value::Return(inner) => *inner,
-        other => other,
-    };
+    let unwrapped_result = eval_function_body(
+        method_info.body.clone().unwrap(),
+        line,
+        column,
+        &mut local_env,
+    )?;
 
     // メソッド呼び出し後、self の変更があればグローバル環境に反映する
     if let Some(self_var) = local_env.get(&"self".to_string(), None) {
@@ -1132,4 +1242,32 @@ mod tests {
             _ => panic!("Expected number"),
         }
     }
+
+    #[test]
+    fn test_builtin_method_names_are_callable() {
+        let values = [
+            Value::Number(2.into()),
+            Value::String("a b".into()),
+            Value::List(vec![Value::Number(1.into())]),
+            Value::Dict(HashMap::from([("a".to_string(), Value::Number(1.into()))])),
+            Value::Option(Some(Box::new(Value::Number(1.into())))),
+            Value::Result(Ok(Box::new(Value::Number(1.into())))),
+        ];
+        for value in values {
+            let names = builtin_method_names(&value.value_type());
+            assert!(!names.is_empty(), "{}", value);
+            for name in names {
+                let mut env = Env::new();
+                let caller = ASTNode::Literal {
+                    value: value.clone(),
+                    line: 0,
+                    column: 0,
+                };
+                let result = call_builtin_method(value.clone(), name, &[], &caller, &mut env, 0, 0);
+                if let Err(e) = result {
+                    assert!(!e.message.contains("is not a method"), "{}", e.message);
+                }
+            }
+        }
+    }
 }
@@ -1,6 +1,8 @@
+pub mod assertion;
 pub mod assign_node;
 pub mod binary_op;
 pub mod comparison_op;
+pub mod destructure_node;
 pub mod for_node;
 pub mod function_node;
 pub mod if_node;
@@ -8,9 +10,11 @@ pub mod import_node;
 pub mod lambda_node;
 pub mod match_node;
 pub mod method_call_node;
+pub mod option_result_method;
 pub mod prefix_op;
 pub mod runtime_error;
 pub mod struct_node;
+pub mod try_node;
 pub mod variable_node;
 use fraction::Fraction;
 
@@ -29,13 +33,38 @@ pub fn evals(asts: Vec<ASTNode>, env: &mut Env) -> Result<Vec<Value>, RuntimeErr
 }
 
 pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
+    let (line, column) = ast.position();
+    let exceeded = |limit| RuntimeError::limit(limit, line, column);
+    env.sandbox().step().map_err(exceeded)?;
+    let value = eval_node(ast, env)?;
+    env.sandbox().check_value(&value).map_err(exceeded)?;
+    Ok(value)
+}
+
+fn eval_node(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
     match ast {
         ASTNode::Import {
             module_name,
             symbols,
+            aliases,
+            namespace,
+            line,
+            column,
+        } => import_node::import_node(
+            module_name,
+            symbols,
+            aliases,
+            namespace,
             line,
             column,
-        } => import_node::import_node(module_name, symbols, line, column, env),
+            env,
+        ),
+        ASTNode::ModuleAccess {
+            module,
+            member,
+            line,
+            column,
+        } => import_node::module_access_node(module, *member, line, column, env),
         ASTNode::Public { node, line, column } => import_node::public_node(node, line, column, env),
         ASTNode::Literal { value, .. } => Ok(value.clone()),
         ASTNode::PrefixOp {
@@ -43,7 +72,7 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
             expr,
             line,
             column,
-        } => prefix_op::prefix_op(op, expr, line, column, env),
+        } => prefix_op::prefix_op(op, *expr, line, column, env),
         ASTNode::Struct {
             name,
             fields,
@@ -53,9 +82,10 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
         ASTNode::Impl {
             base_struct,
             methods,
+            constants,
             line,
             column,
-        } => struct_node::impl_node(base_struct, methods, line, column, env),
+        } => struct_node::impl_node(base_struct, methods, constants, line, column, env),
         ASTNode::MethodCall {
             method_name,
             caller,
@@ -78,7 +108,7 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
                     _ => method_call_node::method_call_node(
                         method_name,
                         caller,
-                        arguments,
+                        *arguments,
                         line,
                         column,
                         env,
@@ -119,13 +149,16 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
             return_type,
             line,
             column,
-        } => function_node::function_node(name, arguments, body, return_type, line, column, env),
+        } => function_node::function_node(name, arguments, *body, return_type, line, column, env),
         ASTNode::Lambda {
-            arguments, body, ..
+            arguments,
+            body,
+            captures,
+            ..
         } => Ok(Value::Lambda {
             arguments,
             body: body.clone(),
-            env: env.clone(),
+            captures: env.capture(&captures),
         }),
         ASTNode::Block {
             nodes: statements,
@@ -144,38 +177,38 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
             right,
             line,
             column,
-        } => comparison_op::comparison_op_node(TokenKind::Eq, left, right, line, column, env),
+        } => comparison_op::comparison_op_node(TokenKind::Eq, *left, *right, line, column, env),
         ASTNode::Gte {
             left,
             right,
             line,
             column,
-        } => comparison_op::comparison_op_node(TokenKind::Gte, left, right, line, column, env),
+        } => comparison_op::comparison_op_node(TokenKind::Gte, *left, *right, line, column, env),
         ASTNode::Gt {
             left,
             right,
             line,
             column,
-        } => comparison_op::comparison_op_node(TokenKind::Gt, left, right, line, column, env),
+        } => comparison_op::comparison_op_node(TokenKind::Gt, *left, *right, line, column, env),
         ASTNode::Lte {
             left,
             right,
             line,
             column,
-        } => comparison_op::comparison_op_node(TokenKind::Lte, left, right, line, column, env),
+        } => comparison_op::comparison_op_node(TokenKind::Lte, *left, *right, line, column, env),
         ASTNode::Lt {
             left,
             right,
             line,
             column,
-        } => comparison_op::comparison_op_node(TokenKind::Lt, left, right, line, column, env),
+        } => comparison_op::comparison_op_node(TokenKind::Lt, *left, *right, line, column, env),
         ASTNode::For {
             variable,
             iterable,
             body,
             line,
             column,
-        } => for_node::for_node(variable, iterable, body, line, column, env),
+        } => for_node::for_node(variable, *iterable, body, line, column, env),
         ASTNode::Match {
             expression,
             cases,
@@ -215,7 +248,7 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
             value_type: _,
             line,
             column,
-        } => if_node::if_node(condition, is_statement, then, else_, line, column, env),
+        } => if_node::if_node(*condition, is_statement, *then, else_, line, column, env),
         ASTNode::Assign {
             name,
             value,
@@ -226,7 +259,7 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
             column,
         } => assign_node::assign_node(
             name,
-            value,
+            *value,
             value_type,
             variable_type,
             is_new,
@@ -258,7 +291,7 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
             right,
             line,
             column,
-        } => binary_op::binary_op(op, left, right, line, column, env),
+        } => binary_op::binary_op(op, *left, *right, line, column, env),
         ASTNode::ListIndexAccess {
             list,
             index,
@@ -453,6 +486,19 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
                 ))
             }
         }
+        ASTNode::Tuple {
+            elements,
+            line,
+            column,
+        } => destructure_node::tuple_node(elements, line, column, env),
+        ASTNode::Destructure {
+            pattern,
+            value,
+            variable_type,
+            line,
+            column,
+        } => destructure_node::destructure_node(pattern, *value, variable_type, line, column, env),
+        ASTNode::Try { expr, line, column } => try_node::try_node(*expr, line, column, env),
         ASTNode::CommentBlock { .. } => Ok(Value::Void),
         _ => Err(RuntimeError::new(
             format!("Unsupported ast node: {:?}", ast).as_str(),
@@ -462,6 +508,40 @@ pub fn eval(ast: ASTNode, env: &mut Env) -> Result<Value, RuntimeError> {
     }
 }
 
+/// テスト用。ソースを解析してツリー評価器で評価し、文ごとの値を返す
+#[cfg(test)]
+pub(crate) fn run_source(input: &str) -> Result<Vec<Value>, RuntimeError> {
+    use crate::builtin::register_builtins;
+    use crate::parsers::Parser;
+    use crate::tokenizer::tokenize;
+
+    let tokens = tokenize(&input.to_string());
+    let mut env = Env::new();
+    let builtins = register_builtins(&mut env);
+    let asts = Parser::new(tokens, builtins).parse_lines().unwrap();
+    evals(asts, &mut env)
+}
+
+/// テスト用。同じソースを各エンジンで実行し、最後の値かエラーメッセージをエンジンごとに返す
+#[cfg(test)]
+pub(crate) fn run_engines(
+    input: &str,
+    engines: &[crate::repl_session::EngineKind],
+) -> Vec<(crate::repl_session::EngineKind, Result<Value, String>)> {
+    use crate::repl_session::{EvalError, ReplSession};
+
+    engines
+        .iter()
+        .map(|&engine| {
+            let result = ReplSession::new(engine).run(input).map_err(|error| match error {
+                EvalError::Parse(error) => error.message,
+                EvalError::Runtime(error) => error.message,
+            });
+            (engine, result)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -637,6 +717,52 @@ mod tests {
         assert!(local_var_check.is_none());
     }
 
+    #[test]
+    fn test_function_call_frame() {
+        let input = r#"
+        fun sum(n: number): number {
+            val mut total = 0
+            for i in range(n) {
+                val step = i
+                total = total + step
+            }
+            return total
+        }
+        val mut calls = 0
+        fun depth(n: number): number {
+            calls = calls + 1
+            val here = n
+            if (n == 0) {
+                return 0
+            }
+            return depth(n - 1) + here
+        }
+        val mut seen = 0
+        for i in [10, 20] {
+            sum(3)
+            seen = seen + i
+        }
+        depth(3)
+        "#
+        .to_string();
+        let mut env = Env::new();
+        let tokens = tokenize(&input);
+        let mut parser = Parser::new(tokens, register_builtins(&mut env));
+        let ast = parser.parse_lines().unwrap();
+        let results = evals(ast, &mut env).unwrap();
+        assert_eq!(*results.last().unwrap(), Value::Number(Fraction::from(6)));
+        // 呼び出し先の for は呼び出し元の i を書き換えない
+        let seen = env.get(&"seen".to_string(), None).unwrap().value;
+        assert_eq!(seen, Value::Number(Fraction::from(30)));
+        let calls = env.get(&"calls".to_string(), None).unwrap().value;
+        assert_eq!(calls, Value::Number(Fraction::from(4)));
+        // 呼び出し中に作ったローカル変数は残らない
+        assert!(env
+            .get_variable_map()
+            .keys()
+            .all(|key| !["total", "step", "here", "n"].contains(&key.name.as_str())));
+    }
+
     #[test]
     fn test_scope_and_global_variable() {
         let input = r#"
@@ -0,0 +1,177 @@
+use crate::value::Value;
+
+/// Option / Result の builtin メソッド。
+/// ラムダの呼び出し方はエンジンごとに異なるので `call` で受け取る
+pub fn call_option_result_method<E>(
+    value: Value,
+    method_name: &str,
+    args: Vec<Value>,
+    call: &mut dyn FnMut(Value, Vec<Value>) -> Result<Value, E>,
+    error: &dyn Fn(String) -> E,
+) -> Result<Value, E> {
+    let arg = |index: usize| -> Result<Value, E> {
+        args.get(index).cloned().ok_or_else(|| {
+            error(format!(
+                "{} requires {} argument(s)",
+                method_name,
+                index + 1
+            ))
+        })
+    };
+    match value {
+        Value::Option(option) => match (method_name, option) {
+            ("is_some", option) => Ok(Value::Bool(option.is_some())),
+            ("is_none", option) => Ok(Value::Bool(option.is_none())),
+            ("unwrap", Some(value)) => Ok(*value),
+            ("unwrap", None) => Err(error("called unwrap on None".to_string())),
+            ("expect", Some(value)) => Ok(*value),
+            ("expect", None) => Err(error(arg(0)?.to_string())),
+            ("unwrap_or", Some(value)) => Ok(*value),
+            ("unwrap_or", None) => arg(0),
+            ("unwrap_or_else", Some(value)) => Ok(*value),
+            ("unwrap_or_else", None) => call(arg(0)?, vec![]),
+            ("map", Some(value)) => Ok(Value::Option(Some(Box::new(call(arg(0)?, vec![*value])?)))),
+            ("map", None) => Ok(Value::Option(None)),
+            ("and_then", Some(value)) => call(arg(0)?, vec![*value]),
+            ("and_then", None) => Ok(Value::Option(None)),
+            ("or_else", Some(value)) => Ok(Value::Option(Some(value))),
+            ("or_else", None) => call(arg(0)?, vec![]),
+            ("ok_or", Some(value)) => Ok(Value::Result(Ok(value))),
+            ("ok_or", None) => Ok(Value::Result(Err(Box::new(arg(0)?)))),
+            _ => Err(error(format!("{} is not a method of Option", method_name))),
+        },
+        Value::Result(result) => match (method_name, result) {
+            ("is_suc", result) => Ok(Value::Bool(result.is_ok())),
+            ("is_fail", result) => Ok(Value::Bool(result.is_err())),
+            ("unwrap", Ok(value)) => Ok(*value),
+            ("unwrap", Err(e)) => Err(error(format!("called unwrap on Fail({})", e))),
+            ("expect", Ok(value)) => Ok(*value),
+            ("expect", Err(e)) => Err(error(format!("{}: {}", arg(0)?, e))),
+            ("unwrap_or", Ok(value)) => Ok(*value),
+            ("unwrap_or", Err(_)) => arg(0),
+            ("unwrap_or_else", Ok(value)) => Ok(*value),
+            ("unwrap_or_else", Err(e)) => call(arg(0)?, vec![*e]),
+            ("map", Ok(value)) => Ok(Value::Result(Ok(Box::new(call(arg(0)?, vec![*value])?)))),
+            ("map", Err(e)) => Ok(Value::Result(Err(e))),
+            ("map_err", Ok(value)) => Ok(Value::Result(Ok(value))),
+            ("map_err", Err(e)) => Ok(Value::Result(Err(Box::new(call(arg(0)?, vec![*e])?)))),
+            ("and_then", Ok(value)) => call(arg(0)?, vec![*value]),
+            ("and_then", Err(e)) => Ok(Value::Result(Err(e))),
+            ("or_else", Ok(value)) => Ok(Value::Result(Ok(value))),
+            ("or_else", Err(e)) => call(arg(0)?, vec![*e]),
+            _ => Err(error(format!("{} is not a method of Result", method_name))),
+        },
+        value => Err(error(format!(
+            "{} is not a method of {}",
+            method_name, value
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evals::{run_engines, run_source};
+    use crate::repl_session::EngineKind;
+    use crate::value::Value;
+    use fraction::Fraction;
+
+    fn number(n: i64) -> Value {
+        Value::Number(Fraction::from(n))
+    }
+
+    #[test]
+    fn test_option_methods() {
+        let input = r#"
+        val some: Option<number> = Some(2)
+        val none: Option<number> = None
+        some.is_some()
+        none.is_none()
+        some.map(\|x: number| => x * 10).unwrap()
+        none.unwrap_or(7)
+        some.and_then(\|x: number| => None).is_none()
+        none.or_else(\|| => Some(3)).unwrap()
+        none.ok_or("missing")
+        none.unwrap_or_else(\|| => 5)
+        "#;
+        let result = run_source(input).unwrap();
+        assert_eq!(
+            result[2..].to_vec(),
+            vec![
+                Value::Bool(true),
+                Value::Bool(true),
+                number(20),
+                number(7),
+                Value::Bool(true),
+                number(3),
+                Value::Result(Err(Box::new(Value::String("missing".into())))),
+                number(5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_result_methods() {
+        let input = r#"
+        val ok: Result<number, string> = Suc(2)
+        val ng: Result<number, string> = Fail("bad")
+        ok.is_suc()
+        ng.is_fail()
+        ok.map(\|x: number| => x + 1).unwrap()
+        ng.map_err(\|e: string| => e + "!")
+        ng.unwrap_or(0)
+        ok.and_then(\|x: number| => Fail("no")).is_fail()
+        ng.or_else(\|e: string| => Suc(9)).unwrap()
+        ng.unwrap_or_else(\|e: string| => 4)
+        ok.expect("must be suc")
+        "#;
+        let result = run_source(input).unwrap();
+        assert_eq!(
+            result[2..].to_vec(),
+            vec![
+                Value::Bool(true),
+                Value::Bool(true),
+                number(3),
+                Value::Result(Err(Box::new(Value::String("bad!".into())))),
+                number(0),
+                Value::Bool(true),
+                number(9),
+                number(4),
+                number(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unwrap_none_is_error() {
+        let result = run_source("val none: Option<number> = None\nnone.unwrap()");
+        assert!(result.is_err());
+        let result = run_source("val ng: Result<number, string> = Fail(\"bad\")\nng.expect(\"boom\")");
+        assert_eq!(result.unwrap_err().message, "boom: bad");
+    }
+
+    #[test]
+    fn test_methods_in_every_engine() {
+        let prelude = r#"
+        val some: Option<number> = Some(2)
+        val none: Option<number> = None
+        val ng: Result<number, string> = Fail("bad")
+        "#;
+        let cases = [
+            (r"some.map(\|x: number| => x * 10).unwrap()", Ok(number(20))),
+            (r"none.or_else(\|| => Some(3)).unwrap()", Ok(number(3))),
+            (r"ng.unwrap_or_else(\|e: string| => 4)", Ok(number(4))),
+            (
+                r#"ng.map_err(\|e: string| => e + "!")"#,
+                Ok(Value::Result(Err(Box::new(Value::String("bad!".into()))))),
+            ),
+            (r#"ng.expect("boom")"#, Err("boom: bad".to_string())),
+        ];
+        let engines = [EngineKind::Tree, EngineKind::Rc, EngineKind::Vm];
+        for (expression, expected) in cases {
+            let input = format!("{}{}", prelude, expression);
+            for (engine, result) in run_engines(&input, &engines) {
+                assert_eq!(result, expected, "engine: {}, {}", engine, expression);
+            }
+        }
+    }
+}
@@ -7,12 +7,12 @@ use crate::value::Value;
 
 pub fn prefix_op(
     op: TokenKind,
-    expr: Box<ASTNode>,
+    expr: ASTNode,
     line: usize,
     column: usize,
     env: &mut Env,
 ) -> Result<Value, RuntimeError> {
-    let value = eval(*expr, env)?;
+    let value = eval(expr, env)?;
     match (op.clone(), value) {
         (TokenKind::Minus, Value::Number(v)) => Ok(Value::Number(-v)),
         _ => Err(RuntimeError::new(
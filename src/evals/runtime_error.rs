@@ -1,8 +1,17 @@
+use crate::sandbox::Limit;
+use crate::value::Value;
+
 #[derive(Debug, Clone)]
 pub struct RuntimeError {
     pub message: String,
     pub line: usize,
     pub column: usize,
+    // `?` で関数から早期リターンする値。関数呼び出しの境界で Return に変換される
+    pub early_return: Option<Box<Value>>,
+    // `exit(code)` で終了するときの終了コード。エラーとして呼び出し元まで伝わる
+    pub exit_code: Option<i32>,
+    // 実行制限を超えて打ち切ったときの上限
+    pub limit: Option<Limit>,
 }
 
 impl RuntimeError {
@@ -11,19 +20,59 @@ impl RuntimeError {
             message: message.to_string(),
             line,
             column,
+            early_return: None,
+            exit_code: None,
+            limit: None,
         }
     }
 
+    pub fn early_return(value: Value, line: usize, column: usize) -> Self {
+        Self {
+            message: format!("`?` returned {} outside of a function", value),
+            line,
+            column,
+            early_return: Some(Box::new(value)),
+            exit_code: None,
+            limit: None,
+        }
+    }
+
+    pub fn exit(code: i32, line: usize, column: usize) -> Self {
+        Self {
+            exit_code: Some(code),
+            ..Self::new(&format!("exit({}) called", code), line, column)
+        }
+    }
+
+    pub fn limit(limit: Limit, line: usize, column: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..Self::new(&limit.to_string(), line, column)
+        }
+    }
+
+    // exit や実行制限による打ち切り。assert_fail でも失敗として扱わずに伝える
+    pub fn stops_execution(&self) -> bool {
+        self.exit_code.is_some() || self.limit.is_some()
+    }
+
     pub fn message_with_source(&self, source: &str) -> String {
         let lines: Vec<&str> = source.lines().collect();
-        let error_line = lines.get(self.line - 1).unwrap_or(&"");
+        // 位置の分からないエラーは line が 0 になるので、メッセージだけを出す
+        let Some(error_line) = self
+            .line
+            .checked_sub(1)
+            .map(|index| lines.get(index).unwrap_or(&""))
+        else {
+            return format!("Runtime Error: {}", self.message);
+        };
         format!(
             "Runtime Error: {}\n --> line {}, column {}\n | {}\n | {}^",
             self.message,
             self.line,
             self.column,
             error_line,
-            " ".repeat(self.column)
+            " ".repeat(self.column.saturating_sub(1))
         )
     }
 }
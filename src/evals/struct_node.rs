@@ -1,5 +1,5 @@
 use crate::ast::ASTNode;
-use crate::environment::{Env, EnvVariableType, MethodInfo, ValueType};
+use crate::environment::{Env, EnvVariableType, FunctionInfo, MethodInfo, ValueType};
 use crate::evals::eval;
 use crate::evals::runtime_error::RuntimeError;
 use crate::value::Value;
@@ -50,10 +50,21 @@ pub fn struct_node(
 pub fn impl_node(
     base_struct: Box<ValueType>,
     methods: Vec<ASTNode>,
+    constants: Vec<ASTNode>,
     line: usize,
     column: usize,
     env: &mut Env,
 ) -> Result<Value, RuntimeError> {
+    let struct_name = match base_struct.as_ref() {
+        ValueType::Struct { name, .. } => name.clone(),
+        _ => {
+            return Err(RuntimeError::new(
+                format!("Invalid base_struct in Impl: {:?}", base_struct).as_str(),
+                line,
+                column,
+            ))
+        }
+    };
     let mut impl_methods = HashMap::new();
     for method in methods {
         match method {
@@ -71,6 +82,18 @@ pub fn impl_node(
                     return_type,
                     is_mut,
                 };
+                // self を取らないメソッドは `Point::new(...)` の形で関数として呼び出す
+                if !has_self_argument(&method_info.arguments) {
+                    env.register_function(
+                        format!("{}::{}", struct_name, name),
+                        FunctionInfo {
+                            arguments: method_info.arguments.clone(),
+                            return_type: method_info.return_type.clone(),
+                            body: method_info.body.clone(),
+                            builtin: None,
+                        },
+                    );
+                }
                 impl_methods.insert(name, method_info);
             }
             _ => {
@@ -87,9 +110,20 @@ pub fn impl_node(
         methods: impl_methods,
     };
     env.register_impl(result.clone())?;
+    // 関連定数は `Point::ORIGIN` という名前の変数として登録する
+    for constant in constants {
+        eval(constant, env)?;
+    }
     Ok(result)
 }
 
+pub fn has_self_argument(arguments: &[ASTNode]) -> bool {
+    matches!(
+        arguments.first(),
+        Some(ASTNode::Variable { name, .. }) if name == "self"
+    )
+}
+
 pub fn struct_instance_node(
     name: String,
     fields: HashMap<String, ASTNode>,
@@ -198,9 +232,9 @@ pub fn struct_field_assign_node(
                         ));
                     }
                     let mut struct_fields = HashMap::new();
-                    match obj.unwrap().value.clone() {
+                    match obj.as_ref().unwrap().value.clone() {
                         Value::StructInstance { .. } => {
-                            let instance_value = obj.unwrap().value.clone();
+                            let instance_value = obj.as_ref().unwrap().value.clone();
                             let updated_value = match instance_value {
                                 Value::StructInstance { name, fields } => {
                                     let mut updated_fields = fields.clone();
@@ -310,7 +344,7 @@ pub fn struct_field_assign_node(
                         ));
                     }
                     let mut struct_fields = HashMap::new();
-                    match obj.unwrap().value.clone() {
+                    match obj.as_ref().unwrap().value.clone() {
                         Value::StructInstance {
                             name: _,
                             fields: obj_fields,
@@ -704,4 +738,131 @@ point.clear()
         assert_eq!(env.get_struct(&"Point".to_string()).is_some(), true);
         assert_eq!(env.get_struct(&"DummuStruct".to_string()).is_some(), false);
     }
+
+    #[test]
+    fn test_static_method() {
+        let input = r#"
+            struct Point {
+              x: number,
+              y: number,
+            }
+
+            impl Point {
+              fun new(x: number, y: number): Point {
+                return Point{x: x, y: y}
+              }
+
+              fun sum(self): number {
+                return self.x + self.y
+              }
+            }
+
+            val mut p = Point::new(1, 2)
+            p.sum()
+        "#;
+
+        let tokens = tokenize(&input.to_string());
+        let mut env = Env::new();
+        let builtins = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtins).parse_lines();
+        let result = evals(asts.unwrap(), &mut env).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(3))));
+    }
+
+    #[test]
+    fn test_static_method_called_on_instance() {
+        let input = r#"
+            struct Point {
+              x: number,
+            }
+
+            impl Point {
+              fun zero(): Point {
+                return Point{x: 0}
+              }
+            }
+
+            val mut p = Point{x: 1}
+            p.zero()
+        "#;
+
+        let tokens = tokenize(&input.to_string());
+        let mut env = Env::new();
+        let builtins = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtins).parse_lines();
+        let result = evals(asts.unwrap(), &mut env);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_associated_constant() {
+        let input = r#"
+            struct Point {
+              x: number,
+              y: number,
+            }
+
+            impl Point {
+              val ORIGIN = Point{x: 0, y: 0}
+              val DIMENSION = 2
+            }
+
+            val origin = Point::ORIGIN
+            origin.x + Point::DIMENSION
+        "#;
+
+        let tokens = tokenize(&input.to_string());
+        let mut env = Env::new();
+        let builtins = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtins).parse_lines();
+        let result = evals(asts.unwrap(), &mut env).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(2))));
+    }
+
+    #[test]
+    fn test_associated_constant_field_access() {
+        let input = r#"
+            struct Point {
+              x: number,
+              y: number,
+            }
+
+            impl Point {
+              val ORIGIN = Point{x: 3, y: 4}
+            }
+
+            Point::ORIGIN.x
+            Point::ORIGIN.x + Point::ORIGIN.y
+        "#;
+
+        let tokens = tokenize(&input.to_string());
+        let mut env = Env::new();
+        let builtins = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtins).parse_lines();
+        let result = evals(asts.unwrap(), &mut env).unwrap();
+        let n = result.len();
+        assert_eq!(result[n - 2], Value::Number(Fraction::from(3)));
+        assert_eq!(result[n - 1], Value::Number(Fraction::from(7)));
+    }
+
+    #[test]
+    fn test_struct_field_default_value() {
+        let input = r#"
+            struct Config {
+              name: string,
+              retries: number = 3,
+              verbose: bool = false,
+            }
+
+            val config = Config{name: "app", verbose: true}
+            config.retries
+        "#;
+
+        let tokens = tokenize(&input.to_string());
+        let mut env = Env::new();
+        let builtins = register_builtins(&mut env);
+        let asts = Parser::new(tokens, builtins).parse_lines();
+        let result = evals(asts.unwrap(), &mut env).unwrap();
+        assert_eq!(result.last(), Some(&Value::Number(Fraction::from(3))));
+    }
 }
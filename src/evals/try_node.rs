@@ -0,0 +1,93 @@
+use crate::ast::ASTNode;
+use crate::environment::Env;
+use crate::evals::eval;
+use crate::evals::runtime_error::RuntimeError;
+use crate::value::Value;
+
+// expr? : Suc/Some の中身を取り出し、Fail/None なら呼び出し元の関数から早期リターンする
+pub fn try_node(
+    expr: ASTNode,
+    line: usize,
+    column: usize,
+    env: &mut Env,
+) -> Result<Value, RuntimeError> {
+    match eval(expr, env)? {
+        Value::Option(Some(value)) => Ok(*value),
+        Value::Result(Ok(value)) => Ok(*value),
+        value @ (Value::Option(None) | Value::Result(Err(_))) => {
+            Err(RuntimeError::early_return(value, line, column))
+        }
+        value => Err(RuntimeError::new(
+            format!("`?` can only be applied to Result or Option, got {}", value).as_str(),
+            line,
+            column,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evals::run_source;
+    use fraction::Fraction;
+
+    #[test]
+    fn test_try_result() {
+        let input = r#"
+        fun check(x: number): Result<number, string> {
+            return if (x > 0) {
+                Suc(x)
+            } else {
+                Fail("negative")
+            }
+        }
+        fun sum(a: number, b: number): Result<number, string> {
+            val x = check(a)?
+            return Suc(x + check(b)?)
+        }
+        sum(1, 2)
+        sum(1, -2)
+        sum(-1, 2)
+        "#;
+        let result = run_source(input).unwrap();
+        let len = result.len();
+        assert_eq!(
+            result[len - 3],
+            Value::Result(Ok(Box::new(Value::Number(Fraction::from(3)))))
+        );
+        assert_eq!(
+            result[len - 2],
+            Value::Result(Err(Box::new(Value::String("negative".into()))))
+        );
+        assert_eq!(
+            result[len - 1],
+            Value::Result(Err(Box::new(Value::String("negative".into()))))
+        );
+    }
+
+    #[test]
+    fn test_try_option() {
+        let input = r#"
+        fun first_positive(x: number): Option<number> {
+            return if (x > 0) {
+                Some(x)
+            } else {
+                None
+            }
+        }
+        fun double(x: number): Option<number> {
+            val v = first_positive(x)?
+            return Some(v * 2)
+        }
+        double(2)
+        double(0)
+        "#;
+        let result = run_source(input).unwrap();
+        let len = result.len();
+        assert_eq!(
+            result[len - 2],
+            Value::Option(Some(Box::new(Value::Number(Fraction::from(4)))))
+        );
+        assert_eq!(result[len - 1], Value::Option(None));
+    }
+}
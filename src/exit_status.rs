@@ -0,0 +1,119 @@
+use crate::evals::runtime_error::RuntimeError;
+use crate::parsers::parse_error::{ParseError, ParseErrorKind};
+use crate::value::Value;
+use fraction::ToPrimitive;
+
+// make やシェルから失敗の種類を区別できるように、終了コードを分ける。
+// 2 は clap がコマンドラインの誤りに使う
+pub const SUCCESS: i32 = 0;
+pub const RUNTIME_ERROR: i32 = 1;
+pub const PARSE_ERROR: i32 = 3;
+pub const TYPE_ERROR: i32 = 4;
+pub const IO_ERROR: i32 = 5;
+
+/// 標準エラーに出すメッセージと、その失敗に対応する終了コード
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExitError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl ExitError {
+    pub fn new(code: i32, message: String) -> Self {
+        ExitError { code, message }
+    }
+
+    pub fn io(path: &str, error: std::io::Error) -> Self {
+        ExitError::new(IO_ERROR, format!("Error: cannot read {}: {}", path, error))
+    }
+
+    pub fn parse(error: &ParseError, source: &str) -> Self {
+        let code = match error.kind {
+            ParseErrorKind::Syntax => PARSE_ERROR,
+            ParseErrorKind::Type => TYPE_ERROR,
+        };
+        ExitError::new(code, error.message_with_source(source))
+    }
+
+    pub fn runtime(error: &RuntimeError, source: &str) -> Self {
+        ExitError::new(RUNTIME_ERROR, error.message_with_source(source))
+    }
+}
+
+/// `exit(code)` の引数。0 から 255 の整数でなければ None。
+/// OS は下位 8 ビットしか残さないので、範囲外の値は別のコードに化ける
+pub fn exit_code_argument(args: &[Value]) -> Option<i32> {
+    match args {
+        [] => Some(SUCCESS),
+        [Value::Number(code)] if code.denom().is_some_and(|denom| *denom == 1) => code
+            .to_i32()
+            .filter(|code| (0..=255).contains(code)),
+        _ => None,
+    }
+}
+
+/// `exit(code)` の呼び出し。評価器はこのエラーをプログラムの終了まで伝える
+pub fn exit_error(args: &[Value], line: usize, column: usize) -> RuntimeError {
+    match exit_code_argument(args) {
+        Some(code) => RuntimeError::exit(code, line, column),
+        None => RuntimeError::new("exit(code?) takes an integer exit code from 0 to 255", line, column),
+    }
+}
+
+/// プログラムの実行結果を終了コードにする。
+/// `fun main()` の戻り値が数値ならその値、`Fail(e)` なら e を表示して失敗、それ以外は成功
+pub fn exit_status(result: Result<Value, RuntimeError>, source: &str) -> Result<i32, ExitError> {
+    match result {
+        Ok(Value::Number(code)) => {
+            exit_code_argument(&[Value::Number(code)]).ok_or_else(|| {
+                ExitError::new(
+                    RUNTIME_ERROR,
+                    format!(
+                        "Error: main returned {}, which is not a valid exit code",
+                        code
+                    ),
+                )
+            })
+        }
+        Ok(Value::Result(Err(error))) => Err(ExitError::new(
+            RUNTIME_ERROR,
+            format!("Error: main returned Fail({})", error),
+        )),
+        Ok(_) => Ok(SUCCESS),
+        Err(RuntimeError {
+            exit_code: Some(code),
+            ..
+        }) => Ok(code),
+        Err(error) => Err(ExitError::runtime(&error, source)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_status() {
+        let number = |n: i64| Value::Number(n.into());
+        assert_eq!(exit_status(Ok(Value::Void), ""), Ok(SUCCESS));
+        assert_eq!(exit_status(Ok(number(7)), ""), Ok(7));
+        assert_eq!(exit_status(Err(RuntimeError::exit(42, 1, 1)), ""), Ok(42));
+        assert_eq!(
+            exit_status(Ok(Value::Result(Err(Box::new(number(1))))), "")
+                .unwrap_err()
+                .code,
+            RUNTIME_ERROR
+        );
+        assert_eq!(
+            exit_status(Err(RuntimeError::new("boom", 1, 0)), "x")
+                .unwrap_err()
+                .code,
+            RUNTIME_ERROR
+        );
+        assert_eq!(exit_code_argument(&[Value::Number((1, 2).into())]), None);
+        assert_eq!(exit_code_argument(&[number(255)]), Some(255));
+        assert_eq!(exit_code_argument(&[number(-1)]), None);
+        assert_eq!(exit_code_argument(&[number(300)]), None);
+        assert_eq!(exit_status(Ok(number(300)), "").unwrap_err().code, RUNTIME_ERROR);
+    }
+}
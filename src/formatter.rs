@@ -0,0 +1,562 @@
+use crate::analysis::analyze;
+use crate::exit_status::{ExitError, IO_ERROR};
+use crate::package::manifest::find_manifest_dir;
+use crate::package::PackageError;
+use crate::parsers::layout::Layout;
+use crate::parsers::parse_error::ParseError;
+use crate::test_runner::collect_sag_files;
+use crate::token::{Token, TokenKind};
+use crate::tokenizer::{tokenize, tokenize_with_comments};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const INDENT: &str = "    ";
+
+/// ソースを整形する。読めないソースはエラーにする。
+/// 改行の位置はパーサーが読んだ文とブロックから決め、書いたときの改行は空行のほかは残さない
+pub fn format_source(source: &str) -> Result<String, ParseError> {
+    let analysis = analyze(source);
+    if let Some(error) = analysis.error {
+        return Err(error);
+    }
+    let layout = analysis.parser.layout().cloned().unwrap_or_default();
+    let formatted = print(source, &layout);
+    // 整形で意味が変わっていないことを、区切りを除いたトークンの並びと読み直した文の数で確かめる
+    let reparsed = analyze(&formatted);
+    if token_kinds(&formatted) != token_kinds(source)
+        || reparsed.error.is_some()
+        || reparsed.nodes.len() != analysis.nodes.len()
+    {
+        return Err(ParseError::new(
+            "Cannot format this source without changing its meaning",
+            &Token {
+                kind: TokenKind::Eof,
+                line: 0,
+                column: 0,
+            },
+        ));
+    }
+    Ok(formatted)
+}
+
+/// 整形や検査をするファイル。パスが指定されなければ、プロジェクトかカレントディレクトリの .sag をすべて使う
+#[allow(dead_code)]
+pub fn source_files(paths: &[PathBuf], current_dir: &Path) -> Result<Vec<PathBuf>, PackageError> {
+    let roots = match paths.is_empty() {
+        true => vec![find_manifest_dir(current_dir).unwrap_or(current_dir.to_path_buf())],
+        false => paths.to_vec(),
+    };
+    let mut files = vec![];
+    for root in roots {
+        if root.is_file() {
+            files.push(root);
+        } else if root.is_dir() {
+            collect_sag_files(&root, &mut files)?;
+        } else {
+            return Err(PackageError::Io(format!("{} does not exist", root.display())));
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// ファイルを整形し、内容の変わるファイルを返す。check のときは書き換えない。
+/// 読めないファイルがあっても残りのファイルを続けて整形し、エラーはファイルごとに返す
+#[allow(dead_code)]
+pub fn format_files(files: &[PathBuf], check: bool) -> (Vec<PathBuf>, Vec<ExitError>) {
+    let mut changed = vec![];
+    let mut errors = vec![];
+    for file in files {
+        match format_file(file, check) {
+            Ok(true) => changed.push(file.clone()),
+            Ok(false) => {}
+            Err(error) => errors.push(error),
+        }
+    }
+    (changed, errors)
+}
+
+// 1 つのファイルを整形する。内容が変わるなら true
+#[allow(dead_code)]
+fn format_file(file: &Path, check: bool) -> Result<bool, ExitError> {
+    let path = file.display().to_string();
+    let source = fs::read_to_string(file).map_err(|e| ExitError::io(&path, e))?;
+    let formatted = format_source(&source).map_err(|e| {
+        let error = ExitError::parse(&e, &source);
+        ExitError::new(error.code, format!("{}: {}", path, error.message))
+    })?;
+    if formatted == source {
+        return Ok(false);
+    }
+    if !check {
+        fs::write(file, &formatted).map_err(|e| {
+            ExitError::new(IO_ERROR, format!("Error: cannot write {}: {}", path, e))
+        })?;
+    }
+    Ok(true)
+}
+
+// 区切りを除いたトークンの種類。文の区切りは整形で変わるので、並びだけを比べる
+fn token_kinds(source: &str) -> Vec<TokenKind> {
+    tokenize(&source.to_string())
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Eof)
+        .map(|token| token.kind)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Role {
+    Plain,
+    UnaryMinus,
+    PipeOpen,
+    PipeClose,
+    GenericOpen,
+    GenericClose,
+    // `{:` と `:}`
+    DictOpen,
+    DictClose,
+    // `import { x } from utils/strings` のモジュールのパスの `/`
+    PathSeparator,
+}
+
+// 出力する 1 つの単位。トークンかコメント
+struct Piece {
+    kind: TokenKind,
+    text: String,
+    line: usize,
+    column: usize,
+    role: Role,
+}
+
+impl Piece {
+    fn end_line(&self) -> usize {
+        self.line + self.text.matches('\n').count()
+    }
+
+    // 元のソースでの終わりの列。複数行なら分からない
+    fn end_column(&self) -> Option<usize> {
+        match self.text.contains('\n') {
+            true => None,
+            false => Some(self.column + self.text.chars().count()),
+        }
+    }
+
+    fn touches(&self, next: &Piece) -> bool {
+        self.line == next.line && self.end_column() == Some(next.column)
+    }
+
+    fn is_word(&self) -> bool {
+        self.text
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    }
+
+    // 値で終わるトークン。この後の `-` は二項演算子
+    fn ends_operand(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Identifier(_)
+                | TokenKind::Number(_)
+                | TokenKind::String(_)
+                | TokenKind::True
+                | TokenKind::False
+                | TokenKind::None
+                | TokenKind::Void
+                | TokenKind::RParen
+                | TokenKind::RBrancket
+                | TokenKind::RBrace
+                | TokenKind::Question
+        ) || self.role == Role::PipeClose
+    }
+}
+
+fn token_text(token: &Token, lines: &[Vec<char>]) -> String {
+    let text = match &token.kind {
+        TokenKind::Immutable => "val",
+        TokenKind::Mutable => "val mut",
+        TokenKind::Colon => ":",
+        TokenKind::DoubleColon => "::",
+        TokenKind::Identifier(name) => name,
+        TokenKind::String(s) => return format!("\"{}\"", s),
+        TokenKind::UnterminatedString(s) => return format!("\"{}", s),
+        // 分数に直さず、書いたとおりの数字を使う
+        TokenKind::Number(_) => {
+            return lines
+                .get(token.line.wrapping_sub(1))
+                .map(|line| {
+                    line.iter()
+                        .skip(token.column.saturating_sub(1))
+                        .take_while(|c| c.is_ascii_digit() || **c == '.')
+                        .collect()
+                })
+                .unwrap_or_default();
+        }
+        TokenKind::Bool(true) | TokenKind::True => "true",
+        TokenKind::Bool(false) | TokenKind::False => "false",
+        TokenKind::Void => "Void",
+        TokenKind::Equal => "=",
+        TokenKind::Plus => "+",
+        TokenKind::Minus => "-",
+        TokenKind::Mul => "*",
+        TokenKind::Div => "/",
+        TokenKind::Mod => "%",
+        TokenKind::Pow => "**",
+        TokenKind::LParen => "(",
+        TokenKind::RParen => ")",
+        TokenKind::LBrace => "{",
+        TokenKind::RBrace => "}",
+        TokenKind::Eof => ";",
+        TokenKind::Function => "fun",
+        TokenKind::BackSlash => "\\",
+        TokenKind::Pipe => "|",
+        TokenKind::Return => "return",
+        TokenKind::Break => "break",
+        TokenKind::Continue => "continue",
+        TokenKind::Comma => ",",
+        TokenKind::RArrow => "->",
+        TokenKind::Match => "match",
+        TokenKind::LBrancket => "[",
+        TokenKind::RBrancket => "]",
+        TokenKind::RRocket => "=>",
+        TokenKind::If => "if",
+        TokenKind::Else => "else",
+        TokenKind::Eq => "==",
+        TokenKind::Neq => "!=",
+        TokenKind::Lte => "<=",
+        TokenKind::Lt => "<",
+        TokenKind::Gte => ">=",
+        TokenKind::Gt => ">",
+        TokenKind::And => "and",
+        TokenKind::Or => "or",
+        TokenKind::Xor => "xor",
+        TokenKind::Struct => "struct",
+        TokenKind::Pub => "pub",
+        TokenKind::Dot => ".",
+        TokenKind::Question => "?",
+        TokenKind::Impl => "impl",
+        TokenKind::CommentBlock(content) => return format!("```{}```", content),
+        TokenKind::CommentLine(content) => return format!("//{}", content.trim_end()),
+        TokenKind::For => "for",
+        TokenKind::In => "in",
+        TokenKind::Import => "import",
+        TokenKind::From => "from",
+        TokenKind::Option => "Option",
+        TokenKind::Some => "Some",
+        TokenKind::None => "None",
+        TokenKind::Result => "Result",
+        TokenKind::Success => "Suc",
+        TokenKind::Failure => "Fail",
+        TokenKind::List => "List",
+        TokenKind::Dict => "Dict",
+    };
+    text.to_string()
+}
+
+// 出力する 1 行
+struct Line {
+    indent: usize,
+    // 前に空行を置くか
+    blank_before: bool,
+    pieces: Vec<Piece>,
+}
+
+// トークンとコメントを位置の順に並べ、パーサーが読んだ文とブロックごとに行を分ける
+fn layout_lines(source: &str, layout: &Layout) -> Vec<Line> {
+    let lines: Vec<Vec<char>> = source.lines().map(|line| line.chars().collect()).collect();
+    let (tokens, comments) = tokenize_with_comments(source);
+    let mut pieces: Vec<Piece> = tokens
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Eof)
+        .chain(comments)
+        .map(|token| Piece {
+            text: token_text(&token, &lines),
+            kind: token.kind,
+            line: token.line,
+            column: token.column,
+            role: Role::Plain,
+        })
+        .collect();
+    pieces.sort_by_key(|piece| (piece.line, piece.column));
+
+    let mut result: Vec<Line> = vec![];
+    // 開いている `{` ごとに、文を並べるブロックかどうか
+    let mut braces: Vec<bool> = vec![];
+    let mut depth = 0;
+    // ブロックの `{` や行コメントの後は、次のトークンを新しい行にする
+    let mut break_pending = false;
+    for piece in pieces {
+        let position = (piece.line, piece.column);
+        let closes_block = piece.kind == TokenKind::RBrace && braces.pop() == Some(true);
+        if closes_block {
+            depth -= 1;
+        }
+        let prev = result.last().and_then(|line| line.pieces.last());
+        let new_line = match (prev, &piece.kind) {
+            (None, _) => true,
+            // 行末のコメントは前のトークンと同じ行に残す
+            (Some(prev), TokenKind::CommentLine(_) | TokenKind::CommentBlock(_)) => {
+                prev.end_line() < piece.line
+            }
+            // 空のブロックは `{}` にする
+            (Some(prev), _) if closes_block && prev.kind == TokenKind::LBrace => false,
+            (Some(prev), _) => {
+                break_pending
+                    || closes_block
+                    || layout.items.contains(&position)
+                    || matches!(prev.kind, TokenKind::CommentBlock(_))
+                        && prev.end_line() < piece.line
+            }
+        };
+        if new_line {
+            let blank_before = prev.is_some_and(|prev| {
+                piece.line > prev.end_line() + 1
+                    && !closes_block
+                    && !(prev.kind == TokenKind::LBrace && break_pending)
+            });
+            result.push(Line {
+                indent: depth,
+                blank_before,
+                pieces: vec![],
+            });
+        }
+        match &piece.kind {
+            TokenKind::CommentLine(_) => break_pending = true,
+            TokenKind::CommentBlock(_) => {}
+            TokenKind::LBrace => {
+                let is_block = layout.blocks.contains(&position);
+                braces.push(is_block);
+                depth += is_block as usize;
+                break_pending = is_block;
+            }
+            _ => break_pending = false,
+        }
+        if let Some(line) = result.last_mut() {
+            line.pieces.push(piece);
+        }
+    }
+    result
+        .iter_mut()
+        .for_each(|line| assign_roles(&mut line.pieces));
+    result
+}
+
+// 前後のトークンで意味の変わる記号に役割をつける
+fn assign_roles(pieces: &mut [Piece]) {
+    let mut pipe_open = false;
+    let mut generics = 0;
+    // from の後の `名前/名前` を読んでいる間
+    let mut module_path = false;
+    for i in 0..pieces.len() {
+        let prev = i.checked_sub(1).map(|i| &pieces[i]);
+        let piece = &pieces[i];
+        let next = pieces.get(i + 1);
+        module_path = match &piece.kind {
+            TokenKind::From => true,
+            TokenKind::Identifier(_) | TokenKind::Div => module_path,
+            _ => false,
+        };
+        let role = match &piece.kind {
+            TokenKind::Div if module_path => Role::PathSeparator,
+            TokenKind::Minus if !prev.is_some_and(|p| p.ends_operand()) => Role::UnaryMinus,
+            TokenKind::Pipe => {
+                pipe_open = prev.is_some_and(|p| p.kind == TokenKind::BackSlash) || !pipe_open;
+                match pipe_open {
+                    true => Role::PipeOpen,
+                    false => Role::PipeClose,
+                }
+            }
+            TokenKind::Lt
+                if prev.is_some_and(|p| {
+                    matches!(p.kind, TokenKind::Option | TokenKind::Result)
+                        || matches!(&p.kind, TokenKind::Identifier(name) if name == "List" || name == "Dict")
+                }) =>
+            {
+                generics += 1;
+                Role::GenericOpen
+            }
+            TokenKind::Gt if generics > 0 => {
+                generics -= 1;
+                Role::GenericClose
+            }
+            TokenKind::Colon
+                if prev.is_some_and(|p| p.kind == TokenKind::LBrace && p.touches(piece)) =>
+            {
+                Role::DictOpen
+            }
+            TokenKind::Colon
+                if next.is_some_and(|n| n.kind == TokenKind::RBrace && piece.touches(n)) =>
+            {
+                Role::DictClose
+            }
+            _ => Role::Plain,
+        };
+        pieces[i].role = role;
+    }
+}
+
+// 同じ行の 2 つのトークンの間に空白を置くか
+fn needs_space(prev: &Piece, next: &Piece) -> bool {
+    use TokenKind::*;
+    // `Something` のようにつながって読まれた単語は離さない
+    if prev.is_word() && next.is_word() && prev.touches(next) {
+        return false;
+    }
+    match (&prev.kind, &next.kind) {
+        (Colon, Colon) | (LBrace, RBrace) => false,
+        (_, RParen | RBrancket | Comma | Dot | DoubleColon | Question | Eof) => false,
+        (LParen | LBrancket | Dot | DoubleColon | BackSlash, _) => false,
+        _ if prev.role == Role::PathSeparator || next.role == Role::PathSeparator => false,
+        _ if next.role == Role::DictClose => true,
+        (_, Colon) => false,
+        (_, RBrace) if prev.role == Role::DictClose => false,
+        (Identifier(_) | RParen | RBrancket | Some | Success | Failure, LParen) => false,
+        (Identifier(_) | RParen | RBrancket, LBrancket) => false,
+        _ => {
+            !matches!(
+                prev.role,
+                Role::PipeOpen | Role::UnaryMinus | Role::GenericOpen
+            ) && !matches!(
+                next.role,
+                Role::PipeClose | Role::GenericOpen | Role::GenericClose
+            )
+        }
+    }
+}
+
+fn print(source: &str, layout: &Layout) -> String {
+    let mut out = String::new();
+    for line in layout_lines(source, layout) {
+        if line.blank_before {
+            out.push('\n');
+        }
+        out.push_str(&INDENT.repeat(line.indent));
+        for (i, piece) in line.pieces.iter().enumerate() {
+            if let Some(prev) = i.checked_sub(1).map(|i| &line.pieces[i]) {
+                let spaces = match &piece.kind {
+                    // 行末のコメントは元の間隔を残す
+                    TokenKind::CommentLine(_) | TokenKind::CommentBlock(_) => prev
+                        .end_column()
+                        .filter(|_| prev.line == piece.line)
+                        .map_or(1, |end| piece.column.saturating_sub(end).max(1)),
+                    _ => needs_space(prev, piece) as usize,
+                };
+                out.push_str(&" ".repeat(spaces));
+            }
+            out.push_str(&piece.text);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_source() {
+        let source = "val   x=1+-2 // one\n\n\n\nfun f(a:number,b :number):number{\n  return a*b\n}\nstruct Point {\nx: number,\n      y: number\n}\nval p=Point{x: 1, y: 2}\nval d={:\"a\"=>1:}\nval l: List<number> = [1,2]\nval g=\\|n: number|=>n**2\n|2, 3| -> f\n";
+        let expected = "val x = 1 + -2 // one\n\nfun f(a: number, b: number): number {\n    return a * b\n}\nstruct Point {\n    x: number,\n    y: number\n}\nval p = Point { x: 1, y: 2 }\nval d = {: \"a\" => 1 :}\nval l: List<number> = [1, 2]\nval g = \\|n: number| => n ** 2\n|2, 3| -> f\n";
+        assert_eq!(format_source(source).unwrap(), expected);
+        assert_eq!(format_source(expected).unwrap(), expected);
+        assert!(format_source("val = 1").is_err());
+    }
+
+    #[test]
+    fn test_format_module_path() {
+        let dir = std::env::temp_dir().join("sag_format_module_path");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("utils/text")).unwrap();
+        fs::write(dir.join("utils/text/strings.sag"), "pub val name = \"strings\"\n").unwrap();
+        fs::write(
+            dir.join("main.sag"),
+            "import {name} from utils / text/strings\nval x = 4/2\n",
+        )
+        .unwrap();
+
+        let expected = "import { name } from utils/text/strings\nval x = 4 / 2\n";
+        assert_eq!(format_files(&[dir.join("main.sag")], false).1.len(), 0);
+        assert_eq!(fs::read_to_string(dir.join("main.sag")).unwrap(), expected);
+        assert_eq!(format_files(&[dir.join("main.sag")], true).0.len(), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_format_source_ignores_line_breaks() {
+        let source = "struct Point { x: number, pub y: number }\nimpl Point { fun new(x: number): Point { return Point { x: x, y: 0 } } val ORIGIN = Point { x: 0, y: 0 } }\nval a = 1; val b = 2\nfun empty() {}\nif (a > 0) { print(a) }\nelse { print(b) }\nval r = match (Some(1)) { Some(v) => { v } None => { 0 } }\nval f = add(1,\n  2)\n";
+        let expected = "struct Point {\n    x: number,\n    pub y: number\n}\nimpl Point {\n    fun new(x: number): Point {\n        return Point { x: x, y: 0 }\n    }\n    val ORIGIN = Point { x: 0, y: 0 }\n}\nval a = 1\nval b = 2\nfun empty() {}\nif (a > 0) {\n    print(a)\n} else {\n    print(b)\n}\nval r = match (Some(1)) {\n    Some(v) => {\n        v\n    }\n    None => {\n        0\n    }\n}\nval f = add(1, 2)\n";
+        let source = format!("fun add(x: number, y: number): number {{ return x + y }}\n{}", source);
+        let expected = format!("fun add(x: number, y: number): number {{\n    return x + y\n}}\n{}", expected);
+        assert_eq!(format_source(&source).unwrap(), expected);
+        assert_eq!(format_source(&expected).unwrap(), expected);
+
+        // コメントは前のトークンと同じ行か、独立した行に残す
+        let source = "fun f() { // start\n  // inside\n\n\n  val a = 1 // trailing\n  a }\n";
+        let expected = "fun f() { // start\n    // inside\n\n    val a = 1 // trailing\n    a\n}\n";
+        assert_eq!(format_source(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_examples_are_stable() {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("example");
+        for file in source_files(&[examples], Path::new(".")).unwrap() {
+            let source = fs::read_to_string(&file).unwrap();
+            let formatted = format_source(&source).unwrap();
+            assert_eq!(
+                format_source(&formatted).unwrap(),
+                formatted,
+                "{}",
+                file.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_files() {
+        let root = std::env::temp_dir().join("sag_formatter");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.sag"), "val x=1\n").unwrap();
+        fs::write(root.join("src/done.sag"), "val y = 2\n").unwrap();
+        fs::write(root.join("notes.txt"), "val x=1\n").unwrap();
+
+        let files = source_files(&[root.clone()], &root).unwrap();
+        assert_eq!(
+            files,
+            vec![root.join("src/done.sag"), root.join("src/main.sag")]
+        );
+        assert_eq!(
+            format_files(&files, true),
+            (vec![root.join("src/main.sag")], vec![])
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("src/main.sag")).unwrap(),
+            "val x=1\n"
+        );
+        assert_eq!(
+            format_files(&files, false),
+            (vec![root.join("src/main.sag")], vec![])
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("src/main.sag")).unwrap(),
+            "val x = 1\n"
+        );
+        assert_eq!(format_files(&files, true), (vec![], vec![]));
+
+        // 読めないファイルがあっても、ほかのファイルを調べてエラーをすべて返す
+        fs::write(root.join("src/done.sag"), "val = 2").unwrap();
+        fs::write(root.join("src/main.sag"), "val = 1").unwrap();
+        fs::write(root.join("src/other.sag"), "val z=3\n").unwrap();
+        let files = source_files(&[root.clone()], &root).unwrap();
+        let (changed, errors) = format_files(&files, true);
+        assert_eq!(changed, vec![root.join("src/other.sag")]);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].code, crate::exit_status::PARSE_ERROR);
+        assert!(errors[0].message.contains("done.sag"));
+        assert!(errors[1].message.contains("main.sag"));
+        assert!(source_files(&[root.join("missing")], &root).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -1,30 +1,87 @@
+use crate::package::lockfile::{LockedPackage, PackageSource};
+use crate::package::manifest::{find_manifest_dir, Dependency, DependencySource, Manifest};
+use crate::package::registry::Registry;
+use crate::package::resolver::{install_locked_package, install_manifest, resolve};
+use crate::package::version::VersionReq;
+use crate::package::{current_dir, PackageError, PACKAGE_DIR};
 use std::fs;
 use std::path::PathBuf;
 
-fn install_local_package(source_path: PathBuf, target_dir: PathBuf) {
+fn install_local_package(source_path: PathBuf, target_dir: PathBuf) -> Result<(), PackageError> {
     let file_name = source_path.file_name().unwrap();
     let target_path = target_dir.join(file_name);
 
-    fs::copy(&source_path, &target_path).expect("Failed to copy file");
+    fs::copy(&source_path, &target_path).map_err(|e| PackageError::io("copy", &source_path, e))?;
 
     println!("Installed local package: {:?}", target_path);
+    Ok(())
 }
 
-fn install_remote_package(_package_name: String, _target_dir: PathBuf) {
-    unimplemented!();
+// パッケージのディレクトリ、またはレジストリのパッケージ名を依存関係ごとインストールする
+fn install_dependency(dependency: Dependency, target_dir: PathBuf) -> Result<(), PackageError> {
+    let manifest = find_manifest_dir(&current_dir()?)
+        .map(|dir| Manifest::load(&dir))
+        .transpose()?;
+    let registry = Registry::configured(manifest.as_ref().and_then(|m| m.registry.as_deref()))?;
+    let packages = resolve(&[dependency], "sag install", registry.as_ref(), None)?;
+    for package in &packages {
+        install_locked_package(package, &target_dir)?;
+        print_installed(package);
+    }
+    Ok(())
+}
+
+fn print_installed(package: &LockedPackage) {
+    let source = match &package.source {
+        PackageSource::Path(dir) => dir.display().to_string(),
+        PackageSource::Registry(root) => format!("registry {}", root.display()),
+    };
+    println!(
+        "Installed {} {} ({})",
+        package.name, package.version, source
+    );
 }
 
-pub fn install_package(package_or_path: String) {
+pub fn install_package(package_or_path: String) -> Result<(), PackageError> {
     let path = PathBuf::from(&package_or_path);
-    let target_dir = PathBuf::from("./.sag_packages/");
+    let target_dir = PathBuf::from("./").join(PACKAGE_DIR);
 
     if !target_dir.exists() {
-        fs::create_dir_all(&target_dir).expect("Failed to create package directory");
+        fs::create_dir_all(&target_dir).map_err(|e| PackageError::io("create", &target_dir, e))?;
     }
 
-    if path.exists() {
-        install_local_package(path, target_dir);
+    if path.is_file() {
+        install_local_package(path, target_dir)
+    } else if path.is_dir() {
+        let name = Manifest::load(&path)?.name;
+        install_dependency(
+            Dependency {
+                name,
+                req: VersionReq::any(),
+                source: DependencySource::Path(path),
+            },
+            target_dir,
+        )
     } else {
-        install_remote_package(package_or_path, target_dir);
+        install_dependency(
+            Dependency {
+                name: package_or_path,
+                req: VersionReq::any(),
+                source: DependencySource::Registry,
+            },
+            target_dir,
+        )
+    }
+}
+
+/// `sag install`: sag.toml の依存関係をすべてインストールして sag.lock を更新する
+pub fn install_all() -> Result<(), PackageError> {
+    let dir = find_manifest_dir(&current_dir()?)
+        .ok_or("sag.toml not found in this directory or any parent".to_string())?;
+    let packages = install_manifest(&dir)?;
+    for package in &packages {
+        print_installed(package);
     }
+    println!("{} package(s) installed", packages.len());
+    Ok(())
 }
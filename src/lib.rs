@@ -1,21 +1,40 @@
+mod analysis;
 mod ast;
 mod builtin;
 mod compiler;
+mod conversion;
+mod engine;
 mod environment;
 mod evals;
+mod exit_status;
+mod module_graph;
+mod module_resolver;
 mod parsers;
 mod rc_builtins;
 mod rc_env;
 mod rc_evals;
 mod rc_value;
+mod sandbox;
 mod token;
 mod tokenizer;
 mod value;
 mod wasm;
 
+// main.rs と共有するモジュール。コマンドラインだけが使う項目には個別に #[allow(dead_code)] を付ける
+mod formatter;
+mod package;
+mod repl_session;
+mod test_runner;
+
+pub use builtin::{set_script_args, Capabilities, OutputSink};
 pub use compiler::{compile_file, run_compiled_file};
+pub use conversion::{FromSag, IntoSag};
+pub use engine::{Engine, SagError};
+pub use exit_status::ExitError;
 pub use rc_builtins::register_rc_builtins;
 pub use rc_env::RcEnv;
 pub use rc_evals::{rc_eval, rc_evals};
 pub use rc_value::RcValue;
+pub use sandbox::{Limit, Limits};
+pub use value::Value;
 pub use wasm::evaluate;
@@ -0,0 +1,870 @@
+use crate::analysis::{analyze, Analysis};
+use crate::ast::ASTNode;
+use crate::environment::EnvVariableType;
+use crate::exit_status::ExitError;
+use crate::parsers::parse_error::ParseError;
+use crate::parsers::symbol::SymbolKind;
+use crate::token::TokenKind;
+use crate::tokenizer::tokenize_with_comments;
+use crate::value::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+// 変数を書き換える組み込みメソッド
+const MUTATING_METHODS: &[&str] = &[
+    "push",
+    "pop",
+    "clear",
+    "insert",
+    "remove",
+    "update",
+    "entry",
+    "get_or_insert",
+];
+
+/// 警告の種類。`// sag:allow(unused-variable)` のようにコメントで止められる
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintCode {
+    UnusedVariable,
+    UnusedImport,
+    UnusedMut,
+    Shadowing,
+    UnreachableCode,
+    NonExhaustiveMatch,
+    ConstantComparison,
+}
+
+impl LintCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LintCode::UnusedVariable => "unused-variable",
+            LintCode::UnusedImport => "unused-import",
+            LintCode::UnusedMut => "unused-mut",
+            LintCode::Shadowing => "shadowing",
+            LintCode::UnreachableCode => "unreachable-code",
+            LintCode::NonExhaustiveMatch => "non-exhaustive-match",
+            LintCode::ConstantComparison => "constant-comparison",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub code: LintCode,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: warning[{}]: {}",
+            self.line,
+            self.column,
+            self.code.as_str(),
+            self.message
+        )
+    }
+}
+
+/// ソースを検査して警告を位置の順に返す。読めないソースはエラーにする
+pub fn lint_source(source: &str) -> Result<Vec<Warning>, ParseError> {
+    let analysis = analyze(source);
+    match analysis.error {
+        Some(error) => Err(error),
+        None => Ok(lint(&analysis, source)),
+    }
+}
+
+/// 読み終えたソースを検査する。エラーのあるソースではエラーの前までを見る
+pub fn lint(analysis: &Analysis, source: &str) -> Vec<Warning> {
+    let mut linter = Linter::new(analysis);
+    linter.enter(true);
+    linter.visit_statements(&analysis.nodes);
+    linter.leave();
+    linter.check_imports();
+
+    let allowed = allowed_codes(source);
+    let mut warnings: Vec<Warning> = linter
+        .warnings
+        .into_iter()
+        .filter(|w| {
+            allowed
+                .get(&w.line)
+                .is_none_or(|codes| !codes.contains(w.code.as_str()))
+        })
+        .collect();
+    warnings.sort_by_key(|w| (w.line, w.column));
+    warnings.dedup();
+    warnings
+}
+
+/// ファイルを検査し、警告のあるファイルだけを返す
+pub fn lint_files(files: &[PathBuf]) -> Result<Vec<(PathBuf, Vec<Warning>)>, ExitError> {
+    let mut results = vec![];
+    for file in files {
+        let path = file.display().to_string();
+        let source = fs::read_to_string(file).map_err(|e| ExitError::io(&path, e))?;
+        let warnings = lint_source(&source).map_err(|e| {
+            let error = ExitError::parse(&e, &source);
+            ExitError::new(error.code, format!("{}: {}", path, error.message))
+        })?;
+        if !warnings.is_empty() {
+            results.push((file.clone(), warnings));
+        }
+    }
+    Ok(results)
+}
+
+// `// sag:allow(code, ...)` のある行と、その行で止める警告。
+// コメントだけの行なら次の行にも効く
+fn allowed_codes(source: &str) -> HashMap<usize, HashSet<String>> {
+    let (tokens, comments) = tokenize_with_comments(source);
+    let mut allowed: HashMap<usize, HashSet<String>> = HashMap::new();
+    for comment in comments {
+        let TokenKind::CommentLine(text) = comment.kind else {
+            continue;
+        };
+        let Some(codes) = text
+            .trim()
+            .strip_prefix("sag:allow(")
+            .and_then(|rest| rest.strip_suffix(')'))
+        else {
+            continue;
+        };
+        let codes: HashSet<String> = codes.split(',').map(|c| c.trim().to_string()).collect();
+        let standalone = !tokens
+            .iter()
+            .any(|t| t.line == comment.line && t.kind != TokenKind::Eof);
+        let lines = match standalone {
+            true => vec![comment.line, comment.line + 1],
+            false => vec![comment.line],
+        };
+        for line in lines {
+            allowed
+                .entry(line)
+                .or_default()
+                .extend(codes.iter().cloned());
+        }
+    }
+    allowed
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BindingKind {
+    // val で宣言した変数。使われていなければ警告する
+    Val,
+    // 引数、for の変数、match の束縛、pub な val など。使われなくても警告しない
+    Other,
+}
+
+struct Binding {
+    name: String,
+    kind: BindingKind,
+    mutable: bool,
+    used: bool,
+    reassigned: bool,
+    line: usize,
+    column: usize,
+}
+
+struct Frame {
+    bindings: Vec<Binding>,
+    // 関数の本体。外側の変数と同じ名前でも shadowing にしない
+    function: bool,
+}
+
+struct Linter<'a> {
+    analysis: &'a Analysis,
+    frames: Vec<Frame>,
+    warnings: Vec<Warning>,
+    // self を書き換えるメソッドの名前
+    mut_methods: HashSet<String>,
+    // pub import で再エクスポートする名前
+    exported_imports: HashSet<String>,
+}
+
+impl<'a> Linter<'a> {
+    fn new(analysis: &'a Analysis) -> Self {
+        let mut mut_methods = HashSet::new();
+        let mut exported_imports = HashSet::new();
+        for node in &analysis.nodes {
+            match node {
+                ASTNode::Impl { methods, .. } => {
+                    for method in methods {
+                        if let ASTNode::Method {
+                            name, is_mut: true, ..
+                        } = method
+                        {
+                            mut_methods.insert(name.clone());
+                        }
+                    }
+                }
+                ASTNode::Public { node, .. } => {
+                    if let ASTNode::Import { aliases, .. } = node.as_ref() {
+                        exported_imports.extend(aliases.iter().cloned());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Linter {
+            analysis,
+            frames: vec![],
+            warnings: vec![],
+            mut_methods,
+            exported_imports,
+        }
+    }
+
+    fn warn(&mut self, code: LintCode, message: String, (line, column): (usize, usize)) {
+        self.warnings.push(Warning {
+            code,
+            message,
+            line,
+            column,
+        });
+    }
+
+    fn enter(&mut self, function: bool) {
+        self.frames.push(Frame {
+            bindings: vec![],
+            function,
+        });
+    }
+
+    fn leave(&mut self) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        for binding in frame.bindings {
+            if binding.kind != BindingKind::Val || binding.name.starts_with('_') {
+                continue;
+            }
+            let position = (binding.line, binding.column);
+            if !binding.used {
+                self.warn(
+                    LintCode::UnusedVariable,
+                    format!("unused variable `{}`", binding.name),
+                    position,
+                );
+            } else if binding.mutable && !binding.reassigned {
+                self.warn(
+                    LintCode::UnusedMut,
+                    format!(
+                        "`{}` is declared `val mut` but never reassigned",
+                        binding.name
+                    ),
+                    position,
+                );
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str, kind: BindingKind, mutable: bool, position: (usize, usize)) {
+        if name == "_" || name.starts_with('<') {
+            return;
+        }
+        // 同じ関数の中で見える宣言を探す
+        let mut shadowed = None;
+        for frame in self.frames.iter().rev() {
+            if let Some(binding) = frame.bindings.iter().rev().find(|b| b.name == name) {
+                shadowed = Some((binding.line, binding.column));
+                break;
+            }
+            if frame.function {
+                break;
+            }
+        }
+        if let Some((line, column)) = shadowed {
+            self.warn(
+                LintCode::Shadowing,
+                format!(
+                    "`{}` shadows the declaration at line {}, column {}",
+                    name, line, column
+                ),
+                position,
+            );
+        }
+        if let Some(frame) = self.frames.last_mut() {
+            frame.bindings.push(Binding {
+                name: name.to_string(),
+                kind,
+                mutable,
+                used: false,
+                reassigned: false,
+                line: position.0,
+                column: position.1,
+            });
+        }
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<&mut Binding> {
+        self.frames
+            .iter_mut()
+            .rev()
+            .find_map(|frame| frame.bindings.iter_mut().rev().find(|b| b.name == name))
+    }
+
+    fn use_name(&mut self, name: &str) {
+        if let Some(binding) = self.lookup(name) {
+            binding.used = true;
+        }
+    }
+
+    fn reassign(&mut self, name: &str) {
+        if let Some(binding) = self.lookup(name) {
+            binding.reassigned = true;
+        }
+    }
+
+    // Assign の位置は val なので、記録した名前の位置を使う
+    fn name_position(&self, name: &str, position: (usize, usize)) -> (usize, usize) {
+        self.analysis
+            .symbols()
+            .iter()
+            .find(|s| {
+                s.kind == SymbolKind::Variable && s.name == name && (s.line, s.column) >= position
+            })
+            .map(|s| (s.line, s.column))
+            .unwrap_or(position)
+    }
+
+    fn visit_statements(&mut self, nodes: &[ASTNode]) {
+        let mut exit = None;
+        // 1 つのブロックで 1 回だけ警告する
+        let mut reported = false;
+        for node in nodes {
+            if matches!(node, ASTNode::CommentBlock { .. }) {
+                continue;
+            }
+            if let Some(keyword) = exit.take() {
+                let position = self.statement_start(node.position());
+                self.warn(
+                    LintCode::UnreachableCode,
+                    format!("unreachable code after `{}`", keyword),
+                    position,
+                );
+                reported = true;
+            }
+            self.visit(node);
+            if !reported && exit.is_none() {
+                exit = diverges(node);
+            }
+        }
+    }
+
+    // 関数呼び出しなどの位置は文の先頭ではないので、その行で直前の区切りの次のトークンにする
+    fn statement_start(&self, (line, column): (usize, usize)) -> (usize, usize) {
+        let mut start = None;
+        let mut after_separator = true;
+        for (token, _) in &self.analysis.spans {
+            if token.line != line || token.column > column {
+                continue;
+            }
+            if token.kind == TokenKind::Eof {
+                after_separator = true;
+            } else if after_separator {
+                start = Some((token.line, token.column));
+                after_separator = false;
+            }
+        }
+        start.unwrap_or((line, column))
+    }
+
+    fn visit_all(&mut self, nodes: &[ASTNode]) {
+        for node in nodes {
+            self.visit(node);
+        }
+    }
+
+    fn declare_arguments(&mut self, arguments: &[ASTNode]) {
+        for argument in arguments {
+            self.declare_pattern(argument, BindingKind::Other, false);
+        }
+    }
+
+    fn declare_pattern(&mut self, pattern: &ASTNode, kind: BindingKind, mutable: bool) {
+        match pattern {
+            ASTNode::Variable {
+                name, line, column, ..
+            } => self.declare(name, kind, mutable, (*line, *column)),
+            ASTNode::Tuple { elements, .. } => {
+                for element in elements {
+                    self.declare_pattern(element, kind, mutable);
+                }
+            }
+            ASTNode::StructInstance { fields, .. } => {
+                let mut fields: Vec<&ASTNode> = fields.values().collect();
+                fields.sort_by_key(|field| field.position());
+                for field in fields {
+                    self.declare_pattern(field, kind, mutable);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn visit(&mut self, node: &ASTNode) {
+        match node {
+            ASTNode::Variable { name, .. } => self.use_name(name),
+            ASTNode::Block { nodes, .. } => {
+                self.enter(false);
+                self.visit_statements(nodes);
+                self.leave();
+            }
+            ASTNode::PrefixOp { expr, .. }
+            | ASTNode::Return { expr, .. }
+            | ASTNode::Try { expr, .. } => self.visit(expr),
+            ASTNode::BinaryOp { left, right, .. } => {
+                self.visit(left);
+                self.visit(right);
+            }
+            ASTNode::Eq { left, right, .. }
+            | ASTNode::Gte { left, right, .. }
+            | ASTNode::Gt { left, right, .. }
+            | ASTNode::Lte { left, right, .. }
+            | ASTNode::Lt { left, right, .. } => {
+                if let Some(result) = constant_comparison(node) {
+                    self.warn(
+                        LintCode::ConstantComparison,
+                        format!("this comparison is always {}", result),
+                        node.position(),
+                    );
+                }
+                self.visit(left);
+                self.visit(right);
+            }
+            ASTNode::Assign {
+                name,
+                value,
+                variable_type,
+                is_new,
+                line,
+                column,
+                ..
+            } => {
+                self.visit(value);
+                if !is_new {
+                    self.reassign(name);
+                } else if !name.contains("::") {
+                    let position = self.name_position(name, (*line, *column));
+                    let mutable = *variable_type == EnvVariableType::Mutable;
+                    self.declare(name, BindingKind::Val, mutable, position);
+                }
+            }
+            ASTNode::Destructure {
+                pattern,
+                value,
+                variable_type,
+                ..
+            } => {
+                self.visit(value);
+                // for (k, v) in ... を変換したものはループ変数として扱う
+                let kind = match value.as_ref() {
+                    ASTNode::Variable { name, .. } if name.starts_with('<') => BindingKind::Other,
+                    _ => BindingKind::Val,
+                };
+                let mutable = *variable_type == EnvVariableType::Mutable;
+                self.declare_pattern(pattern, kind, mutable);
+            }
+            ASTNode::Function {
+                arguments, body, ..
+            }
+            | ASTNode::Method {
+                arguments, body, ..
+            } => {
+                self.enter(true);
+                self.declare_arguments(arguments);
+                self.visit(body);
+                self.leave();
+            }
+            ASTNode::Lambda {
+                arguments, body, ..
+            } => {
+                self.enter(false);
+                self.declare_arguments(arguments);
+                self.visit(body);
+                self.leave();
+            }
+            ASTNode::MethodCall {
+                method_name,
+                caller,
+                arguments,
+                builtin,
+                ..
+            } => {
+                self.visit(caller);
+                self.visit(arguments);
+                let mutates = match builtin {
+                    true => MUTATING_METHODS.contains(&method_name.as_str()),
+                    false => self.mut_methods.contains(method_name),
+                };
+                if let (true, Some(name)) = (mutates, root_variable(caller)) {
+                    self.reassign(name);
+                }
+            }
+            ASTNode::FunctionCall {
+                name, arguments, ..
+            } => {
+                // ラムダを入れた変数の呼び出し
+                self.use_name(name);
+                self.visit(arguments);
+            }
+            ASTNode::FunctionCallArgs { args, .. } => self.visit_all(args),
+            ASTNode::LambdaCall {
+                lambda, arguments, ..
+            } => {
+                self.visit(lambda);
+                self.visit_all(arguments);
+            }
+            ASTNode::If {
+                condition,
+                then,
+                else_,
+                ..
+            } => {
+                self.visit(condition);
+                self.visit(then);
+                if let Some(else_) = else_ {
+                    self.visit(else_);
+                }
+            }
+            ASTNode::Struct { fields, .. } => {
+                for field in fields.values() {
+                    self.visit(field);
+                }
+            }
+            ASTNode::StructField {
+                default_value: Some(value),
+                ..
+            } => self.visit(value),
+            ASTNode::StructFieldAccess { instance, .. } => self.visit(instance),
+            ASTNode::StructFieldAssign {
+                instance, value, ..
+            } => {
+                self.visit(instance);
+                self.visit(value);
+                if let Some(name) = root_variable(instance) {
+                    self.reassign(name);
+                }
+            }
+            ASTNode::StructInstance { fields, .. } => {
+                for field in fields.values() {
+                    self.visit(field);
+                }
+            }
+            ASTNode::Tuple { elements, .. } => self.visit_all(elements),
+            ASTNode::Impl {
+                methods, constants, ..
+            } => {
+                self.visit_all(constants);
+                self.visit_all(methods);
+            }
+            ASTNode::For {
+                variable,
+                iterable,
+                body,
+                line,
+                column,
+            } => {
+                self.visit(iterable);
+                self.enter(false);
+                let position = self.name_position(variable, (*line, *column));
+                self.declare(variable, BindingKind::Other, false, position);
+                self.visit(body);
+                self.leave();
+            }
+            ASTNode::ModuleAccess { member, .. } => self.visit_module_member(member),
+            ASTNode::Public { node, .. } => match node.as_ref() {
+                // エクスポートする変数は使われていなくてもよい
+                ASTNode::Assign {
+                    name,
+                    value,
+                    line,
+                    column,
+                    ..
+                } => {
+                    self.visit(value);
+                    let position = self.name_position(name, (*line, *column));
+                    self.declare(name, BindingKind::Other, false, position);
+                }
+                node => self.visit(node),
+            },
+            ASTNode::OptionSome { value, .. }
+            | ASTNode::ResultSuccess { value, .. }
+            | ASTNode::ResultFailure { value, .. } => self.visit(value),
+            ASTNode::Match {
+                expression, cases, ..
+            } => {
+                self.visit(expression);
+                if let Some(missing) = missing_patterns(cases) {
+                    self.warn(
+                        LintCode::NonExhaustiveMatch,
+                        format!("non-exhaustive match: {} not covered", missing),
+                        expression.position(),
+                    );
+                }
+                for (pattern, body) in cases {
+                    self.enter(false);
+                    if let ASTNode::OptionSome { value, .. }
+                    | ASTNode::ResultSuccess { value, .. }
+                    | ASTNode::ResultFailure { value, .. } = pattern
+                    {
+                        self.declare_pattern(value, BindingKind::Other, false);
+                    }
+                    self.visit(body);
+                    self.leave();
+                }
+            }
+            ASTNode::DictKeyAccess { dict, key, .. } => {
+                self.visit(dict);
+                self.visit(key);
+            }
+            ASTNode::ListIndexAccess { list, index, .. } => {
+                self.visit(list);
+                self.visit(index);
+            }
+            ASTNode::DictAssign {
+                dict, key, value, ..
+            } => {
+                self.visit(dict);
+                self.visit(key);
+                self.visit(value);
+                if let Some(name) = root_variable(dict) {
+                    self.reassign(name);
+                }
+            }
+            ASTNode::ListIndexAssign {
+                list, index, value, ..
+            } => {
+                self.visit(list);
+                self.visit(index);
+                self.visit(value);
+                if let Some(name) = root_variable(list) {
+                    self.reassign(name);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // モジュールのメンバーの名前はこのファイルの変数ではないので、引数だけを見る
+    fn visit_module_member(&mut self, member: &ASTNode) {
+        match member {
+            ASTNode::FunctionCall { arguments, .. } => self.visit(arguments),
+            ASTNode::StructInstance { fields, .. } => {
+                for field in fields.values() {
+                    self.visit(field);
+                }
+            }
+            ASTNode::ModuleAccess { member, .. } => self.visit_module_member(member),
+            _ => {}
+        }
+    }
+
+    // import した名前は型注釈やモジュール名としても使うので、トークンで探す
+    fn check_imports(&mut self) {
+        let imports: Vec<(String, usize, usize)> = self
+            .analysis
+            .symbols()
+            .iter()
+            .filter(|s| s.kind == SymbolKind::Import && !self.exported_imports.contains(&s.name))
+            .map(|s| (s.name.clone(), s.line, s.column))
+            .collect();
+        for (name, line, column) in imports {
+            let used = self.analysis.spans.iter().any(|(token, _)| {
+                token.line != line && token.kind == TokenKind::Identifier(name.clone())
+            });
+            if !used && !name.starts_with('_') {
+                self.warn(
+                    LintCode::UnusedImport,
+                    format!("unused import `{}`", name),
+                    (line, column),
+                );
+            }
+        }
+    }
+}
+
+// 文がその後ろに進まないなら、その理由のキーワード
+fn diverges(node: &ASTNode) -> Option<&'static str> {
+    match node {
+        ASTNode::Return { .. } => Some("return"),
+        ASTNode::Break { .. } => Some("break"),
+        ASTNode::Continue { .. } => Some("continue"),
+        ASTNode::Block { nodes, .. } => nodes.iter().find_map(diverges),
+        ASTNode::If {
+            then,
+            else_: Some(else_),
+            ..
+        } => diverges(then).and(diverges(else_)),
+        _ => None,
+    }
+}
+
+// x.a[0] のような書き換え先の元の変数
+fn root_variable(node: &ASTNode) -> Option<&str> {
+    match node {
+        ASTNode::Variable { name, .. } => Some(name),
+        ASTNode::StructFieldAccess { instance, .. } => root_variable(instance),
+        ASTNode::ListIndexAccess { list, .. } => root_variable(list),
+        ASTNode::DictKeyAccess { dict, .. } => root_variable(dict),
+        _ => None,
+    }
+}
+
+// 両辺がリテラルか同じ変数なら、比較の結果は決まっている
+fn constant_comparison(node: &ASTNode) -> Option<bool> {
+    let (left, right, accepts): (_, _, fn(Ordering) -> bool) = match node {
+        ASTNode::Eq { left, right, .. } => (left, right, |o| o == Ordering::Equal),
+        ASTNode::Gte { left, right, .. } => (left, right, |o| o != Ordering::Less),
+        ASTNode::Gt { left, right, .. } => (left, right, |o| o == Ordering::Greater),
+        ASTNode::Lte { left, right, .. } => (left, right, |o| o != Ordering::Greater),
+        ASTNode::Lt { left, right, .. } => (left, right, |o| o == Ordering::Less),
+        _ => return None,
+    };
+    let ordering = match (left.as_ref(), right.as_ref()) {
+        (ASTNode::Variable { name: l, .. }, ASTNode::Variable { name: r, .. }) if l == r => {
+            Ordering::Equal
+        }
+        (ASTNode::Literal { value: l, .. }, ASTNode::Literal { value: r, .. }) => match (l, r) {
+            (Value::Number(l), Value::Number(r)) => l.partial_cmp(r)?,
+            (Value::String(l), Value::String(r)) => l.cmp(r),
+            (Value::Bool(l), Value::Bool(r)) => l.cmp(r),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(accepts(ordering))
+}
+
+// match で取りこぼす値。`_` があるか、すべての場合を並べていれば None
+fn missing_patterns(cases: &[(ASTNode, ASTNode)]) -> Option<String> {
+    let patterns: Vec<&ASTNode> = cases.iter().map(|(pattern, _)| pattern).collect();
+    let binds = |value: &ASTNode| matches!(value, ASTNode::Variable { .. });
+    if patterns
+        .iter()
+        .any(|p| matches!(p, ASTNode::Variable { name, .. } if name == "_"))
+    {
+        return None;
+    }
+    let mut missing = vec![];
+    // パターンの種類は最初の腕で決まる
+    if let Some(pattern) = patterns.first() {
+        match pattern {
+            ASTNode::OptionSome { .. } | ASTNode::OptionNone { .. } => {
+                let some = patterns
+                    .iter()
+                    .any(|p| matches!(p, ASTNode::OptionSome { value, .. } if binds(value)));
+                let none = patterns
+                    .iter()
+                    .any(|p| matches!(p, ASTNode::OptionNone { .. }));
+                if !some {
+                    missing.push("`Some(_)`");
+                }
+                if !none {
+                    missing.push("`None`");
+                }
+            }
+            ASTNode::ResultSuccess { .. } | ASTNode::ResultFailure { .. } => {
+                let success = patterns
+                    .iter()
+                    .any(|p| matches!(p, ASTNode::ResultSuccess { value, .. } if binds(value)));
+                let failure = patterns
+                    .iter()
+                    .any(|p| matches!(p, ASTNode::ResultFailure { value, .. } if binds(value)));
+                if !success {
+                    missing.push("`Suc(_)`");
+                }
+                if !failure {
+                    missing.push("`Fail(_)`");
+                }
+            }
+            ASTNode::Literal {
+                value: Value::Bool(_),
+                ..
+            } => {
+                for (value, name) in [(true, "`true`"), (false, "`false`")] {
+                    let covered = patterns.iter().any(|p| {
+                        matches!(p, ASTNode::Literal { value: Value::Bool(b), .. } if *b == value)
+                    });
+                    if !covered {
+                        missing.push(name);
+                    }
+                }
+            }
+            _ => missing.push("`_`"),
+        }
+    }
+    match missing.is_empty() {
+        true => None,
+        false => Some(missing.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(source: &str) -> Vec<(&'static str, usize, usize)> {
+        lint_source(source)
+            .unwrap()
+            .into_iter()
+            .map(|w| (w.code.as_str(), w.line, w.column))
+            .collect()
+    }
+
+    #[test]
+    fn test_unused_and_mut() {
+        let source = "val used = 1\nval unused = 2\nval mut count = 0\nval mut fixed = 0\ncount = count + used + fixed\nval _ignored = 3\nval mut items = [1]\nitems.push(2)\nprint(items)\n";
+        assert_eq!(
+            codes(source),
+            vec![("unused-variable", 2, 5), ("unused-mut", 4, 9)]
+        );
+    }
+
+    #[test]
+    fn test_shadowing_and_unreachable() {
+        let source = "val x = 1\nfun f(n: number): number {\n    val x = n\n    for i in range(n) {\n        val n = i\n        print(n)\n        break\n        print(i)\n    }\n    return x\n    print(x)\n}\nprint(f(x))\n";
+        assert_eq!(
+            codes(source),
+            vec![
+                ("shadowing", 5, 13),
+                ("unreachable-code", 8, 9),
+                ("unreachable-code", 11, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_match_and_comparisons() {
+        let source = "val maybe: Option<number> = Some(1)\nval a = match maybe {\n    Some(n) => { n }\n}\nval b = match 2 {\n    1 => { 10 }\n    _ => { 20 }\n}\nprint(a + b)\nprint(1 == 1, 2 > 3, a >= a, a > b)\n";
+        assert_eq!(
+            codes(source),
+            vec![
+                ("non-exhaustive-match", 2, 15),
+                ("constant-comparison", 10, 9),
+                ("constant-comparison", 10, 17),
+                ("constant-comparison", 10, 24),
+            ]
+        );
+        let warning = lint_source(source).unwrap().remove(0);
+        assert_eq!(
+            warning.to_string(),
+            "2:15: warning[non-exhaustive-match]: non-exhaustive match: `None` not covered"
+        );
+    }
+
+    #[test]
+    fn test_imports_and_allow_directive() {
+        let source = "import { add, sub as minus } from math\nprint(add(1, 2))\nval unused = 1 // sag:allow(unused-variable)\n// sag:allow(unused-variable, shadowing)\nval other = 2\n";
+        assert_eq!(codes(source), vec![("unused-import", 1, 22)]);
+        assert!(lint_source("val = 1").is_err());
+    }
+}
@@ -0,0 +1,821 @@
+use crate::analysis::{analyze, Analysis};
+use crate::ast::ASTNode;
+use crate::builtin::register_builtins;
+use crate::environment::{Env, ValueType};
+use crate::evals::method_call_node::builtin_method_names;
+use crate::linter::lint;
+use crate::module_resolver::resolve_module;
+use crate::parsers::parse_error::ParseErrorKind;
+use crate::parsers::symbol::{Symbol, SymbolKind};
+use serde_json::{json, Value as Json};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+// 補完に出すキーワード
+const KEYWORDS: &[&str] = &[
+    "val", "val mut", "fun", "return", "if", "else", "match", "for", "in", "break", "continue",
+    "struct", "impl", "pub", "import", "from", "true", "false", "Some", "None", "Suc", "Fail",
+];
+
+/// 標準入出力で LSP を話す。exit を受け取ったら終了コードを返す
+pub fn run_lsp() -> io::Result<i32> {
+    serve(io::stdin().lock(), io::stdout().lock())
+}
+
+/// reader から要求を読み、応答と通知を writer に書く
+pub fn serve(mut reader: impl BufRead, mut writer: impl Write) -> io::Result<i32> {
+    let mut server = Server::default();
+    while let Some(message) = read_message(&mut reader)? {
+        for reply in server.handle(&message) {
+            write_message(&mut writer, &reply)?;
+        }
+        if let Some(code) = server.exit_code {
+            return Ok(code);
+        }
+    }
+    // exit を送らずにクライアントが終わった
+    Ok(if server.shutdown { 0 } else { 1 })
+}
+
+// `Content-Length: n` のヘッダーと空行の後に JSON が続く。読めない JSON は Null にする
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body).unwrap_or(Json::Null)))
+}
+
+fn write_message(writer: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+fn response(id: Json, result: Json) -> Json {
+    json!({"jsonrpc": "2.0", "id": id, "result": result})
+}
+
+fn error_response(id: Json, code: i32, message: String) -> Json {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    json!({"jsonrpc": "2.0", "method": method, "params": params})
+}
+
+#[derive(Default)]
+struct Server {
+    // uri ごとの編集中の内容
+    documents: HashMap<String, String>,
+    shutdown: bool,
+    exit_code: Option<i32>,
+}
+
+impl Server {
+    fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        match (method, message.get("id").cloned()) {
+            _ if message.is_null() => {
+                vec![error_response(Json::Null, -32700, "Parse error".into())]
+            }
+            ("initialize", Some(id)) => vec![response(id, initialize_result())],
+            ("shutdown", Some(id)) => {
+                self.shutdown = true;
+                vec![response(id, Json::Null)]
+            }
+            ("exit", _) => {
+                self.exit_code = Some(if self.shutdown { 0 } else { 1 });
+                vec![]
+            }
+            ("textDocument/didOpen", None) => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_string());
+                vec![publish_diagnostics(&uri, text)]
+            }
+            // 同期は全文なので、最後の変更が新しい内容になる
+            ("textDocument/didChange", None) => {
+                let changes = params["contentChanges"].as_array();
+                match changes.and_then(|changes| changes.last()?["text"].as_str()) {
+                    Some(text) => {
+                        self.documents.insert(uri.clone(), text.to_string());
+                        vec![publish_diagnostics(&uri, text)]
+                    }
+                    None => vec![],
+                }
+            }
+            ("textDocument/didClose", None) => {
+                self.documents.remove(&uri);
+                vec![notification(
+                    "textDocument/publishDiagnostics",
+                    json!({"uri": uri, "diagnostics": []}),
+                )]
+            }
+            ("textDocument/definition", Some(id)) => {
+                vec![response(id, self.at_position(params, definition))]
+            }
+            ("textDocument/hover", Some(id)) => vec![response(id, self.at_position(params, hover))],
+            ("textDocument/completion", Some(id)) => {
+                vec![response(id, self.at_position(params, completion))]
+            }
+            ("textDocument/documentSymbol", Some(id)) => {
+                let symbols = self.documents.get(&uri).map(|text| document_symbols(text));
+                vec![response(id, symbols.unwrap_or(Json::Null))]
+            }
+            (_, Some(id)) => vec![error_response(
+                id,
+                -32601,
+                format!("Method not found: {}", method),
+            )],
+            // 知らない通知は無視する
+            (_, None) => vec![],
+        }
+    }
+
+    // 開いている文書の位置についての要求に答える。位置は 1 始まりの行と列にして渡す
+    fn at_position(&self, params: &Json, answer: fn(&str, &str, usize, usize) -> Json) -> Json {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match self.documents.get(uri) {
+            Some(text) => {
+                let (line, column) = from_position(text, &params["position"]);
+                answer(uri, text, line, column)
+            }
+            None => Json::Null,
+        }
+    }
+}
+
+fn initialize_result() -> Json {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "definitionProvider": true,
+            "hoverProvider": true,
+            "completionProvider": {"triggerCharacters": ["."]},
+            "documentSymbolProvider": true,
+        },
+        "serverInfo": {"name": "sag", "version": env!("CARGO_PKG_VERSION")},
+    })
+}
+
+// LSP の位置は 0 始まりの行と UTF-16 での列
+fn to_position(text: &str, line: usize, column: usize) -> Json {
+    let character: usize = text.lines().nth(line.saturating_sub(1)).map_or(0, |l| {
+        l.chars()
+            .take(column.saturating_sub(1))
+            .map(char::len_utf16)
+            .sum()
+    });
+    json!({"line": line.saturating_sub(1), "character": character})
+}
+
+fn from_position(text: &str, position: &Json) -> (usize, usize) {
+    let line = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+    let mut units = 0;
+    let mut column = 1;
+    for c in text.lines().nth(line).unwrap_or_default().chars() {
+        if units >= character {
+            break;
+        }
+        units += c.len_utf16();
+        column += 1;
+    }
+    (line + 1, column)
+}
+
+fn range(text: &str, line: usize, column: usize, end_column: usize) -> Json {
+    json!({
+        "start": to_position(text, line, column),
+        "end": to_position(text, line, end_column),
+    })
+}
+
+fn symbol_range(text: &str, symbol: &Symbol) -> Json {
+    range(
+        text,
+        symbol.line,
+        symbol.column,
+        symbol.column + symbol.name.chars().count(),
+    )
+}
+
+fn publish_diagnostics(uri: &str, text: &str) -> Json {
+    notification(
+        "textDocument/publishDiagnostics",
+        json!({"uri": uri, "diagnostics": diagnostics(text)}),
+    )
+}
+
+// 構文エラーと型エラー、なければ lint の警告。エラーは最初の 1 つで読むのをやめる
+fn diagnostics(text: &str) -> Vec<Json> {
+    let analysis = analyze(text);
+    let token_end = |line: usize, column: usize| {
+        analysis
+            .spans
+            .iter()
+            .find(|(token, _)| token.line == line && token.column == column)
+            .map_or(column, |(_, end)| *end)
+    };
+    let Some(error) = &analysis.error else {
+        return lint(&analysis, text)
+            .into_iter()
+            .map(|warning| {
+                json!({
+                    "range": range(text, warning.line, warning.column, token_end(warning.line, warning.column)),
+                    "severity": 2,
+                    "source": "sag",
+                    "code": warning.code.as_str(),
+                    "message": warning.message,
+                })
+            })
+            .collect();
+    };
+    let code = match error.kind {
+        ParseErrorKind::Syntax => "parse",
+        ParseErrorKind::Type => "type",
+    };
+    vec![json!({
+        "range": range(text, error.line, error.column, token_end(error.line, error.column)),
+        "severity": 1,
+        "source": "sag",
+        "code": code,
+        "message": error.message,
+    })]
+}
+
+// 位置にある名前。名前の直後にカーソルがあっても見つける
+fn symbol_at(analysis: &Analysis, line: usize, column: usize) -> Option<&Symbol> {
+    analysis.symbols().iter().rev().find(|s| {
+        s.line == line && (s.column..=s.column + s.name.chars().count()).contains(&column)
+    })
+}
+
+// 名前を宣言した場所。同じスコープで前に宣言したもの、トップレベル、どこかのスコープの順に探す
+fn find_declaration<'a>(
+    analysis: &'a Analysis,
+    name: &str,
+    scope: Option<&str>,
+    position: (usize, usize),
+) -> Option<&'a Symbol> {
+    let declarations = || {
+        analysis.symbols().iter().filter(move |s| {
+            s.name == name && !matches!(s.kind, SymbolKind::Reference | SymbolKind::Method)
+        })
+    };
+    let before = |s: &&Symbol| (s.line, s.column) <= position;
+    declarations()
+        .filter(|s| scope.is_none_or(|scope| s.scope == scope))
+        .rfind(before)
+        .or_else(|| declarations().filter(|s| s.scope == "global").rfind(before))
+        .or_else(|| declarations().next())
+}
+
+fn declaration_of<'a>(analysis: &'a Analysis, symbol: &'a Symbol) -> Option<&'a Symbol> {
+    match symbol.kind {
+        SymbolKind::Reference => find_declaration(
+            analysis,
+            &symbol.name,
+            Some(&symbol.scope),
+            (symbol.line, symbol.column),
+        ),
+        _ => Some(symbol),
+    }
+}
+
+fn location(uri: &str, text: &str, symbol: &Symbol) -> Json {
+    json!({"uri": uri, "range": symbol_range(text, symbol)})
+}
+
+fn definition(uri: &str, text: &str, line: usize, column: usize) -> Json {
+    let analysis = analyze(text);
+    let Some(declaration) =
+        symbol_at(&analysis, line, column).and_then(|symbol| declaration_of(&analysis, symbol))
+    else {
+        return Json::Null;
+    };
+    match declaration.kind {
+        SymbolKind::Import => imported_location(uri, &analysis, &declaration.name)
+            .unwrap_or_else(|| location(uri, text, declaration)),
+        _ => location(uri, text, declaration),
+    }
+}
+
+// import した名前を、読み込むファイルでの宣言に解決する。モジュール全体ならファイルの先頭
+fn imported_location(uri: &str, analysis: &Analysis, name: &str) -> Option<Json> {
+    let (module_name, symbol) = analysis
+        .nodes
+        .iter()
+        .find_map(|node| imported_symbol(node, name))?;
+    let importing_file = uri_to_path(uri);
+    let path = resolve_module(&module_name, importing_file.as_deref()).ok()?;
+    let text = fs::read_to_string(&path).ok()?;
+    let uri = path_to_uri(&path);
+    let Some(symbol) = symbol else {
+        return Some(json!({"uri": uri, "range": range(&text, 1, 1, 1)}));
+    };
+    let module = analyze(&text);
+    let declaration = module.symbols().iter().find(|s| {
+        s.scope == "global"
+            && s.name == symbol
+            && matches!(
+                s.kind,
+                SymbolKind::Function | SymbolKind::Struct | SymbolKind::Variable
+            )
+    })?;
+    Some(location(&uri, &text, declaration))
+}
+
+// import の文から、名前のモジュールと元の名前を探す
+fn imported_symbol(node: &ASTNode, name: &str) -> Option<(String, Option<String>)> {
+    match node {
+        ASTNode::Public { node, .. } => imported_symbol(node, name),
+        ASTNode::Import {
+            module_name,
+            symbols,
+            aliases,
+            namespace,
+            ..
+        } => {
+            if namespace.as_deref() == Some(name) {
+                return Some((module_name.clone(), None));
+            }
+            let index = aliases.iter().position(|alias| alias == name)?;
+            Some((module_name.clone(), symbols.get(index).cloned()))
+        }
+        _ => None,
+    }
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let bytes = path.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok().map(PathBuf::from)
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let path = fs::canonicalize(path).unwrap_or(path.to_path_buf());
+    format!("file://{}", path.display().to_string().replace(' ', "%20"))
+}
+
+fn hover(_uri: &str, text: &str, line: usize, column: usize) -> Json {
+    let analysis = analyze(text);
+    let Some(symbol) = symbol_at(&analysis, line, column) else {
+        return Json::Null;
+    };
+    let declaration = declaration_of(&analysis, symbol).unwrap_or(symbol);
+    let value_type = symbol
+        .value_type
+        .clone()
+        .or_else(|| declaration.value_type.clone());
+    let signature = match (declaration.kind, &value_type) {
+        (SymbolKind::Function | SymbolKind::Method, _) => {
+            function_signature(&analysis.nodes, &declaration.name)
+                .unwrap_or_else(|| format!("fun {}", declaration.name))
+        }
+        (SymbolKind::Struct, Some(ValueType::Struct { fields, .. })) => {
+            let mut fields: Vec<String> = fields
+                .iter()
+                .map(|(name, value_type)| format!("{}: {}", name, value_type))
+                .collect();
+            fields.sort();
+            format!("struct {} {{ {} }}", declaration.name, fields.join(", "))
+        }
+        (_, Some(value_type)) => format!("{}: {}", symbol.name, value_type),
+        (_, None) => symbol.name.clone(),
+    };
+    json!({
+        "contents": {"kind": "markdown", "value": format!("```sag\n{}\n```", signature)},
+        "range": symbol_range(text, symbol),
+    })
+}
+
+// `fun add(a: number, b: number): number` の形にする
+fn function_signature(nodes: &[ASTNode], name: &str) -> Option<String> {
+    nodes.iter().find_map(|node| match node {
+        ASTNode::Public { node, .. } => function_signature(std::slice::from_ref(node), name),
+        ASTNode::Impl { methods, .. } => function_signature(methods, name),
+        ASTNode::Function {
+            name: function_name,
+            arguments,
+            return_type,
+            ..
+        }
+        | ASTNode::Method {
+            name: function_name,
+            arguments,
+            return_type,
+            ..
+        } if function_name == name => {
+            let arguments: Vec<String> = arguments
+                .iter()
+                .map(|argument| match argument {
+                    ASTNode::Variable {
+                        name,
+                        value_type: Some(value_type),
+                        ..
+                    } if name != "self" => format!("{}: {}", name, value_type),
+                    ASTNode::Variable { name, .. } => name.clone(),
+                    _ => "..".to_string(),
+                })
+                .collect();
+            Some(format!(
+                "fun {}({}): {}",
+                name,
+                arguments.join(", "),
+                return_type
+            ))
+        }
+        _ => None,
+    })
+}
+
+fn completion_item(label: &str, kind: u8, detail: String) -> Json {
+    json!({"label": label, "kind": kind, "detail": detail})
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn completion(_uri: &str, text: &str, line: usize, column: usize) -> Json {
+    let analysis = analyze(text);
+    let before: Vec<char> = text
+        .lines()
+        .nth(line - 1)
+        .unwrap_or_default()
+        .chars()
+        .take(column - 1)
+        .collect();
+    // 入力途中の単語の前が `.` ならメンバーを出す
+    let mut end = before.len();
+    while end > 0 && is_word_char(before[end - 1]) {
+        end -= 1;
+    }
+    let items = if end > 0 && before[end - 1] == '.' {
+        let mut start = end - 1;
+        while start > 0 && is_word_char(before[start - 1]) {
+            start -= 1;
+        }
+        let receiver: String = before[start..end - 1].iter().collect();
+        member_items(&analysis, &receiver, (line, start + 1)).unwrap_or_default()
+    } else {
+        global_items(&analysis, (line, column))
+    };
+    Json::Array(items)
+}
+
+// 構造体ならフィールドとメソッド、それ以外は型の builtin メソッド
+fn member_items(
+    analysis: &Analysis,
+    receiver: &str,
+    position: (usize, usize),
+) -> Option<Vec<Json>> {
+    let value_type = if receiver == "self" {
+        // 直前に読んだメソッドの構造体
+        let method = analysis
+            .symbols()
+            .iter()
+            .rfind(|s| s.kind == SymbolKind::Method && (s.line, s.column) <= position)?;
+        find_declaration(analysis, &method.scope, None, position)?
+            .value_type
+            .clone()?
+    } else {
+        find_declaration(analysis, receiver, None, position)?
+            .value_type
+            .clone()?
+    };
+    match value_type {
+        ValueType::Struct { name, fields, .. } | ValueType::StructInstance { name, fields } => {
+            let mut fields: Vec<(String, ValueType)> = fields.into_iter().collect();
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            let mut items: Vec<Json> = fields
+                .iter()
+                .map(|(field, value_type)| completion_item(field, 5, value_type.to_string()))
+                .collect();
+            for method in analysis
+                .symbols()
+                .iter()
+                .filter(|s| s.kind == SymbolKind::Method && s.scope == name)
+            {
+                let detail = method
+                    .value_type
+                    .as_ref()
+                    .map_or(String::new(), |t| t.to_string());
+                items.push(completion_item(&method.name, 2, detail));
+            }
+            Some(items)
+        }
+        value_type => Some(
+            builtin_method_names(&value_type)
+                .iter()
+                .map(|name| completion_item(name, 2, format!("{} method", value_type)))
+                .collect(),
+        ),
+    }
+}
+
+// 見えている宣言、builtin 関数、キーワード
+fn global_items(analysis: &Analysis, position: (usize, usize)) -> Vec<Json> {
+    let mut items = vec![];
+    let mut seen = vec![];
+    for symbol in analysis.symbols().iter().rev() {
+        let kind = match symbol.kind {
+            SymbolKind::Function => 3,
+            SymbolKind::Struct => 22,
+            SymbolKind::Import => 9,
+            SymbolKind::Variable => 6,
+            SymbolKind::Method | SymbolKind::Reference => continue,
+        };
+        let visible = symbol.scope == "global" || (symbol.line, symbol.column) <= position;
+        if !visible || seen.contains(&&symbol.name) {
+            continue;
+        }
+        seen.push(&symbol.name);
+        let detail = symbol
+            .value_type
+            .as_ref()
+            .map_or(String::new(), |t| t.to_string());
+        items.push(completion_item(&symbol.name, kind, detail));
+    }
+    let mut builtins: Vec<String> = register_builtins(&mut Env::new())
+        .into_keys()
+        .map(|(_, name)| name)
+        .collect();
+    builtins.sort();
+    for name in builtins.iter().filter(|name| !seen.contains(name)) {
+        items.push(completion_item(name, 3, "builtin".to_string()));
+    }
+    for keyword in KEYWORDS {
+        items.push(completion_item(keyword, 14, "keyword".to_string()));
+    }
+    items
+}
+
+// トップレベルの関数、構造体とそのメソッド、変数、import
+fn document_symbols(text: &str) -> Json {
+    let analysis = analyze(text);
+    let entry = |symbol: &Symbol, kind: u8, children: Vec<Json>| {
+        let detail = symbol
+            .value_type
+            .as_ref()
+            .map_or(String::new(), |t| t.to_string());
+        json!({
+            "name": symbol.name,
+            "detail": detail,
+            "kind": kind,
+            "range": symbol_range(text, symbol),
+            "selectionRange": symbol_range(text, symbol),
+            "children": children,
+        })
+    };
+    let symbols: Vec<Json> = analysis
+        .symbols()
+        .iter()
+        .filter(|s| s.scope == "global")
+        .filter_map(|symbol| match symbol.kind {
+            SymbolKind::Function => Some(entry(symbol, 12, vec![])),
+            SymbolKind::Variable => Some(entry(symbol, 13, vec![])),
+            SymbolKind::Import => Some(entry(symbol, 2, vec![])),
+            SymbolKind::Struct => {
+                let methods = analysis
+                    .symbols()
+                    .iter()
+                    .filter(|s| s.kind == SymbolKind::Method && s.scope == symbol.name)
+                    .map(|method| entry(method, 6, vec![]))
+                    .collect();
+                Some(entry(symbol, 23, methods))
+            }
+            SymbolKind::Method | SymbolKind::Reference => None,
+        })
+        .collect();
+    Json::Array(symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const MAIN: &str = "import { add as plus } from \"math.sag\"
+struct Point {
+    x: number,
+    y: number
+}
+impl Point {
+    fun norm(self): number {
+        return self.x + self.y
+    }
+}
+fun twice(n: number): number {
+    return n * 2
+}
+val mut p = Point { x: 1, y: 2 }
+val total = twice(plus(p.x, 1))
+";
+
+    // 要求を順に送り、終了コードと受け取ったメッセージを返す
+    fn run_client(messages: &[Json]) -> (i32, Vec<Json>) {
+        let mut input = vec![];
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+        let mut output = vec![];
+        let code = serve(Cursor::new(input), &mut output).unwrap();
+        let mut reader = Cursor::new(output);
+        let mut replies = vec![];
+        while let Some(reply) = read_message(&mut reader).unwrap() {
+            replies.push(reply);
+        }
+        (code, replies)
+    }
+
+    fn request(id: u64, method: &str, params: Json) -> Json {
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    }
+
+    fn at(uri: &str, line: u64, character: u64) -> Json {
+        json!({"textDocument": {"uri": uri}, "position": {"line": line, "character": character}})
+    }
+
+    fn labels(items: &Json) -> Vec<&str> {
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_scripted_session() {
+        let root = std::env::temp_dir().join("sag_lsp");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("math.sag"),
+            "pub fun add(a: number, b: number): number {\n    return a + b\n}\n",
+        )
+        .unwrap();
+        let uri = path_to_uri(&root.join("main.sag"));
+        let edited = format!("{}val s = \"a\"\np.\ns.\n", MAIN);
+
+        let (code, replies) = run_client(&[
+            request(1, "initialize", json!({"capabilities": {}})),
+            notification("initialized", json!({})),
+            notification(
+                "textDocument/didOpen",
+                json!({"textDocument": {"uri": uri, "languageId": "sag", "version": 1, "text": MAIN}}),
+            ),
+            request(2, "textDocument/definition", at(&uri, 14, 13)),
+            request(3, "textDocument/definition", at(&uri, 14, 19)),
+            request(4, "textDocument/definition", at(&uri, 11, 11)),
+            request(5, "textDocument/hover", at(&uri, 14, 13)),
+            request(6, "textDocument/hover", at(&uri, 14, 5)),
+            request(
+                7,
+                "textDocument/documentSymbol",
+                json!({"textDocument": {"uri": uri}}),
+            ),
+            notification(
+                "textDocument/didChange",
+                json!({"textDocument": {"uri": uri, "version": 2}, "contentChanges": [{"text": edited}]}),
+            ),
+            request(8, "textDocument/completion", at(&uri, 16, 2)),
+            request(9, "textDocument/completion", at(&uri, 17, 2)),
+            request(10, "textDocument/completion", at(&uri, 15, 0)),
+            request(11, "textDocument/formatting", json!({})),
+            request(12, "shutdown", Json::Null),
+            notification("exit", Json::Null),
+        ]);
+        assert_eq!(code, 0);
+        assert_eq!(replies.len(), 14);
+        assert_eq!(replies[0]["result"]["capabilities"]["hoverProvider"], true);
+        assert_eq!(replies[1]["method"], "textDocument/publishDiagnostics");
+        // 読めるソースには lint の警告を出す
+        let warnings: Vec<(&str, &Json, &Json)> = replies[1]["params"]["diagnostics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| {
+                (
+                    d["code"].as_str().unwrap(),
+                    &d["severity"],
+                    &d["range"]["start"],
+                )
+            })
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                (
+                    "unused-mut",
+                    &json!(2),
+                    &json!({"line": 13, "character": 8})
+                ),
+                (
+                    "unused-variable",
+                    &json!(2),
+                    &json!({"line": 14, "character": 4})
+                ),
+            ]
+        );
+
+        // 関数、import した名前、引数の定義
+        assert_eq!(
+            replies[2]["result"],
+            json!({"uri": uri, "range": {"start": {"line": 10, "character": 4}, "end": {"line": 10, "character": 9}}})
+        );
+        assert_eq!(
+            replies[3]["result"]["uri"],
+            path_to_uri(&root.join("math.sag"))
+        );
+        assert_eq!(
+            replies[3]["result"]["range"]["start"],
+            json!({"line": 0, "character": 8})
+        );
+        assert_eq!(
+            replies[4]["result"]["range"]["start"],
+            json!({"line": 10, "character": 10})
+        );
+
+        let hover = |reply: &Json| {
+            reply["result"]["contents"]["value"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            hover(&replies[5]),
+            "```sag\nfun twice(n: number): number\n```"
+        );
+        assert_eq!(hover(&replies[6]), "```sag\ntotal: number\n```");
+
+        let symbols = replies[7]["result"].as_array().unwrap();
+        let names: Vec<&str> = symbols
+            .iter()
+            .map(|s| s["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["plus", "Point", "twice", "p", "total"]);
+        assert_eq!(symbols[1]["kind"], 23);
+        assert_eq!(symbols[1]["children"][0]["name"], "norm");
+
+        // 編集中の `p.` は構文エラーになる
+        assert_eq!(replies[8]["params"]["diagnostics"][0]["code"], "parse");
+        assert_eq!(labels(&replies[9]["result"]), vec!["x", "y", "norm"]);
+        assert!(labels(&replies[10]["result"]).contains(&"to_uppercase"));
+        let globals = labels(&replies[11]["result"]);
+        for name in ["s", "total", "twice", "Point", "plus", "print", "val"] {
+            assert!(globals.contains(&name), "{}", name);
+        }
+        assert_eq!(replies[12]["error"]["code"], -32601);
+        assert_eq!(replies[13]["result"], Json::Null);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_exit_without_shutdown() {
+        let (code, replies) = run_client(&[notification("exit", Json::Null)]);
+        assert_eq!(code, 1);
+        assert!(replies.is_empty());
+    }
+}
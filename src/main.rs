@@ -1,30 +1,50 @@
+mod analysis;
 mod ast;
 mod builtin;
 mod compiler;
 mod environment;
 mod evals;
+mod exit_status;
+mod formatter;
 mod install;
+mod linter;
+mod lsp;
+mod module_graph;
+mod module_resolver;
+mod package;
 mod parsers;
 mod rc_builtins;
 mod rc_env;
 mod rc_evals;
 mod rc_value;
+mod repl;
+mod repl_session;
+mod runner;
+mod sandbox;
+mod test_runner;
 mod token;
 mod tokenizer;
 mod value;
 mod wasm;
 
-use crate::builtin::register_builtins;
+use crate::builtin::set_script_args;
 use crate::compiler::{compile_file, run_compiled_file};
-use crate::environment::Env;
-use crate::evals::{eval, evals};
-use crate::install::install_package;
-use crate::parsers::Parser as SagParser;
-use crate::rc_builtins::register_rc_builtins;
-use crate::rc_env::RcEnv;
-use crate::rc_evals::{rc_eval, rc_evals};
-use crate::tokenizer::tokenize;
-use clap::{Parser, Subcommand};
+use crate::exit_status::{ExitError, IO_ERROR, RUNTIME_ERROR, SUCCESS};
+use crate::formatter::{format_files, source_files};
+use crate::install::{install_all, install_package};
+use crate::linter::lint_files;
+use crate::lsp::run_lsp;
+use crate::package::manifest::find_entry_point;
+use crate::package::scaffold::{init_project, new_project};
+use crate::package::{current_dir, PackageError};
+use crate::repl::run_repl;
+use crate::repl_session::EngineKind;
+use crate::runner::{run_file, run_file_with_rc};
+use crate::sandbox::Limits;
+use crate::test_runner::{run_tests, test_files};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -35,193 +55,227 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
+    /// 引数がなければ sag.toml の依存関係をすべてインストールする
     Install {
-        package_or_path: String,
+        package_or_path: Option<String>,
     },
+    /// ファイルを省略すると sag.toml の entry をコンパイルする
     Compile {
-        file_path: String,
+        file_path: Option<String>,
         #[arg(short, long)]
         output: Option<String>,
     },
+    /// ファイルを省略すると sag.toml の entry を実行する
     Run {
-        file_path: String,
+        file_path: Option<String>,
         #[arg(short, long)]
         use_rc: bool,
+        #[command(flatten)]
+        limits: LimitArgs,
+        /// `--` の後ろの引数。プログラムからは args() で読める
+        #[arg(last = true)]
+        args: Vec<String>,
     },
+    /// 新しいディレクトリにプロジェクトを作る
+    New {
+        name: String,
+    },
+    /// 現在のディレクトリをプロジェクトにする
+    Init,
+    /// `fun test_*()` を実行する。パスを省略すると tests/ と entry のディレクトリから探す
+    Test {
+        paths: Vec<PathBuf>,
+        // 名前にこの文字列を含むテストだけを実行する
+        #[arg(short, long)]
+        filter: Option<String>,
+    },
+    /// ソースを整形して書き換える。パスを省略するとプロジェクトかカレントディレクトリの .sag をすべて整形する
+    Fmt {
+        paths: Vec<PathBuf>,
+        /// 書き換えずに、整形が必要なファイルを表示して失敗する
+        #[arg(long)]
+        check: bool,
+    },
+    /// 未使用の変数や到達しないコードを警告する。警告があれば失敗する
+    Lint { paths: Vec<PathBuf> },
+    /// 標準入出力で Language Server Protocol を話す
+    Lsp,
     Repl {
         #[arg(short, long)]
         use_rc: bool,
+        // tree、rc、vm のどれで評価するか
+        #[arg(short, long, conflicts_with = "use_rc")]
+        engine: Option<EngineKind>,
     },
 }
 
-fn run_repl_with_rc() -> Result<(), Box<dyn std::error::Error>> {
-    // 初期環境を作成
-    let mut rc_env = RcEnv::new();
-
-    // ビルトイン関数を直接RcEnvに登録
-    let rc_builtins = register_rc_builtins(&mut rc_env);
-
-    println!("SAG REPL (with Rc optimization)");
-    println!("Type expressions to evaluate them. Press Ctrl+D to exit.");
-
-    for line in std::io::stdin().lines() {
-        let line = line?;
-        let tokens = tokenize(&line);
-        let mut parser = SagParser::new(tokens.to_vec(), rc_builtins.clone());
-        let ast_node = parser.parse();
-        if let Err(e) = ast_node {
-            eprint!("{}", e.message_with_source(&line));
-            continue;
-        }
-
-        // RcEnvとRcValueを直接使用して評価
-        let result = rc_eval(ast_node.unwrap(), &mut rc_env);
-
-        match result {
-            Ok(value) => println!("=> {}", value),
-            Err(e) => eprint!("{}", e.message_with_source(&line)),
-        }
-    }
-    Ok(())
+/// 信頼できないスクリプトを動かすときの実行制限
+#[derive(Args)]
+struct LimitArgs {
+    /// 評価する式の数の上限。.sagc では命令の数
+    #[arg(long)]
+    max_steps: Option<u64>,
+    /// 関数呼び出しの深さの上限 [default: 1000]
+    #[arg(long)]
+    max_call_depth: Option<usize>,
+    /// リストと辞書の要素数の上限
+    #[arg(long)]
+    max_collection_len: Option<usize>,
+    /// 文字列のバイト数の上限
+    #[arg(long)]
+    max_string_len: Option<usize>,
+    /// 実行時間の上限 (ミリ秒)
+    #[arg(long)]
+    timeout_ms: Option<u64>,
 }
 
-fn run_repl() -> Result<(), Box<dyn std::error::Error>> {
-    let mut env = Env::new();
-    let builtins = register_builtins(&mut env);
-
-    println!("SAG REPL");
-    println!("Type expressions to evaluate them. Press Ctrl+D to exit.");
-
-    for line in std::io::stdin().lines() {
-        let line = line?;
-        let tokens = tokenize(&line);
-        let mut parser = SagParser::new(tokens.to_vec(), builtins.clone());
-        let ast_node = parser.parse();
-        if let Err(e) = ast_node {
-            eprint!("{}", e.message_with_source(&line));
-            continue;
-        }
-
-        let result = eval(ast_node.unwrap(), &mut env);
-        match result {
-            Ok(value) => println!("=> {}", value),
-            Err(e) => eprint!("{}", e.message_with_source(&line)),
+impl From<LimitArgs> for Limits {
+    fn from(args: LimitArgs) -> Self {
+        let default = Limits::default();
+        Limits {
+            max_steps: args.max_steps,
+            max_call_depth: args.max_call_depth.or(default.max_call_depth),
+            max_collection_len: args.max_collection_len,
+            max_string_len: args.max_string_len,
+            timeout: args.timeout_ms.map(Duration::from_millis),
         }
     }
-    Ok(())
 }
 
-fn run_file_with_rc(file_path: String, debug: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let file = std::fs::read_to_string(file_path)?;
-
-    let tokens = tokenize(&file);
-    if debug {
-        println!("tokens: {:?}", tokens);
-    }
-
-    // 初期環境を作成
-    let mut rc_env = RcEnv::new();
-
-    // ビルトイン関数を直接RcEnvに登録
-    let rc_builtins = register_rc_builtins(&mut rc_env);
-
-    // デバッグ用：登録されたビルトイン関数を表示
-    let mut parser = SagParser::new(tokens.to_vec(), rc_builtins.clone());
-    let ast_nodes = parser.parse_lines();
-    if let Err(e) = ast_nodes {
-        eprint!("{}", e.message_with_source(&file));
-        return Ok(());
-    }
-
-    if debug {
-        println!("ast: {:?}", ast_nodes);
-    }
-
-    // RcEnvとRcValueを直接使用して評価
-    let result = rc_evals(ast_nodes.unwrap(), &mut rc_env);
-
-    if let Err(e) = result {
-        eprint!("{}", e.message_with_source(&file));
-        return Ok(());
-    }
-
-    if debug {
-        println!("env: {:?}", rc_env);
-    }
-
-    Ok(())
+// ファイルが指定されなかったときは、現在のディレクトリを含むプロジェクトの entry を使う
+fn project_entry_point() -> Result<String, ExitError> {
+    current_dir()
+        .and_then(|dir| find_entry_point(&dir))
+        .map(|entry| entry.display().to_string())
+        .map_err(failure)
 }
 
-fn run_file(file_path: String, debug: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let file = std::fs::read_to_string(file_path)?;
-
-    let tokens = tokenize(&file);
-    if debug {
-        println!("tokens: {:?}", tokens);
-    }
-    let mut env = Env::new();
-    let builtins = register_builtins(&mut env);
-    let mut parser = SagParser::new(tokens.to_vec(), builtins.clone());
-    let ast_nodes = parser.parse_lines();
-    if let Err(e) = ast_nodes {
-        eprint!("{}", e.message_with_source(&file));
-        return Ok(());
-    }
-    if debug {
-        println!("ast: {:?}", ast_nodes);
-    }
-    let result = evals(ast_nodes.unwrap(), &mut env);
-    if let Err(e) = result {
-        eprint!("{}", e.message_with_source(&file));
-        return Ok(());
-    }
-    if debug {
-        println!("env: {:?}", env);
-    }
-    Ok(())
+// パッケージ管理やプロジェクトの操作の失敗。ファイル操作の失敗は IO_ERROR にする
+fn failure(error: PackageError) -> ExitError {
+    let code = match error {
+        PackageError::Io(_) => IO_ERROR,
+        PackageError::Invalid(_) => RUNTIME_ERROR,
+    };
+    ExitError::new(code, format!("Error: {}", error))
 }
 
 fn main() {
     let args = Cli::parse();
-    match args.command {
-        Commands::Install { package_or_path } => {
-            install_package(package_or_path);
+    let status = match args.command {
+        Commands::Install { package_or_path } => match package_or_path {
+            Some(package_or_path) => install_package(package_or_path),
+            None => install_all(),
         }
-        Commands::Compile { file_path, output } => {
-            match compile_file(&file_path, output.as_deref()) {
-                Ok(output_path) => println!("{}", output_path),
-                Err(e) => eprintln!("Error: {}", e),
-            }
-        }
-        Commands::Run { file_path, use_rc } => {
+        .map(|_| SUCCESS)
+        .map_err(failure),
+        Commands::Compile { file_path, output } => file_path
+            .map_or_else(project_entry_point, Ok)
+            .and_then(|file_path| compile_file(&file_path, output.as_deref()))
+            .map(|output_path| {
+                println!("{}", output_path);
+                SUCCESS
+            }),
+        Commands::Run {
+            file_path,
+            use_rc,
+            limits,
+            args,
+        } => {
             let debug = false; // Set to true if you want debug mode
-
-            if file_path.ends_with(".sagc") {
-                if let Err(e) = run_compiled_file(&file_path) {
-                    eprintln!("Error: {}", e);
+            let limits = Limits::from(limits);
+            set_script_args(args);
+            file_path
+                .map_or_else(project_entry_point, Ok)
+                .and_then(|file_path| {
+                    if file_path.ends_with(".sagc") {
+                        run_compiled_file(&file_path, limits)
+                    } else if use_rc {
+                        eprintln!("Running with Rc optimization");
+                        run_file_with_rc(file_path, debug, limits)
+                    } else {
+                        run_file(file_path, debug, limits)
+                    }
+                })
+        }
+        Commands::New { name } => new_project(&PathBuf::from(&name), &name)
+            .map(|_| {
+                println!("Created project {}", name);
+                SUCCESS
+            })
+            .map_err(failure),
+        Commands::Init => current_dir()
+            .and_then(|dir| init_project(&dir))
+            .map(|name| {
+                println!("Initialized project {}", name);
+                SUCCESS
+            })
+            .map_err(failure),
+        Commands::Test { paths, filter } => current_dir()
+            .and_then(|dir| test_files(&paths, &dir))
+            .map_err(failure)
+            .map(|files| {
+                let report = run_tests(&files, filter.as_deref());
+                println!("{}", report);
+                if report.is_success() {
+                    SUCCESS
+                } else {
+                    RUNTIME_ERROR
                 }
-            } else if use_rc {
-                println!("Running with Rc optimization");
-                if let Err(e) = run_file_with_rc(file_path, debug) {
-                    eprintln!("Error: {}", e);
+            }),
+        Commands::Fmt { paths, check } => current_dir()
+            .and_then(|dir| source_files(&paths, &dir))
+            .map_err(failure)
+            .and_then(|files| {
+                let (changed, errors) = format_files(&files, check);
+                for path in &changed {
+                    match check {
+                        true => println!("{} is not formatted", path.display()),
+                        false => println!("Formatted {}", path.display()),
+                    }
                 }
-            } else {
-                if let Err(e) = run_file(file_path, debug) {
-                    eprintln!("Error: {}", e);
+                // 読めなかったファイルはまとめて報告し、最初のエラーの終了コードにする
+                if let Some(first) = errors.first() {
+                    let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+                    return Err(ExitError::new(first.code, messages.join("\n")));
                 }
-            }
-        }
-        Commands::Repl { use_rc } => {
-            if use_rc {
-                println!("Starting REPL with Rc optimization");
-                if let Err(e) = run_repl_with_rc() {
-                    eprintln!("Error: {}", e);
+                match check && !changed.is_empty() {
+                    true => Ok(RUNTIME_ERROR),
+                    false => Ok(SUCCESS),
+                }
+            }),
+        Commands::Lint { paths } => current_dir()
+            .and_then(|dir| source_files(&paths, &dir))
+            .map_err(failure)
+            .and_then(|files| lint_files(&files))
+            .map(|results| {
+                for (path, warnings) in &results {
+                    for warning in warnings {
+                        println!("{}:{}", path.display(), warning);
+                    }
                 }
-            } else {
-                if let Err(e) = run_repl() {
-                    eprintln!("Error: {}", e);
+                match results.is_empty() {
+                    true => SUCCESS,
+                    false => RUNTIME_ERROR,
                 }
-            }
+            }),
+        Commands::Lsp => {
+            run_lsp().map_err(|e| ExitError::new(IO_ERROR, format!("Error: {}", e)))
+        }
+        Commands::Repl { use_rc, engine } => {
+            let engine = match (use_rc, engine) {
+                (true, _) => EngineKind::Rc,
+                (false, engine) => engine.unwrap_or(EngineKind::Tree),
+            };
+            run_repl(engine)
+                .map(|exit_code| exit_code.unwrap_or(SUCCESS))
+                .map_err(|e| ExitError::new(IO_ERROR, format!("Error: {}", e)))
+        }
+    };
+    match status {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("{}", e.message);
+            std::process::exit(e.code);
         }
     }
 }
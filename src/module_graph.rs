@@ -0,0 +1,167 @@
+use crate::environment::Env;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// プログラム全体で共有するモジュールの読み込み状態。
+/// 同じファイルは一度だけ評価し、読み込み中のファイルを辿って循環を見つける
+#[derive(Clone, Default)]
+pub struct ModuleGraph(Rc<RefCell<ModuleGraphInner>>);
+
+#[derive(Default)]
+struct ModuleGraphInner {
+    loaded: HashMap<PathBuf, Env>,
+    // 読み込み中のファイル (正規化したパス, 表示用のパス)
+    loading: Vec<(PathBuf, String)>,
+}
+
+impl ModuleGraph {
+    pub fn get(&self, path: &Path) -> Option<Env> {
+        self.0.borrow().loaded.get(&module_key(path)).cloned()
+    }
+
+    pub fn insert(&self, path: &Path, env: Env) {
+        self.0.borrow_mut().loaded.insert(module_key(path), env);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().loaded.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().loaded.is_empty()
+    }
+
+    /// 読み込みを開始する。循環していたら `a.sag -> b.sag -> a.sag` の形のエラーを返す。
+    /// 戻り値は `finish_loading` に渡す
+    pub fn start_loading(&self, path: &Path, importer: Option<&Path>) -> Result<usize, String> {
+        let mut inner = self.0.borrow_mut();
+        let depth = inner.loading.len();
+        // エントリーファイルは import されないのでここで積む
+        match importer {
+            Some(importer) if depth == 0 => {
+                inner
+                    .loading
+                    .push((module_key(importer), display_path(importer)));
+            }
+            _ => {}
+        }
+
+        let key = module_key(path);
+        if let Some(start) = inner
+            .loading
+            .iter()
+            .position(|(loading, _)| *loading == key)
+        {
+            let mut chain = inner.loading[start..]
+                .iter()
+                .map(|(_, name)| name.clone())
+                .collect::<Vec<_>>();
+            chain.push(display_path(path));
+            inner.loading.truncate(depth);
+            return Err(format!("circular import: {}", chain.join(" -> ")));
+        }
+        inner.loading.push((key, display_path(path)));
+        Ok(depth)
+    }
+
+    pub fn finish_loading(&self, depth: usize) {
+        self.0.borrow_mut().loading.truncate(depth);
+    }
+}
+
+fn module_key(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn display_path(path: &Path) -> String {
+    path.strip_prefix(".").unwrap_or(path).display().to_string()
+}
+
+// モジュールの Env は同じグラフを持つので中身は表示しない
+impl fmt::Debug for ModuleGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.0.borrow();
+        let mut paths = inner.loaded.keys().collect::<Vec<_>>();
+        paths.sort();
+        f.debug_tuple("ModuleGraph").field(&paths).finish()
+    }
+}
+
+// 読み込み済みのモジュールは環境の比較に含めない
+impl PartialEq for ModuleGraph {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::ASTNode;
+    use crate::environment::{Env, VariableKeyInfo};
+    use crate::evals::eval;
+    use std::fs;
+    use std::rc::Rc;
+
+    fn import(module_name: &str, env: &mut Env) -> Result<(), String> {
+        let ast = ASTNode::Import {
+            module_name: module_name.to_string(),
+            symbols: vec![],
+            aliases: vec![],
+            namespace: None,
+            line: 0,
+            column: 0,
+        };
+        eval(ast, env).map(|_| ()).map_err(|e| e.message)
+    }
+
+    #[test]
+    fn test_diamond_dependency_loads_once() {
+        let dir = std::env::temp_dir().join("sag_module_graph_diamond");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("b.sag"), "import value from d\npub val b = 1").unwrap();
+        fs::write(dir.join("c.sag"), "import value from d\npub val c = 2").unwrap();
+        fs::write(dir.join("d.sag"), "pub val value = 3").unwrap();
+
+        let mut env = Env::new();
+        env.set_file_path(dir.join("main.sag"));
+        import("b", &mut env).unwrap();
+        import("c", &mut env).unwrap();
+        assert_eq!(env.get_module_graph().len(), 3);
+
+        let key = VariableKeyInfo {
+            name: "value".to_string(),
+            scope: "global".to_string(),
+        };
+        let d_from = |module: &str| {
+            env.get_module(&module.to_string())
+                .and_then(|m| m.get_module(&"d".to_string()))
+                .and_then(|d| d.get_variable_map().get(&key).cloned())
+                .unwrap()
+        };
+        assert!(Rc::ptr_eq(&d_from("b"), &d_from("c")));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_circular_import_reports_chain() {
+        let dir = std::env::temp_dir().join("sag_module_graph_cycle");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.sag"), "import b from b\npub val a = 1").unwrap();
+        fs::write(dir.join("b.sag"), "import a from a\npub val b = 2").unwrap();
+
+        let mut env = Env::new();
+        env.set_file_path(dir.join("a.sag"));
+        let message = import("b", &mut env).unwrap_err();
+        let a = dir.join("a.sag").display().to_string();
+        let b = dir.join("b.sag").display().to_string();
+        assert!(
+            message.contains(&format!("circular import: {} -> {} -> {}", a, b, a)),
+            "{}",
+            message
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::package::lockfile::Lockfile;
+use crate::package::manifest::{find_manifest_dir, Manifest, DEFAULT_LIB};
+use crate::package::version::Version;
+use crate::package::PACKAGE_DIR;
+use std::path::{Path, PathBuf};
+
+/// モジュール名から読み込むファイルを探す。
+/// 探す順番は、インポート元のファイルのディレクトリ、`./.sag_packages`、`SAG_PATH` の各ディレクトリ。
+/// `.sag_packages` にインストールしたパッケージは `import math from math` でその lib ファイルを読み込む。
+/// 見つからなかった場合は試したパスをすべて返す
+pub fn resolve_module(
+    module_name: &str,
+    importing_file: Option<&Path>,
+) -> Result<PathBuf, Vec<PathBuf>> {
+    let candidates = module_candidates(module_name, importing_file, &sag_path());
+    match candidates.iter().find(|path| path.is_file()) {
+        Some(path) => Ok(path.clone()),
+        None => Err(candidates),
+    }
+}
+
+/// 環境変数 `SAG_PATH` に並べられたディレクトリ
+pub fn sag_path() -> Vec<PathBuf> {
+    match std::env::var_os("SAG_PATH") {
+        Some(sag_path) => std::env::split_paths(&sag_path)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .collect(),
+        None => vec![],
+    }
+}
+
+pub fn module_candidates(
+    module_name: &str,
+    importing_file: Option<&Path>,
+    search_path: &[PathBuf],
+) -> Vec<PathBuf> {
+    let file_name = module_file_name(module_name);
+    let base_dir = importing_file
+        .and_then(|file| file.parent())
+        .map(|dir| dir.to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    let project_dir = project_dir(&base_dir);
+    let mut candidates = vec![base_dir.join(&file_name)];
+    candidates.push(project_dir.join(PACKAGE_DIR).join(&file_name));
+    if let Some(path) = package_module(&project_dir, module_name) {
+        candidates.push(path);
+    }
+    for dir in search_path {
+        candidates.push(dir.join(&file_name));
+    }
+
+    // 同じ場所を二度探さない
+    let mut unique: Vec<PathBuf> = vec![];
+    for candidate in candidates {
+        if !unique.contains(&candidate) {
+            unique.push(candidate);
+        }
+    }
+    unique
+}
+
+/// `.sag_packages` があるディレクトリ。
+/// インストールしたパッケージの中のファイルならそのパッケージをインストールしたプロジェクト、
+/// それ以外は sag.toml のあるプロジェクト、どちらでもなければ現在のディレクトリ
+fn project_dir(base_dir: &Path) -> PathBuf {
+    let base_dir = base_dir
+        .canonicalize()
+        .unwrap_or_else(|_| base_dir.to_path_buf());
+    if let Some(installed) = base_dir
+        .ancestors()
+        .find(|dir| dir.file_name().is_some_and(|name| name == PACKAGE_DIR))
+    {
+        return installed.parent().unwrap_or(Path::new(".")).to_path_buf();
+    }
+    find_manifest_dir(&base_dir).unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// `.sag_packages` にインストールしたパッケージのファイル。
+/// `math` は `math/<version>/lib.sag`、`math/vector` は `math/<version>/vector.sag`
+fn package_module(project_dir: &Path, module_name: &str) -> Option<PathBuf> {
+    if module_name.ends_with(".sag") {
+        return None;
+    }
+    let (name, rest) = match module_name.split_once('/') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (module_name, None),
+    };
+    let dir = installed_package_dir(project_dir, name);
+    match rest {
+        Some(rest) => Some(dir.join(module_file_name(rest))),
+        None => Some(package_lib(&dir)),
+    }
+}
+
+/// sag.lock に書かれたバージョン、なければインストールされている最新のバージョンのディレクトリ
+fn installed_package_dir(project_dir: &Path, name: &str) -> PathBuf {
+    let dir = project_dir.join(PACKAGE_DIR).join(name);
+    let locked = Lockfile::load(project_dir)
+        .ok()
+        .flatten()
+        .and_then(|lockfile| lockfile.get(name).map(|package| package.version.clone()));
+    if let Some(version) = locked {
+        let locked_dir = dir.join(version.to_string());
+        if locked_dir.is_dir() {
+            return locked_dir;
+        }
+    }
+    let mut versions = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| Version::parse(&entry.file_name().to_string_lossy()).ok())
+            .collect::<Vec<_>>(),
+        Err(_) => vec![],
+    };
+    versions.sort();
+    match versions.last() {
+        Some(version) => dir.join(version.to_string()),
+        None => dir,
+    }
+}
+
+// インストールしたパッケージの sag.toml に書かれた lib ファイル
+fn package_lib(dir: &Path) -> PathBuf {
+    match Manifest::load(dir) {
+        Ok(manifest) => dir.join(manifest.lib),
+        Err(_) => dir.join(DEFAULT_LIB),
+    }
+}
+
+/// `utils/strings` -> `utils/strings.sag`
+fn module_file_name(module_name: &str) -> PathBuf {
+    if module_name.ends_with(".sag") {
+        PathBuf::from(module_name)
+    } else {
+        PathBuf::from(format!("{}.sag", module_name))
+    }
+}
+
+pub fn module_not_found_message(module_name: &str, tried: &[PathBuf]) -> String {
+    let mut message = format!("module '{}' not found. Searched:", module_name);
+    for path in tried {
+        message.push_str(&format!("\n  {}", path.display()));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_resolve_relative_to_importing_file() {
+        let dir = std::env::temp_dir().join("sag_resolve_relative");
+        fs::create_dir_all(dir.join("utils")).unwrap();
+        fs::write(dir.join("utils/strings.sag"), "pub val a = 1").unwrap();
+        let main = dir.join("main.sag");
+
+        let resolved = resolve_module("utils/strings", Some(&main)).unwrap();
+        assert_eq!(resolved, dir.join("utils/strings.sag"));
+
+        let tried = resolve_module("missing", Some(&main)).unwrap_err();
+        assert_eq!(tried[0], dir.join("missing.sag"));
+        assert!(tried.contains(&PathBuf::from("./.sag_packages/missing.sag")));
+        let message = module_not_found_message("missing", &tried);
+        assert!(message.contains(&dir.join("missing.sag").display().to_string()));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_candidates_include_search_path() {
+        let candidates = module_candidates(
+            "math",
+            None,
+            &[PathBuf::from("/opt/sag/lib"), PathBuf::from("/opt/sag/lib")],
+        );
+        assert_eq!(
+            candidates,
+            vec![
+                PathBuf::from("./math.sag"),
+                PathBuf::from("./.sag_packages/math.sag"),
+                PathBuf::from("./.sag_packages/math/lib.sag"),
+                PathBuf::from("/opt/sag/lib/math.sag"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_package_module_picks_locked_version() {
+        let dir = std::env::temp_dir().join("sag_resolve_package_version");
+        let _ = fs::remove_dir_all(&dir);
+        for version in ["1.0.0", "1.2.0", "2.0.0"] {
+            fs::create_dir_all(dir.join(".sag_packages/math").join(version)).unwrap();
+        }
+        let installed = dir.join(".sag_packages/math");
+        assert_eq!(
+            package_module(&dir, "math"),
+            Some(installed.join("2.0.0/lib.sag"))
+        );
+
+        fs::write(
+            dir.join("sag.lock"),
+            "[[package]]\nname = \"math\"\nversion = \"1.2.0\"\nsource = \"path+../math\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            package_module(&dir, "math"),
+            Some(installed.join("1.2.0/lib.sag"))
+        );
+        assert_eq!(
+            package_module(&dir, "math/vector"),
+            Some(installed.join("1.2.0/vector.sag"))
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+}
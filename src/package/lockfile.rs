@@ -0,0 +1,203 @@
+use crate::package::version::Version;
+use crate::package::{normalize_path, relative_path, PackageError};
+use std::path::{Path, PathBuf};
+use toml::{Table, Value as TomlValue};
+
+pub const LOCK_FILE: &str = "sag.lock";
+const LOCK_HEADER: &str = "# This file is generated by `sag install`. Do not edit it by hand.\n";
+
+/// sag.lock に記録する、インストールしたパッケージ
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: Version,
+    pub source: PackageSource,
+    pub dependencies: Vec<String>,
+    // レジストリのアーカイブのチェックサム
+    pub checksum: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackageSource {
+    Path(PathBuf),
+    // レジストリのルート
+    Registry(PathBuf),
+}
+
+impl PackageSource {
+    fn encode(&self) -> String {
+        match self {
+            PackageSource::Path(path) => format!("path+{}", path.display()),
+            PackageSource::Registry(root) => format!("registry+{}", root.display()),
+        }
+    }
+
+    fn decode(source: &str) -> Result<PackageSource, String> {
+        if let Some(path) = source.strip_prefix("path+") {
+            Ok(PackageSource::Path(PathBuf::from(path)))
+        } else if let Some(root) = source.strip_prefix("registry+") {
+            Ok(PackageSource::Registry(PathBuf::from(root)))
+        } else {
+            Err(format!("unknown package source '{}'", source))
+        }
+    }
+
+    // パスの依存関係とレジストリは、別の場所に置いたプロジェクトでも使えるように dir からの相対パスで書く
+    #[allow(dead_code)]
+    fn relative_to(&self, dir: &Path) -> PackageSource {
+        match self {
+            PackageSource::Path(path) => PackageSource::Path(relative_path(path, dir)),
+            PackageSource::Registry(root) => PackageSource::Registry(relative_path(root, dir)),
+        }
+    }
+
+    fn resolve_from(&self, dir: &Path) -> PackageSource {
+        match self {
+            PackageSource::Path(path) => PackageSource::Path(normalize_path(&dir.join(path))),
+            PackageSource::Registry(root) => {
+                PackageSource::Registry(normalize_path(&dir.join(root)))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Lockfile {
+    pub packages: Vec<LockedPackage>,
+}
+
+impl Lockfile {
+    /// sag.lock がなければ None。パスの依存関係とレジストリは dir を基準にしたパスにする
+    pub fn load(dir: &Path) -> Result<Option<Lockfile>, PackageError> {
+        let path = dir.join(LOCK_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content =
+            std::fs::read_to_string(&path).map_err(|e| PackageError::io("read", &path, e))?;
+        let lockfile =
+            Lockfile::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Some(lockfile.map_sources(|source| source.resolve_from(dir))))
+    }
+
+    /// パスの依存関係とレジストリは dir からの相対パスで書き出す
+    #[allow(dead_code)]
+    pub fn save(&self, dir: &Path) -> Result<(), PackageError> {
+        let path = dir.join(LOCK_FILE);
+        let lockfile = self.clone().map_sources(|source| source.relative_to(dir));
+        std::fs::write(&path, lockfile.to_string())
+            .map_err(|e| PackageError::io("write", &path, e))
+    }
+
+    pub fn parse(content: &str) -> Result<Lockfile, String> {
+        let table = content.parse::<Table>().map_err(|e| e.to_string())?;
+        let entries = match table.get("package") {
+            Some(TomlValue::Array(entries)) => entries.clone(),
+            Some(_) => return Err("[[package]] must be an array of tables".to_string()),
+            None => vec![],
+        };
+        let mut packages = vec![];
+        for entry in entries {
+            let entry = entry.as_table().ok_or("[[package]] must be a table")?;
+            let field = |key: &str| {
+                entry
+                    .get(key)
+                    .and_then(|value| value.as_str())
+                    .ok_or(format!("package.{} is missing", key))
+            };
+            let dependencies = match entry.get("dependencies") {
+                Some(TomlValue::Array(names)) => names
+                    .iter()
+                    .filter_map(|name| name.as_str().map(|name| name.to_string()))
+                    .collect(),
+                _ => vec![],
+            };
+            packages.push(LockedPackage {
+                name: field("name")?.to_string(),
+                version: Version::parse(field("version")?)?,
+                source: PackageSource::decode(field("source")?)?,
+                dependencies,
+                checksum: entry
+                    .get("checksum")
+                    .and_then(|value| value.as_str())
+                    .map(|checksum| checksum.to_string()),
+            });
+        }
+        Ok(Lockfile { packages })
+    }
+
+    fn map_sources(mut self, f: impl Fn(&PackageSource) -> PackageSource) -> Lockfile {
+        for package in &mut self.packages {
+            package.source = f(&package.source);
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|package| package.name == name)
+    }
+}
+
+impl std::fmt::Display for Lockfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut entries = vec![];
+        for package in &self.packages {
+            let mut entry = Table::new();
+            entry.insert("name".into(), TomlValue::String(package.name.clone()));
+            entry.insert(
+                "version".into(),
+                TomlValue::String(package.version.to_string()),
+            );
+            entry.insert("source".into(), TomlValue::String(package.source.encode()));
+            entry.insert(
+                "dependencies".into(),
+                TomlValue::Array(
+                    package
+                        .dependencies
+                        .iter()
+                        .map(|name| TomlValue::String(name.clone()))
+                        .collect(),
+                ),
+            );
+            if let Some(checksum) = &package.checksum {
+                entry.insert("checksum".into(), TomlValue::String(checksum.clone()));
+            }
+            entries.push(TomlValue::Table(entry));
+        }
+        let mut table = Table::new();
+        table.insert("package".into(), TomlValue::Array(entries));
+        let body = toml::to_string(&table).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}\n{}", LOCK_HEADER, body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockfile_round_trip() {
+        let lockfile = Lockfile {
+            packages: vec![
+                LockedPackage {
+                    name: "math".to_string(),
+                    version: Version::new(1, 2, 0),
+                    source: PackageSource::Registry(PathBuf::from("/srv/registry")),
+                    dependencies: vec!["strings".to_string()],
+                    checksum: Some("sha256:0123abcd".to_string()),
+                },
+                LockedPackage {
+                    name: "strings".to_string(),
+                    version: Version::new(0, 3, 1),
+                    source: PackageSource::Path(PathBuf::from("../strings")),
+                    dependencies: vec![],
+                    checksum: None,
+                },
+            ],
+        };
+        let content = lockfile.to_string();
+        assert!(content.starts_with(LOCK_HEADER));
+        assert!(content.contains("[[package]]"));
+        assert_eq!(Lockfile::parse(&content).unwrap(), lockfile);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::package::version::{Version, VersionReq};
+use crate::package::PackageError;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value as TomlValue};
+
+pub const MANIFEST_FILE: &str = "sag.toml";
+pub const DEFAULT_LIB: &str = "lib.sag";
+pub const DEFAULT_ENTRY: &str = "src/main.sag";
+
+/// sag.toml の内容
+///
+/// ```toml
+/// [package]
+/// name = "app"
+/// version = "0.1.0"
+/// entry = "src/main.sag"
+///
+/// [dependencies]
+/// math = { path = "../math" }
+/// strings = "^1.2"
+///
+/// [registry]
+/// path = "/mnt/shared/sag-registry"
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub name: String,
+    pub version: Version,
+    // import されたときに読み込むファイル。パッケージのディレクトリからの相対パス
+    pub lib: String,
+    // sag run / sag compile で実行するファイル
+    pub entry: String,
+    pub dependencies: Vec<Dependency>,
+    pub registry: Option<PathBuf>,
+    // sag.toml があるディレクトリ
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub name: String,
+    pub req: VersionReq,
+    pub source: DependencySource,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DependencySource {
+    Path(PathBuf),
+    Registry,
+}
+
+impl Manifest {
+    pub fn load(dir: &Path) -> Result<Manifest, PackageError> {
+        let path = dir.join(MANIFEST_FILE);
+        let content =
+            std::fs::read_to_string(&path).map_err(|e| PackageError::io("read", &path, e))?;
+        Manifest::parse(&content, dir)
+            .map_err(|e| PackageError::Invalid(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn parse(content: &str, dir: &Path) -> Result<Manifest, String> {
+        let table = content.parse::<Table>().map_err(|e| e.to_string())?;
+        let package = match table.get("package") {
+            Some(TomlValue::Table(package)) => package,
+            _ => return Err("missing [package] section".to_string()),
+        };
+        let name = get_str(package, "name", "package")?
+            .ok_or("missing package.name")?
+            .to_string();
+        let version = match get_str(package, "version", "package")? {
+            Some(version) => Version::parse(version)?,
+            None => return Err("missing package.version".to_string()),
+        };
+        let lib = get_str(package, "lib", "package")?
+            .unwrap_or(DEFAULT_LIB)
+            .to_string();
+        let entry = get_str(package, "entry", "package")?
+            .unwrap_or(DEFAULT_ENTRY)
+            .to_string();
+
+        let mut dependencies = vec![];
+        match table.get("dependencies") {
+            Some(TomlValue::Table(deps)) => {
+                for (dep_name, spec) in deps {
+                    dependencies.push(parse_dependency(dep_name, spec, dir)?);
+                }
+            }
+            Some(_) => return Err("[dependencies] must be a table".to_string()),
+            None => {}
+        }
+
+        let registry = match table.get("registry") {
+            Some(TomlValue::Table(registry)) => {
+                get_str(registry, "path", "registry")?.map(|path| dir.join(path))
+            }
+            Some(_) => return Err("[registry] must be a table".to_string()),
+            None => None,
+        };
+
+        Ok(Manifest {
+            name,
+            version,
+            lib,
+            entry,
+            dependencies,
+            registry,
+            dir: dir.to_path_buf(),
+        })
+    }
+}
+
+fn parse_dependency(name: &str, spec: &TomlValue, dir: &Path) -> Result<Dependency, String> {
+    match spec {
+        // math = "^1.2"
+        TomlValue::String(req) => Ok(Dependency {
+            name: name.to_string(),
+            req: VersionReq::parse(req)?,
+            source: DependencySource::Registry,
+        }),
+        // math = { path = "../math", version = "1.0" }
+        TomlValue::Table(spec) => {
+            let section = format!("dependencies.{}", name);
+            let req = match get_str(spec, "version", &section)? {
+                Some(req) => VersionReq::parse(req)?,
+                None => VersionReq::any(),
+            };
+            let source = match get_str(spec, "path", &section)? {
+                Some(path) => DependencySource::Path(dir.join(path)),
+                None => DependencySource::Registry,
+            };
+            Ok(Dependency {
+                name: name.to_string(),
+                req,
+                source,
+            })
+        }
+        _ => Err(format!(
+            "dependency '{}' must be a version string or a table",
+            name
+        )),
+    }
+}
+
+fn get_str<'a>(table: &'a Table, key: &str, section: &str) -> Result<Option<&'a str>, String> {
+    match table.get(key) {
+        Some(TomlValue::String(value)) => Ok(Some(value)),
+        Some(_) => Err(format!("{}.{} must be a string", section, key)),
+        None => Ok(None),
+    }
+}
+
+/// 現在のディレクトリから親に向かって sag.toml を探す
+pub fn find_manifest_dir(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(MANIFEST_FILE).is_file())
+        .map(|dir| dir.to_path_buf())
+}
+
+/// ファイルが指定されなかったときに実行する、プロジェクトの entry のファイル
+#[allow(dead_code)]
+pub fn find_entry_point(start: &Path) -> Result<PathBuf, PackageError> {
+    let dir = find_manifest_dir(start)
+        .ok_or("no file given and no sag.toml found in this directory or any parent".to_string())?;
+    let manifest = Manifest::load(&dir)?;
+    let entry = dir.join(&manifest.entry);
+    if !entry.is_file() {
+        return Err(format!(
+            "entry point {} in {} does not exist",
+            entry.display(),
+            dir.join(MANIFEST_FILE).display()
+        )
+        .into());
+    }
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let content = r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+math = { path = "../math" }
+strings = "^1.2"
+
+[registry]
+path = "registry"
+"#;
+        let manifest = Manifest::parse(content, Path::new("/work/app")).unwrap();
+        assert_eq!(manifest.name, "app");
+        assert_eq!(manifest.version, Version::new(0, 1, 0));
+        assert_eq!(manifest.lib, "lib.sag");
+        assert_eq!(manifest.entry, "src/main.sag");
+        assert_eq!(manifest.registry, Some(PathBuf::from("/work/app/registry")));
+        assert_eq!(
+            manifest.dependencies[0].source,
+            DependencySource::Path(PathBuf::from("/work/app/../math"))
+        );
+        assert_eq!(manifest.dependencies[1].name, "strings");
+        assert_eq!(manifest.dependencies[1].source, DependencySource::Registry);
+        assert!(manifest.dependencies[1].req.matches(&Version::new(1, 4, 0)));
+
+        let error = Manifest::parse("[package]\nname = \"app\"", Path::new(".")).unwrap_err();
+        assert_eq!(error, "missing package.version");
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+pub mod lockfile;
+pub mod manifest;
+pub mod registry;
+pub mod resolver;
+pub mod scaffold;
+pub mod version;
+
+/// インストールしたパッケージを置くディレクトリ
+pub const PACKAGE_DIR: &str = ".sag_packages";
+
+/// パッケージやプロジェクトの操作の失敗。ファイル操作の失敗は終了コードを分けるので区別する
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackageError {
+    Io(String),
+    Invalid(String),
+}
+
+impl PackageError {
+    pub fn io(action: &str, path: &Path, error: std::io::Error) -> Self {
+        PackageError::Io(format!("cannot {} {}: {}", action, path.display(), error))
+    }
+}
+
+impl From<String> for PackageError {
+    fn from(message: String) -> Self {
+        PackageError::Invalid(message)
+    }
+}
+
+impl fmt::Display for PackageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackageError::Io(message) | PackageError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn current_dir() -> Result<PathBuf, PackageError> {
+    std::env::current_dir()
+        .map_err(|e| PackageError::Io(format!("cannot read current directory: {}", e)))
+}
+
+/// パッケージのディレクトリをコピーする
+#[allow(dead_code)]
+pub fn copy_dir(source: &Path, target: &Path) -> Result<(), PackageError> {
+    fs::create_dir_all(target).map_err(|e| PackageError::io("create", target, e))?;
+    let entries = fs::read_dir(source).map_err(|e| PackageError::io("read", source, e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| PackageError::io("read", source, e))?;
+        let name = entry.file_name();
+        // インストール先や VCS のディレクトリはコピーしない
+        if name == PACKAGE_DIR || name == ".git" {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            copy_dir(&path, &target.join(&name))?;
+        } else {
+            fs::copy(&path, target.join(&name)).map_err(|e| PackageError::io("copy", &path, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// `.` と `..` を取り除いたパス。ファイルシステムは見ない
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(normalized.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// base から見た path の相対パス。共通の先頭がなければ path をそのまま返す
+#[allow(dead_code)]
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path = normalize_path(path);
+    let base = normalize_path(base);
+    if path.has_root() != base.has_root() {
+        return path;
+    }
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 && path.has_root() {
+        return path;
+    }
+    let mut relative = PathBuf::new();
+    for _ in base.components().skip(common) {
+        relative.push("..");
+    }
+    for component in path.components().skip(common) {
+        relative.push(component);
+    }
+    relative
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            normalize_path(Path::new("/tmp/t/proj/./../dep")),
+            PathBuf::from("/tmp/t/dep")
+        );
+        assert_eq!(normalize_path(Path::new("../dep")), PathBuf::from("../dep"));
+        assert_eq!(
+            relative_path(Path::new("/tmp/t/proj/../dep"), Path::new("/tmp/t/proj")),
+            PathBuf::from("../dep")
+        );
+        assert_eq!(
+            relative_path(Path::new("/tmp/t/proj/libs/a"), Path::new("/tmp/t/proj")),
+            PathBuf::from("libs/a")
+        );
+        assert_eq!(
+            relative_path(Path::new("/tmp/t/proj/../dep"), Path::new(".")),
+            PathBuf::from("/tmp/t/dep")
+        );
+    }
+}
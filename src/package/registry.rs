@@ -0,0 +1,305 @@
+use crate::package::{copy_dir, normalize_path, PackageError};
+use crate::package::manifest::{Dependency, DependencySource, Manifest};
+use crate::package::version::{Version, VersionReq};
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value as TomlValue};
+
+#[allow(dead_code)]
+pub const INDEX_FILE: &str = "index.toml";
+
+/// ネットワークを使わないレジストリ。共有ファイルシステム上のミラーなどを想定している。
+///
+/// ルートに index.toml があれば、そこに並べたアーカイブ (tar.gz) からインストールする。
+///
+/// ```toml
+/// [[package]]
+/// name = "strings"
+/// version = "1.4.2"
+/// archive = "strings/strings-1.4.2.tar.gz"  # ルートからの相対パス
+/// checksum = "sha256:9f86d0..."
+/// dependencies = { math = "^0.2" }
+/// ```
+///
+/// index.toml がなければ `<root>/<name>/<version>/` に置いたパッケージのディレクトリを使う
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Registry {
+    pub root: PathBuf,
+    index: Option<Vec<IndexEntry>>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+struct IndexEntry {
+    name: String,
+    version: Version,
+    archive: PathBuf,
+    checksum: String,
+    dependencies: Vec<Dependency>,
+}
+
+#[allow(dead_code)]
+impl Registry {
+    pub fn open(root: &Path) -> Result<Registry, PackageError> {
+        let index_path = root.join(INDEX_FILE);
+        let index = if index_path.is_file() {
+            let content = fs::read_to_string(&index_path)
+                .map_err(|e| PackageError::io("read", &index_path, e))?;
+            Some(parse_index(&content).map_err(|e| format!("{}: {}", index_path.display(), e))?)
+        } else if root.is_dir() {
+            None
+        } else {
+            return Err(PackageError::Io(format!(
+                "registry {} does not exist",
+                root.display()
+            )));
+        };
+        Ok(Registry {
+            // sag.lock に書いたルートと比べられるように、`..` を取り除いておく
+            root: normalize_path(root),
+            index,
+        })
+    }
+
+    /// manifest の `[registry] path`、なければ環境変数 `SAG_REGISTRY`
+    pub fn configured(
+        manifest_registry: Option<&Path>,
+    ) -> Result<Option<Registry>, PackageError> {
+        let root = match manifest_registry {
+            Some(root) => root.to_path_buf(),
+            None => match std::env::var_os("SAG_REGISTRY").filter(|root| !root.is_empty()) {
+                Some(root) => std::path::absolute(root)
+                    .map_err(|e| PackageError::Io(format!("invalid SAG_REGISTRY: {}", e)))?,
+                None => return Ok(None),
+            },
+        };
+        Registry::open(&root).map(Some)
+    }
+
+    /// 新しい順に並べた、公開されているバージョン
+    pub fn versions(&self, name: &str) -> Vec<Version> {
+        let mut versions = match &self.index {
+            Some(index) => index
+                .iter()
+                .filter(|entry| entry.name == name)
+                .map(|entry| entry.version.clone())
+                .collect::<Vec<_>>(),
+            None => match fs::read_dir(self.root.join(name)) {
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_dir())
+                    .filter_map(|entry| Version::parse(&entry.file_name().to_string_lossy()).ok())
+                    .collect::<Vec<_>>(),
+                Err(_) => vec![],
+            },
+        };
+        versions.sort();
+        versions.reverse();
+        versions
+    }
+
+    pub fn dependencies(
+        &self,
+        name: &str,
+        version: &Version,
+    ) -> Result<Vec<Dependency>, PackageError> {
+        match &self.index {
+            Some(_) => Ok(self.entry(name, version)?.dependencies.clone()),
+            None => Ok(Manifest::load(&self.package_dir(name, version))?.dependencies),
+        }
+    }
+
+    /// アーカイブのチェックサム。ディレクトリのレジストリにはない
+    pub fn checksum(&self, name: &str, version: &Version) -> Result<Option<String>, String> {
+        match &self.index {
+            Some(_) => Ok(Some(self.entry(name, version)?.checksum.clone())),
+            None => Ok(None),
+        }
+    }
+
+    /// パッケージを target に展開する。アーカイブはチェックサムを確かめてから展開する
+    pub fn unpack(&self, name: &str, version: &Version, target: &Path) -> Result<(), PackageError> {
+        if self.index.is_none() {
+            return copy_dir(&self.package_dir(name, version), target);
+        }
+        let entry = self.entry(name, version)?;
+        let archive_path = self.root.join(&entry.archive);
+        let bytes = fs::read(&archive_path).map_err(|e| PackageError::io("read", &archive_path, e))?;
+        let actual = sha256(&bytes);
+        if actual != entry.checksum {
+            return Err(format!(
+                "checksum mismatch for {} {}: expected {}, got {}",
+                name, version, entry.checksum, actual
+            )
+            .into());
+        }
+        fs::create_dir_all(target).map_err(|e| PackageError::io("create", target, e))?;
+        tar::Archive::new(GzDecoder::new(bytes.as_slice()))
+            .unpack(target)
+            .map_err(|e| PackageError::io("unpack", &archive_path, e))
+    }
+
+    fn package_dir(&self, name: &str, version: &Version) -> PathBuf {
+        self.root.join(name).join(version.to_string())
+    }
+
+    fn entry(&self, name: &str, version: &Version) -> Result<&IndexEntry, String> {
+        self.index
+            .iter()
+            .flatten()
+            .find(|entry| entry.name == name && entry.version == *version)
+            .ok_or(format!("{} {} is not in the registry index", name, version))
+    }
+}
+
+/// `sha256:<hex>` の形のチェックサム
+#[allow(dead_code)]
+pub fn sha256(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    let hex = digest
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!("sha256:{}", hex)
+}
+
+#[allow(dead_code)]
+fn parse_index(content: &str) -> Result<Vec<IndexEntry>, String> {
+    let table = content.parse::<Table>().map_err(|e| e.to_string())?;
+    let packages = match table.get("package") {
+        Some(TomlValue::Array(packages)) => packages,
+        Some(_) => return Err("[[package]] must be an array of tables".to_string()),
+        None => return Ok(vec![]),
+    };
+    let mut entries = vec![];
+    for package in packages {
+        let package = package.as_table().ok_or("[[package]] must be a table")?;
+        let field = |key: &str| {
+            package
+                .get(key)
+                .and_then(|value| value.as_str())
+                .ok_or(format!("package.{} is missing", key))
+        };
+        let name = field("name")?.to_string();
+        let mut checksum = field("checksum")?.to_string();
+        if !checksum.starts_with("sha256:") {
+            checksum = format!("sha256:{}", checksum);
+        }
+        let mut dependencies = vec![];
+        if let Some(TomlValue::Table(deps)) = package.get("dependencies") {
+            for (dep_name, req) in deps {
+                let req = req.as_str().ok_or(format!(
+                    "{}: dependency '{}' must be a version string",
+                    name, dep_name
+                ))?;
+                dependencies.push(Dependency {
+                    name: dep_name.clone(),
+                    req: VersionReq::parse(req)?,
+                    source: DependencySource::Registry,
+                });
+            }
+        }
+        entries.push(IndexEntry {
+            version: Version::parse(field("version")?)?,
+            archive: PathBuf::from(field("archive")?),
+            checksum,
+            dependencies,
+            name,
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    // name-version.tar.gz を作り、index.toml に書く行を返す
+    fn write_archive(root: &Path, name: &str, version: &str, dependencies: &str) -> String {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let manifest = format!(
+            "[package]\nname = \"{}\"\nversion = \"{}\"\n",
+            name, version
+        );
+        let lib = format!("pub val version = \"{}\"", version);
+        for (path, content) in [("sag.toml", manifest), ("lib.sag", lib)] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        let bytes = builder.into_inner().unwrap().finish().unwrap();
+        let archive = format!("{}/{}-{}.tar.gz", name, name, version);
+        fs::create_dir_all(root.join(name)).unwrap();
+        fs::write(root.join(&archive), &bytes).unwrap();
+        format!(
+            "[[package]]\nname = \"{}\"\nversion = \"{}\"\narchive = \"{}\"\nchecksum = \"{}\"\ndependencies = {{ {} }}\n",
+            name,
+            version,
+            archive,
+            sha256(&bytes),
+            dependencies
+        )
+    }
+
+    #[test]
+    fn test_index_registry_unpacks_verified_archive() {
+        let root = std::env::temp_dir().join("sag_registry_index");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let index = [
+            write_archive(&root, "strings", "1.0.0", ""),
+            write_archive(&root, "strings", "1.2.0", ""),
+            write_archive(&root, "math", "0.2.0", "strings = \"^1.0\""),
+        ]
+        .join("\n");
+        fs::write(root.join(INDEX_FILE), index).unwrap();
+
+        let registry = Registry::open(&root).unwrap();
+        assert_eq!(
+            registry.versions("strings"),
+            vec![Version::new(1, 2, 0), Version::new(1, 0, 0)]
+        );
+        let dependencies = registry
+            .dependencies("math", &Version::new(0, 2, 0))
+            .unwrap();
+        assert_eq!(dependencies[0].name, "strings");
+
+        let target = root.join("out/strings/1.2.0");
+        registry
+            .unpack("strings", &Version::new(1, 2, 0), &target)
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(target.join("lib.sag")).unwrap(),
+            "pub val version = \"1.2.0\""
+        );
+
+        // アーカイブが書き換えられていたら展開しない
+        fs::write(root.join("strings/strings-1.0.0.tar.gz"), b"tampered").unwrap();
+        let error = registry
+            .unpack("strings", &Version::new(1, 0, 0), &root.join("out/bad"))
+            .unwrap_err();
+        assert!(
+            matches!(&error, PackageError::Invalid(message) if message.starts_with("checksum mismatch for strings 1.0.0")),
+            "{}",
+            error
+        );
+        assert!(!root.join("out/bad").exists());
+
+        // アーカイブが読めないのはファイル操作の失敗
+        fs::remove_file(root.join("strings/strings-1.2.0.tar.gz")).unwrap();
+        let error = registry
+            .unpack("strings", &Version::new(1, 2, 0), &root.join("out/missing"))
+            .unwrap_err();
+        assert!(matches!(error, PackageError::Io(_)), "{}", error);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
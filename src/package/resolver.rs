@@ -0,0 +1,415 @@
+use crate::package::lockfile::{LockedPackage, Lockfile, PackageSource};
+use crate::package::manifest::{Dependency, DependencySource, Manifest};
+use crate::package::registry::Registry;
+use crate::package::version::Version;
+use crate::package::{copy_dir, normalize_path, PackageError, PACKAGE_DIR};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+
+// 依存関係と、それを要求したパッケージの名前
+#[allow(dead_code)]
+type Requirement = (Dependency, String);
+
+// インストールできるパッケージのバージョンと、その依存関係
+#[allow(dead_code)]
+struct Candidate {
+    version: Version,
+    source: PackageSource,
+    dependencies: Vec<Dependency>,
+    checksum: Option<String>,
+}
+
+/// 依存関係を辿って、インストールするパッケージとバージョンを決める。
+/// ロックファイルにあるバージョンがすべての制約を満たしていればそれを使い、なければ制約を満たす最新のものを選ぶ。
+/// 選んだバージョンが後から見つかった制約と合わなければ、戻って次に新しいバージョンを試す
+#[allow(dead_code)]
+pub fn resolve(
+    dependencies: &[Dependency],
+    root_name: &str,
+    registry: Option<&Registry>,
+    lockfile: Option<&Lockfile>,
+) -> Result<Vec<LockedPackage>, PackageError> {
+    let queue = dependencies
+        .iter()
+        .map(|dependency| (dependency.clone(), root_name.to_string()))
+        .collect::<VecDeque<_>>();
+    let mut resolved = Resolver { registry, lockfile }.solve(queue, vec![])?;
+    resolved.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(resolved)
+}
+
+#[allow(dead_code)]
+struct Resolver<'a> {
+    registry: Option<&'a Registry>,
+    lockfile: Option<&'a Lockfile>,
+}
+
+#[allow(dead_code)]
+impl Resolver<'_> {
+    fn solve(
+        &self,
+        mut queue: VecDeque<Requirement>,
+        resolved: Vec<LockedPackage>,
+    ) -> Result<Vec<LockedPackage>, PackageError> {
+        while let Some((dependency, required_by)) = queue.pop_front() {
+            if let Some(package) = resolved.iter().find(|p| p.name == dependency.name) {
+                if !dependency.req.matches(&package.version) {
+                    return Err(format!(
+                        "version conflict for '{}': {} requires {}, but {} was selected",
+                        dependency.name, required_by, dependency.req, package.version
+                    )
+                    .into());
+                }
+                continue;
+            }
+
+            // どのバージョンでも解決できなければ、一番優先したバージョンでの失敗を返す
+            let mut first_error = None;
+            for candidate in self.candidates(&dependency, &required_by, &queue)? {
+                let mut queue = queue.clone();
+                for child in &candidate.dependencies {
+                    queue.push_back((child.clone(), dependency.name.clone()));
+                }
+                let mut resolved = resolved.clone();
+                resolved.push(LockedPackage {
+                    name: dependency.name.clone(),
+                    version: candidate.version,
+                    source: candidate.source,
+                    dependencies: candidate
+                        .dependencies
+                        .iter()
+                        .map(|child| child.name.clone())
+                        .collect(),
+                    checksum: candidate.checksum,
+                });
+                match self.solve(queue, resolved) {
+                    Ok(resolved) => return Ok(resolved),
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+            return Err(first_error.unwrap_or_else(|| {
+                PackageError::Invalid(format!("no version of '{}' to try", dependency.name))
+            }));
+        }
+        Ok(resolved)
+    }
+
+    /// dependency として試すバージョンを優先する順に返す。空にはならない。
+    /// レジストリのパッケージは、まだ辿っていない同じ名前の制約もすべて満たすものに絞る
+    fn candidates(
+        &self,
+        dependency: &Dependency,
+        required_by: &str,
+        queue: &VecDeque<Requirement>,
+    ) -> Result<Vec<Candidate>, PackageError> {
+        match &dependency.source {
+            DependencySource::Path(dir) => {
+                let manifest = Manifest::load(dir)?;
+                if manifest.name != dependency.name {
+                    return Err(format!(
+                        "{} depends on '{}', but the package at {} is named '{}'",
+                        required_by,
+                        dependency.name,
+                        dir.display(),
+                        manifest.name
+                    )
+                    .into());
+                }
+                if !dependency.req.matches(&manifest.version) {
+                    return Err(format!(
+                        "{} requires {} {}, but {} is {}",
+                        required_by,
+                        dependency.name,
+                        dependency.req,
+                        dir.display(),
+                        manifest.version
+                    )
+                    .into());
+                }
+                Ok(vec![Candidate {
+                    version: manifest.version,
+                    source: PackageSource::Path(normalize_path(dir)),
+                    dependencies: manifest.dependencies,
+                    checksum: None,
+                }])
+            }
+            DependencySource::Registry => {
+                let registry = self.registry.ok_or(format!(
+                    "cannot resolve '{}': no registry configured. Set [registry] path in sag.toml or SAG_REGISTRY",
+                    dependency.name
+                ))?;
+                let versions = registry
+                    .versions(&dependency.name)
+                    .into_iter()
+                    .filter(|version| dependency.req.matches(version))
+                    .collect::<Vec<_>>();
+                if versions.is_empty() {
+                    return Err(format!(
+                        "no version of '{}' matches {} (required by {})",
+                        dependency.name, dependency.req, required_by
+                    )
+                    .into());
+                }
+                let requirements = queue
+                    .iter()
+                    .filter(|(other, _)| other.name == dependency.name)
+                    .collect::<Vec<_>>();
+                let mut versions = versions
+                    .into_iter()
+                    .filter(|version| {
+                        requirements
+                            .iter()
+                            .all(|(other, _)| other.req.matches(version))
+                    })
+                    .collect::<Vec<_>>();
+                if versions.is_empty() {
+                    let mut requirements = requirements
+                        .iter()
+                        .map(|(other, by)| format!("{} requires {}", by, other.req))
+                        .collect::<Vec<_>>();
+                    requirements.insert(0, format!("{} requires {}", required_by, dependency.req));
+                    return Err(format!(
+                        "version conflict for '{}': no version satisfies {}",
+                        dependency.name,
+                        requirements.join(", ")
+                    )
+                    .into());
+                }
+
+                let source = PackageSource::Registry(registry.root.clone());
+                let locked = self
+                    .lockfile
+                    .and_then(|lockfile| lockfile.get(&dependency.name))
+                    .filter(|locked| {
+                        locked.source == source && versions.contains(&locked.version)
+                    });
+                if let Some(locked) = locked {
+                    versions.retain(|version| *version != locked.version);
+                    versions.insert(0, locked.version.clone());
+                }
+                let mut candidates = vec![];
+                for version in versions {
+                    let checksum = registry.checksum(&dependency.name, &version)?;
+                    // ロックしたときと中身が変わっていたらインストールしない
+                    let locked_checksum = locked
+                        .filter(|locked| locked.version == version)
+                        .and_then(|locked| locked.checksum.clone());
+                    if locked_checksum.is_some() && locked_checksum != checksum {
+                        return Err(format!(
+                            "checksum of {} {} in the registry does not match sag.lock",
+                            dependency.name, version
+                        )
+                        .into());
+                    }
+                    candidates.push(Candidate {
+                        dependencies: registry.dependencies(&dependency.name, &version)?,
+                        version,
+                        source: source.clone(),
+                        checksum,
+                    });
+                }
+                Ok(candidates)
+            }
+        }
+    }
+}
+
+/// sag.toml に書かれた依存関係をすべて `.sag_packages/<name>/<version>` にインストールし、sag.lock を書き出す
+#[allow(dead_code)]
+pub fn install_manifest(dir: &Path) -> Result<Vec<LockedPackage>, PackageError> {
+    let manifest = Manifest::load(dir)?;
+    let lockfile = Lockfile::load(dir)?;
+    let registry = Registry::configured(manifest.registry.as_deref())?;
+    let packages = resolve(
+        &manifest.dependencies,
+        &manifest.name,
+        registry.as_ref(),
+        lockfile.as_ref(),
+    )?;
+    for package in &packages {
+        install_locked_package(package, &dir.join(PACKAGE_DIR))?;
+    }
+    Lockfile {
+        packages: packages.clone(),
+    }
+    .save(dir)?;
+    Ok(packages)
+}
+
+#[allow(dead_code)]
+pub fn install_locked_package(
+    package: &LockedPackage,
+    target_dir: &Path,
+) -> Result<(), PackageError> {
+    let target = target_dir
+        .join(&package.name)
+        .join(package.version.to_string());
+    if target.exists() {
+        fs::remove_dir_all(&target)
+            .map_err(|e| PackageError::io("remove", &target, e))?;
+    }
+    match &package.source {
+        PackageSource::Path(dir) => copy_dir(dir, &target),
+        PackageSource::Registry(root) => {
+            Registry::open(root)?.unpack(&package.name, &package.version, &target)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_package(dir: &Path, name: &str, version: &str, dependencies: &str) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(
+            dir.join("sag.toml"),
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"{}\"\n\n[dependencies]\n{}",
+                name, version, dependencies
+            ),
+        )
+        .unwrap();
+        fs::write(dir.join("lib.sag"), format!("pub val name = \"{}\"", name)).unwrap();
+    }
+
+    #[test]
+    fn test_install_manifest_resolves_and_locks() {
+        let dir = std::env::temp_dir().join("sag_resolver_install");
+        let _ = fs::remove_dir_all(&dir);
+        let registry = dir.join("registry");
+        write_package(&registry.join("strings/1.0.0"), "strings", "1.0.0", "");
+        write_package(&registry.join("strings/1.4.2"), "strings", "1.4.2", "");
+        write_package(&registry.join("strings/2.0.0"), "strings", "2.0.0", "");
+        write_package(&dir.join("math"), "math", "0.2.0", "strings = \"^1.0\"");
+        write_package(
+            &dir.join("app"),
+            "app",
+            "0.1.0",
+            "math = { path = \"../math\" }\nstrings = \">=1.0, <1.4\"\n\n[registry]\npath = \"../registry\"",
+        );
+
+        let app = dir.join("app");
+        let packages = install_manifest(&app).unwrap();
+        let versions = packages
+            .iter()
+            .map(|p| (p.name.as_str(), p.version.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            versions,
+            vec![
+                ("math", "0.2.0".to_string()),
+                ("strings", "1.0.0".to_string())
+            ]
+        );
+        assert!(app.join(".sag_packages/math/0.2.0/lib.sag").is_file());
+        assert!(app.join(".sag_packages/strings/1.0.0/lib.sag").is_file());
+
+        // ロックされたバージョンは新しいバージョンが出ても変わらない
+        write_package(&registry.join("strings/1.3.0"), "strings", "1.3.0", "");
+        let content = fs::read_to_string(app.join("sag.lock")).unwrap();
+        assert!(content.contains("source = \"path+../math\""));
+        assert!(content.contains("source = \"registry+../registry\""));
+        let lockfile = Lockfile::load(&app).unwrap().unwrap();
+        assert_eq!(
+            lockfile.get("math").unwrap().source,
+            PackageSource::Path(dir.join("math"))
+        );
+        assert_eq!(
+            lockfile.get("strings").unwrap().source,
+            PackageSource::Registry(registry)
+        );
+        assert_eq!(
+            lockfile.get("strings").unwrap().version,
+            Version::new(1, 0, 0)
+        );
+        let packages = install_manifest(&app).unwrap();
+        assert_eq!(packages[1].version, Version::new(1, 0, 0));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_picks_version_matching_every_requirement() {
+        let dir = std::env::temp_dir().join("sag_resolver_backtrack");
+        let _ = fs::remove_dir_all(&dir);
+        let registry_dir = dir.join("registry");
+        write_package(&registry_dir.join("strings/1.0.0"), "strings", "1.0.0", "");
+        write_package(&registry_dir.join("strings/1.4.2"), "strings", "1.4.2", "");
+        write_package(&registry_dir.join("strings/2.0.0"), "strings", "2.0.0", "");
+        write_package(&dir.join("math"), "math", "0.2.0", "strings = \">=1.0, <1.4\"");
+        write_package(
+            &dir.join("geometry"),
+            "geometry",
+            "0.1.0",
+            "math = { path = \"../math\" }",
+        );
+        let registry = Registry::open(&registry_dir).unwrap();
+        let resolve_app = |dependencies: &str| {
+            let manifest = Manifest::parse(
+                &format!(
+                    "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}",
+                    dependencies
+                ),
+                &dir,
+            )
+            .unwrap();
+            resolve(&manifest.dependencies, "app", Some(&registry), None)
+        };
+        let strings_version = |dependencies: &str| {
+            resolve_app(dependencies)
+                .unwrap()
+                .into_iter()
+                .find(|package| package.name == "strings")
+                .unwrap()
+                .version
+        };
+
+        // 同じパッケージへの制約をまとめてから選ぶ
+        assert_eq!(
+            strings_version("math = { path = \"math\" }\nstrings = \"^1.0\""),
+            Version::new(1, 0, 0)
+        );
+        // 先に 1.4.2 を選んでも、後から見つかった制約に合わせて選び直す
+        assert_eq!(
+            strings_version("geometry = { path = \"geometry\" }\nstrings = \"^1.0\""),
+            Version::new(1, 0, 0)
+        );
+
+        let error = resolve_app("math = { path = \"math\" }\nstrings = \"^2.0\"").unwrap_err();
+        assert_eq!(
+            error,
+            PackageError::Invalid(
+                "version conflict for 'strings': no version satisfies app requires ^2.0, math requires >=1.0, <1.4"
+                    .to_string()
+            )
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_conflicting_requirements() {
+        let dir = std::env::temp_dir().join("sag_resolver_conflict");
+        let _ = fs::remove_dir_all(&dir);
+        write_package(&dir.join("strings"), "strings", "2.1.0", "");
+        write_package(
+            &dir.join("math"),
+            "math",
+            "0.2.0",
+            "strings = { path = \"../strings\", version = \"^1.0\" }",
+        );
+        let manifest = Manifest::parse(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nmath = { path = \"math\" }\nstrings = { path = \"strings\" }",
+            &dir,
+        )
+        .unwrap();
+        let error = resolve(&manifest.dependencies, "app", None, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "version conflict for 'strings': math requires ^1.0, but 2.1.0 was selected"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
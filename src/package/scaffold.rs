@@ -0,0 +1,126 @@
+use crate::package::manifest::{DEFAULT_ENTRY, MANIFEST_FILE};
+use crate::package::{PackageError, PACKAGE_DIR};
+use std::fs;
+use std::path::Path;
+
+/// `sag new <name>`: name のディレクトリを作り、その中にプロジェクトを作る
+#[allow(dead_code)]
+pub fn new_project(dir: &Path, name: &str) -> Result<(), PackageError> {
+    validate_name(name)?;
+    if dir.exists() {
+        return Err(format!("destination {} already exists", dir.display()).into());
+    }
+    create_dir(dir)?;
+    write_project(dir, name)
+}
+
+/// `sag init`: 既存のディレクトリをプロジェクトにする。名前はディレクトリ名から決める
+#[allow(dead_code)]
+pub fn init_project(dir: &Path) -> Result<String, PackageError> {
+    if dir.join(MANIFEST_FILE).exists() {
+        return Err(format!(
+            "{} already exists in {}",
+            MANIFEST_FILE,
+            dir.display()
+        )
+        .into());
+    }
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or(format!("cannot use {} as a package name", dir.display()))?;
+    validate_name(&name)?;
+    write_project(dir, &name)?;
+    Ok(name)
+}
+
+// 既にあるファイルは上書きしない
+#[allow(dead_code)]
+fn write_project(dir: &Path, name: &str) -> Result<(), PackageError> {
+    let manifest = format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nentry = \"{}\"\n\n[dependencies]\n",
+        name, DEFAULT_ENTRY
+    );
+    write_new_file(&dir.join(MANIFEST_FILE), &manifest)?;
+    write_new_file(
+        &dir.join(DEFAULT_ENTRY),
+        &format!("print(\"Hello from {}!\")\n", name),
+    )?;
+    write_new_file(
+        &dir.join("tests/main_test.sag"),
+        "fun test_addition() {\n    assert_eq(1 + 1, 2)\n}\n",
+    )?;
+    write_new_file(&dir.join(".gitignore"), &format!("/{}/\n", PACKAGE_DIR))
+}
+
+#[allow(dead_code)]
+fn write_new_file(path: &Path, content: &str) -> Result<(), PackageError> {
+    if path.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        create_dir(parent)?;
+    }
+    fs::write(path, content).map_err(|e| PackageError::io("write", path, e))
+}
+
+#[allow(dead_code)]
+fn create_dir(path: &Path) -> Result<(), PackageError> {
+    fs::create_dir_all(path).map_err(|e| PackageError::io("create", path, e))
+}
+
+// パッケージ名はモジュール名としても使うので識別子と同じ文字だけにする
+#[allow(dead_code)]
+fn validate_name(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(first) => {
+            (first.is_ascii_alphabetic() || first == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid package name '{}': use letters, digits and underscores, starting with a letter",
+            name
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package::manifest::{find_entry_point, Manifest};
+
+    #[test]
+    fn test_new_and_init_project() {
+        let root = std::env::temp_dir().join("sag_scaffold");
+        let _ = fs::remove_dir_all(&root);
+        let dir = root.join("hello");
+        new_project(&dir, "hello").unwrap();
+        let manifest = Manifest::load(&dir).unwrap();
+        assert_eq!(manifest.name, "hello");
+        assert!(dir.join("tests").is_dir());
+        assert_eq!(
+            find_entry_point(&dir.join("tests")).unwrap(),
+            dir.join("src/main.sag")
+        );
+        assert!(new_project(&dir, "hello").is_err());
+        assert!(new_project(&root.join("bad"), "my-app").is_err());
+
+        // 既にある main.sag は残す
+        let existing = root.join("existing");
+        fs::create_dir_all(existing.join("src")).unwrap();
+        fs::write(existing.join("src/main.sag"), "print(1)").unwrap();
+        assert_eq!(init_project(&existing).unwrap(), "existing");
+        assert_eq!(
+            fs::read_to_string(existing.join("src/main.sag")).unwrap(),
+            "print(1)"
+        );
+        assert!(init_project(&existing).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
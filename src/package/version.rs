@@ -0,0 +1,185 @@
+use std::fmt;
+
+/// `1.2.3` 形式のパッケージのバージョン
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    pub fn parse(input: &str) -> Result<Version, String> {
+        let (version, given) = parse_partial(input)?;
+        if given != 3 {
+            return Err(format!(
+                "invalid version '{}': expected major.minor.patch",
+                input
+            ));
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+// `1.2` のように省略されたバージョンを読む。戻り値は (0 で埋めたバージョン, 書かれていた桁数)
+fn parse_partial(input: &str) -> Result<(Version, usize), String> {
+    let parts = input.trim().split('.').collect::<Vec<_>>();
+    if parts.is_empty() || parts.len() > 3 {
+        return Err(format!("invalid version '{}'", input));
+    }
+    let mut numbers = [0; 3];
+    for (i, part) in parts.iter().enumerate() {
+        numbers[i] = part
+            .parse::<u64>()
+            .map_err(|_| format!("invalid version '{}'", input))?;
+    }
+    Ok((
+        Version::new(numbers[0], numbers[1], numbers[2]),
+        parts.len(),
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+}
+
+/// `^1.2`, `~1.2.3`, `>=1.0, <2.0`, `*` のようなバージョンの制約。
+/// 演算子のない `1.2.3` は `^1.2.3` と同じ
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionReq {
+    source: String,
+    comparators: Vec<(Op, Version)>,
+}
+
+impl VersionReq {
+    pub fn any() -> Self {
+        VersionReq {
+            source: "*".to_string(),
+            comparators: vec![],
+        }
+    }
+
+    pub fn parse(input: &str) -> Result<VersionReq, String> {
+        let mut comparators = vec![];
+        for part in input.split(',') {
+            let part = part.trim();
+            if part == "*" {
+                continue;
+            }
+            let (op, rest) = [">=", "<=", ">", "<", "=", "^", "~"]
+                .iter()
+                .find_map(|op| part.strip_prefix(op).map(|rest| (*op, rest)))
+                .unwrap_or(("^", part));
+            let (version, given) =
+                parse_partial(rest).map_err(|e| format!("invalid version requirement: {}", e))?;
+            match op {
+                ">=" => comparators.push((Op::GreaterEq, version)),
+                "<=" => comparators.push((Op::LessEq, version)),
+                ">" => comparators.push((Op::Greater, version)),
+                "<" => comparators.push((Op::Less, version)),
+                "=" if given == 3 => comparators.push((Op::Exact, version)),
+                "=" => {
+                    // =1.2 は 1.2.x のすべて
+                    let upper = bump(&version, given - 1);
+                    comparators.push((Op::GreaterEq, version));
+                    comparators.push((Op::Less, upper));
+                }
+                "~" => {
+                    let upper = bump(&version, if given == 1 { 0 } else { 1 });
+                    comparators.push((Op::GreaterEq, version));
+                    comparators.push((Op::Less, upper));
+                }
+                _ => {
+                    // 0 でない最初の桁までを固定する
+                    let fixed = if version.major > 0 || given == 1 {
+                        0
+                    } else if version.minor > 0 || given == 2 {
+                        1
+                    } else {
+                        2
+                    };
+                    let upper = bump(&version, fixed);
+                    comparators.push((Op::GreaterEq, version));
+                    comparators.push((Op::Less, upper));
+                }
+            }
+        }
+        Ok(VersionReq {
+            source: input.trim().to_string(),
+            comparators,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn matches(&self, version: &Version) -> bool {
+        self.comparators.iter().all(|(op, bound)| match op {
+            Op::Exact => version == bound,
+            Op::Greater => version > bound,
+            Op::GreaterEq => version >= bound,
+            Op::Less => version < bound,
+            Op::LessEq => version <= bound,
+        })
+    }
+}
+
+// index 桁目を 1 増やし、それより下の桁を 0 にする
+fn bump(version: &Version, index: usize) -> Version {
+    match index {
+        0 => Version::new(version.major + 1, 0, 0),
+        1 => Version::new(version.major, version.minor + 1, 0),
+        _ => Version::new(version.major, version.minor, version.patch + 1),
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(req: &str, version: &str) -> bool {
+        VersionReq::parse(req)
+            .unwrap()
+            .matches(&Version::parse(version).unwrap())
+    }
+
+    #[test]
+    fn test_version_requirements() {
+        assert!(matches("1.2.3", "1.9.0"));
+        assert!(!matches("1.2.3", "2.0.0"));
+        assert!(!matches("^0.2.3", "0.3.0"));
+        assert!(matches("^0.2", "0.2.9"));
+        assert!(matches("~1.2.3", "1.2.9"));
+        assert!(!matches("~1.2.3", "1.3.0"));
+        assert!(matches(">=1.0, <2.0", "1.5.0"));
+        assert!(!matches(">=1.0, <2.0", "2.0.0"));
+        assert!(matches("=1.2", "1.2.7"));
+        assert!(!matches("=1.2.3", "1.2.4"));
+        assert!(matches("*", "0.0.1"));
+        assert!(VersionReq::parse("^x.1").is_err());
+        assert!(Version::parse("1.2").is_err());
+    }
+}
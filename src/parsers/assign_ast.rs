@@ -1,68 +1,70 @@
 use crate::ast::ASTNode;
 use crate::environment::{EnvVariableType, ValueType};
-use crate::parsers::parse_error::ParseError;
+use crate::parsers::parse_error::{ParseError, ParseErrorKind};
+use crate::parsers::symbol::SymbolKind;
 use crate::parsers::Parser;
 use crate::token::{Token, TokenKind};
 
 impl Parser {
-    fn get_result_value_type(&mut self) -> Result<ValueType, ParseError> {
+    pub fn get_result_value_type(&mut self) -> Result<ValueType, ParseError> {
         match self.consume_token() {
             Some(token) => match token.kind {
-                TokenKind::Identifier(value_type) => Ok(self.string_to_value_type(value_type)),
+                TokenKind::Identifier(value_type) => self.string_to_value_type(value_type),
                 TokenKind::Option => {
-                    self.extract_token(TokenKind::Lt);
+                    self.extract_token(TokenKind::Lt)?;
                     let value_type = match self.consume_token() {
                         Some(token) => match token.kind {
                             TokenKind::Identifier(value_type) => {
-                                self.string_to_value_type(value_type)
+                                self.string_to_value_type(value_type)?
                             }
                             TokenKind::Option => {
-                                self.extract_token(TokenKind::Lt);
+                                self.extract_token(TokenKind::Lt)?;
                                 let result = self.get_result_value_type()?;
-                                self.extract_token(TokenKind::Gt);
+                                self.extract_token(TokenKind::Gt)?;
                                 ValueType::OptionType(Box::new(result))
                             }
                             TokenKind::Result => {
-                                self.extract_token(TokenKind::Lt);
+                                self.extract_token(TokenKind::Lt)?;
                                 let success_value_type = self.get_result_value_type()?;
-                                self.extract_token(TokenKind::Comma);
+                                self.extract_token(TokenKind::Comma)?;
                                 let failure_value_type = self.get_result_value_type()?;
-                                self.extract_token(TokenKind::Gt);
+                                self.extract_token(TokenKind::Gt)?;
                                 ValueType::ResultType {
                                     success: Box::new(success_value_type),
                                     failure: Box::new(failure_value_type),
                                 }
                             }
                             TokenKind::List => {
-                                self.extract_token(TokenKind::Lt);
+                                self.extract_token(TokenKind::Lt)?;
                                 let element_type = self.get_result_value_type()?;
-                                self.extract_token(TokenKind::Gt);
+                                self.extract_token(TokenKind::Gt)?;
                                 ValueType::List(Box::new(element_type))
                             }
                             _ => return Err(ParseError::new("unexpected token", &token)),
                         },
                         _ => return Err(ParseError::new("unexpected token", &token)),
                     };
-                    self.extract_token(TokenKind::Gt);
+                    self.extract_token(TokenKind::Gt)?;
                     Ok(ValueType::OptionType(Box::new(value_type)))
                 }
                 TokenKind::Result => {
-                    self.extract_token(TokenKind::Lt);
+                    self.extract_token(TokenKind::Lt)?;
                     let success_value_type = self.get_result_value_type()?;
-                    self.extract_token(TokenKind::Comma);
+                    self.extract_token(TokenKind::Comma)?;
                     let failure_value_type = self.get_result_value_type()?;
-                    self.extract_token(TokenKind::Gt);
+                    self.extract_token(TokenKind::Gt)?;
                     Ok(ValueType::ResultType {
                         success: Box::new(success_value_type),
                         failure: Box::new(failure_value_type),
                     })
                 }
                 TokenKind::List => {
-                    self.extract_token(TokenKind::Lt);
+                    self.extract_token(TokenKind::Lt)?;
                     let element_type = self.get_result_value_type()?;
-                    self.extract_token(TokenKind::Gt);
+                    self.extract_token(TokenKind::Gt)?;
                     Ok(ValueType::List(Box::new(element_type)))
                 }
+                TokenKind::LParen => self.parse_tuple_type(),
                 _ => Err(ParseError::new("unexpected token", &token)),
             },
             _ => Err(ParseError::new(
@@ -78,19 +80,18 @@ impl Parser {
 
     pub fn parse_assign(&mut self) -> Result<ASTNode, ParseError> {
         let scope = self.get_current_scope();
-        let mutable_or_immutable = self.consume_token().unwrap();
-        let name = match self.consume_token() {
+        let mutable_or_immutable = self.current_token()?;
+        self.consume_token();
+        if self.is_pattern_start() {
+            return self.parse_destructure(mutable_or_immutable);
+        }
+        let (name, name_position) = match self.consume_token() {
             Some(Token {
                 kind: TokenKind::Identifier(name),
-                ..
-            }) => name,
-            _ => {
-                let current_token = self.get_current_token().unwrap();
-                return Err(ParseError::new(
-                    "unexpected token missing variable name",
-                    &current_token,
-                ));
-            }
+                line,
+                column,
+            }) => (name, (line, column)),
+            _ => return Err(self.error_here("unexpected token missing variable name")),
         };
         match self.consume_token() {
             Some(Token {
@@ -100,7 +101,15 @@ impl Parser {
                 let value = self.parse_expression(0)?;
                 let value_type = match self.infer_type(&value) {
                     Ok(value_type) => value_type,
-                    Err(e) => panic!("{}", e),
+                    Err(e) => {
+                        let (line, column) = self.get_line_column();
+                        return Err(ParseError {
+                            message: e,
+                            line,
+                            column,
+                            kind: ParseErrorKind::Type,
+                        });
+                    }
                 };
                 let variable_type = if mutable_or_immutable.kind == TokenKind::Mutable {
                     EnvVariableType::Mutable
@@ -109,6 +118,13 @@ impl Parser {
                 };
 
                 self.register_variables(scope.clone(), &name, &value_type, &variable_type);
+                self.record_symbol(
+                    &scope,
+                    &name,
+                    SymbolKind::Variable,
+                    Some(value_type.clone()),
+                    name_position,
+                );
                 Ok(ASTNode::Assign {
                     name,
                     value: Box::new(value),
@@ -130,36 +146,37 @@ impl Parser {
                             "str" => ValueType::String,
                             "bool" => ValueType::Bool,
                             "void" => ValueType::Void,
-                            _ => self.string_to_value_type(value_type),
+                            _ => self.string_to_value_type(value_type)?,
                         },
                         TokenKind::Option => {
-                            self.extract_token(TokenKind::Lt);
+                            self.extract_token(TokenKind::Lt)?;
                             let value_type = match self.consume_token() {
                                 Some(token) => match token.kind {
                                     TokenKind::Identifier(value_type) => {
-                                        self.string_to_value_type(value_type)
+                                        self.string_to_value_type(value_type)?
                                     }
                                     _ => return Err(ParseError::new("unexpected token", &token)),
                                 },
                                 _ => return Err(ParseError::new("unexpected token", &token)),
                             };
-                            self.extract_token(TokenKind::Gt);
+                            self.extract_token(TokenKind::Gt)?;
                             ValueType::OptionType(Box::new(value_type))
                         }
                         TokenKind::Result => {
-                            self.extract_token(TokenKind::Lt);
+                            self.extract_token(TokenKind::Lt)?;
                             let success_value_type = self.get_result_value_type()?;
-                            self.extract_token(TokenKind::Comma);
+                            self.extract_token(TokenKind::Comma)?;
                             let failure_value_type = self.get_result_value_type()?;
-                            self.extract_token(TokenKind::Gt);
-                            ValueType::ResultType {
+                            self.extract_token(TokenKind::Gt)?;
+                                ValueType::ResultType {
                                 success: Box::new(success_value_type),
                                 failure: Box::new(failure_value_type),
                             }
                         }
+                        TokenKind::LParen => self.parse_tuple_type()?,
                         _ => return Err(ParseError::new("unexpected token", &token)),
                     },
-                    _ => panic!("missing token"),
+                    _ => return Err(self.error_here("expected type name")),
                 };
                 let token = self.consume_token();
                 match token {
@@ -176,42 +193,49 @@ impl Parser {
                         match value_type.clone() {
                             ValueType::ResultType { success, failure } => match value {
                                 ASTNode::ResultSuccess { ref value, .. } => {
-                                    if *success.as_ref() != self.infer_type(&value).unwrap() {
-                                        return Err(ParseError::new(
+                                    if self.infer_type(value).ok().as_ref() != Some(success.as_ref()) {
+                                        return Err(ParseError::type_error(
                                             "type mismatch",
                                             &token.unwrap(),
                                         ));
                                     }
                                 }
                                 ASTNode::ResultFailure { ref value, .. } => {
-                                    if *failure.as_ref() != self.infer_type(&value).unwrap() {
-                                        return Err(ParseError::new(
+                                    if self.infer_type(value).ok().as_ref() != Some(failure.as_ref()) {
+                                        return Err(ParseError::type_error(
                                             "type mismatch",
                                             &token.unwrap(),
                                         ));
                                     }
                                 }
-                                _ => return Err(ParseError::new("type mismatch", &token.unwrap())),
+                                _ => return Err(ParseError::type_error("type mismatch", &token.unwrap())),
                             },
                             ValueType::OptionType(ref value_type) => match value {
                                 ASTNode::OptionSome { ref value, .. } => {
-                                    if *value_type.as_ref() != self.infer_type(&value).unwrap() {
-                                        return Err(ParseError::new(
+                                    if self.infer_type(value).ok().as_ref() != Some(value_type.as_ref()) {
+                                        return Err(ParseError::type_error(
                                             "type mismatch",
                                             &token.unwrap(),
                                         ));
                                     }
                                 }
                                 ASTNode::OptionNone { .. } => {}
-                                _ => return Err(ParseError::new("type mismatch", &token.unwrap())),
+                                _ => return Err(ParseError::type_error("type mismatch", &token.unwrap())),
                             },
                             _ => {
-                                if value_type != self.infer_type(&value).unwrap() {
-                                    return Err(ParseError::new("type mismatch", &token.unwrap()));
+                                if self.infer_type(&value).ok() != Some(value_type.clone()) {
+                                    return Err(ParseError::type_error("type mismatch", &token.unwrap()));
                                 }
                             }
                         }
-                        self.register_variables(scope, &name, &value_type, &variable_type);
+                        self.register_variables(scope.clone(), &name, &value_type, &variable_type);
+                        self.record_symbol(
+                            &scope,
+                            &name,
+                            SymbolKind::Variable,
+                            Some(value_type.clone()),
+                            name_position,
+                        );
                         Ok(ASTNode::Assign {
                             name,
                             value: Box::new(value),
@@ -222,10 +246,10 @@ impl Parser {
                             column: mutable_or_immutable.column,
                         })
                     }
-                    _ => panic!("No valid statement found on the right-hand side"),
+                    _ => Err(self.error_here("expected '=' after the type")),
                 }
             }
-            _ => panic!("unexpected token"),
+            _ => Err(self.error_here("expected '=' or ':' after the variable name")),
         }
     }
 }
@@ -1,5 +1,5 @@
 use crate::ast::ASTNode;
-use crate::parsers::parse_error::ParseError;
+use crate::parsers::parse_error::{ParseError, ParseErrorKind};
 use crate::parsers::Parser;
 use crate::token::{Token, TokenKind};
 
@@ -11,6 +11,7 @@ impl Parser {
                 kind: TokenKind::LBrace,
                 ..
             }) => {
+                self.record_block_start();
                 self.consume_token();
             }
             _ => {
@@ -19,6 +20,7 @@ impl Parser {
                     message: "Expected '{' at the start of a block".to_string(),
                     line,
                     column,
+                    kind: ParseErrorKind::Syntax,
                 });
             }
         }
@@ -48,6 +50,7 @@ impl Parser {
                             message: "Unexpected end of file, expected '}'".to_string(),
                             line,
                             column,
+                            kind: ParseErrorKind::Syntax,
                         });
                     }
                     self.pos = 0;
@@ -55,6 +58,7 @@ impl Parser {
                     continue;
                 }
                 _ => {
+                    self.record_item_start();
                     let statement = self.parse_expression(0)?;
                     statements.push(statement);
                 }
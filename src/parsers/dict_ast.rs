@@ -13,8 +13,8 @@ impl Parser {
         while let Some(token) = self.get_current_token() {
             if token.kind == TokenKind::Colon {
                 self.pos += 1;
-                let next_token = self.get_current_token();
-                if next_token.unwrap().kind == TokenKind::RBrace {
+                let next_token = self.current_token()?;
+                if next_token.kind == TokenKind::RBrace {
                     self.consume_token();
                     break;
                 }
@@ -22,21 +22,16 @@ impl Parser {
             }
             if token.kind == TokenKind::RRocket {
                 self.consume_token();
-                if key.is_none() {
-                    panic!("Expected key-value pair in dictionary");
-                }
-                let value = self.get_current_token().map(|t| match t.kind {
+                let value = match self.current_token()?.kind {
                     TokenKind::Number(value) => Value::Number(value),
                     TokenKind::String(value) => Value::String(value),
-                    _ => panic!("unexpected token: {:?}", t),
-                });
+                    _ => return Err(self.error_here("dictionary values must be numbers or strings")),
+                };
                 self.consume_token();
-                if let Some(Value::String(k)) = key {
-                    dict.insert(k, value.unwrap());
-                } else {
-                    panic!("Expected string key in dictionary");
-                }
-                key = None;
+                match key.take() {
+                    Some(Value::String(k)) => dict.insert(k, value),
+                    _ => return Err(ParseError::new("Expected key-value pair in dictionary", &token)),
+                };
                 continue;
             }
             if token.kind == TokenKind::Comma {
@@ -45,7 +40,7 @@ impl Parser {
             }
             key = match token.kind {
                 TokenKind::String(value) => Some(Value::String(value)),
-                _ => panic!("unexpected token: {:?}", token),
+                _ => return Err(ParseError::new("Expected string key in dictionary", &token)),
             };
             self.consume_token();
         }
@@ -1,6 +1,7 @@
 use crate::ast::ASTNode;
 use crate::environment::{EnvVariableType, ValueType};
 use crate::parsers::parse_error::ParseError;
+use crate::parsers::symbol::SymbolKind;
 use crate::parsers::Parser;
 use crate::token::{Token, TokenKind};
 
@@ -16,28 +17,24 @@ impl Parser {
                 ..
             }) => self.consume_token(),
             _ => {
-                let current_token = self.get_current_token().unwrap();
-                return Err(ParseError::new(
-                    "unexpected token missing for",
-                    &current_token,
-                ));
+                return Err(self.error_here("unexpected token missing for"));
             }
         };
-        let variable = match self.get_current_token() {
+        if self.is_pattern_start() {
+            return self.parse_for_pattern(line, column);
+        }
+        let (variable, variable_position) = match self.get_current_token() {
             Some(Token {
                 kind: TokenKind::Identifier(name),
-                ..
-            }) => name,
+                line,
+                column,
+            }) => (name, (line, column)),
             _ => {
-                let current_token = self.get_current_token().unwrap();
-                return Err(ParseError::new(
-                    "unexpected token missing variable name",
-                    &current_token,
-                ));
+                return Err(self.error_here("unexpected token missing variable name"));
             }
         };
         self.consume_token();
-        self.extract_token(TokenKind::In);
+        self.extract_token(TokenKind::In)?;
         let iterable = self.parse_expression(0)?;
         let variable_value_type = self.infer_type(&iterable).unwrap_or(ValueType::Any);
         let variable_value_type = match variable_value_type {
@@ -50,6 +47,13 @@ impl Parser {
             &variable_value_type,
             &EnvVariableType::Mutable,
         );
+        self.record_symbol(
+            &self.get_current_scope(),
+            &variable,
+            SymbolKind::Variable,
+            Some(variable_value_type.clone()),
+            variable_position,
+        );
         let body = self.parse_expression(0)?;
         Ok(ASTNode::For {
             variable,
@@ -59,6 +63,57 @@ impl Parser {
             column,
         })
     }
+
+    // `for (k, v) in pairs { ... }` はループ変数を分割代入するブロックに変換する
+    fn parse_for_pattern(&mut self, line: usize, column: usize) -> Result<ASTNode, ParseError> {
+        let pattern = self.parse_pattern()?;
+        self.extract_token(TokenKind::In)?;
+        let iterable = self.parse_expression(0)?;
+        let element_type = match self.infer_type(&iterable).unwrap_or(ValueType::Any) {
+            ValueType::List(value_type) => *value_type,
+            value_type => value_type,
+        };
+        self.register_pattern_variables(&pattern, &element_type, &EnvVariableType::Immutable);
+        let variable = "<pattern>".to_string();
+        let destructure = ASTNode::Destructure {
+            pattern: Box::new(pattern),
+            value: Box::new(ASTNode::Variable {
+                name: variable.clone(),
+                value_type: None,
+                line,
+                column,
+            }),
+            variable_type: EnvVariableType::Immutable,
+            line,
+            column,
+        };
+        let body = match self.parse_expression(0)? {
+            ASTNode::Block {
+                mut nodes,
+                line,
+                column,
+            } => {
+                nodes.insert(0, destructure);
+                ASTNode::Block {
+                    nodes,
+                    line,
+                    column,
+                }
+            }
+            body => ASTNode::Block {
+                nodes: vec![destructure, body],
+                line,
+                column,
+            },
+        };
+        Ok(ASTNode::For {
+            variable,
+            iterable: Box::new(iterable),
+            body: Box::new(body),
+            line,
+            column,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -1,6 +1,7 @@
 use crate::ast::ASTNode;
 use crate::environment::{EnvVariableType, ValueType};
 use crate::parsers::parse_error::ParseError;
+use crate::parsers::symbol::SymbolKind;
 use crate::parsers::Parser;
 use crate::token::{Token, TokenKind};
 
@@ -16,20 +17,27 @@ impl Parser {
                 kind: TokenKind::Identifier(name),
                 ..
             }) => name,
-            _ => Err(ParseError::new(
-                "Expected function name",
-                &self.get_current_token().unwrap(),
-            ))?,
+            _ => Err(self.error_here("Expected function name"))?,
         };
         let function_scope = self.get_current_scope();
         self.enter_scope(name.to_string());
         self.pos += 1;
-        self.extract_token(TokenKind::LParen);
+        self.extract_token(TokenKind::LParen)?;
 
         let arguments = self.parse_function_arguments()?;
-        let return_type = self.parse_return_type();
-        self.register_functions(function_scope, &name, &arguments, &return_type);
-        let body = self.parse_block()?;
+        let return_type = self.parse_return_type()?;
+        self.register_functions(function_scope.clone(), &name, &arguments, &return_type);
+        self.record_symbol(
+            &function_scope,
+            &name,
+            SymbolKind::Function,
+            Some(return_type.clone()),
+            (line, column),
+        );
+        self.return_types.push(return_type.clone());
+        let body = self.parse_block();
+        self.return_types.pop();
+        let body = body?;
 
         self.leave_scope();
 
@@ -43,11 +51,10 @@ impl Parser {
                         include_return = true;
                         if let Ok(return_value_type) = self.infer_type(&value.clone()) {
                             if return_value_type != return_type {
-                                return Err(ParseError::new(
-                                    format!("Return type mismatch Expected type: {:?}, Actual type: {:?}", return_type, return_value_type).as_str(),
-                                    &self.get_current_token().unwrap()
-                                    )
-                                );
+                                return Err(self.type_error_here(&format!(
+                                    "Return type mismatch Expected type: {:?}, Actual type: {:?}",
+                                    return_type, return_value_type
+                                )));
                             }
                         }
                     }
@@ -57,8 +64,7 @@ impl Parser {
         };
 
         if !include_return && return_type != ValueType::Void {
-            let token = self.get_current_token().unwrap();
-            Err(ParseError::new("Expected return statement", &token))?;
+            Err(self.error_here("Expected return statement"))?;
         }
 
         Ok(ASTNode::Function {
@@ -115,20 +121,24 @@ impl Parser {
             if token.kind == TokenKind::RParen {
                 break;
             }
-            if let TokenKind::Identifier(name) = self.consume_token().unwrap().kind {
+            if self.is_pattern_start() {
+                arguments.push(self.parse_pattern_argument()?);
+            } else if let Token {
+                kind: TokenKind::Identifier(name),
+                line: name_line,
+                column: name_column,
+            } = self.consume_token().unwrap()
+            {
                 let mut variable_name = name.clone();
                 let current_token = self.get_current_token();
                 let arg_type = if current_token.is_none() {
-                    self.extract_token(TokenKind::Colon);
+                    self.extract_token(TokenKind::Colon)?;
                     match self.consume_token() {
                         Some(Token {
                             kind: TokenKind::Identifier(type_name),
                             ..
-                        }) => self.string_to_value_type(type_name),
-                        _ => Err(ParseError::new(
-                            "Expected type for argument",
-                            &self.get_current_token().unwrap(),
-                        ))?,
+                        }) => self.string_to_value_type(type_name)?,
+                        _ => Err(self.error_here("Expected type for argument"))?,
                     }
                 } else {
                     let current_token_kind = current_token.unwrap().kind.clone();
@@ -141,29 +151,27 @@ impl Parser {
                         && current_token_kind == TokenKind::Identifier("self".to_string())
                     {
                         self.consume_token();
-                        let current_token_kind = self.get_current_token().unwrap().kind.clone();
+                        let current_token_kind = self.current_token()?.kind;
                         if current_token_kind == TokenKind::Comma
                             || current_token_kind == TokenKind::RParen
                         {
                             variable_name = "self".to_string();
                             ValueType::MutSelfType
                         } else {
-                            Err(ParseError::new(
-                                "Expected type for argument",
-                                &self.get_current_token().unwrap(),
-                            ))?
+                            Err(self.error_here("Expected type for argument"))?
                         }
                     } else {
-                        self.extract_token(TokenKind::Colon);
+                        self.extract_token(TokenKind::Colon)?;
                         match self.consume_token() {
                             Some(Token {
                                 kind: TokenKind::Identifier(type_name),
                                 ..
-                            }) => self.string_to_value_type(type_name),
-                            _ => Err(ParseError::new(
-                                "Expected type for argument",
-                                &self.get_current_token().unwrap(),
-                            ))?,
+                            }) => self.string_to_value_type(type_name)?,
+                            Some(Token {
+                                kind: TokenKind::LParen,
+                                ..
+                            }) => self.parse_tuple_type()?,
+                            _ => Err(self.error_here("Expected type for argument"))?,
                         }
                     }
                 };
@@ -173,6 +181,13 @@ impl Parser {
                     &arg_type,
                     &EnvVariableType::Immutable,
                 );
+                self.record_symbol(
+                    &scope,
+                    &variable_name,
+                    SymbolKind::Variable,
+                    Some(arg_type.clone()),
+                    (name_line, name_column),
+                );
                 let (line, column) = match self.get_current_token() {
                     Some(token) => (token.line, token.column),
                     None => (self.line, self.pos),
@@ -194,7 +209,7 @@ impl Parser {
                 _ => {}
             };
         }
-        self.extract_token(TokenKind::RParen);
+        self.extract_token(TokenKind::RParen)?;
         Ok(arguments)
     }
 
@@ -222,10 +237,7 @@ impl Parser {
                 kind: TokenKind::Identifier(name),
                 ..
             }) => name,
-            _ => Err(ParseError::new(
-                "Expected function name",
-                &self.get_current_token().unwrap(),
-            ))?,
+            _ => Err(self.error_here("Expected function name"))?,
         };
 
         let (line, column) = match self.get_current_token() {
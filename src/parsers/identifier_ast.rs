@@ -1,6 +1,7 @@
 use crate::ast::ASTNode;
 use crate::environment::{EnvVariableType, ValueType};
 use crate::parsers::parse_error::ParseError;
+use crate::parsers::symbol::SymbolKind;
 use crate::parsers::Parser;
 use crate::token::{Token, TokenKind};
 use std::collections::HashMap;
@@ -27,9 +28,34 @@ impl Parser {
         }
     }
     pub fn parse_identifier(&mut self, name: String) -> Result<ASTNode, ParseError> {
+        let (identifier_line, identifier_column) = self.get_line_column();
         self.pos += 1;
         let scope = self.get_current_scope().to_string();
         let variable_info = self.find_variables(scope.clone(), name.clone());
+        let reference_type = match &variable_info {
+            Some((value_type, _)) => Some(value_type.clone()),
+            None => self
+                .get_function(scope.clone(), name.clone())
+                .or_else(|| self.get_struct(scope.clone(), name.clone())),
+        };
+        self.record_symbol(
+            &scope,
+            &name,
+            SymbolKind::Reference,
+            reference_type,
+            (identifier_line, identifier_column),
+        );
+        if self.namespaces.contains(&name)
+            && matches!(
+                self.get_current_token(),
+                Some(Token {
+                    kind: TokenKind::Dot,
+                    ..
+                })
+            )
+        {
+            return self.parse_module_access(name);
+        }
 
         //let struct_exists = self.get_struct(scope.clone(), name.clone()).is_some();
         //if name == "self" && self.is_in_method_scope() {
@@ -50,7 +76,7 @@ impl Parser {
                 // リストかdictのインデックスアクセス
                 self.consume_token();
                 let index = self.parse_expression(0)?;
-                self.extract_token(TokenKind::RBrancket);
+                self.extract_token(TokenKind::RBrancket)?;
                 let (line, column) = self.get_line_column();
                 let value_type = self.resolve_variable_type(&scope, &name, variable_info.clone());
 
@@ -89,13 +115,10 @@ impl Parser {
                             line,
                             column,
                         }),
-                        _ => {
-                            let current_token = self.get_current_token().unwrap();
-                            Err(ParseError::new(
-                                format!("type mismatch: expected List or Dict for assignment, found {:?}", value_type).as_str(),
-                                &current_token,
-                            ))
-                        }
+                        _ => Err(self.type_error_here(&format!(
+                            "type mismatch: expected List or Dict for assignment, found {:?}",
+                            value_type
+                        ))),
                     }
                 } else {
                     // 通常のアクセス
@@ -122,17 +145,10 @@ impl Parser {
                             line,
                             column,
                         }),
-                        _ => {
-                            let current_token = self.get_current_token().unwrap();
-                            Err(ParseError::new(
-                                format!(
-                                    "type mismatch: expected List or Dict, found {:?}",
-                                    value_type
-                                )
-                                .as_str(),
-                                &current_token,
-                            ))
-                        }
+                        _ => Err(self.type_error_here(&format!(
+                            "type mismatch: expected List or Dict, found {:?}",
+                            value_type
+                        ))),
                     }
                 }
             }
@@ -150,8 +166,8 @@ impl Parser {
                     Ok(ASTNode::Variable {
                         name,
                         value_type,
-                        line: self.line,
-                        column: self.pos,
+                        line: identifier_line,
+                        column: identifier_column,
                     })
                 }
             }
@@ -171,19 +187,23 @@ impl Parser {
                 kind: TokenKind::Dot,
                 ..
             }) => self.create_struct_field_access(name.clone()),
+            Some(Token {
+                kind: TokenKind::DoubleColon,
+                ..
+            }) => self.parse_static_access(name.clone()),
             _ => {
                 // 代入
                 let value_type = self.resolve_variable_type(&scope, &name, variable_info.clone());
                 Ok(ASTNode::Variable {
                     name,
                     value_type,
-                    line: self.line,
-                    column: self.pos,
+                    line: identifier_line,
+                    column: identifier_column,
                 })
             }
         }
     }
-    fn create_struct_instance(&mut self, name: String) -> Result<ASTNode, ParseError> {
+    pub fn create_struct_instance(&mut self, name: String) -> Result<ASTNode, ParseError> {
         // 構造体のインスタンス化
         self.consume_token();
         let mut fields = HashMap::new();
@@ -198,12 +218,17 @@ impl Parser {
             }
             if let TokenKind::Identifier(field_name) = token.kind {
                 self.consume_token();
-                self.extract_token(TokenKind::Colon);
+                self.extract_token(TokenKind::Colon)?;
                 let value = self.parse_expression(0)?;
                 fields.insert(field_name, value);
                 continue;
             }
         }
+        // 省略されたフィールドはデフォルト値で補う
+        let scope = self.get_current_scope();
+        for (field_name, default_value) in self.get_struct_default_values(scope, name.clone()) {
+            fields.entry(field_name).or_insert(default_value);
+        }
         let (line, column) = self.get_line_column();
         Ok(ASTNode::StructInstance {
             name,
@@ -213,9 +238,8 @@ impl Parser {
         })
     }
 
-    fn create_function_call(&mut self, name: String) -> Result<ASTNode, ParseError> {
-        // 関数呼び出し
-        self.consume_token();
+    pub fn create_function_call(&mut self, name: String) -> Result<ASTNode, ParseError> {
+        // 関数呼び出し。`(` は parse_function_call_arguments_paren で消費する
         let arguments = self.parse_function_call_arguments_paren()?;
         let function_call = self.parse_function_call_front(name, arguments)?;
         Ok(function_call)
@@ -237,24 +261,15 @@ impl Parser {
         }
         let (value_type, variable_type) = variable_info.clone().unwrap();
         if variable_type == EnvVariableType::Immutable {
-            let current_token = self.get_current_token().unwrap();
-            return Err(ParseError::new(
-                format!(
-                    "It is an immutable variable and cannot be reassigned: {:?}",
-                    name
-                )
-                .as_str(),
-                &current_token,
-            ));
+            return Err(self.error_here(&format!(
+                "It is an immutable variable and cannot be reassigned: {:?}",
+                name
+            )));
         }
         let value = self.parse_expression(0)?;
         let infer_type = self.infer_type(&value);
         if infer_type.is_err() {
-            let current_token = self.get_current_token().unwrap();
-            return Err(ParseError::new(
-                format!("undefined type").as_str(),
-                &current_token,
-            ));
+            return Err(self.type_error_here(format!("undefined type").as_str()));
         }
         match value_type {
             ValueType::Any => {}
@@ -262,19 +277,11 @@ impl Parser {
                 ASTNode::OptionNone { .. } => {}
                 ASTNode::OptionSome { value: _, .. } => {
                     if value_type != infer_type.unwrap() {
-                        let current_token = self.get_current_token().unwrap();
-                        return Err(ParseError::new(
-                            format!("type mismatch").as_str(),
-                            &current_token,
-                        ));
+                        return Err(self.type_error_here(format!("type mismatch").as_str()));
                     }
                 }
                 _ => {
-                    let current_token = self.get_current_token().unwrap();
-                    return Err(ParseError::new(
-                        format!("type mismatch").as_str(),
-                        &current_token,
-                    ));
+                    return Err(self.type_error_here(format!("type mismatch").as_str()));
                 }
             },
             ValueType::ResultType {
@@ -287,19 +294,11 @@ impl Parser {
                         failure: _,
                     }) => {
                         if success != value_type {
-                            let current_token = self.get_current_token().unwrap();
-                            return Err(ParseError::new(
-                                format!("type mismatch").as_str(),
-                                &current_token,
-                            ));
+                            return Err(self.type_error_here(format!("type mismatch").as_str()));
                         }
                     }
                     _ => {
-                        let current_token = self.get_current_token().unwrap();
-                        return Err(ParseError::new(
-                            format!("undefined type").as_str(),
-                            &current_token,
-                        ));
+                        return Err(self.error_here(format!("undefined type").as_str()));
                     }
                 },
                 ASTNode::ResultFailure { value: _, .. } => match infer_type {
@@ -308,36 +307,20 @@ impl Parser {
                         failure: ref value_type,
                     }) => {
                         if failure != value_type {
-                            let current_token = self.get_current_token().unwrap();
-                            return Err(ParseError::new(
-                                format!("type mismatch").as_str(),
-                                &current_token,
-                            ));
+                            return Err(self.type_error_here(format!("type mismatch").as_str()));
                         }
                     }
                     _ => {
-                        let current_token = self.get_current_token().unwrap();
-                        return Err(ParseError::new(
-                            format!("undefined type").as_str(),
-                            &current_token,
-                        ));
+                        return Err(self.error_here(format!("undefined type").as_str()));
                     }
                 },
                 _ => {
-                    let current_token = self.get_current_token().unwrap();
-                    return Err(ParseError::new(
-                        format!("type mismatch").as_str(),
-                        &current_token,
-                    ));
+                    return Err(self.type_error_here(format!("type mismatch").as_str()));
                 }
             },
             _ => {
                 if value_type != infer_type.unwrap() {
-                    let current_token = self.get_current_token().unwrap();
-                    return Err(ParseError::new(
-                        format!("type mismatch").as_str(),
-                        &current_token,
-                    ));
+                    return Err(self.type_error_here(format!("type mismatch").as_str()));
                 }
             }
         };
@@ -359,13 +342,9 @@ impl Parser {
             ..
         }) = self.get_current_token()
         {
-            Some(self.string_to_value_type(type_name))
+            Some(self.string_to_value_type(type_name)?)
         } else {
-            let current_token = self.get_current_token().unwrap();
-            return Err(ParseError::new(
-                format!("undefined type").as_str(),
-                &current_token,
-            ));
+            return Err(self.type_error_here(format!("undefined type").as_str()));
         };
         let (line, column) = self.get_line_column();
         Ok(ASTNode::Variable {
@@ -376,7 +355,7 @@ impl Parser {
         })
     }
 
-    fn create_struct_field_access(&mut self, name: String) -> Result<ASTNode, ParseError> {
+    pub fn create_struct_field_access(&mut self, name: String) -> Result<ASTNode, ParseError> {
         self.pos += 2;
         match self.get_current_token() {
             Some(Token {
@@ -389,7 +368,7 @@ impl Parser {
                         kind: TokenKind::Identifier(method_name),
                         ..
                     }) => method_name,
-                    _ => panic!("missing method name: {:?}", self.get_current_token()),
+                    _ => return Err(self.error_here("missing method name")),
                 };
                 self.pos += 1;
                 let arguments = self.parse_function_call_arguments_paren()?;
@@ -425,7 +404,7 @@ impl Parser {
             let value = self.parse_expression(0)?;
             let field_name = match struct_instance_access.clone() {
                 ASTNode::StructFieldAccess { field_name, .. } => field_name,
-                _ => panic!("unexpected token"),
+                _ => return Err(self.error_here("only struct fields can be assigned")),
             };
             let (line, column) = self.get_line_column();
             Ok(ASTNode::StructFieldAssign {
@@ -446,7 +425,7 @@ impl Parser {
                     instance: _,
                     ..
                 } => self.parse_identifier(field_name),
-                _ => panic!("unexpected token"),
+                _ => Err(self.error_here("unexpected token")),
             }
         } else {
             Ok(struct_instance_access)
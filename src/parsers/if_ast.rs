@@ -14,11 +14,7 @@ impl Parser {
                 ..
             }) => self.consume_token(),
             _ => {
-                let current_token = self.get_current_token().unwrap();
-                return Err(ParseError::new(
-                    "unexpected token missing if",
-                    &current_token,
-                ));
+                return Err(self.error_here("unexpected token missing if"));
             }
         };
         let condition = match self.get_current_token() {
@@ -161,7 +157,7 @@ impl Parser {
         };
         if value_type.is_err() {
             if let Some(token) = self.get_current_token() {
-                return Err(ParseError::new(&value_type.err().unwrap(), &token));
+                return Err(ParseError::type_error(&value_type.err().unwrap(), &token));
             }
         }
 
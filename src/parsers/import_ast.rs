@@ -1,12 +1,32 @@
 use crate::ast::ASTNode;
+use crate::builtin::register_builtins;
+use crate::environment::{Env, EnvVariableType, ValueType};
+use crate::module_resolver::resolve_module;
 use crate::parsers::parse_error::ParseError;
+use crate::parsers::symbol::SymbolKind;
 use crate::parsers::Parser;
 use crate::token::{Token, TokenKind};
+use crate::tokenizer::tokenize;
+use std::collections::HashMap;
+use std::fs;
+
+type StructEntry = (ValueType, EnvVariableType, HashMap<String, ASTNode>);
 
 impl Parser {
     pub fn parse_import(&mut self) -> Result<ASTNode, ParseError> {
-        self.extract_token(TokenKind::Import);
+        self.extract_token(TokenKind::Import)?;
+        let braced = matches!(
+            self.get_current_token(),
+            Some(Token {
+                kind: TokenKind::LBrace,
+                ..
+            })
+        );
+        if braced {
+            self.consume_token();
+        }
         let mut symbols = vec![];
+        let mut aliases = vec![];
         while let Some(token) = self.get_current_token() {
             if token.kind == TokenKind::Comma {
                 self.consume_token();
@@ -15,50 +35,255 @@ impl Parser {
             if token.kind == TokenKind::From {
                 break;
             }
+            if braced && token.kind == TokenKind::RBrace {
+                self.consume_token();
+                // `}` で行が分かれるので次の行の from に進む
+                if let Some(Token {
+                    kind: TokenKind::Eof,
+                    ..
+                }) = self.get_current_token()
+                {
+                    self.pos = 0;
+                    self.line += 1;
+                }
+                break;
+            }
             match token.kind {
                 TokenKind::Identifier(name) => {
                     self.consume_token();
+                    // `add as plus` なら plus の位置を残す
+                    let alias_position = match self.tokens[self.line].get(self.pos..self.pos + 2) {
+                        Some(
+                            [
+                                Token {
+                                    kind: TokenKind::Identifier(keyword),
+                                    ..
+                                },
+                                alias,
+                            ],
+                        ) if keyword == "as" => (alias.line, alias.column),
+                        _ => (token.line, token.column),
+                    };
+                    let alias = self.parse_import_alias()?.unwrap_or(name.clone());
+                    let scope = self.get_current_scope();
+                    self.record_symbol(&scope, &alias, SymbolKind::Import, None, alias_position);
                     symbols.push(name);
+                    aliases.push(alias);
                 }
                 _ => return Err(ParseError::new("Expected identifier", &token)),
             };
         }
-        self.extract_token(TokenKind::From);
-        let module_name = match self.get_current_token() {
+        self.extract_token(TokenKind::From)?;
+        let module_name = self.parse_module_path()?;
+        let (line, column) = match self.get_current_token() {
+            Some(token) => (token.line, token.column),
+            None => (self.line, self.pos),
+        };
+
+        // `import math from math` はモジュール全体を取り込む。
+        // 同じ名前のシンボルがエクスポートされていればそのシンボルを取り込む (実行時に判断する)
+        let exported_structs = self.exported_structs(&module_name);
+        let scope = self.get_current_scope();
+        let namespace = if !braced && symbols.len() == 1 && symbols[0] == aliases[0] {
+            let name = symbols.remove(0);
+            aliases.clear();
+            self.namespaces.insert(name.clone());
+            self.register_imported_symbol(&name);
+            // math.Vector { .. } のインスタンスの型は Vector なので、その名前で構造体を引けるようにする
+            for (struct_name, entry) in exported_structs {
+                self.structs.entry((scope.clone(), struct_name)).or_insert(entry);
+            }
+            Some(name)
+        } else {
+            for (symbol, alias) in symbols.iter().zip(&aliases) {
+                self.register_imported_symbol(alias);
+                if let Some(entry) = exported_structs.get(symbol) {
+                    self.structs.insert((scope.clone(), alias.clone()), entry.clone());
+                }
+            }
+            None
+        };
+        Ok(ASTNode::Import {
+            module_name,
+            symbols,
+            aliases,
+            namespace,
+            line,
+            column,
+        })
+    }
+
+    /// モジュールを解析して pub な構造体の型とデフォルト値を返す。
+    /// 見つからない・解析できないモジュールは実行時にエラーになるので、ここでは空にする
+    fn exported_structs(&self, module_name: &str) -> HashMap<String, StructEntry> {
+        let Ok(path) = resolve_module(module_name, self.file_path.as_deref()) else {
+            return HashMap::new();
+        };
+        if self.importing.contains(&path) || self.file_path.as_ref() == Some(&path) {
+            return HashMap::new();
+        }
+        let Ok(source) = fs::read_to_string(&path) else {
+            return HashMap::new();
+        };
+        let builtins = register_builtins(&mut Env::new());
+        let mut parser = Parser::new(tokenize(&source), builtins);
+        parser.importing = self.importing.clone();
+        parser.importing.extend(self.file_path.clone());
+        parser.set_file_path(path);
+        let Ok(nodes) = parser.parse_lines() else {
+            return HashMap::new();
+        };
+        nodes
+            .iter()
+            .filter_map(|node| match node {
+                ASTNode::Public { node, .. } => match node.as_ref() {
+                    ASTNode::Struct { name, .. } => parser
+                        .structs
+                        .get(&("global".to_string(), name.clone()))
+                        .map(|entry| (name.clone(), entry.clone())),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    // 取り込んだ名前の型はモジュールを読み込むまで分からないので Any として扱う
+    fn register_imported_symbol(&mut self, name: &String) {
+        let scope = self.get_current_scope();
+        self.register_variables(
+            scope.clone(),
+            name,
+            &ValueType::Any,
+            &EnvVariableType::Immutable,
+        );
+        self.register_function_type(scope, name.clone(), ValueType::Any);
+    }
+
+    /// `add as plus` の `as plus` を読む
+    fn parse_import_alias(&mut self) -> Result<Option<String>, ParseError> {
+        match self.get_current_token() {
+            Some(Token {
+                kind: TokenKind::Identifier(keyword),
+                ..
+            }) if keyword == "as" => {
+                self.consume_token();
+            }
+            _ => return Ok(None),
+        }
+        match self.get_current_token() {
+            Some(Token {
+                kind: TokenKind::Identifier(alias),
+                ..
+            }) => {
+                self.consume_token();
+                Ok(Some(alias))
+            }
+            Some(token) => Err(ParseError::new("Expected identifier after as", &token)),
+            None => Err(ParseError::new(
+                "Expected identifier after as",
+                &Token {
+                    kind: TokenKind::Eof,
+                    line: self.line,
+                    column: self.pos,
+                },
+            )),
+        }
+    }
+
+    /// `math.add(1, 2)`, `math.PI`, `math.Vector { x: 1 }` のようなモジュールのメンバーへのアクセス
+    pub fn parse_module_access(&mut self, module: String) -> Result<ASTNode, ParseError> {
+        let (line, column) = self.get_line_column();
+        self.extract_token(TokenKind::Dot)?;
+        let name = match self.get_current_token() {
+            Some(Token {
+                kind: TokenKind::Identifier(name),
+                ..
+            }) => name,
+            Some(token) => return Err(ParseError::new("Expected module member", &token)),
+            None => {
+                return Err(ParseError::new(
+                    "Expected module member",
+                    &Token {
+                        kind: TokenKind::Eof,
+                        line: self.line,
+                        column: self.pos,
+                    },
+                ))
+            }
+        };
+        self.consume_token();
+        let member = match self.get_current_token().map(|token| token.kind) {
+            Some(TokenKind::LParen) => self.create_function_call(name)?,
+            Some(TokenKind::LBrace) => self.create_struct_instance(name)?,
+            // 再エクスポートされたモジュール (lib.math.add(1, 2))
+            Some(TokenKind::Dot) => self.parse_module_access(name)?,
+            _ => ASTNode::Variable {
+                name,
+                value_type: Some(ValueType::Any),
+                line,
+                column,
+            },
+        };
+        Ok(ASTNode::ModuleAccess {
+            module,
+            member: Box::new(member),
+            line,
+            column,
+        })
+    }
+
+    /// `math`, `utils/strings`, `"../lib/math.sag"` のようなモジュールのパスを読む
+    fn parse_module_path(&mut self) -> Result<String, ParseError> {
+        let mut module_name = match self.get_current_token() {
             Some(Token {
                 kind: TokenKind::Identifier(module_name),
                 ..
             }) => module_name.clone(),
+            Some(Token {
+                kind: TokenKind::String(module_path),
+                ..
+            }) => {
+                self.consume_token();
+                return Ok(module_path);
+            }
             Some(token) => return Err(ParseError::new("Expected module name", &token)),
             None => {
-                let (line, column) = match self.get_current_token() {
-                    Some(token) => (token.line, token.column),
-                    None => (self.line, self.pos),
-                };
                 return Err(ParseError::new(
                     "Expected module name",
                     &Token {
                         kind: TokenKind::Eof,
-                        line,
-                        column,
+                        line: self.line,
+                        column: self.pos,
                     },
                 ));
             }
         };
-        let (line, column) = match self.get_current_token() {
-            Some(token) => (token.line, token.column),
-            None => (self.line, self.pos),
-        };
-        Ok(ASTNode::Import {
-            module_name,
-            symbols,
-            line,
-            column,
-        })
+        self.consume_token();
+        while let Some(Token {
+            kind: TokenKind::Div,
+            ..
+        }) = self.get_current_token()
+        {
+            self.consume_token();
+            match self.get_current_token() {
+                Some(Token {
+                    kind: TokenKind::Identifier(segment),
+                    ..
+                }) => {
+                    self.consume_token();
+                    module_name.push('/');
+                    module_name.push_str(&segment);
+                }
+                Some(token) => return Err(ParseError::new("Expected module name", &token)),
+                None => break,
+            }
+        }
+        Ok(module_name)
     }
 
     pub fn parse_public(&mut self) -> Result<ASTNode, ParseError> {
-        self.extract_token(TokenKind::Pub);
+        self.extract_token(TokenKind::Pub)?;
         let (line, column) = match self.get_current_token() {
             Some(token) => (token.line, token.column),
             None => (self.line, self.pos),
@@ -99,6 +324,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_import_nested_path() {
+        for (input, expected) in [
+            ("import x from utils/strings", "utils/strings"),
+            ("import x from \"../lib/math.sag\"", "../lib/math.sag"),
+        ] {
+            let builtin = register_builtins(&mut Env::new());
+            let tokens = tokenize(&input.to_string());
+            let mut parser = Parser::new(tokens, builtin);
+            match parser.parse() {
+                Ok(ASTNode::Import { module_name, .. }) => assert_eq!(module_name, expected),
+                other => panic!("Expected Import, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_import_aliases_and_module_access() {
+        let input = "import { add as plus, sub } from math\nimport math from math\nmath.add(1, 2)";
+        let builtin = register_builtins(&mut Env::new());
+        let tokens = tokenize(&input.to_string());
+        let asts = Parser::new(tokens, builtin).parse_lines().unwrap();
+        match &asts[0] {
+            ASTNode::Import {
+                symbols,
+                aliases,
+                namespace,
+                ..
+            } => {
+                assert_eq!(symbols, &vec!["add", "sub"]);
+                assert_eq!(aliases, &vec!["plus", "sub"]);
+                assert_eq!(namespace, &None);
+            }
+            other => panic!("Expected Import, got {:?}", other),
+        }
+        match &asts[1] {
+            ASTNode::Import { namespace, .. } => assert_eq!(namespace, &Some("math".to_string())),
+            other => panic!("Expected Import, got {:?}", other),
+        }
+        match &asts[2] {
+            ASTNode::ModuleAccess { module, member, .. } => {
+                assert_eq!(module, "math");
+                assert!(
+                    matches!(member.as_ref(), ASTNode::FunctionCall { name, .. } if name == "add")
+                );
+            }
+            other => panic!("Expected ModuleAccess, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_parse_public() {
         let input = "pub val foo = \"hello\"";
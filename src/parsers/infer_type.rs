@@ -101,8 +101,22 @@ impl Parser {
                 if function.is_none() {
                     return Err(format!("undefined function: {:?}", name));
                 }
-                let value_type = function.unwrap();
-                Ok(value_type.clone())
+                // 構造体を返す関数の呼び出し結果はそのインスタンスになる
+                match function.unwrap() {
+                    ValueType::Struct { name, fields, .. } => Ok(ValueType::StructInstance {
+                        name,
+                        fields: fields
+                            .into_iter()
+                            .map(|(field_name, field_type)| match field_type {
+                                ValueType::StructField { value_type, .. } => {
+                                    (field_name, *value_type)
+                                }
+                                _ => (field_name, field_type),
+                            })
+                            .collect(),
+                    }),
+                    value_type => Ok(value_type),
+                }
             }
             ASTNode::MethodCall {
                 method_name,
@@ -202,6 +216,25 @@ impl Parser {
                 is_new: _,
                 ..
             } => Ok(value_type.clone()),
+            ASTNode::Tuple { elements, .. } => {
+                let mut value_types = vec![];
+                for element in elements {
+                    value_types.push(self.infer_type(element)?);
+                }
+                Ok(ValueType::Tuple(value_types))
+            }
+            ASTNode::Destructure { value, .. } => self.infer_type(value),
+            // math.Vector { .. } はモジュールの構造体のインスタンス
+            ASTNode::ModuleAccess { member, .. }
+                if matches!(member.as_ref(), ASTNode::StructInstance { .. }) =>
+            {
+                self.infer_type(member)
+            }
+            ASTNode::Try { expr, .. } => match self.infer_type(expr)? {
+                ValueType::OptionType(value_type) => Ok(*value_type),
+                ValueType::ResultType { success, .. } => Ok(*success),
+                _ => Ok(ValueType::Any),
+            },
             ASTNode::Block { nodes, .. } => {
                 let node = nodes.last();
                 match node {
@@ -1,6 +1,7 @@
 use crate::ast::ASTNode;
-use crate::environment::EnvVariableType;
+use crate::environment::{EnvVariableType, ValueType};
 use crate::parsers::parse_error::ParseError;
+use crate::parsers::symbol::SymbolKind;
 use crate::parsers::Parser;
 use crate::token::{Token, TokenKind};
 
@@ -31,15 +32,19 @@ impl Parser {
                         }
                         _ => {}
                     };
+                    if self.is_pattern_start() {
+                        arguments.push(self.parse_pattern_argument()?);
+                        continue;
+                    }
                     if let TokenKind::Identifier(argument) = token.kind {
                         self.consume_token();
-                        self.extract_token(TokenKind::Colon);
+                        self.extract_token(TokenKind::Colon)?;
                         let value_type = if let Some(Token {
                             kind: TokenKind::Identifier(type_name),
                             ..
                         }) = self.get_current_token()
                         {
-                            Some(self.string_to_value_type(type_name))
+                            Some(self.string_to_value_type(type_name)?)
                         } else {
                             None
                         };
@@ -51,6 +56,13 @@ impl Parser {
                             line,
                             column,
                         });
+                        self.record_symbol(
+                            "lambda",
+                            &argument,
+                            SymbolKind::Variable,
+                            value_type.clone(),
+                            (token.line, token.column),
+                        );
                         self.register_variables(
                             "lambda".to_string(),
                             &argument,
@@ -64,19 +76,27 @@ impl Parser {
             }
             Some(Token {
                 kind: TokenKind::Identifier(argument),
-                ..
+                line: argument_line,
+                column: argument_column,
             }) => {
                 self.consume_token();
-                self.extract_token(TokenKind::Colon);
+                self.extract_token(TokenKind::Colon)?;
                 let value_type = if let Some(Token {
                     kind: TokenKind::Identifier(type_name),
                     ..
                 }) = self.get_current_token()
                 {
-                    Some(self.string_to_value_type(type_name))
+                    Some(self.string_to_value_type(type_name)?)
                 } else {
                     None
                 };
+                self.record_symbol(
+                    "lambda",
+                    &argument,
+                    SymbolKind::Variable,
+                    value_type.clone(),
+                    (argument_line, argument_column),
+                );
                 let (line, column) = self.get_line_column();
                 arguments.push(ASTNode::Variable {
                     name: argument.clone(),
@@ -89,35 +109,71 @@ impl Parser {
             _ => {}
         };
 
-        self.extract_token(TokenKind::RRocket);
+        self.extract_token(TokenKind::RRocket)?;
+
+        // ラムダの戻り値の型は書かないので、本体の `?` はどちらの型でも受け付ける
+        self.return_types.push(ValueType::Any);
+        let result = self.parse_lambda_body(arguments);
+        self.return_types.pop();
+        self.leave_scope();
+        result
+    }
 
-        let result = match self.get_current_token() {
+    fn parse_lambda_body(&mut self, arguments: Vec<ASTNode>) -> Result<ASTNode, ParseError> {
+        let start = (self.line, self.pos);
+        let body = match self.get_current_token() {
             Some(Token {
                 kind: TokenKind::LBrace,
                 ..
-            }) => {
-                let statement = self.parse_block()?;
-                let (line, column) = self.get_line_column();
-                ASTNode::Lambda {
-                    arguments,
-                    body: Box::new(statement),
-                    line,
-                    column,
-                }
-            }
-            _ => {
-                let statement = self.parse_expression(0)?;
-                let (line, column) = self.get_line_column();
-                ASTNode::Lambda {
-                    arguments,
-                    body: Box::new(statement),
-                    line,
-                    column,
+            }) => self.parse_block()?,
+            _ => self.parse_expression(0)?,
+        };
+        let (line, column) = self.get_line_column();
+        let captures = self.captured_names(start, &arguments);
+        Ok(ASTNode::Lambda {
+            arguments,
+            body: Box::new(body),
+            captures,
+            line,
+            column,
+        })
+    }
+
+    // start から今の位置までに出てくる、引数以外の名前。
+    // フィールド名や関数名も混じるが、ラムダを作るときに外側のローカル変数として見つからなければ捕捉しない
+    fn captured_names(
+        &self,
+        (start_line, start_pos): (usize, usize),
+        arguments: &[ASTNode],
+    ) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for (index, tokens) in self
+            .tokens
+            .iter()
+            .enumerate()
+            .take(self.line + 1)
+            .skip(start_line)
+        {
+            let end = match index == self.line {
+                true => self.pos.min(tokens.len()),
+                false => tokens.len(),
+            };
+            let begin = match index == start_line {
+                true => start_pos.min(end),
+                false => 0,
+            };
+            for token in &tokens[begin..end] {
+                if let TokenKind::Identifier(name) = &token.kind
+                    && !names.contains(name)
+                    && !arguments.iter().any(|argument| {
+                        matches!(argument, ASTNode::Variable { name: argument, .. } if argument == name)
+                    })
+                {
+                    names.push(name.clone());
                 }
             }
-        };
-        self.leave_scope();
-        Ok(result)
+        }
+        names
     }
 
     pub fn parse_lambda_call(&mut self, left: ASTNode) -> Result<ASTNode, ParseError> {
@@ -0,0 +1,41 @@
+use crate::parsers::Parser;
+use std::collections::HashSet;
+
+/// パーサーが読んだ文の並び。フォーマッタが改行とインデントを決めるのに使う
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Layout {
+    // 文やフィールドを並べる `{` の位置 (行, 列)。関数や if のブロック、struct、impl、match
+    pub blocks: HashSet<(usize, usize)>,
+    // 文、フィールド、メソッド、match の腕の先頭の位置
+    pub items: HashSet<(usize, usize)>,
+}
+
+impl Parser {
+    /// 文とブロックの位置の記録を始める
+    pub fn record_layout(&mut self) {
+        self.layout = Some(Layout::default());
+    }
+
+    /// 記録した位置。record_layout を呼んでいなければ None
+    pub fn layout(&self) -> Option<&Layout> {
+        self.layout.as_ref()
+    }
+
+    // 今のトークンが文などを並べる `{` であることを残す
+    pub(crate) fn record_block_start(&mut self) {
+        if let Some(token) = self.get_current_token()
+            && let Some(layout) = &mut self.layout
+        {
+            layout.blocks.insert((token.line, token.column));
+        }
+    }
+
+    // 今のトークンから文などが始まることを残す
+    pub(crate) fn record_item_start(&mut self) {
+        if let Some(token) = self.get_current_token()
+            && let Some(layout) = &mut self.layout
+        {
+            layout.items.insert((token.line, token.column));
+        }
+    }
+}
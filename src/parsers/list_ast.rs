@@ -17,30 +17,80 @@ impl Parser {
                 self.consume_token();
                 continue;
             }
-            let value = match token.kind {
-                TokenKind::Number(value) => Value::Number(value),
-                TokenKind::String(value) => Value::String(value),
-                _ => panic!("unexpected token: {:?}", token),
-            };
-            list.push(ASTNode::Literal {
-                value,
-                line: token.line,
-                column: token.column,
-            });
-            self.consume_token();
+            // リストは定数として作るので、要素はリテラルかリテラルのタプルに限る
+            let element = self.parse_expression(0)?;
+            match constant_value(&element) {
+                Some(value) => list.push(value),
+                None => {
+                    return Err(ParseError::new(
+                        "list elements must be literals or tuples of literals",
+                        &token,
+                    ))
+                }
+            }
         }
         let (line, column) = self.get_line_column();
         Ok(ASTNode::Literal {
-            value: Value::List(
-                list.iter()
-                    .map(|x| match x {
-                        ASTNode::Literal { value, .. } => value.clone(),
-                        _ => panic!("unexpected node"),
-                    })
-                    .collect(),
-            ),
+            value: Value::List(list),
             line,
             column,
         })
     }
 }
+
+fn constant_value(node: &ASTNode) -> Option<Value> {
+    match node {
+        ASTNode::Literal { value, .. } => Some(value.clone()),
+        ASTNode::PrefixOp {
+            op: TokenKind::Minus,
+            expr,
+            ..
+        } => match constant_value(expr)? {
+            Value::Number(n) => Some(Value::Number(-n)),
+            _ => None,
+        },
+        ASTNode::Tuple { elements, .. } => elements
+            .iter()
+            .map(constant_value)
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Tuple),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::register_builtins;
+    use crate::environment::Env;
+    use crate::tokenizer::tokenize;
+    use fraction::Fraction;
+
+    fn parse(input: &str) -> Result<ASTNode, ParseError> {
+        let tokens = tokenize(&input.to_string());
+        let builtin = register_builtins(&mut Env::new());
+        Parser::new(tokens, builtin).parse()
+    }
+
+    #[test]
+    fn test_parse_list_of_tuples() {
+        let number = |n: i64| Value::Number(Fraction::from(n));
+        match parse("[(1, \"a\"), (2), [3, -4]]") {
+            Ok(ASTNode::Literal {
+                value: Value::List(values),
+                ..
+            }) => assert_eq!(
+                values,
+                vec![
+                    Value::Tuple(vec![number(1), Value::String("a".into())]),
+                    number(2),
+                    Value::List(vec![number(3), number(-4)]),
+                ]
+            ),
+            other => panic!("Expected List, got {:?}", other),
+        }
+        let error = parse("[true, (1, x)]").unwrap_err();
+        assert_eq!(error.message, "list elements must be literals or tuples of literals");
+        assert_eq!((error.line, error.column), (1, 8));
+    }
+}
@@ -8,7 +8,8 @@ impl Parser {
     pub fn parse_match(&mut self) -> Result<ASTNode, ParseError> {
         self.consume_token();
         let expression = self.parse_expression(0)?;
-        self.extract_token(TokenKind::LBrace);
+        self.record_block_start();
+        self.extract_token(TokenKind::LBrace)?;
         let mut cases = vec![];
         let case_pattern_type: Result<ValueType, String> = self.infer_type(&expression);
         let mut case_body_type: Option<ValueType> = None;
@@ -24,6 +25,7 @@ impl Parser {
                 self.line += 1;
                 continue;
             }
+            self.record_item_start();
             let pattern = self.parse_expression(0)?;
             self.enter_scope(format!("match-{:?}", count).to_string());
             count += 1;
@@ -45,7 +47,7 @@ impl Parser {
                                     self.register_variables(
                                         self.get_current_scope().clone(),
                                         &name,
-                                        &expression_type.unwrap(),
+                                        &expression_type.clone().unwrap_or(ValueType::Any),
                                         &EnvVariableType::Immutable,
                                     );
                                 }
@@ -71,7 +73,7 @@ impl Parser {
                                     self.register_variables(
                                         self.get_current_scope().clone(),
                                         &name,
-                                        &expression_type.unwrap(),
+                                        &expression_type.clone().unwrap_or(ValueType::Any),
                                         &EnvVariableType::Immutable,
                                     );
                                 }
@@ -92,13 +94,12 @@ impl Parser {
                                         &failure,
                                         &EnvVariableType::Immutable,
                                     );
-                                    println!("variable!!: {:?}", self.variables);
                                 }
                                 _ => {
                                     self.register_variables(
                                         self.get_current_scope().clone(),
                                         &name,
-                                        &expression_type.unwrap(),
+                                        &expression_type.clone().unwrap_or(ValueType::Any),
                                         &EnvVariableType::Immutable,
                                     );
                                 }
@@ -109,7 +110,7 @@ impl Parser {
                 }
                 _ => {}
             };
-            self.extract_token(TokenKind::RRocket);
+            self.extract_token(TokenKind::RRocket)?;
             let body = self.parse_block()?;
             cases.push((pattern.clone(), body.clone()));
             let is_underscore = match pattern {
@@ -119,36 +120,24 @@ impl Parser {
             if is_underscore {
                 under_score_pattern_count += 1;
                 if under_score_pattern_count >= 2 {
-                    return Err(ParseError::new(
-                        "too many wild card pattern _",
-                        &self.get_current_token().unwrap(),
-                    ));
+                    return Err(self.error_here("too many wild card pattern _"));
                 }
             }
             if !is_underscore && self.infer_type(&pattern).is_err() {
-                return Err(ParseError::new(
-                    "Unsupported pattern",
-                    &self.get_current_token().unwrap(),
-                ));
+                return Err(self.error_here("Unsupported pattern"));
             }
             if case_body_type.is_none() {
                 case_body_type = self.infer_type(&body).ok();
                 if case_pattern_type.is_err() {
-                    return Err(ParseError::new(
-                        "Pattern type mismatch",
-                        &self.get_current_token().unwrap(),
-                    ));
+                    return Err(self.type_error_here("Pattern type mismatch"));
                 }
             } else if case_body_type != self.infer_type(&body).ok() {
-                return Err(ParseError::new(
-                    "Pattern type mismatch",
-                    &self.get_current_token().unwrap(),
-                ));
+                return Err(self.type_error_here("Pattern type mismatch"));
             }
             self.leave_scope();
         }
 
-        self.extract_token(TokenKind::RBrace);
+        self.extract_token(TokenKind::RBrace)?;
         let (line, column) = self.get_line_column();
         Ok(ASTNode::Match {
             expression: Box::new(expression),
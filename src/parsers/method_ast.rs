@@ -1,6 +1,7 @@
 use crate::ast::ASTNode;
 use crate::environment::ValueType;
 use crate::parsers::parse_error::ParseError;
+use crate::parsers::symbol::SymbolKind;
 use crate::parsers::Parser;
 use crate::token::{Token, TokenKind};
 use crate::value::Value;
@@ -8,23 +9,24 @@ use crate::value::Value;
 impl Parser {
     pub fn parse_method(&mut self) -> Result<ASTNode, ParseError> {
         self.consume_token();
-        let name = match self.get_current_token() {
+        let (name, name_position) = match self.get_current_token() {
             Some(Token {
                 kind: TokenKind::Identifier(name),
-                ..
-            }) => name,
-            _ => panic!("unexpected token"),
+                line,
+                column,
+            }) => (name, (line, column)),
+            _ => return Err(self.error_here("Expected method name")),
         };
         self.enter_scope(name.to_string());
         // メソッドスコープに入る
         self.enter_method_scope();
 
         self.consume_token();
-        self.extract_token(TokenKind::LParen);
+        self.extract_token(TokenKind::LParen)?;
         let arguments = self.parse_function_arguments()?;
         let mut is_mut = false;
         // Check if this is a static method (no self parameter) or instance method
-        let _is_static_method = if arguments.len() > 0 {
+        let is_static_method = if arguments.len() > 0 {
             match arguments.first() {
                 Some(ASTNode::Variable {
                     name, value_type, ..
@@ -46,8 +48,24 @@ impl Parser {
         } else {
             true // No arguments, so static method
         };
-        let return_type = self.parse_return_type();
-        let body = self.parse_block()?;
+        let return_type = self.parse_return_type()?;
+        let struct_name = self.current_struct.clone().unwrap_or_default();
+        self.record_symbol(
+            &struct_name,
+            &name,
+            SymbolKind::Method,
+            Some(return_type.clone()),
+            name_position,
+        );
+        if is_static_method {
+            // 静的メソッドは `Point::new` という名前の関数として呼び出せるようにする
+            let qualified_name = format!("{}::{}", self.current_struct.clone().unwrap(), name);
+            self.register_functions("global".into(), &qualified_name, &arguments, &return_type);
+        }
+        self.return_types.push(return_type.clone());
+        let body = self.parse_block();
+        self.return_types.pop();
+        let body = body?;
         // メソッドスコープから出る
         self.leave_method_scope();
         self.leave_scope();
@@ -107,6 +125,8 @@ impl Parser {
                             ValueType::Void => true,
                             ValueType::List(_) => true,
                             ValueType::Dict(_) => true,
+                            ValueType::OptionType(_) => true,
+                            ValueType::ResultType { .. } => true,
                             _ => false,
                         },
                         _ => false,
@@ -120,6 +140,8 @@ impl Parser {
                             ValueType::Void => true,
                             ValueType::List(_) => true,
                             ValueType::Dict(_) => true,
+                            ValueType::OptionType(_) => true,
+                            ValueType::ResultType { .. } => true,
                             _ => false,
                         },
                         _ => false,
@@ -133,6 +155,8 @@ impl Parser {
                 Ok(ValueType::Void) => true,
                 Ok(ValueType::List(_)) => true,
                 Ok(ValueType::Dict(_)) => true,
+                Ok(ValueType::OptionType(_)) => true,
+                Ok(ValueType::ResultType { .. }) => true,
                 _ => false,
             },
             _ => false,
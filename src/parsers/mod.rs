@@ -10,6 +10,7 @@ pub mod if_ast;
 pub mod import_ast;
 pub mod infer_type;
 pub mod lambda_ast;
+pub mod layout;
 pub mod list_ast;
 pub mod literal_ast;
 pub mod match_ast;
@@ -22,24 +23,37 @@ pub mod result_ast;
 pub mod return_ast;
 pub mod string_to_value_type;
 pub mod struct_ast;
+pub mod symbol;
+pub mod try_ast;
+pub mod tuple_ast;
 
 use crate::ast::ASTNode;
 use crate::environment::{EnvVariableType, MethodInfo, ValueType};
-use crate::parsers::parse_error::ParseError;
+use crate::parsers::layout::Layout;
+use crate::parsers::parse_error::{ParseError, ParseErrorKind};
+use crate::parsers::symbol::Symbol;
 use crate::token::{Token, TokenKind};
 use crate::value::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
+#[derive(Clone)]
 pub struct Parser {
     tokens: Vec<Vec<Token>>,
     pos: usize,
     line: usize,
     scopes: Vec<String>,
     variables: HashMap<(String, String), (ValueType, EnvVariableType)>, // key: (scope, name), value: value_type
-    structs: HashMap<(String, String), (ValueType, EnvVariableType, HashMap<String, ASTNode>)>, // key: (scope, name), value: value_type
+    structs: HashMap<(String, String), (ValueType, EnvVariableType, HashMap<String, ASTNode>)>, // key: (scope, name), value: (value_type, variable_type, field default values)
     functions: HashMap<(String, String), ValueType>, // key: (scope, name, arguments), value: (body, return_type)
     current_struct: Option<String>,
     in_method_scope: bool, // メソッド内かどうかを追跡
+    namespaces: HashSet<String>, // import math from math で取り込んだモジュール名
+    symbols: Option<Vec<Symbol>>, // record_symbols を呼んだときだけ名前の位置を残す
+    layout: Option<Layout>,       // record_layout を呼んだときだけ文とブロックの位置を残す
+    file_path: Option<PathBuf>,   // import のモジュールを探す基準のファイル
+    importing: Vec<PathBuf>,      // 構造体の型を読むために解析中のモジュール (循環を避ける)
+    return_types: Vec<ValueType>, // 解析中の関数の戻り値の型。`?` の検査に使う
 }
 
 impl Parser {
@@ -58,9 +72,19 @@ impl Parser {
             functions: initial_functions,
             current_struct: None,
             in_method_scope: false, // 初期状態ではメソッドスコープではない
+            namespaces: HashSet::new(),
+            symbols: None,
+            layout: None,
+            file_path: None,
+            importing: vec![],
+            return_types: vec![],
         }
     }
 
+    pub fn set_file_path(&mut self, file_path: PathBuf) {
+        self.file_path = Some(file_path);
+    }
+
     fn get_line_column(&self) -> (usize, usize) {
         match self.get_current_token() {
             Some(token) => (token.line, token.column),
@@ -124,6 +148,17 @@ impl Parser {
                 })
                 .collect();
             let methods = HashMap::new();
+            // フィールドのデフォルト値 (インスタンス生成時に省略されたフィールドを補う)
+            let default_values = fields
+                .iter()
+                .filter_map(|(name, field)| match field {
+                    ASTNode::StructField {
+                        default_value: Some(default_value),
+                        ..
+                    } => Some((name.clone(), *default_value.clone())),
+                    _ => None,
+                })
+                .collect();
             let insert_value = (
                 ValueType::Struct {
                     name: name.clone(),
@@ -131,7 +166,7 @@ impl Parser {
                     methods,
                 },
                 EnvVariableType::Immutable,
-                HashMap::new(),
+                default_values,
             );
             self.structs
                 .insert((scope.to_string(), name.to_string()), insert_value);
@@ -193,6 +228,18 @@ impl Parser {
         None
     }
 
+    fn get_struct_default_values(&self, scope: String, name: String) -> HashMap<String, ASTNode> {
+        for checked_scope in [scope, "global".to_string()] {
+            if let Some((_, _, default_values)) = self
+                .structs
+                .get(&(checked_scope.to_string(), name.to_string()))
+            {
+                return default_values.clone();
+            }
+        }
+        HashMap::new()
+    }
+
     fn register_functions(
         &mut self,
         scope: String,
@@ -377,6 +424,46 @@ impl Parser {
                     _ => None,
                 }
             }
+            ValueType::OptionType(value_type) => {
+                let return_type = match method_name.as_str() {
+                    "is_some" | "is_none" => ValueType::Bool,
+                    "unwrap" | "expect" | "unwrap_or" | "unwrap_or_else" => *value_type,
+                    "or_else" => ValueType::OptionType(value_type),
+                    "map" | "and_then" => ValueType::OptionType(Box::new(ValueType::Any)),
+                    "ok_or" => ValueType::ResultType {
+                        success: value_type,
+                        failure: Box::new(ValueType::Any),
+                    },
+                    _ => return None,
+                };
+                Some(MethodInfo {
+                    arguments: vec![],
+                    body: None,
+                    return_type,
+                    is_mut: false,
+                })
+            }
+            ValueType::ResultType { success, failure } => {
+                let return_type = match method_name.as_str() {
+                    "is_suc" | "is_fail" => ValueType::Bool,
+                    "unwrap" | "expect" | "unwrap_or" | "unwrap_or_else" => *success,
+                    "map" | "and_then" => ValueType::ResultType {
+                        success: Box::new(ValueType::Any),
+                        failure,
+                    },
+                    "map_err" | "or_else" => ValueType::ResultType {
+                        success,
+                        failure: Box::new(ValueType::Any),
+                    },
+                    _ => return None,
+                };
+                Some(MethodInfo {
+                    arguments: vec![],
+                    body: None,
+                    return_type,
+                    is_mut: false,
+                })
+            }
             ValueType::Number => match method_name.as_str() {
                 "to_string" => Some(MethodInfo {
                     arguments: vec![],
@@ -479,7 +566,10 @@ impl Parser {
     }
 
     fn find_variables(&self, scope: String, name: String) -> Option<(ValueType, EnvVariableType)> {
-        for checked_scope in vec![scope.to_string(), "global".to_string()] {
+        // ラムダから外側の関数のローカル変数が見えるように、囲んでいるスコープを内側から順に探す
+        let mut checked_scopes = vec![scope.to_string()];
+        checked_scopes.extend(self.scopes.iter().rev().cloned());
+        for checked_scope in checked_scopes {
             match self
                 .variables
                 .get(&(checked_scope.to_string(), name.to_string()))
@@ -525,6 +615,9 @@ impl Parser {
                             value.1.clone(),
                         ))
                     }
+                    ValueType::Tuple(value_types) => {
+                        return Some((ValueType::Tuple(value_types.clone()), value.1.clone()))
+                    }
                     &ValueType::Any => return Some((ValueType::Any, value.1.clone())),
                     _ => return None,
                 },
@@ -577,7 +670,7 @@ impl Parser {
         Some(token)
     }
 
-    pub fn extract_token(&mut self, token: TokenKind) -> Token {
+    pub fn extract_token(&mut self, token: TokenKind) -> Result<Token, ParseError> {
         match self.get_current_token() {
             Some(Token {
                 kind: current_token_kind,
@@ -585,16 +678,40 @@ impl Parser {
                 column,
             }) if current_token_kind == token => {
                 self.pos += 1;
-                Token {
+                Ok(Token {
                     kind: current_token_kind,
                     line,
                     column,
-                }
+                })
             }
-            _ => panic!("unexpected token: {:?}", token),
+            _ => Err(self.error_here(&format!("expected {:?}", token))),
+        }
+    }
+
+    // 今の位置のエラー。入力が終わっていれば読んでいた行の終わりを指す
+    fn error_here(&self, message: &str) -> ParseError {
+        let (line, column) = self.get_line_column();
+        ParseError {
+            message: message.to_string(),
+            line,
+            column,
+            kind: ParseErrorKind::Syntax,
         }
     }
 
+    fn type_error_here(&self, message: &str) -> ParseError {
+        ParseError {
+            kind: ParseErrorKind::Type,
+            ..self.error_here(message)
+        }
+    }
+
+    // 今のトークン。入力が終わっていればエラー
+    fn current_token(&self) -> Result<Token, ParseError> {
+        self.get_current_token()
+            .ok_or_else(|| self.error_here("unexpected end of input"))
+    }
+
     fn is_lparen_call(&mut self) -> bool {
         self.pos += 1;
         let next_token = self.get_current_token();
@@ -609,10 +726,7 @@ impl Parser {
     }
 
     fn parse_primary(&mut self) -> Result<ASTNode, ParseError> {
-        let token = match self.get_current_token() {
-            Some(token) => token,
-            _ => panic!("token not found!"),
-        };
+        let token = self.current_token()?;
         match token.kind {
             TokenKind::Match => self.parse_match(),
             TokenKind::Struct => self.parse_struct(),
@@ -651,7 +765,7 @@ impl Parser {
                     } => {
                         if !is_statement && *value_type != ValueType::Void {
                             if else_.is_none() {
-                                panic!("if expressions without else");
+                                return Err(self.error_here("if expressions without else"));
                             }
                         }
                     }
@@ -672,12 +786,18 @@ impl Parser {
                         self.consume_token();
                         Ok(expr)
                     }
+                    // `(a, b)` はタプル
+                    Some(Token {
+                        kind: TokenKind::Comma,
+                        ..
+                    }) => self.parse_tuple(expr, token.line, token.column),
                     _ => {
                         let (line, column) = self.get_line_column();
                         Err(ParseError {
                             message: "Expected closing parenthesis".to_string(),
                             line,
                             column,
+                            kind: ParseErrorKind::Syntax,
                         })
                     }
                 }
@@ -715,11 +835,9 @@ impl Parser {
             };
             if token.kind == TokenKind::Dot {
                 self.pos += 2;
-                if let TokenKind::LParen = self.get_current_token().unwrap().kind {
+                if let TokenKind::LParen = self.current_token()?.kind {
                     self.pos -= 1;
-                    if let TokenKind::Identifier(method_name) =
-                        self.get_current_token().unwrap().kind
-                    {
+                    if let TokenKind::Identifier(method_name) = self.current_token()?.kind {
                         self.pos += 1;
                         let args = self.parse_function_call_arguments_paren()?;
 
@@ -756,12 +874,18 @@ impl Parser {
                                         ValueType::Void => true,
                                         ValueType::List(_) => true,
                                         ValueType::Dict(_) => true,
+                                        ValueType::OptionType(_) => true,
+                                        ValueType::ResultType { .. } => true,
                                         _ => false,
                                     },
                                     _ => false,
                                 }
                             }
-                            ASTNode::MethodCall { ref caller, .. } => {
+                            ASTNode::MethodCall {
+                                ref caller,
+                                method_name: ref caller_method_name,
+                                ..
+                            } => {
                                 let method_info = match self.infer_type(caller) {
                                     Ok(ValueType::StructInstance { name, .. }) => {
                                         let methods = match self
@@ -774,12 +898,6 @@ impl Parser {
                                             }) => methods,
                                             _ => panic!("invalid struct"),
                                         };
-                                        let caller_method_name = match lhs {
-                                            ASTNode::MethodCall {
-                                                ref method_name, ..
-                                            } => method_name,
-                                            _ => panic!("invalid method call"),
-                                        };
                                         match methods.get(caller_method_name) {
                                             Some(method_info) => Some(method_info.clone()),
                                             None => None,
@@ -789,7 +907,7 @@ impl Parser {
                                         match self.get_method(
                                             self.get_current_scope(),
                                             value_type,
-                                            method_name.clone(),
+                                            caller_method_name.clone(),
                                         ) {
                                             Some(method_info) => Some(method_info.clone()),
                                             None => None,
@@ -805,6 +923,8 @@ impl Parser {
                                         ValueType::Void => true,
                                         ValueType::List(_) => true,
                                         ValueType::Dict(_) => true,
+                                        ValueType::OptionType(_) => true,
+                                        ValueType::ResultType { .. } => true,
                                         _ => false,
                                     },
                                     None => false,
@@ -819,6 +939,8 @@ impl Parser {
                                         ValueType::Void => true,
                                         ValueType::List(_) => true,
                                         ValueType::Dict(_) => true,
+                                        ValueType::OptionType(_) => true,
+                                        ValueType::ResultType { .. } => true,
                                         _ => false,
                                     },
                                     None => false,
@@ -831,31 +953,17 @@ impl Parser {
                                 Ok(ValueType::Void) => true,
                                 Ok(ValueType::List(_)) => true,
                                 Ok(ValueType::Dict(_)) => true,
+                                Ok(ValueType::OptionType(_)) => true,
+                                Ok(ValueType::ResultType { .. }) => true,
                                 _ => false,
                             },
                         };
 
                         lhs = ASTNode::MethodCall {
-                            caller: Box::new(lhs.clone()),
+                            caller: Box::new(lhs),
                             method_name,
                             builtin,
-                            arguments: match args {
-                                ASTNode::FunctionCallArgs { args, line, column } => {
-                                    Box::new(ASTNode::FunctionCallArgs {
-                                        args: vec![lhs]
-                                            .into_iter()
-                                            .chain(args.into_iter())
-                                            .collect(),
-                                        line,
-                                        column,
-                                    })
-                                }
-                                _ => Box::new(ASTNode::FunctionCallArgs {
-                                    args: vec![lhs],
-                                    line: token.line,
-                                    column: token.column,
-                                }),
-                            },
+                            arguments: Box::new(args),
                             line: token.line,
                             column: token.column,
                         };
@@ -864,6 +972,10 @@ impl Parser {
                 }
                 continue;
             }
+            if token.kind == TokenKind::Question {
+                lhs = self.parse_try(lhs, token)?;
+                continue;
+            }
             if token.kind == TokenKind::RArrow {
                 if self.is_lparen_call() {
                     self.pos += 1;
@@ -962,9 +1074,79 @@ impl Parser {
         self.parse_expression(0)
     }
 
+    /// 記号表を残したまま次の入力を読む。REPL で前の入力の定義を使うため
+    pub fn set_input(&mut self, tokens: Vec<Token>) {
+        self.tokens = Self::split_lines(tokens);
+        self.pos = 0;
+        self.line = 0;
+        self.scopes = vec!["global".into()];
+        self.current_struct = None;
+        self.in_method_scope = false;
+        if let Some(symbols) = &mut self.symbols {
+            symbols.clear();
+        }
+        if let Some(layout) = &mut self.layout {
+            *layout = Layout::default();
+        }
+    }
+
+    /// 次の文を 1 つ読む。入力の終わりなら None
+    pub fn parse_next(&mut self) -> Option<Result<ASTNode, ParseError>> {
+        if self.line >= self.tokens.len() {
+            return None;
+        }
+        if let Err(e) = self.check_unterminated_string() {
+            self.line = self.tokens.len();
+            return Some(Err(e));
+        }
+        self.record_item_start();
+        let result = self.parse();
+        self.line += 1;
+        self.pos = 0;
+        Some(result)
+    }
+
+    pub fn get_variables(&self) -> &HashMap<(String, String), (ValueType, EnvVariableType)> {
+        &self.variables
+    }
+
+    #[allow(dead_code)]
+    pub fn get_functions(&self) -> &HashMap<(String, String), ValueType> {
+        &self.functions
+    }
+
+    /// 定義を読まずに使える関数を、戻り値の型とともに登録する
+    pub fn register_function_type(&mut self, scope: String, name: String, return_type: ValueType) {
+        self.functions.insert((scope, name), return_type);
+    }
+
+    #[allow(dead_code)]
+    pub fn get_struct_types(&self) -> impl Iterator<Item = &ValueType> {
+        self.structs.values().map(|(value_type, _, _)| value_type)
+    }
+
+    #[allow(dead_code)]
+    pub fn get_namespaces(&self) -> &HashSet<String> {
+        &self.namespaces
+    }
+
+    // 閉じていない文字列は入力の終わりまで続くので、読み始める前にエラーにする
+    fn check_unterminated_string(&self) -> Result<(), ParseError> {
+        match self.tokens[self.line..]
+            .iter()
+            .flatten()
+            .find(|token| matches!(token.kind, TokenKind::UnterminatedString(_)))
+        {
+            Some(token) => Err(ParseError::new("unterminated string literal", token)),
+            None => Ok(()),
+        }
+    }
+
     pub fn parse_lines(&mut self) -> Result<Vec<ASTNode>, ParseError> {
+        self.check_unterminated_string()?;
         let mut ast_nodes = vec![];
         for _ in 0..self.tokens.len() {
+            self.record_item_start();
             ast_nodes.push(self.parse()?);
             self.line += 1;
             if self.line >= self.tokens.len() {
@@ -2471,6 +2653,7 @@ mod tests {
                 ASTNode::StructField {
                     value_type: ValueType::Number,
                     is_public: false,
+                    default_value: None,
                     line: 2,
                     column: 13,
                 },
@@ -2665,4 +2848,15 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_unterminated_string() {
+        let input = "val t = 1\nval s = \"abc\nprint(s)";
+        let builtins = register_builtins(&mut Env::new());
+        let tokens = tokenize(&input.to_string());
+        let error = Parser::new(tokens, builtins).parse_lines().unwrap_err();
+        assert_eq!(error.message, "unterminated string literal");
+        assert_eq!((error.line, error.column), (2, 9));
+        assert_eq!(error.kind, ParseErrorKind::Syntax);
+    }
 }
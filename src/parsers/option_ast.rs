@@ -6,9 +6,9 @@ use crate::token::TokenKind;
 impl Parser {
     pub fn parse_option_some(&mut self) -> Result<ASTNode, ParseError> {
         self.consume_token();
-        self.extract_token(TokenKind::LParen);
+        self.extract_token(TokenKind::LParen)?;
         let value = self.parse_expression(0)?;
-        self.extract_token(TokenKind::RParen);
+        self.extract_token(TokenKind::RParen)?;
         let (line, column) = self.get_line_column();
         Ok(ASTNode::OptionSome {
             value: Box::new(value),
@@ -1,10 +1,18 @@
 use crate::token::Token;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseErrorKind {
+    Syntax,
+    // 型検査で見つかったエラー。CLI の終了コードを分けるために区別する
+    Type,
+}
+
 #[derive(Debug, Clone)]
 pub struct ParseError {
     pub message: String,
     pub line: usize,
     pub column: usize,
+    pub kind: ParseErrorKind,
 }
 
 impl ParseError {
@@ -13,19 +21,36 @@ impl ParseError {
             message: message.to_string(),
             line: token.line,
             column: token.column,
+            kind: ParseErrorKind::Syntax,
+        }
+    }
+
+    pub fn type_error(message: &str, token: &Token) -> Self {
+        Self {
+            kind: ParseErrorKind::Type,
+            ..Self::new(message, token)
         }
     }
 
     pub fn message_with_source(&self, source: &str) -> String {
         let lines: Vec<&str> = source.lines().collect();
-        let error_line = lines.get(self.line - 1).unwrap_or(&"");
+        // 位置の分からないエラーは line が 0 になる
+        let error_line = self
+            .line
+            .checked_sub(1)
+            .and_then(|index| lines.get(index))
+            .unwrap_or(&"");
         format!(
-            "Parse Error: {}\n --> line {}, column {}\n | {}\n | {}^",
+            "{}: {}\n --> line {}, column {}\n | {}\n | {}^",
+            match self.kind {
+                ParseErrorKind::Syntax => "Parse Error",
+                ParseErrorKind::Type => "Type Error",
+            },
             self.message,
             self.line,
             self.column,
             error_line,
-            " ".repeat(self.column)
+            " ".repeat(self.column.saturating_sub(1))
         )
     }
 }
@@ -6,9 +6,9 @@ use crate::token::TokenKind;
 impl Parser {
     pub fn parse_result_success(&mut self) -> Result<ASTNode, ParseError> {
         self.consume_token();
-        self.extract_token(TokenKind::LParen);
+        self.extract_token(TokenKind::LParen)?;
         let value = self.parse_expression(0)?;
-        self.extract_token(TokenKind::RParen);
+        self.extract_token(TokenKind::RParen)?;
         let (line, column) = self.get_line_column();
         Ok(ASTNode::ResultSuccess {
             value: Box::new(value),
@@ -19,9 +19,9 @@ impl Parser {
 
     pub fn parse_result_failure(&mut self) -> Result<ASTNode, ParseError> {
         self.consume_token();
-        self.extract_token(TokenKind::LParen);
+        self.extract_token(TokenKind::LParen)?;
         let value = self.parse_expression(0)?;
-        self.extract_token(TokenKind::RParen);
+        self.extract_token(TokenKind::RParen)?;
         let (line, column) = self.get_line_column();
         Ok(ASTNode::ResultFailure {
             value: Box::new(value),
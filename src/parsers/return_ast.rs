@@ -16,7 +16,7 @@ impl Parser {
         })
     }
 
-    pub fn parse_return_type(&mut self) -> ValueType {
+    pub fn parse_return_type(&mut self) -> Result<ValueType, ParseError> {
         match self.get_current_token() {
             Some(Token {
                 kind: TokenKind::Colon,
@@ -37,19 +37,19 @@ impl Parser {
                 }) = self.get_current_token()
                 {
                     self.consume_token();
-                    self.extract_token(TokenKind::Lt);
+                    self.extract_token(TokenKind::Lt)?;
                     let some = match self.get_current_token() {
                         Some(Token {
                             kind: TokenKind::Identifier(type_name),
                             ..
                         }) => {
                             self.consume_token();
-                            self.string_to_value_type(type_name)
+                            self.string_to_value_type(type_name)?
                         }
                         _ => ValueType::Void,
                     };
-                    self.extract_token(TokenKind::Gt);
-                    return ValueType::OptionType(Box::new(some));
+                    self.extract_token(TokenKind::Gt)?;
+                    return Ok(ValueType::OptionType(Box::new(some)));
                 }
                 if let Some(Token {
                     kind: TokenKind::Result,
@@ -57,33 +57,33 @@ impl Parser {
                 }) = self.get_current_token()
                 {
                     self.consume_token();
-                    self.extract_token(TokenKind::Lt);
+                    self.extract_token(TokenKind::Lt)?;
                     let success = match self.get_current_token() {
                         Some(Token {
                             kind: TokenKind::Identifier(type_name),
                             ..
                         }) => {
                             self.consume_token();
-                            self.string_to_value_type(type_name)
+                            self.string_to_value_type(type_name)?
                         }
                         _ => ValueType::Void,
                     };
-                    self.extract_token(TokenKind::Comma);
+                    self.extract_token(TokenKind::Comma)?;
                     let failure = match self.get_current_token() {
                         Some(Token {
                             kind: TokenKind::Identifier(type_name),
                             ..
                         }) => {
                             self.consume_token();
-                            self.string_to_value_type(type_name)
+                            self.string_to_value_type(type_name)?
                         }
                         _ => ValueType::Void,
                     };
                     self.consume_token();
-                    return ValueType::ResultType {
+                    return Ok(ValueType::ResultType {
                         success: Box::new(success),
                         failure: Box::new(failure),
-                    };
+                    });
                 }
                 if let Some(Token {
                     kind: TokenKind::List,
@@ -91,23 +91,31 @@ impl Parser {
                 }) = self.get_current_token()
                 {
                     self.consume_token();
-                    self.extract_token(TokenKind::Lt);
+                    self.extract_token(TokenKind::Lt)?;
                     let element_type = match self.get_current_token() {
                         Some(Token {
                             kind: TokenKind::Identifier(type_name),
                             ..
                         }) => {
                             self.consume_token();
-                            self.string_to_value_type(type_name)
+                            self.string_to_value_type(type_name)?
                         }
                         _ => ValueType::Void,
                     };
-                    self.extract_token(TokenKind::Gt);
-                    return ValueType::List(Box::new(element_type));
+                    self.extract_token(TokenKind::Gt)?;
+                    return Ok(ValueType::List(Box::new(element_type)));
+                }
+                if let Some(Token {
+                    kind: TokenKind::LParen,
+                    ..
+                }) = self.get_current_token()
+                {
+                    self.consume_token();
+                    return Ok(self.parse_tuple_type().unwrap_or(ValueType::Void));
                 }
             }
             _ => {}
         };
-        ValueType::Void
+        Ok(ValueType::Void)
     }
 }
@@ -1,36 +1,34 @@
 use crate::environment::ValueType;
+use crate::parsers::parse_error::ParseError;
 use crate::parsers::Parser;
 use crate::token::TokenKind;
 
 impl Parser {
-    pub fn string_to_value_type(&mut self, type_name: String) -> ValueType {
+    pub fn string_to_value_type(&mut self, type_name: String) -> Result<ValueType, ParseError> {
         let scope = self.get_current_scope();
         if let Some(struct_value) = self.get_struct(scope, type_name.clone()) {
-            return struct_value;
+            return Ok(struct_value);
         }
 
         match type_name.as_str() {
-            "number" => ValueType::Number,
-            "string" => ValueType::String,
-            "bool" => ValueType::Bool,
-            "void" => ValueType::Void,
+            "number" => Ok(ValueType::Number),
+            "string" => Ok(ValueType::String),
+            "bool" => Ok(ValueType::Bool),
+            "void" => Ok(ValueType::Void),
+            "lambda" => Ok(ValueType::Lambda),
             "List" => {
-                self.extract_token(TokenKind::Lt);
-                let element_type = match self.get_current_token() {
-                    Some(token) => {
-                        if let TokenKind::Identifier(type_name) = &token.kind {
-                            self.consume_token();
-                            self.string_to_value_type(type_name.clone())
-                        } else {
-                            panic!("expected type name");
-                        }
+                self.extract_token(TokenKind::Lt)?;
+                let element_type = match self.get_current_token().map(|token| token.kind) {
+                    Some(TokenKind::Identifier(type_name)) => {
+                        self.consume_token();
+                        self.string_to_value_type(type_name)?
                     }
-                    None => panic!("expected type name"),
+                    _ => return Err(self.error_here("expected type name")),
                 };
-                self.extract_token(TokenKind::Gt);
-                ValueType::List(Box::new(element_type))
+                self.extract_token(TokenKind::Gt)?;
+                Ok(ValueType::List(Box::new(element_type)))
             }
-            _ => panic!("undefined type: {:?}", type_name),
+            _ => Err(self.type_error_here(&format!("undefined type: {:?}", type_name))),
         }
     }
 }
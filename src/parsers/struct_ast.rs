@@ -1,6 +1,7 @@
 use crate::ast::ASTNode;
 use crate::environment::ValueType;
-use crate::parsers::parse_error::ParseError;
+use crate::parsers::parse_error::{ParseError, ParseErrorKind};
+use crate::parsers::symbol::SymbolKind;
 use crate::parsers::Parser;
 use crate::token::{Token, TokenKind};
 use std::collections::HashMap;
@@ -8,19 +9,21 @@ use std::collections::HashMap;
 impl Parser {
     pub fn parse_struct(&mut self) -> Result<ASTNode, ParseError> {
         self.consume_token();
-        let name = match self.get_current_token() {
+        let (name, name_position) = match self.get_current_token() {
             Some(Token {
                 kind: TokenKind::Identifier(name),
-                ..
-            }) => name,
-            _ => panic!("unexpected token"),
+                line,
+                column,
+            }) => (name, (line, column)),
+            _ => return Err(self.error_here("Expected struct name")),
         };
-        self.enter_struct(name.clone());
-        if name[0..1] != name[0..1].to_uppercase() {
-            panic!("struct name must start with a capital letter");
+        if name.chars().next().is_some_and(char::is_lowercase) {
+            return Err(self.error_here("struct name must start with a capital letter"));
         }
+        self.enter_struct(name.clone());
         self.consume_token();
-        self.extract_token(TokenKind::LBrace);
+        self.record_block_start();
+        self.extract_token(TokenKind::LBrace)?;
         let mut fields = HashMap::new();
         let mut field_is_public = false;
         while let Some(token) = self.get_current_token() {
@@ -38,6 +41,7 @@ impl Parser {
                 continue;
             }
             if token.kind == TokenKind::Pub {
+                self.record_item_start();
                 field_is_public = true;
                 self.consume_token();
                 continue;
@@ -48,39 +52,76 @@ impl Parser {
                 ..
             } = token
             {
+                if !field_is_public {
+                    self.record_item_start();
+                }
                 self.consume_token();
-                self.extract_token(TokenKind::Colon);
+                self.extract_token(TokenKind::Colon)?;
                 let value_type = match self.get_current_token() {
                     Some(Token {
                         kind: TokenKind::Identifier(type_name),
                         ..
-                    }) => self.string_to_value_type(type_name),
-                    _ => panic!("undefined type"),
+                    }) => self.string_to_value_type(type_name)?,
+                    _ => return Err(self.error_here("expected type name")),
                 };
                 let (line, column) = self.get_line_column();
+                self.consume_token();
+                // `x: number = 0` のようにデフォルト値が指定されている場合
+                let default_value = match self.get_current_token() {
+                    Some(Token {
+                        kind: TokenKind::Equal,
+                        ..
+                    }) => {
+                        self.consume_token();
+                        let default_value = self.parse_expression(0)?;
+                        let default_type = self.infer_type(&default_value);
+                        if default_type.is_err() || default_type.unwrap() != value_type {
+                            let (line, column) = self.get_line_column();
+                            return Err(ParseError {
+                                message: format!(
+                                    "type mismatch in default value of field: {:?}",
+                                    name
+                                ),
+                                line,
+                                column,
+                                kind: ParseErrorKind::Type,
+                            });
+                        }
+                        Some(Box::new(default_value))
+                    }
+                    _ => None,
+                };
                 fields.insert(
                     name,
                     ASTNode::StructField {
                         value_type,
                         is_public: field_is_public,
+                        default_value,
                         line,
                         column,
                     },
                 );
-                self.consume_token();
                 field_is_public = false;
                 continue;
             }
         }
         let (line, column) = self.get_line_column();
         let result = ASTNode::Struct {
-            name,
+            name: name.clone(),
             fields,
             line,
             column,
         };
         let scope = self.get_current_scope().clone();
-        self.register_struct(scope, result.clone());
+        self.register_struct(scope.clone(), result.clone());
+        let struct_type = self.get_struct(scope.clone(), name.clone());
+        self.record_symbol(
+            &scope,
+            &name,
+            SymbolKind::Struct,
+            struct_type,
+            name_position,
+        );
         self.leave_struct();
         Ok(result)
     }
@@ -92,18 +133,18 @@ impl Parser {
                 kind: TokenKind::Identifier(name),
                 ..
             }) => name,
-            _ => panic!("unexpected token"),
+            _ => return Err(self.error_here("Expected field name")),
         };
         self.consume_token();
         let scope = self.get_current_scope().clone();
         if name == "self" {
-            if self.current_struct.is_none() {
-                panic!("undefined struct for self");
-            }
-            let current_struct = self.current_struct.clone().unwrap();
             let struct_type = self
-                .get_struct(scope.clone(), current_struct.to_string())
-                .expect("undefined struct for self");
+                .current_struct
+                .clone()
+                .and_then(|current_struct| self.get_struct(scope.clone(), current_struct));
+            let Some(struct_type) = struct_type else {
+                return Err(self.error_here("`self` can only be used inside impl"));
+            };
 
             let (line, column) = self.get_line_column();
             return Ok(ASTNode::StructFieldAccess {
@@ -141,7 +182,14 @@ impl Parser {
                 line,
                 column,
             }),
-            _ => panic!("undefined struct: {:?}", name),
+            _ => Err(ParseError::new(
+                format!("undefined struct: {:?}", name).as_str(),
+                &Token {
+                    kind: TokenKind::Identifier(name),
+                    line,
+                    column,
+                },
+            )),
         }
     }
 
@@ -153,22 +201,21 @@ impl Parser {
                 kind: TokenKind::Identifier(name),
                 ..
             }) => name,
-            _ => panic!("unexpected token"),
+            _ => return Err(self.error_here("Expected struct name after impl")),
         };
 
         self.enter_struct(struct_name.clone());
 
         let base_struct = self.get_struct(scope.clone(), struct_name.to_string());
         if base_struct.is_none() {
-            return Err(ParseError::new(
-                format!("undefined struct: {:?}", struct_name).as_str(),
-                &self.get_current_token().unwrap(),
-            ));
+            return Err(self.error_here(format!("undefined struct: {:?}", struct_name).as_str()));
         }
         self.current_struct = Some(struct_name.clone());
         self.consume_token();
-        self.extract_token(TokenKind::LBrace);
+        self.record_block_start();
+        self.extract_token(TokenKind::LBrace)?;
         let mut methods = Vec::new();
+        let mut constants = Vec::new();
         while let Some(token) = self.get_current_token() {
             if token.kind == TokenKind::RBrace {
                 self.consume_token();
@@ -183,11 +230,29 @@ impl Parser {
                 self.consume_token();
                 continue;
             }
+            if matches!(token.kind, TokenKind::Function | TokenKind::Immutable) {
+                self.record_item_start();
+            }
             if token.kind == TokenKind::Function {
                 let method = self.parse_method()?;
                 methods.push(method);
                 continue;
             }
+            if token.kind == TokenKind::Immutable {
+                let constant = self.parse_associated_constant(&struct_name)?;
+                constants.push(constant);
+                continue;
+            }
+            if token.kind == TokenKind::Mutable {
+                return Err(ParseError::new(
+                    "associated constants must be immutable",
+                    &token,
+                ));
+            }
+            return Err(ParseError::new(
+                format!("unexpected token in impl: {:?}", token.kind).as_str(),
+                &token,
+            ));
         }
         self.current_struct = None;
         self.leave_struct();
@@ -195,10 +260,117 @@ impl Parser {
         Ok(ASTNode::Impl {
             base_struct: Box::new(base_struct.unwrap()),
             methods,
+            constants,
             line,
             column,
         })
     }
+
+    // `val ORIGIN = Point{x: 0, y: 0}` を `Point::ORIGIN` という名前の定数として登録する
+    fn parse_associated_constant(&mut self, struct_name: &String) -> Result<ASTNode, ParseError> {
+        let scope = self.get_current_scope();
+        match self.parse_assign()? {
+            ASTNode::Assign {
+                name,
+                value,
+                variable_type,
+                value_type,
+                is_new,
+                line,
+                column,
+            } => {
+                self.variables.remove(&(scope.clone(), name.clone()));
+                let qualified_name = format!("{}::{}", struct_name, name);
+                self.register_variables(scope, &qualified_name, &value_type, &variable_type);
+                Ok(ASTNode::Assign {
+                    name: qualified_name,
+                    value,
+                    variable_type,
+                    value_type,
+                    is_new,
+                    line,
+                    column,
+                })
+            }
+            _ => {
+                let (line, column) = self.get_line_column();
+                Err(ParseError {
+                    message: "invalid associated constant".to_string(),
+                    line,
+                    column,
+                    kind: ParseErrorKind::Syntax,
+                })
+            }
+        }
+    }
+
+    // `Point::new(1, 2)` (静的メソッド) と `Point::ORIGIN` (関連定数)
+    pub fn parse_static_access(&mut self, struct_name: String) -> Result<ASTNode, ParseError> {
+        let scope = self.get_current_scope();
+        let double_colon = self.extract_token(TokenKind::DoubleColon)?;
+        if self.get_struct(scope.clone(), struct_name.clone()).is_none() {
+            return Err(ParseError::new(
+                format!("undefined struct: {:?}", struct_name).as_str(),
+                &double_colon,
+            ));
+        }
+        let member_token = self.consume_token();
+        let member_name = match member_token {
+            Some(Token {
+                kind: TokenKind::Identifier(ref name),
+                ..
+            }) => name.clone(),
+            _ => {
+                return Err(ParseError::new(
+                    "expected associated item name after '::'",
+                    &double_colon,
+                ));
+            }
+        };
+        let qualified_name = format!("{}::{}", struct_name, member_name);
+        if let Some(Token {
+            kind: TokenKind::LParen,
+            ..
+        }) = self.get_current_token()
+        {
+            if self.get_function(scope, qualified_name.clone()).is_none() {
+                return Err(ParseError::new(
+                    format!("undefined static method: {:?}", qualified_name).as_str(),
+                    &member_token.unwrap(),
+                ));
+            }
+            self.consume_token();
+            let arguments = self.parse_function_call_arguments_paren()?;
+            return self.parse_function_call_front(qualified_name, arguments);
+        }
+        match self.find_variables(scope, qualified_name.clone()) {
+            // Point::ORIGIN.x は定数のフィールドやメソッドへのアクセス
+            Some(_)
+                if matches!(
+                    self.get_current_token(),
+                    Some(Token {
+                        kind: TokenKind::Dot,
+                        ..
+                    })
+                ) =>
+            {
+                self.create_struct_field_access(qualified_name)
+            }
+            Some((value_type, _)) => {
+                let member_token = member_token.unwrap();
+                Ok(ASTNode::Variable {
+                    name: qualified_name,
+                    value_type: Some(value_type),
+                    line: member_token.line,
+                    column: member_token.column,
+                })
+            }
+            None => Err(ParseError::new(
+                format!("undefined associated constant: {:?}", qualified_name).as_str(),
+                &member_token.unwrap(),
+            )),
+        }
+    }
 }
 
 #[cfg(test)]
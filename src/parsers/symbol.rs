@@ -0,0 +1,60 @@
+use crate::environment::ValueType;
+use crate::parsers::Parser;
+
+/// パーサーが読んだ名前。エディタや lint のために位置と型を残す
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    // 読んだときのスコープ。関数名、"lambda"、"global" など
+    pub scope: String,
+    pub value_type: Option<ValueType>,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    // val、関数の引数、for やラムダの変数の宣言
+    Variable,
+    Function,
+    // impl の中の関数。scope は構造体の名前
+    Method,
+    Struct,
+    // import で取り込んだ名前。型は読み込むまで分からない
+    Import,
+    // 宣言済みの名前を使った場所
+    Reference,
+}
+
+impl Parser {
+    /// 名前の記録を始める。REPL では文ごとにパーサーを複製するので、必要なときだけ記録する
+    pub fn record_symbols(&mut self) {
+        self.symbols = Some(vec![]);
+    }
+
+    /// 記録した名前を読んだ順に返す
+    pub fn symbols(&self) -> &[Symbol] {
+        self.symbols.as_deref().unwrap_or(&[])
+    }
+
+    pub(crate) fn record_symbol(
+        &mut self,
+        scope: &str,
+        name: &str,
+        kind: SymbolKind,
+        value_type: Option<ValueType>,
+        (line, column): (usize, usize),
+    ) {
+        if let Some(symbols) = &mut self.symbols {
+            symbols.push(Symbol {
+                name: name.to_string(),
+                kind,
+                scope: scope.to_string(),
+                value_type,
+                line,
+                column,
+            });
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::ast::ASTNode;
+use crate::environment::ValueType;
+use crate::parsers::parse_error::ParseError;
+use crate::parsers::Parser;
+use crate::token::Token;
+
+impl Parser {
+    // 後置の `?` 演算子
+    pub fn parse_try(&mut self, expr: ASTNode, question: Token) -> Result<ASTNode, ParseError> {
+        self.consume_token();
+        // `?` は None や Fail をそのまま関数から返すので、関数の中でしか使えない
+        let Some(return_type) = self.return_types.last().cloned() else {
+            return Err(ParseError::new(
+                "`?` can only be used inside a function",
+                &question,
+            ));
+        };
+        match self.infer_type(&expr) {
+            Ok(ValueType::OptionType(_)) => {
+                if !matches!(return_type, ValueType::OptionType(_) | ValueType::Any) {
+                    return Err(ParseError::type_error(
+                        format!(
+                            "`?` on an Option requires the function to return Option, but it returns {}",
+                            return_type
+                        )
+                        .as_str(),
+                        &question,
+                    ));
+                }
+            }
+            Ok(ValueType::ResultType { .. }) => {
+                if !matches!(return_type, ValueType::ResultType { .. } | ValueType::Any) {
+                    return Err(ParseError::type_error(
+                        format!(
+                            "`?` on a Result requires the function to return Result, but it returns {}",
+                            return_type
+                        )
+                        .as_str(),
+                        &question,
+                    ));
+                }
+            }
+            Ok(ValueType::Any) => {
+                if !matches!(
+                    return_type,
+                    ValueType::OptionType(_) | ValueType::ResultType { .. } | ValueType::Any
+                ) {
+                    return Err(ParseError::type_error(
+                        format!(
+                            "`?` requires the function to return Result or Option, but it returns {}",
+                            return_type
+                        )
+                        .as_str(),
+                        &question,
+                    ));
+                }
+            }
+            Ok(value_type) => {
+                return Err(ParseError::new(
+                    format!(
+                        "`?` can only be applied to Result or Option, got {:?}",
+                        value_type
+                    )
+                    .as_str(),
+                    &question,
+                ))
+            }
+            Err(e) => return Err(ParseError::new(e.as_str(), &question)),
+        }
+        Ok(ASTNode::Try {
+            expr: Box::new(expr),
+            line: question.line,
+            column: question.column,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::register_builtins;
+    use crate::environment::Env;
+    use crate::tokenizer::tokenize;
+
+    #[test]
+    fn test_try_type_error() {
+        let input = r#"
+        val x = 1
+        x?
+        "#
+        .to_string();
+        let tokens = tokenize(&input);
+        let builtin = register_builtins(&mut Env::new());
+        let mut parser = Parser::new(tokens, builtin);
+        assert!(parser.parse_lines().is_err());
+    }
+
+    fn parse_error(input: &str) -> Option<ParseError> {
+        let tokens = tokenize(&input.to_string());
+        let builtin = register_builtins(&mut Env::new());
+        Parser::new(tokens, builtin).parse_lines().err()
+    }
+
+    #[test]
+    fn test_try_checks_enclosing_return_type() {
+        let error = parse_error("val r: Result<number, string> = Fail(\"e\")\nr?").unwrap();
+        assert_eq!(error.message, "`?` can only be used inside a function");
+        assert_eq!((error.line, error.column), (2, 2));
+
+        let error = parse_error(
+            r#"
+        fun first(): Option<number> {
+            return Some(1)
+        }
+        fun f(): Result<number, string> {
+            val x = first()?
+            return Suc(x)
+        }
+        "#,
+        )
+        .unwrap();
+        assert_eq!(error.kind, crate::parsers::parse_error::ParseErrorKind::Type);
+        assert!(error.message.contains("requires the function to return Option"));
+
+        let error = parse_error(
+            r#"
+        fun check(): Result<number, string> {
+            return Suc(1)
+        }
+        fun f(): number {
+            return check()?
+        }
+        "#,
+        )
+        .unwrap();
+        assert!(error.message.contains("requires the function to return Result"));
+
+        assert!(parse_error(
+            r#"
+        fun first(): Option<number> {
+            return Some(1)
+        }
+        fun f(): Option<number> {
+            val g = \|x: number| => Some(x)?
+            return Some(first()? + 1)
+        }
+        "#,
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,342 @@
+use crate::ast::ASTNode;
+use crate::environment::{EnvVariableType, ValueType};
+use crate::parsers::parse_error::{ParseError, ParseErrorKind};
+use crate::parsers::Parser;
+use crate::token::{Token, TokenKind};
+use std::collections::HashMap;
+
+impl Parser {
+    // `(a, b, ...)` の2要素目以降をパースする。最初の要素はパース済み
+    pub fn parse_tuple(
+        &mut self,
+        first: ASTNode,
+        line: usize,
+        column: usize,
+    ) -> Result<ASTNode, ParseError> {
+        let mut elements = vec![first];
+        while let Some(Token {
+            kind: TokenKind::Comma,
+            ..
+        }) = self.get_current_token()
+        {
+            self.consume_token();
+            if let Some(Token {
+                kind: TokenKind::RParen,
+                ..
+            }) = self.get_current_token()
+            {
+                break;
+            }
+            elements.push(self.parse_expression(0)?);
+        }
+        match self.get_current_token() {
+            Some(Token {
+                kind: TokenKind::RParen,
+                ..
+            }) => {
+                self.consume_token();
+                Ok(ASTNode::Tuple {
+                    elements,
+                    line,
+                    column,
+                })
+            }
+            _ => {
+                let (line, column) = self.get_line_column();
+                Err(ParseError {
+                    message: "Expected closing parenthesis".to_string(),
+                    line,
+                    column,
+                    kind: ParseErrorKind::Syntax,
+                })
+            }
+        }
+    }
+
+    // `(number, string)` の `(` 以降をパースする
+    pub fn parse_tuple_type(&mut self) -> Result<ValueType, ParseError> {
+        let mut element_types = vec![];
+        while let Some(token) = self.get_current_token() {
+            match token.kind {
+                TokenKind::RParen => {
+                    self.consume_token();
+                    return Ok(ValueType::Tuple(element_types));
+                }
+                TokenKind::Comma => {
+                    self.consume_token();
+                }
+                _ => element_types.push(self.get_result_value_type()?),
+            }
+        }
+        let (line, column) = self.get_line_column();
+        Err(ParseError {
+            message: "Expected closing parenthesis of tuple type".to_string(),
+            line,
+            column,
+            kind: ParseErrorKind::Syntax,
+        })
+    }
+
+    // 分割代入のパターンが始まるかどうか: `(a, b)` または `Point { x, y }`
+    pub fn is_pattern_start(&mut self) -> bool {
+        match self.get_current_token() {
+            Some(Token {
+                kind: TokenKind::LParen,
+                ..
+            }) => true,
+            Some(Token {
+                kind: TokenKind::Identifier(name),
+                ..
+            }) => {
+                self.pos += 1;
+                let next_token = self.get_current_token();
+                self.pos -= 1;
+                matches!(
+                    next_token,
+                    Some(Token {
+                        kind: TokenKind::LBrace,
+                        ..
+                    })
+                ) && self.get_struct(self.get_current_scope(), name).is_some()
+            }
+            _ => false,
+        }
+    }
+
+    pub fn parse_pattern(&mut self) -> Result<ASTNode, ParseError> {
+        let token = match self.consume_token() {
+            Some(token) => token,
+            None => {
+                let (line, column) = self.get_line_column();
+                return Err(ParseError {
+                    message: "Expected pattern".to_string(),
+                    line,
+                    column,
+                    kind: ParseErrorKind::Syntax,
+                });
+            }
+        };
+        match token.kind.clone() {
+            TokenKind::LParen => {
+                let mut elements = vec![];
+                while let Some(current_token) = self.get_current_token() {
+                    match current_token.kind {
+                        TokenKind::RParen => {
+                            self.consume_token();
+                            return Ok(ASTNode::Tuple {
+                                elements,
+                                line: token.line,
+                                column: token.column,
+                            });
+                        }
+                        TokenKind::Comma => {
+                            self.consume_token();
+                        }
+                        _ => elements.push(self.parse_pattern()?),
+                    }
+                }
+                Err(ParseError::new("Expected closing parenthesis", &token))
+            }
+            TokenKind::Identifier(name) => {
+                if !matches!(
+                    self.get_current_token(),
+                    Some(Token {
+                        kind: TokenKind::LBrace,
+                        ..
+                    })
+                ) {
+                    return Ok(ASTNode::Variable {
+                        name,
+                        value_type: None,
+                        line: token.line,
+                        column: token.column,
+                    });
+                }
+                // `Point { x, y: py }`
+                self.consume_token();
+                let mut fields = HashMap::new();
+                while let Some(current_token) = self.get_current_token() {
+                    match current_token.kind {
+                        TokenKind::RBrace => {
+                            self.consume_token();
+                            return Ok(ASTNode::StructInstance {
+                                name,
+                                fields,
+                                line: token.line,
+                                column: token.column,
+                            });
+                        }
+                        TokenKind::Comma => {
+                            self.consume_token();
+                        }
+                        TokenKind::Identifier(field_name) => {
+                            self.consume_token();
+                            let field_pattern = match self.get_current_token() {
+                                Some(Token {
+                                    kind: TokenKind::Colon,
+                                    ..
+                                }) => {
+                                    self.consume_token();
+                                    self.parse_pattern()?
+                                }
+                                _ => ASTNode::Variable {
+                                    name: field_name.clone(),
+                                    value_type: None,
+                                    line: current_token.line,
+                                    column: current_token.column,
+                                },
+                            };
+                            fields.insert(field_name, field_pattern);
+                        }
+                        _ => {
+                            return Err(ParseError::new(
+                                "unexpected token in struct pattern",
+                                &current_token,
+                            ))
+                        }
+                    }
+                }
+                Err(ParseError::new("Expected closing brace", &token))
+            }
+            _ => Err(ParseError::new(
+                format!("unexpected token in pattern: {:?}", token.kind).as_str(),
+                &token,
+            )),
+        }
+    }
+
+    // パターン内の変数をパーサーに登録する
+    pub fn register_pattern_variables(
+        &mut self,
+        pattern: &ASTNode,
+        value_type: &ValueType,
+        variable_type: &EnvVariableType,
+    ) {
+        let scope = self.get_current_scope();
+        match pattern {
+            ASTNode::Variable { name, .. } if name != "_" => {
+                self.register_variables(scope, name, value_type, variable_type);
+            }
+            ASTNode::Tuple { elements, .. } => {
+                for (i, element) in elements.iter().enumerate() {
+                    let element_type = match value_type {
+                        ValueType::Tuple(element_types) => {
+                            element_types.get(i).cloned().unwrap_or(ValueType::Any)
+                        }
+                        ValueType::List(element_type) => *element_type.clone(),
+                        _ => ValueType::Any,
+                    };
+                    self.register_pattern_variables(element, &element_type, variable_type);
+                }
+            }
+            ASTNode::StructInstance { name, fields, .. } => {
+                let field_types = match self.get_struct(scope, name.clone()) {
+                    Some(ValueType::Struct { fields, .. }) => fields,
+                    _ => HashMap::new(),
+                };
+                for (field_name, field_pattern) in fields {
+                    let field_type = match field_types.get(field_name) {
+                        Some(ValueType::StructField { value_type, .. }) => *value_type.clone(),
+                        Some(field_type) => field_type.clone(),
+                        None => ValueType::Any,
+                    };
+                    self.register_pattern_variables(field_pattern, &field_type, variable_type);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // 関数・ラムダの引数のパターン: `(a, b): (number, number)`
+    pub fn parse_pattern_argument(&mut self) -> Result<ASTNode, ParseError> {
+        let pattern = self.parse_pattern()?;
+        let value_type = match self.get_current_token() {
+            Some(Token {
+                kind: TokenKind::Colon,
+                ..
+            }) => {
+                self.consume_token();
+                self.get_result_value_type()?
+            }
+            _ => ValueType::Any,
+        };
+        self.register_pattern_variables(&pattern, &value_type, &EnvVariableType::Immutable);
+        Ok(pattern)
+    }
+
+    // `val (q, r) = divmod(7, 2)` / `val Point { x, y } = p`
+    pub fn parse_destructure(
+        &mut self,
+        mutable_or_immutable: Token,
+    ) -> Result<ASTNode, ParseError> {
+        let pattern = self.parse_pattern()?;
+        // `}` の後は行が分割されるので次の行に進む
+        if let Some(Token {
+            kind: TokenKind::Eof,
+            ..
+        }) = self.get_current_token()
+        {
+            self.pos = 0;
+            self.line += 1;
+        }
+        let equal = self.consume_token();
+        if !matches!(
+            equal,
+            Some(Token {
+                kind: TokenKind::Equal,
+                ..
+            })
+        ) {
+            return Err(ParseError::new(
+                "Expected '=' after destructuring pattern",
+                &mutable_or_immutable,
+            ));
+        }
+        let value = self.parse_expression(0)?;
+        let value_type = self.infer_type(&value).unwrap_or(ValueType::Any);
+        let variable_type = if mutable_or_immutable.kind == TokenKind::Mutable {
+            EnvVariableType::Mutable
+        } else {
+            EnvVariableType::Immutable
+        };
+        self.register_pattern_variables(&pattern, &value_type, &variable_type);
+        Ok(ASTNode::Destructure {
+            pattern: Box::new(pattern),
+            value: Box::new(value),
+            variable_type,
+            line: mutable_or_immutable.line,
+            column: mutable_or_immutable.column,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::register_builtins;
+    use crate::environment::Env;
+    use crate::tokenizer::tokenize;
+
+    #[test]
+    fn test_parse_tuple_and_destructure() {
+        let input = r#"
+val t = (1, "a")
+val (n, s) = t
+"#;
+        let tokens = tokenize(&input.to_string());
+        let builtin = register_builtins(&mut Env::new());
+        let mut parser = Parser::new(tokens, builtin);
+        let ast = parser.parse_lines().unwrap();
+        match &ast[1] {
+            ASTNode::Destructure { pattern, .. } => match pattern.as_ref() {
+                ASTNode::Tuple { elements, .. } => assert_eq!(elements.len(), 2),
+                _ => panic!("unexpected pattern: {:?}", pattern),
+            },
+            _ => panic!("unexpected ast: {:?}", ast[1]),
+        }
+        assert_eq!(
+            parser.find_variables("global".into(), "s".into()),
+            Some((ValueType::String, EnvVariableType::Immutable))
+        );
+    }
+}
@@ -1,40 +1,87 @@
+use crate::builtin::{builtin_function, env_var, io_result_type, script_args, write_output};
 use crate::environment::ValueType;
+use crate::evals::assertion::ASSERTIONS;
 use crate::rc_env::RcEnv;
 use crate::rc_value::RcValue;
 use fraction::{Fraction, ToPrimitive};
 use std::collections::HashMap;
 use std::rc::Rc;
 
+type RcBuiltin = (&'static str, fn(Vec<RcValue>) -> RcValue, ValueType);
+
+fn rc_builtin_table() -> Vec<RcBuiltin> {
+    vec![
+        ("print", rc_print, ValueType::Void),
+        ("len", rc_len, ValueType::Number),
+        (
+            "range",
+            rc_range,
+            ValueType::List(Box::new(ValueType::Number)),
+        ),
+        (
+            "args",
+            |_| RcValue::from_value(&script_args()),
+            ValueType::List(Box::new(ValueType::String)),
+        ),
+        (
+            "env_var",
+            rc_env_var,
+            ValueType::OptionType(Box::new(ValueType::String)),
+        ),
+        (
+            "read_file",
+            |args| rc_call("read_file", args),
+            io_result_type(ValueType::String),
+        ),
+        (
+            "write_file",
+            |args| rc_call("write_file", args),
+            io_result_type(ValueType::Void),
+        ),
+        ("now", |args| rc_call("now", args), ValueType::Number),
+        ("random", |args| rc_call("random", args), ValueType::Number),
+    ]
+}
+
+/// env に許された機能の builtin を直接 RcEnv に登録する
 pub fn register_rc_builtins(env: &mut RcEnv) -> HashMap<(String, String), ValueType> {
+    let capabilities = env.capabilities();
     let mut builtins = HashMap::new();
+    for (name, function, value_type) in rc_builtin_table() {
+        if capabilities.allows(name) {
+            env.register_rc_builtin(name.to_string(), function);
+            builtins.insert(("global".into(), name.to_string()), value_type);
+        }
+    }
 
-    // print関数を登録
-    env.register_rc_builtin("print".to_string(), rc_print);
-    builtins.insert(("global".into(), "print".to_string()), ValueType::Void);
+    // アサーションと exit は rc_function_call が直接処理する
+    for name in ASSERTIONS.iter().chain(&["exit"]) {
+        builtins.insert(("global".into(), name.to_string()), ValueType::Void);
+    }
 
-    // len関数を登録
-    env.register_rc_builtin("len".to_string(), rc_len);
-    builtins.insert(("global".into(), "len".to_string()), ValueType::Number);
+    builtins
+}
 
-    // range関数を登録
-    env.register_rc_builtin("range".to_string(), rc_range);
-    builtins.insert(
-        ("global".into(), "range".to_string()),
-        ValueType::List(Box::new(ValueType::Number)),
-    );
+// RcValue 用の実装がない builtin は、値を変換して通常の実装を呼ぶ
+fn rc_call(name: &str, args: Vec<RcValue>) -> RcValue {
+    let function = builtin_function(name).expect("builtin is registered");
+    RcValue::from_value(&function(args.iter().map(|arg| arg.to_value()).collect()))
+}
 
-    builtins
+fn rc_env_var(args: Vec<RcValue>) -> RcValue {
+    match args.as_slice() {
+        [RcValue::String(name)] => RcValue::from_value(&env_var(name)),
+        _ => panic!("env_var function takes a variable name"),
+    }
 }
 
 fn rc_print(args: Vec<RcValue>) -> RcValue {
-    // 引数を表示
-    for (i, arg) in args.iter().enumerate() {
-        if i > 0 {
-            print!(" ");
-        }
-        print!("{}", arg);
-    }
-    println!();
+    let line = args
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    write_output(&line);
     RcValue::Void
 }
 
@@ -1,12 +1,15 @@
 use crate::ast::ASTNode;
-use crate::builtin::register_builtins;
+use crate::builtin::{register_builtins, Capabilities};
 use crate::environment::{
-    Env, EnvVariableType, ExportedSymbolType, FunctionInfo, ValueType, VariableKeyInfo,
+    Env, EnvVariableType, EnvVariableValueInfo, ExportedSymbolType, FunctionInfo, ValueType,
+    VariableCell, VariableKeyInfo,
 };
 use crate::evals::evals;
 use crate::evals::runtime_error::RuntimeError;
+use crate::module_graph::ModuleGraph;
 use crate::parsers::Parser;
 use crate::rc_value::RcValue;
+use crate::sandbox::{Limits, Sandbox};
 use crate::tokenizer::tokenize;
 use crate::value::Value;
 use std::cell::RefCell;
@@ -31,6 +34,14 @@ struct RcEnvInner {
     rc_builtins: HashMap<String, RcFunctionInfo>,
     modules: HashMap<String, RcEnv>,
     exported_symbols: HashMap<String, ExportedSymbolType>,
+    // 通常のEnvとやり取りした変数のセルとその時点の値。
+    // クロージャが捕捉していることがあるので使い回す
+    cells: HashMap<VariableKeyInfo, (VariableCell, RcValue)>,
+    file_path: Option<PathBuf>,
+    module_graph: ModuleGraph,
+    // 実行制限。通常の Env にフォールバックしたときも同じものを使う
+    sandbox: Rc<Sandbox>,
+    capabilities: Capabilities,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -61,31 +72,38 @@ impl RcEnv {
                 rc_builtins: HashMap::new(),
                 modules: HashMap::new(),
                 exported_symbols: HashMap::new(),
+                cells: HashMap::new(),
+                file_path: None,
+                module_graph: ModuleGraph::default(),
+                sandbox: Rc::new(Sandbox::default()),
+                capabilities: Capabilities::all(),
             })),
         }
     }
 
     /// 通常のEnvからRcEnvに変換する（最適化版）
     pub fn from_env(env: &Env) -> Self {
-        let mut rc_env = RcEnv::new();
-
-        // 変数マップをコピー
-        for (key, value) in env.get_variable_map() {
-            let rc_value = RcValue::from_value(&value.value);
-            rc_env
-                .set(
-                    key.name.clone(),
-                    rc_value,
-                    value.variable_type.clone(),
-                    value.value_type.clone(),
-                    true, // is_new
-                )
-                .unwrap();
-        }
+        let rc_env = RcEnv::new();
 
         // 関数をコピー
         {
             let mut inner = rc_env.inner.borrow_mut();
+
+            // 変数マップをスコープごとコピーし、セルを覚えておく
+            for (key, cell) in env.get_variable_map() {
+                let value = cell.borrow();
+                let rc_value = RcValue::from_value(&value.value);
+                inner.variable_map.insert(
+                    key.clone(),
+                    RcEnvVariableValueInfo {
+                        value: rc_value.clone(),
+                        variable_type: value.variable_type.clone(),
+                        value_type: value.value_type.clone(),
+                    },
+                );
+                inner.cells.insert(key.clone(), (cell.clone(), rc_value));
+            }
+
             for (name, function) in env.get_functions() {
                 inner.functions.insert(name.clone(), function.clone());
             }
@@ -104,6 +122,10 @@ impl RcEnv {
 
             // スコープスタックをコピー
             inner.scope_stack = env.get_scope_stack().clone();
+            inner.file_path = env.get_file_path().cloned();
+            inner.module_graph = env.get_module_graph().clone();
+            inner.sandbox = env.sandbox().clone();
+            inner.capabilities = env.capabilities();
 
             // エクスポートされたシンボルをコピー
             for (name, symbol_type) in env.get_exported_symbols() {
@@ -128,17 +150,23 @@ impl RcEnv {
         let mut env = Env::new();
         let inner = self.inner.borrow();
 
-        // 変数マップをコピー
+        // 変数マップをコピー。以前渡したセルがあれば同じセルを使い、
+        // rc_eval 側で値が変わっていればセルに書き戻す
         for (key, value) in &inner.variable_map {
-            let std_value = value.value.to_value();
-            env.set(
-                key.name.clone(),
-                std_value,
-                value.variable_type.clone(),
-                value.value_type.clone(),
-                true, // is_new
-            )
-            .unwrap();
+            let value_info = EnvVariableValueInfo {
+                value: value.value.to_value(),
+                variable_type: value.variable_type.clone(),
+                value_type: value.value_type.clone(),
+            };
+            let cell = match inner.cells.get(key) {
+                Some((cell, synced)) if *synced == value.value => cell.clone(),
+                Some((cell, _)) => {
+                    *cell.borrow_mut() = value_info;
+                    cell.clone()
+                }
+                None => Rc::new(RefCell::new(value_info)),
+            };
+            env.insert_cell(key.clone(), cell);
         }
 
         // 関数をコピー
@@ -146,11 +174,28 @@ impl RcEnv {
             env.register_function(name.clone(), function.clone());
         }
 
+        // rc_eval で定義された関数も通常のevalから呼び出せるようにする
+        for (name, function) in &inner.rc_functions {
+            env.register_function(
+                name.clone(),
+                FunctionInfo {
+                    arguments: function.arguments.clone(),
+                    return_type: function.return_type.clone(),
+                    body: function.body.clone(),
+                    builtin: None,
+                },
+            );
+        }
+
         // 構造体をコピー
         for (_name, struct_value) in &inner.structs {
             env.register_struct(struct_value.to_value()).unwrap();
         }
 
+        // 通常の eval にフォールバックした式からも、許された builtin を呼べるようにする
+        env.set_capabilities(inner.capabilities);
+        register_builtins(&mut env);
+
         // ビルトイン関数をコピー
         for (name, function) in &inner.builtins {
             if let Some(builtin_fn) = function.builtin {
@@ -160,6 +205,11 @@ impl RcEnv {
 
         // スコープスタックをコピー
         env.set_scope_stack(inner.scope_stack.clone());
+        if let Some(file_path) = &inner.file_path {
+            env.set_file_path(file_path.clone());
+        }
+        env.set_module_graph(inner.module_graph.clone());
+        env.set_sandbox(inner.sandbox.clone());
 
         // エクスポートされたシンボルをコピー
         for (name, _symbol_type) in &inner.exported_symbols {
@@ -174,6 +224,45 @@ impl RcEnv {
         env
     }
 
+    /// 通常のevalで処理した結果の環境を取り込む。
+    /// rc_builtins と rc_functions は Env に存在しないので保持したままにする。
+    pub fn sync_from_env(&mut self, env: &Env) {
+        let synced = RcEnv::from_env(env);
+        let mut synced_inner = synced.inner.borrow_mut();
+        let mut inner = self.inner.borrow_mut();
+        inner.variable_map = std::mem::take(&mut synced_inner.variable_map);
+        inner.scope_stack = std::mem::take(&mut synced_inner.scope_stack);
+        inner.functions = std::mem::take(&mut synced_inner.functions);
+        inner.structs = std::mem::take(&mut synced_inner.structs);
+        inner.builtins = std::mem::take(&mut synced_inner.builtins);
+        inner.modules = std::mem::take(&mut synced_inner.modules);
+        inner.exported_symbols = std::mem::take(&mut synced_inner.exported_symbols);
+        inner.cells = std::mem::take(&mut synced_inner.cells);
+        inner.file_path = synced_inner.file_path.take();
+        inner.module_graph = synced_inner.module_graph.clone();
+    }
+
+    pub fn sandbox(&self) -> Rc<Sandbox> {
+        self.inner.borrow().sandbox.clone()
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.inner.borrow_mut().sandbox = Rc::new(Sandbox::new(limits));
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.inner.borrow().capabilities
+    }
+
+    /// register_rc_builtins より前に呼ぶ
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.inner.borrow_mut().capabilities = capabilities;
+    }
+
+    pub fn set_file_path(&mut self, file_path: PathBuf) {
+        self.inner.borrow_mut().file_path = Some(file_path);
+    }
+
     pub fn get(
         &self,
         name: &String,
@@ -333,6 +422,11 @@ impl RcEnv {
         Ok(())
     }
 
+    // REPL で構造体を定義し直すときに、前の定義を消す
+    pub fn remove_struct(&mut self, name: &str) {
+        self.inner.borrow_mut().structs.remove(name);
+    }
+
     pub fn get_struct(&self, name: &String) -> Option<RcValue> {
         let inner = self.inner.borrow();
         inner.structs.get(name).cloned()
@@ -407,6 +501,7 @@ impl RcEnv {
         }
 
         let mut module_env = Env::new();
+        module_env.set_sandbox(self.sandbox());
         let result = evals(ast_nodes.unwrap(), &mut module_env);
         if let Err(e) = result {
             return Err(format!("Error: {:?}", e));
@@ -504,6 +599,11 @@ impl Clone for RcEnv {
             rc_builtins: inner.rc_builtins.clone(),
             modules: inner.modules.clone(),
             exported_symbols: inner.exported_symbols.clone(),
+            cells: inner.cells.clone(),
+            file_path: inner.file_path.clone(),
+            module_graph: inner.module_graph.clone(),
+            sandbox: inner.sandbox.clone(),
+            capabilities: inner.capabilities,
         };
         RcEnv {
             inner: Rc::new(RefCell::new(new_inner)),
@@ -1,6 +1,11 @@
 use crate::ast::ASTNode;
+use crate::builtin::check_builtin_arguments;
 use crate::environment::{EnvVariableType, FunctionInfo, ValueType};
+use crate::evals::assertion::{call_assertion, is_assertion};
+use crate::evals::function_node::main_arguments;
+use crate::evals::lambda_node::call_lambda_value;
 use crate::evals::runtime_error::RuntimeError;
+use crate::exit_status::exit_error;
 use crate::rc_env::RcEnv;
 use crate::rc_value::RcValue;
 use crate::value::Value;
@@ -19,6 +24,22 @@ pub fn rc_evals(nodes: Vec<ASTNode>, env: &mut RcEnv) -> Result<RcValue, Runtime
 }
 
 pub fn rc_eval(node: ASTNode, env: &mut RcEnv) -> Result<RcValue, RuntimeError> {
+    let (line, column) = node.position();
+    let exceeded = |limit| RuntimeError::limit(limit, line, column);
+    let sandbox = env.sandbox();
+    sandbox.step().map_err(exceeded)?;
+    let value = rc_eval_node(node, env)?;
+    match &value {
+        RcValue::List(values) => sandbox.check_len(values.len()),
+        RcValue::Dict(entries) => sandbox.check_len(entries.len()),
+        RcValue::String(s) => sandbox.check_string(s.len()),
+        _ => Ok(()),
+    }
+    .map_err(exceeded)?;
+    Ok(value)
+}
+
+fn rc_eval_node(node: ASTNode, env: &mut RcEnv) -> Result<RcValue, RuntimeError> {
     match node {
         ASTNode::Literal { value, .. } => {
             // リテラル値をRcValueに変換
@@ -50,6 +71,11 @@ pub fn rc_eval(node: ASTNode, env: &mut RcEnv) -> Result<RcValue, RuntimeError>
             // 二項演算
             let left_val = rc_eval(*left, env)?;
             let right_val = rc_eval(*right, env)?;
+            if let (RcValue::String(l), RcValue::String(r)) = (&left_val, &right_val) {
+                env.sandbox()
+                    .check_concat(l, r)
+                    .map_err(|limit| RuntimeError::limit(limit, line, column))?;
+            }
             let op_str = format!("{:?}", op);
             rc_binary_op(left_val, op_str, right_val, line, column)
         }
@@ -105,14 +131,18 @@ pub fn rc_eval(node: ASTNode, env: &mut RcEnv) -> Result<RcValue, RuntimeError>
             arguments,
             line,
             column,
-        } => rc_function_call(name, arguments, line, column, env),
+        } => rc_function_call(name, *arguments, line, column, env),
+        // 分割代入の引数を持つ関数は通常の評価器に任せる
         ASTNode::Function {
             name,
             arguments,
             body,
             return_type,
             ..
-        } => {
+        } if arguments
+            .iter()
+            .all(|argument| matches!(argument, ASTNode::Variable { .. })) =>
+        {
             env.register_rc_function(
                 name,
                 crate::rc_env::RcFunctionInfo {
@@ -226,20 +256,31 @@ pub fn rc_eval(node: ASTNode, env: &mut RcEnv) -> Result<RcValue, RuntimeError>
             // 最終的には、すべてのノードタイプをrc_evalで直接処理するように実装する
             let mut temp_env = env.to_env();
             let result = crate::evals::eval(node, &mut temp_env)?;
-            *env = RcEnv::from_env(&temp_env);
+            env.sync_from_env(&temp_env);
             Ok(RcValue::from_value(&result))
         }
     }
 }
 
+/// `fun main()` が定義されていれば呼び出して戻り値を返す。なければ Void
+#[allow(dead_code)]
+pub fn rc_call_main(env: &mut RcEnv) -> Result<RcValue, RuntimeError> {
+    let name = "main".to_string();
+    let parameters = match env.get_rc_function(&name) {
+        Some(function) => function.arguments.len(),
+        None => return Ok(RcValue::Void),
+    };
+    rc_function_call(name, main_arguments(parameters), 0, 0, env)
+}
+
 fn rc_function_call(
     name: String,
-    arguments: Box<ASTNode>,
+    arguments: ASTNode,
     line: usize,
     column: usize,
     env: &mut RcEnv,
 ) -> Result<RcValue, RuntimeError> {
-    let args_vec = match *arguments {
+    let args_vec = match arguments {
         ASTNode::FunctionCallArgs { args, .. } => args,
         _ => return Err(RuntimeError::new("illegal arguments", line, column)),
     };
@@ -249,8 +290,45 @@ fn rc_function_call(
         arg_values.push(rc_eval(arg, env)?);
     }
 
+    if name == "exit" && env.get_rc_function(&name).is_none() {
+        let args = arg_values.iter().map(|v| v.to_value()).collect::<Vec<_>>();
+        return Err(exit_error(&args, line, column));
+    }
+
+    if is_assertion(&name) && env.get_rc_function(&name).is_none() {
+        // assert_fail のラムダは通常の評価器で呼び出す
+        let mut temp_env = env.to_env();
+        let result = call_assertion(
+            &name,
+            arg_values.iter().map(|v| v.to_value()).collect(),
+            &mut |lambda, lambda_args| {
+                match call_lambda_value(lambda, lambda_args, line, column, &mut temp_env) {
+                    Err(error) if error.stops_execution() => Err(error),
+                    result => Ok(result),
+                }
+            },
+            &|message| RuntimeError::new(&message, line, column),
+        );
+        env.sync_from_env(&temp_env);
+        return result.map(|value| RcValue::from_value(&value));
+    }
+
     if let Some(function_info) = env.get_rc_builtin(&name) {
         if let Some(builtin_fn) = function_info.builtin {
+            check_builtin_arguments(&name, &arg_values, RcValue::value_type)
+                .map_err(|message| RuntimeError::new(&message, line, column))?;
+            if name == "range" {
+                let numbers: Vec<Fraction> = arg_values
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        RcValue::Number(n) => Some(*n),
+                        _ => None,
+                    })
+                    .collect();
+                env.sandbox()
+                    .check_range(&numbers)
+                    .map_err(|limit| RuntimeError::limit(limit, line, column))?;
+            }
             return Ok(builtin_fn(arg_values));
         }
     }
@@ -299,7 +377,18 @@ fn rc_function_call(
         let body = function
             .body
             .ok_or_else(|| RuntimeError::new("function body missing", line, column))?;
-        let result = rc_eval(body, &mut local_env)?;
+        let sandbox = local_env.sandbox();
+        let result = sandbox
+            .call(|| rc_eval(body, &mut local_env))
+            .map_err(|limit| RuntimeError::limit(limit, line, column))?;
+        let result = match result {
+            // `?` による早期リターン
+            Err(RuntimeError {
+                early_return: Some(value),
+                ..
+            }) => RcValue::from_value(&value),
+            result => result?,
+        };
         env.update_global_env(&local_env);
         return match result {
             RcValue::Return(value) => Ok((*value).clone()),
@@ -307,6 +396,20 @@ fn rc_function_call(
         };
     }
 
+    // 変数に入ったラムダは通常の評価器で呼び出す
+    if let Some(lambda @ RcValue::Lambda { .. }) = env.get(&name, None).map(|info| info.value) {
+        let mut temp_env = env.to_env();
+        let result = call_lambda_value(
+            lambda.to_value(),
+            arg_values.iter().map(|v| v.to_value()).collect(),
+            line,
+            column,
+            &mut temp_env,
+        )?;
+        env.sync_from_env(&temp_env);
+        return Ok(RcValue::from_value(&result));
+    }
+
     if let Some(function) = env.get_function(&name) {
         let mut temp_env = env.to_env();
         temp_env.register_function(
@@ -338,7 +441,7 @@ fn rc_function_call(
             },
             &mut temp_env,
         )?;
-        *env = RcEnv::from_env(&temp_env);
+        env.sync_from_env(&temp_env);
         return Ok(RcValue::from_value(&result));
     }
 
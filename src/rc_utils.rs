@@ -15,7 +15,7 @@ pub fn convert_to_rc_value(value: Value) -> Value {
         Value::Dict(dict) => Value::new_dict(dict),
         Value::Struct { name, fields, methods } => Value::new_struct(name, fields, methods),
         Value::StructInstance { name, fields } => Value::new_struct_instance(name, fields),
-        Value::Lambda { arguments, body, env } => Value::new_lambda(arguments, *body, env),
+        Value::Lambda { arguments, body, captures } => Value::new_lambda(arguments, *body, captures),
         Value::Option(opt) => Value::new_option(opt.map(|v| *v)),
         Value::Result(res) => match res {
             Ok(v) => Value::new_result_ok(*v),
@@ -42,10 +42,10 @@ pub fn convert_from_rc_value(value: Value) -> Value {
             name: (*name).clone(),
             fields: (*fields).clone(),
         },
-        Value::Lambda { arguments, body, env } => Value::Lambda {
+        Value::Lambda { arguments, body, captures } => Value::Lambda {
             arguments: (*arguments).clone(),
             body: Box::new((*body).clone()),
-            env: (*env).clone(),
+            captures: (*captures).clone(),
         },
         Value::Option(opt) => Value::Option(opt.map(|v| Box::new((*v).clone()))),
         Value::Result(res) => match res {
@@ -1,6 +1,5 @@
 use crate::ast::ASTNode;
-use crate::environment::Env;
-use crate::environment::{MethodInfo, ValueType};
+use crate::environment::{Captures, MethodInfo, ValueType};
 use crate::value::Value;
 use fraction::Fraction;
 use std::collections::HashMap;
@@ -17,6 +16,7 @@ pub enum RcValue {
     Bool(bool),
     Void,
     List(Rc<Vec<RcValue>>),
+    Tuple(Rc<Vec<RcValue>>),
     Dict(Rc<HashMap<String, RcValue>>),
     Function,
     Return(Rc<RcValue>),
@@ -42,7 +42,7 @@ pub enum RcValue {
     Lambda {
         arguments: Rc<Vec<ASTNode>>,
         body: Rc<ASTNode>,
-        env: Rc<Env>,
+        captures: Captures,
     },
 }
 
@@ -72,6 +72,9 @@ impl RcValue {
                     .collect::<Vec<_>>();
                 RcValue::List(Rc::new(rc_list))
             }
+            Value::Tuple(values) => {
+                RcValue::Tuple(Rc::new(values.iter().map(RcValue::from_value).collect()))
+            }
             Value::Dict(dict) => {
                 // 辞書の要素を一度だけ変換
                 let mut rc_dict = HashMap::new();
@@ -123,11 +126,11 @@ impl RcValue {
             Value::Lambda {
                 arguments,
                 body,
-                env,
+                captures,
             } => RcValue::Lambda {
                 arguments: Rc::new(arguments.clone()),
                 body: Rc::new(body.as_ref().clone()),
-                env: Rc::new(env.clone()),
+                captures: captures.clone(),
             },
         }
     }
@@ -151,6 +154,7 @@ impl RcValue {
                 let value_list = list.iter().map(|v| v.to_value()).collect();
                 Value::List(value_list)
             }
+            RcValue::Tuple(values) => Value::Tuple(values.iter().map(|v| v.to_value()).collect()),
             RcValue::Dict(dict) => {
                 let mut value_dict = HashMap::new();
                 for (k, v) in dict.iter() {
@@ -201,11 +205,11 @@ impl RcValue {
             RcValue::Lambda {
                 arguments,
                 body,
-                env,
+                captures,
             } => Value::Lambda {
                 arguments: arguments.as_ref().clone(),
                 body: Box::new(body.as_ref().clone()),
-                env: env.as_ref().clone(),
+                captures: captures.clone(),
             },
         }
     }
@@ -233,7 +237,7 @@ impl RcValue {
                 } else {
                     ValueType::ResultType {
                         success: Box::new(ValueType::Void),
-                        failure: Box::new(v.as_ref().unwrap().value_type()),
+                        failure: Box::new(v.as_ref().unwrap_err().value_type()),
                     }
                 }
             }
@@ -283,6 +287,9 @@ impl RcValue {
                     ValueType::List(Box::new(value_type))
                 }
             }
+            RcValue::Tuple(values) => {
+                ValueType::Tuple(values.iter().map(|value| value.value_type()).collect())
+            }
             RcValue::Dict(dict) => {
                 let mut value_type = ValueType::Any;
                 for key in dict.keys() {
@@ -331,11 +338,11 @@ impl RcValue {
         }
     }
 
-    pub fn new_lambda(arguments: Vec<ASTNode>, body: ASTNode, env: Env) -> Self {
+    pub fn new_lambda(arguments: Vec<ASTNode>, body: ASTNode, captures: Captures) -> Self {
         RcValue::Lambda {
             arguments: Rc::new(arguments),
             body: Rc::new(body),
-            env: Rc::new(env),
+            captures,
         }
     }
 
@@ -516,6 +523,16 @@ impl fmt::Display for RcValue {
                 }
                 write!(f, "[{}]", result)
             }
+            RcValue::Tuple(values) => {
+                let mut result = String::new();
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        result.push_str(", ");
+                    }
+                    result.push_str(&format!("{}", value));
+                }
+                write!(f, "({})", result)
+            }
             RcValue::Dict(dict) => {
                 let mut result = String::new();
                 for (i, (key, value)) in dict.iter().enumerate() {
@@ -0,0 +1,494 @@
+use crate::repl_session::{EngineKind, ReplError, ReplSession};
+use crate::token::TokenKind;
+use crate::tokenizer::tokenize;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const PROMPT: &str = ">> ";
+// 括弧が閉じていない間の続きの行
+const CONTINUATION_PROMPT: &str = ".. ";
+const HISTORY_FILE: &str = ".sag_history";
+
+const KEYWORDS: [&str; 22] = [
+    "val", "mut", "fun", "return", "if", "else", "match", "for", "in", "break", "continue",
+    "struct", "impl", "pub", "import", "from", "true", "false", "Some", "None", "Suc", "Fail",
+];
+
+// evals/method_call_node.rs と evals/option_result_method.rs が処理する組み込みメソッド
+const BUILTIN_METHODS: [&str; 41] = [
+    "to_string",
+    "round",
+    "sqrt",
+    "push",
+    "pop",
+    "len",
+    "is_empty",
+    "first",
+    "last",
+    "clear",
+    "contains",
+    "reverse",
+    "get",
+    "insert",
+    "remove",
+    "contains_key",
+    "keys",
+    "values",
+    "update",
+    "entry",
+    "get_or_insert",
+    "to_uppercase",
+    "to_lowercase",
+    "trim",
+    "starts_with",
+    "ends_with",
+    "split",
+    "replace",
+    "is_some",
+    "is_none",
+    "is_suc",
+    "is_fail",
+    "unwrap",
+    "expect",
+    "unwrap_or",
+    "unwrap_or_else",
+    "map",
+    "map_err",
+    "and_then",
+    "or_else",
+    "ok_or",
+];
+
+const COMMANDS: [&str; 8] = [
+    ":type", ":ast", ":env", ":load", ":reset", ":time", ":engine", ":help",
+];
+
+const HELP: &str = "\
+:type <expr>           show the inferred type of an expression
+:ast <source>          show the parsed syntax tree
+:env                   list top-level bindings
+:load <file.sag>       evaluate a file into the session
+:reset                 discard all definitions
+:time <expr>           evaluate and show the elapsed time
+:engine [rc|tree|vm]   show or switch the evaluation engine
+:help                  show this message";
+
+// 補完の候補にする、定義済みの名前と構造体のメソッド
+fn completions(session: &ReplSession) -> (Vec<String>, Vec<String>) {
+    let (mut names, mut methods) = session.symbols();
+    names.extend(KEYWORDS.iter().map(|keyword| keyword.to_string()));
+    methods.extend(BUILTIN_METHODS.iter().map(|method| method.to_string()));
+    (names, methods)
+}
+
+/// `:` で始まるメタコマンドを実行し、表示する内容を返す
+fn run_command(session: &mut ReplSession, input: &str) -> Result<String, ReplError> {
+    let input = input.trim();
+    let (command, argument) = match input.split_once(char::is_whitespace) {
+        Some((command, argument)) => (command, argument.trim()),
+        None => (input, ""),
+    };
+    let usage = |usage: &str| Err(ReplError::Message(format!("usage: {}", usage)));
+    match command {
+        ":type" if argument.is_empty() => usage(":type <expr>"),
+        ":type" => session
+            .type_of(argument)
+            .map(|value_type| value_type.to_string())
+            .map_err(ReplError::Message),
+        ":ast" if argument.is_empty() => usage(":ast <source>"),
+        ":ast" => session
+            .parse(argument)
+            .map(|nodes| {
+                nodes
+                    .iter()
+                    .map(|node| format!("{:#?}", node))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .map_err(ReplError::Message),
+        ":env" => {
+            let bindings = session
+                .bindings()
+                .iter()
+                .map(|binding| binding.to_string())
+                .collect::<Vec<_>>();
+            if bindings.is_empty() {
+                Ok("(no bindings)".into())
+            } else {
+                Ok(bindings.join("\n"))
+            }
+        }
+        ":load" if argument.is_empty() => usage(":load <file.sag>"),
+        ":load" => session
+            .load(Path::new(argument))
+            .map(|_| format!("loaded {}", argument)),
+        ":reset" => {
+            session.reset();
+            Ok("session reset".into())
+        }
+        ":time" if argument.is_empty() => usage(":time <expr>"),
+        ":time" => {
+            let start = Instant::now();
+            let value = session.eval(argument)?;
+            Ok(format!("=> {}\ntime: {:?}", value, start.elapsed()))
+        }
+        ":engine" if argument.is_empty() => Ok(format!("engine: {}", session.engine())),
+        ":engine" => {
+            let engine = argument.parse::<EngineKind>().map_err(ReplError::Message)?;
+            if session.set_engine(engine) {
+                Ok(format!("switched to the {} engine", engine))
+            } else {
+                Ok(format!(
+                    "switched to the {} engine (definitions were reset)",
+                    engine
+                ))
+            }
+        }
+        ":help" => Ok(HELP.into()),
+        _ => Err(ReplError::Message(format!(
+            "unknown command: {} (type :help for a list)",
+            command
+        ))),
+    }
+}
+
+#[derive(Default)]
+struct ReplHelper {
+    names: Vec<String>,
+    methods: Vec<String>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        // `.` の後ろはメソッド名、行頭の `:` の後ろはメタコマンドを補完する
+        let commands = COMMANDS.map(|command| command.to_string());
+        let (start, candidates) = if line[..start].ends_with('.') {
+            (start, &self.methods[..])
+        } else if start == 1 && line.starts_with(':') {
+            (0, &commands[..])
+        } else {
+            (start, &self.names[..])
+        };
+        let word = &line[start..pos];
+        let mut matches = candidates
+            .iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .cloned()
+            .collect::<Vec<_>>();
+        matches.sort();
+        matches.dedup();
+        Ok((start, matches))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Validator for ReplHelper {}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight(line))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        true
+    }
+}
+
+impl Helper for ReplHelper {}
+
+const STRING_COLOR: &str = "\x1b[32m";
+const COMMENT_COLOR: &str = "\x1b[90m";
+
+fn color(kind: &TokenKind, is_call: bool) -> Option<&'static str> {
+    match kind {
+        TokenKind::Immutable
+        | TokenKind::Mutable
+        | TokenKind::Function
+        | TokenKind::Return
+        | TokenKind::Break
+        | TokenKind::Continue
+        | TokenKind::Match
+        | TokenKind::If
+        | TokenKind::Else
+        | TokenKind::Struct
+        | TokenKind::Pub
+        | TokenKind::Impl
+        | TokenKind::For
+        | TokenKind::In
+        | TokenKind::Import
+        | TokenKind::From => Some("\x1b[35m"),
+        TokenKind::String(_) | TokenKind::UnterminatedString(_) => Some(STRING_COLOR),
+        TokenKind::Number(_) | TokenKind::Bool(_) | TokenKind::True | TokenKind::False => {
+            Some("\x1b[33m")
+        }
+        TokenKind::Some | TokenKind::None | TokenKind::Success | TokenKind::Failure => {
+            Some("\x1b[36m")
+        }
+        TokenKind::CommentLine(_) | TokenKind::CommentBlock(_) => Some(COMMENT_COLOR),
+        TokenKind::Identifier(_) if is_call => Some("\x1b[34m"),
+        _ => None,
+    }
+}
+
+/// 1 行に色を付ける。tokenize の位置情報は行内の列とずれることがあるので、
+/// 語の切り出しはここで行い、語の種類だけを tokenize で判定する
+fn highlight(line: &str) -> String {
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut highlighted = String::new();
+    let mut pos = 0;
+    let push = |highlighted: &mut String, text: String, color: Option<&str>| match color {
+        Some(color) => {
+            highlighted.push_str(color);
+            highlighted.push_str(&text);
+            highlighted.push_str("\x1b[0m");
+        }
+        None => highlighted.push_str(&text),
+    };
+    while pos < chars.len() {
+        let start = pos;
+        let c = chars[pos];
+        if c == '/' && chars.get(pos + 1) == Some(&'/') {
+            pos = chars.len();
+        } else if c == '/' && chars.get(pos + 1) == Some(&'*') {
+            pos += 2;
+            while pos < chars.len() && !(chars[pos] == '*' && chars.get(pos + 1) == Some(&'/')) {
+                pos += 1;
+            }
+            pos = (pos + 2).min(chars.len());
+        } else if c == '"' {
+            pos += 1;
+            while pos < chars.len() && chars[pos] != '"' {
+                pos += 1;
+            }
+            pos = (pos + 1).min(chars.len());
+        } else if c.is_alphanumeric() || c == '_' {
+            while pos < chars.len()
+                && (chars[pos].is_alphanumeric()
+                    || chars[pos] == '_'
+                    || (c.is_ascii_digit() && chars[pos] == '.'))
+            {
+                pos += 1;
+            }
+        } else {
+            pos += 1;
+        }
+        let text = chars[start..pos].iter().collect::<String>();
+        let color = if text.starts_with("//") || text.starts_with("/*") {
+            Some(COMMENT_COLOR)
+        } else if text.starts_with('"') {
+            Some(STRING_COLOR)
+        } else if c.is_alphanumeric() || c == '_' {
+            let next = chars[pos..].iter().find(|c| !c.is_whitespace());
+            // キーワードは後ろの空白まで見て判定されるので、空白を付けて渡す
+            tokenize(&format!("{} ", text))
+                .first()
+                .and_then(|token| color(&token.kind, next == Some(&'(')))
+        } else {
+            None
+        };
+        push(&mut highlighted, text, color);
+    }
+    highlighted
+}
+
+/// `{`、`(`、`[` や文字列が閉じていなければ続きの行を読む
+fn is_incomplete(source: &str) -> bool {
+    let tokens = tokenize(&source.to_string());
+    // 閉じていない文字列は次の行に続く
+    if tokens
+        .iter()
+        .any(|token| matches!(token.kind, TokenKind::UnterminatedString(_)))
+    {
+        return true;
+    }
+    let depth = tokens
+        .iter()
+        .fold(0i32, |depth, token| match token.kind {
+            TokenKind::LBrace | TokenKind::LParen | TokenKind::LBrancket => depth + 1,
+            TokenKind::RBrace | TokenKind::RParen | TokenKind::RBrancket => depth - 1,
+            _ => depth,
+        });
+    depth > 0
+}
+
+// SAG_HISTORY があればそのファイル、なければホームディレクトリの .sag_history
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("SAG_HISTORY")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE)))
+}
+
+/// REPL を終えたら、exit(code) で終了したときはその終了コードを返す
+pub fn run_repl(engine: EngineKind) -> Result<Option<i32>, ReadlineError> {
+    let mut session = ReplSession::new(engine);
+    match engine {
+        EngineKind::Tree => println!("SAG REPL"),
+        _ => println!("SAG REPL ({} engine)", engine),
+    }
+    println!("Type expressions to evaluate them, or :help for commands. Press Ctrl+D to exit.");
+
+    let mut editor = Editor::<ReplHelper, DefaultHistory>::new()?;
+    let history = history_path();
+    if let Some(path) = &history {
+        // 履歴がまだなければ何もしない
+        let _ = editor.load_history(path);
+    }
+    let (names, methods) = completions(&session);
+    editor.set_helper(Some(ReplHelper { names, methods }));
+
+    let exit_code = loop {
+        let mut source = String::new();
+        let mut prompt = PROMPT;
+        let input = loop {
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    source.push_str(&line);
+                    source.push('\n');
+                    if !is_incomplete(&source) {
+                        break Some(source);
+                    }
+                    prompt = CONTINUATION_PROMPT;
+                }
+                // Ctrl+C は入力中の式を捨てる
+                Err(ReadlineError::Interrupted) => break Some(String::new()),
+                Err(ReadlineError::Eof) => break None,
+                Err(e) => return Err(e),
+            }
+        };
+        let Some(input) = input else {
+            break None;
+        };
+        if input.trim().is_empty() {
+            continue;
+        }
+        editor.add_history_entry(input.trim_end())?;
+
+        let result = if input.trim_start().starts_with(':') {
+            run_command(&mut session, &input)
+        } else {
+            session.eval(&input).map(|value| format!("=> {}", value))
+        };
+        match result {
+            Ok(output) => println!("{}", output),
+            Err(ReplError::Message(message)) => eprintln!("{}", message),
+            Err(ReplError::Exit(code)) => break Some(code),
+        }
+        if let Some(helper) = editor.helper_mut() {
+            (helper.names, helper.methods) = completions(&session);
+        }
+    };
+
+    if let Some(path) = &history {
+        editor.save_history(path)?;
+    }
+    Ok(exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::History;
+
+    #[test]
+    fn test_incomplete_input() {
+        assert!(is_incomplete("fun add(a: number, b: number): number {\n"));
+        assert!(is_incomplete("val list = [1,\n"));
+        assert!(!is_incomplete("fun one(): number {\n    return 1\n}\n"));
+        assert!(!is_incomplete("print(\"{\")\n"));
+        assert!(is_incomplete("val s = \"abc\n"));
+    }
+
+    #[test]
+    fn test_highlight_keeps_text() {
+        let line = "val x = add(1, \"a\") // note";
+        let highlighted = highlight(line);
+        assert!(highlighted.contains("\x1b[35mval\x1b[0m"));
+        assert!(highlighted.contains("\x1b[34madd\x1b[0m"));
+        assert!(highlighted.contains("\x1b[32m\"a\"\x1b[0m"));
+        let plain = highlighted
+            .split("\x1b[")
+            .map(|part| part.split_once('m').map_or(part, |(_, rest)| rest))
+            .collect::<String>();
+        assert_eq!(plain, line);
+    }
+
+    #[test]
+    fn test_complete_names_and_methods() {
+        let mut session = ReplSession::new(EngineKind::Tree);
+        session
+            .eval("struct Point {\n    x: number\n}\nval counter = 1\n")
+            .unwrap();
+        let (names, methods) = completions(&session);
+        let helper = ReplHelper { names, methods };
+        let history = DefaultHistory::new();
+        let ctx = Context::new(&history);
+        let (start, candidates) = helper.complete("print(cou", 9, &ctx).unwrap();
+        assert_eq!((start, candidates), (6, vec!["counter".to_string()]));
+        let (_, candidates) = helper.complete("Po", 2, &ctx).unwrap();
+        assert_eq!(candidates, vec!["Point".to_string()]);
+        let (start, candidates) = helper.complete("name.to_up", 10, &ctx).unwrap();
+        assert_eq!((start, candidates), (5, vec!["to_uppercase".to_string()]));
+        assert_eq!(history.len(), 0);
+    }
+
+    #[test]
+    fn test_meta_commands() {
+        let mut session = ReplSession::new(EngineKind::Tree);
+        session.eval("val mut total = 10\n").unwrap();
+        assert_eq!(
+            run_command(&mut session, ":type total * 2\n"),
+            Ok("number".to_string())
+        );
+        assert_eq!(
+            run_command(&mut session, ":env"),
+            Ok("val mut total: number = 10".to_string())
+        );
+        assert!(run_command(&mut session, ":ast total + 1")
+            .unwrap()
+            .contains("BinaryOp"));
+        assert!(run_command(&mut session, ":time total + 1")
+            .unwrap()
+            .starts_with("=> 11\ntime: "));
+        assert!(run_command(&mut session, ":type").is_err());
+        assert!(run_command(&mut session, ":load /nonexistent.sag").is_err());
+        assert!(run_command(&mut session, ":engine jit").is_err());
+        assert!(run_command(&mut session, ":nope").is_err());
+        assert_eq!(
+            run_command(&mut session, ":engine rc"),
+            Ok("switched to the rc engine".to_string())
+        );
+        assert_eq!(
+            run_command(&mut session, ":reset"),
+            Ok("session reset".to_string())
+        );
+        assert_eq!(
+            run_command(&mut session, ":env"),
+            Ok("(no bindings)".to_string())
+        );
+        assert_eq!(session.engine(), EngineKind::Rc);
+    }
+}
@@ -0,0 +1,500 @@
+use crate::ast::ASTNode;
+use crate::builtin::{register_builtins, Capabilities};
+use crate::compiler::VmSession;
+use crate::environment::{Env, EnvVariableType, ValueType};
+use crate::evals::eval;
+use crate::evals::runtime_error::RuntimeError;
+use crate::parsers::parse_error::ParseError;
+use crate::parsers::Parser;
+use crate::rc_builtins::register_rc_builtins;
+use crate::rc_env::RcEnv;
+use crate::rc_evals::rc_eval;
+use crate::sandbox::Limits;
+use crate::tokenizer::tokenize;
+use crate::value::Value;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// REPL で入力を評価するエンジン
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EngineKind {
+    Tree,
+    Rc,
+    Vm,
+}
+
+impl FromStr for EngineKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "tree" => Ok(EngineKind::Tree),
+            "rc" => Ok(EngineKind::Rc),
+            "vm" => Ok(EngineKind::Vm),
+            _ => Err(format!(
+                "unknown engine: {} (expected rc, tree or vm)",
+                name
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EngineKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineKind::Tree => write!(f, "tree"),
+            EngineKind::Rc => write!(f, "rc"),
+            EngineKind::Vm => write!(f, "vm"),
+        }
+    }
+}
+
+pub(crate) enum SessionEnv {
+    Tree(Box<Env>),
+    Rc(RcEnv),
+    Vm(Box<VmSession>),
+}
+
+/// 評価の失敗。REPL は表示用の文字列にし、埋め込み API は位置を含めて返す
+#[derive(Debug, Clone)]
+pub enum EvalError {
+    Parse(ParseError),
+    Runtime(RuntimeError),
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplError {
+    // 表示するエラーメッセージ
+    Message(String),
+    // exit(code) が呼ばれた
+    Exit(i32),
+}
+
+/// `:env` で表示するトップレベルの変数
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub name: String,
+    pub variable_type: EnvVariableType,
+    pub value_type: ValueType,
+    pub value: Option<Value>,
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keyword = match self.variable_type {
+            EnvVariableType::Immutable => "val",
+            EnvVariableType::Mutable => "val mut",
+        };
+        write!(f, "{} {}: {}", keyword, self.name, self.value_type)?;
+        match &self.value {
+            Some(value) => write!(f, " = {}", value),
+            None => Ok(()),
+        }
+    }
+}
+
+/// REPL の入力をまたいで、パーサーの記号表と評価器の環境を同じ状態に保つ。
+/// 入力は文ごとに読んで評価し、失敗した文はパーサー側にも残さない
+pub struct ReplSession {
+    pub(crate) parser: Parser,
+    pub(crate) env: SessionEnv,
+    limits: Limits,
+    capabilities: Capabilities,
+}
+
+impl ReplSession {
+    pub fn new(engine: EngineKind) -> Self {
+        ReplSession::with_capabilities(engine, Capabilities::all())
+    }
+
+    /// 許した機能の builtin だけを使えるセッションを作る
+    pub fn with_capabilities(engine: EngineKind, capabilities: Capabilities) -> Self {
+        let (env, builtins) = match engine {
+            EngineKind::Tree => {
+                let mut env = Env::new();
+                env.set_capabilities(capabilities);
+                let builtins = register_builtins(&mut env);
+                (SessionEnv::Tree(Box::new(env)), builtins)
+            }
+            EngineKind::Rc => {
+                let mut env = RcEnv::new();
+                env.set_capabilities(capabilities);
+                let builtins = register_rc_builtins(&mut env);
+                (SessionEnv::Rc(env), builtins)
+            }
+            EngineKind::Vm => {
+                let mut env = Env::new();
+                env.set_capabilities(capabilities);
+                let builtins = register_builtins(&mut env);
+                let mut vm = VmSession::new();
+                vm.set_capabilities(capabilities);
+                (SessionEnv::Vm(Box::new(vm)), builtins)
+            }
+        };
+        ReplSession {
+            parser: Parser::new(vec![], builtins),
+            env,
+            limits: Limits::default(),
+            capabilities,
+        }
+    }
+
+    /// 実行制限を変える。エンジンを切り替えたりリセットしたりしても引き継ぐ
+    pub fn set_limits(&mut self, limits: Limits) {
+        match &mut self.env {
+            SessionEnv::Tree(env) => env.set_limits(limits.clone()),
+            SessionEnv::Rc(env) => env.set_limits(limits.clone()),
+            SessionEnv::Vm(vm) => vm.set_limits(limits.clone()),
+        }
+        self.limits = limits;
+    }
+
+    /// ステップ数と経過時間を入力ごとに数え直す
+    pub(crate) fn restart_limits(&self) {
+        match &self.env {
+            SessionEnv::Tree(env) => env.sandbox().restart(),
+            SessionEnv::Rc(env) => env.sandbox().restart(),
+            SessionEnv::Vm(vm) => vm.sandbox().restart(),
+        }
+    }
+
+    pub fn engine(&self) -> EngineKind {
+        match self.env {
+            SessionEnv::Tree(_) => EngineKind::Tree,
+            SessionEnv::Rc(_) => EngineKind::Rc,
+            SessionEnv::Vm(_) => EngineKind::Vm,
+        }
+    }
+
+    /// エンジンを切り替える。tree と rc の間では定義を引き継ぎ、vm との間では引き継げないので
+    /// セッションを作り直す。定義を引き継いだら true
+    #[allow(dead_code)]
+    pub fn set_engine(&mut self, engine: EngineKind) -> bool {
+        if engine == self.engine() {
+            return true;
+        }
+        let env = match &self.env {
+            SessionEnv::Tree(env) if engine == EngineKind::Rc => {
+                let mut rc_env = RcEnv::from_env(env);
+                register_rc_builtins(&mut rc_env);
+                SessionEnv::Rc(rc_env)
+            }
+            SessionEnv::Rc(env) if engine == EngineKind::Tree => {
+                SessionEnv::Tree(Box::new(env.to_env()))
+            }
+            _ => {
+                let limits = self.limits.clone();
+                *self = ReplSession::with_capabilities(engine, self.capabilities);
+                self.set_limits(limits);
+                return false;
+            }
+        };
+        self.env = env;
+        true
+    }
+
+    /// すべての定義を捨てる
+    pub fn reset(&mut self) {
+        let limits = self.limits.clone();
+        *self = ReplSession::with_capabilities(self.engine(), self.capabilities);
+        self.set_limits(limits);
+    }
+
+    /// 入力全体を評価し、最後の文の値を返す
+    #[allow(dead_code)]
+    pub fn eval(&mut self, source: &str) -> Result<Value, ReplError> {
+        self.run(source).map_err(|error| match error {
+            EvalError::Parse(e) => ReplError::Message(e.message_with_source(source)),
+            EvalError::Runtime(RuntimeError {
+                exit_code: Some(code),
+                ..
+            }) => ReplError::Exit(code),
+            EvalError::Runtime(e) => ReplError::Message(e.message_with_source(source)),
+        })
+    }
+
+    /// eval と同じだが、失敗を位置つきのエラーのまま返す
+    pub fn run(&mut self, source: &str) -> Result<Value, EvalError> {
+        self.parser.set_input(tokenize(&source.to_string()));
+        self.restart_limits();
+        let mut last = Value::Void;
+        loop {
+            let snapshot = self.parser.clone();
+            let node = match self.parser.parse_next() {
+                None => break,
+                Some(Ok(node)) => node,
+                Some(Err(e)) => {
+                    self.parser = snapshot;
+                    return Err(EvalError::Parse(e));
+                }
+            };
+            // 同じ名前の構造体は定義し直せるようにする
+            if let ASTNode::Struct { name, .. } = &node {
+                match &mut self.env {
+                    SessionEnv::Tree(env) => env.remove_struct(name),
+                    SessionEnv::Rc(env) => env.remove_struct(name),
+                    SessionEnv::Vm(_) => (),
+                }
+            }
+            last = match self.eval_node(node) {
+                Ok(value) => value,
+                Err(e) if e.exit_code.is_some() => return Err(EvalError::Runtime(e)),
+                Err(e) => {
+                    // 評価できなかった定義はパーサーからも取り消す
+                    self.parser = snapshot;
+                    return Err(EvalError::Runtime(e));
+                }
+            };
+        }
+        Ok(last)
+    }
+
+    pub(crate) fn eval_node(&mut self, node: ASTNode) -> Result<Value, RuntimeError> {
+        match &mut self.env {
+            SessionEnv::Tree(env) => eval(node, env),
+            SessionEnv::Rc(env) => rc_eval(node, env).map(|value| value.to_value()),
+            SessionEnv::Vm(vm) => vm.eval(&node).map_err(|message| {
+                match (vm.exit_code(), vm.limit()) {
+                    (Some(code), _) => RuntimeError::exit(code, 0, 0),
+                    (None, Some(limit)) => RuntimeError::limit(limit, 0, 0),
+                    (None, None) => RuntimeError::new(&message, 0, 0),
+                }
+            }),
+        }
+    }
+
+    /// import をこのファイルからの相対パスで解決する
+    pub fn set_file_path(&mut self, path: PathBuf) {
+        self.parser.set_file_path(path.clone());
+        match &mut self.env {
+            SessionEnv::Tree(env) => env.set_file_path(path),
+            SessionEnv::Rc(env) => env.set_file_path(path),
+            SessionEnv::Vm(_) => (),
+        }
+    }
+
+    /// ファイルを読み込んでセッションで評価する
+    #[allow(dead_code)]
+    pub fn load(&mut self, path: &Path) -> Result<Value, ReplError> {
+        let source = fs::read_to_string(path).map_err(|e| {
+            ReplError::Message(format!("Error: cannot read {}: {}", path.display(), e))
+        })?;
+        self.set_file_path(path.to_path_buf());
+        self.eval(&source)
+    }
+
+    /// 評価せずに構文木にする。セッションの定義は変わらない
+    #[allow(dead_code)]
+    pub fn parse(&self, source: &str) -> Result<Vec<ASTNode>, String> {
+        let mut parser = self.parser.clone();
+        parser.set_input(tokenize(&source.to_string()));
+        let mut nodes = vec![];
+        while let Some(result) = parser.parse_next() {
+            nodes.push(result.map_err(|e| e.message_with_source(source))?);
+        }
+        Ok(nodes)
+    }
+
+    /// 式を評価せずに、パーサーが推論する型を返す
+    #[allow(dead_code)]
+    pub fn type_of(&self, source: &str) -> Result<ValueType, String> {
+        let mut parser = self.parser.clone();
+        parser.set_input(tokenize(&source.to_string()));
+        let node = match parser.parse_next() {
+            Some(result) => result.map_err(|e| e.message_with_source(source))?,
+            None => return Err("expected an expression".into()),
+        };
+        parser.infer_type(&node)
+    }
+
+    /// トップレベルの変数を名前順に返す
+    pub fn bindings(&self) -> Vec<Binding> {
+        let mut bindings = self
+            .parser
+            .get_variables()
+            .iter()
+            .filter(|((scope, _), _)| scope == "global")
+            .map(|((_, name), (value_type, variable_type))| Binding {
+                name: name.clone(),
+                variable_type: variable_type.clone(),
+                value_type: value_type.clone(),
+                value: self.value(name),
+            })
+            .collect::<Vec<_>>();
+        bindings.sort_by(|a, b| a.name.cmp(&b.name));
+        bindings
+    }
+
+    fn value(&self, name: &String) -> Option<Value> {
+        match &self.env {
+            SessionEnv::Tree(env) => env.get(name, None).map(|info| info.value),
+            SessionEnv::Rc(env) => env.get(name, None).map(|info| info.value.to_value()),
+            SessionEnv::Vm(vm) => vm.globals().get(name).cloned(),
+        }
+    }
+
+    /// 補完の候補にする、定義済みの名前と構造体のメソッド名
+    #[allow(dead_code)]
+    pub fn symbols(&self) -> (Vec<String>, Vec<String>) {
+        let names = self
+            .parser
+            .get_variables()
+            .keys()
+            .chain(self.parser.get_functions().keys())
+            .filter(|(scope, _)| scope == "global")
+            .map(|(_, name)| name.clone())
+            .chain(
+                self.parser
+                    .get_struct_types()
+                    .filter_map(|value_type| match value_type {
+                        ValueType::Struct { name, .. } => Some(name.clone()),
+                        _ => None,
+                    }),
+            )
+            .chain(self.parser.get_namespaces().iter().cloned())
+            .collect();
+        let methods = self
+            .parser
+            .get_struct_types()
+            .filter_map(|value_type| match value_type {
+                ValueType::Struct { methods, .. } => Some(methods.keys().cloned()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        (names, methods)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_all(session: &mut ReplSession, inputs: &[&str]) -> Result<Value, ReplError> {
+        let mut last = Value::Void;
+        for input in inputs {
+            last = session.eval(input)?;
+        }
+        Ok(last)
+    }
+
+    #[test]
+    fn test_definitions_persist_across_inputs() {
+        for engine in [EngineKind::Tree, EngineKind::Rc] {
+            let mut session = ReplSession::new(engine);
+            let value = eval_all(
+                &mut session,
+                &[
+                    "struct Point {\n    x: number,\n    y: number\n}\n",
+                    "fun sum(a: number, b: number): number {\n    return a + b\n}\n",
+                    "val p = Point { x: 1, y: 2 }\n",
+                    "sum(p.x, p.y)\n",
+                ],
+            );
+            assert_eq!(value, Ok(Value::Number(3.into())), "engine: {}", engine);
+        }
+    }
+
+    #[test]
+    fn test_redefinition() {
+        for engine in [EngineKind::Tree, EngineKind::Rc] {
+            let mut session = ReplSession::new(engine);
+            let value = eval_all(
+                &mut session,
+                &[
+                    "val x = 1\n",
+                    "val x = \"one\"\n",
+                    "fun f(): number {\n    return 1\n}\n",
+                    "fun f(): number {\n    return 2\n}\n",
+                    "struct P {\n    a: number\n}\n",
+                    "struct P {\n    a: number,\n    b: number\n}\n",
+                    "val p = P { a: 1, b: f() }\n",
+                    "p.b\n",
+                ],
+            );
+            assert_eq!(value, Ok(Value::Number(2.into())), "engine: {}", engine);
+            assert_eq!(session.eval("x\n"), Ok(Value::String("one".into())));
+        }
+    }
+
+    #[test]
+    fn test_failed_input_is_not_defined() {
+        let mut session = ReplSession::new(EngineKind::Tree);
+        session.eval("val list = [1]\n").unwrap();
+        assert!(session.eval("val x = list[3]\n").is_err());
+        assert!(session.eval("x\n").is_err());
+        assert_eq!(session.eval("val x = 2\nx\n"), Ok(Value::Number(2.into())));
+        assert_eq!(session.eval("exit(3)\n"), Err(ReplError::Exit(3)));
+    }
+
+    #[test]
+    fn test_introspection() {
+        let mut session = ReplSession::new(EngineKind::Tree);
+        session
+            .eval("val mut count = 1\nval names = [\"a\"]\n")
+            .unwrap();
+        assert_eq!(
+            session.type_of("count + 1").map(|t| t.to_string()),
+            Ok("number".to_string())
+        );
+        assert_eq!(
+            session.type_of("names").map(|t| t.to_string()),
+            Ok("List<string>".to_string())
+        );
+        // 解析だけでは定義されない
+        assert!(session.parse("val other = 1").is_ok());
+        let bindings = session
+            .bindings()
+            .iter()
+            .map(|binding| binding.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bindings,
+            vec![
+                "val mut count: number = 1".to_string(),
+                "val names: List<string> = [a]".to_string()
+            ]
+        );
+        session.reset();
+        assert!(session.bindings().is_empty());
+    }
+
+    #[test]
+    fn test_switch_engine() {
+        let mut session = ReplSession::new(EngineKind::Tree);
+        session.eval("val x = 2\n").unwrap();
+        assert!(session.set_engine(EngineKind::Rc));
+        assert_eq!(session.eval("x + 3\n"), Ok(Value::Number(5.into())));
+        assert!(!session.set_engine(EngineKind::Vm));
+        assert_eq!(session.engine(), EngineKind::Vm);
+        let value = session
+            .eval("fun double(n: number): number {\n    return n * 2\n}\nval y = double(4)\n");
+        assert_eq!(value, Ok(Value::Number(8.into())));
+        assert_eq!(session.eval("y + 1\n"), Ok(Value::Number(9.into())));
+        assert_eq!(session.eval("exit(2)\n"), Err(ReplError::Exit(2)));
+        assert_eq!("vm".parse::<EngineKind>(), Ok(EngineKind::Vm));
+        assert!("jit".parse::<EngineKind>().is_err());
+    }
+
+    #[test]
+    fn test_assert_fail_does_not_catch_exit() {
+        for engine in [EngineKind::Tree, EngineKind::Rc, EngineKind::Vm] {
+            let mut session = ReplSession::new(engine);
+            assert_eq!(
+                session.eval("assert_fail(\\|| => Err(\"bad\"))\n"),
+                Ok(Value::Void),
+                "engine: {}",
+                engine
+            );
+            assert_eq!(
+                session.eval("assert_fail(\\|| => exit(4))\n"),
+                Err(ReplError::Exit(4)),
+                "engine: {}",
+                engine
+            );
+        }
+    }
+}
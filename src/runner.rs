@@ -0,0 +1,137 @@
+use crate::builtin::register_builtins;
+use crate::environment::Env;
+use crate::evals::evals;
+use crate::evals::function_node::call_main;
+use crate::exit_status::{exit_status, ExitError};
+use crate::parsers::Parser as SagParser;
+use crate::rc_builtins::register_rc_builtins;
+use crate::rc_env::RcEnv;
+use crate::rc_evals::{rc_call_main, rc_evals};
+use crate::sandbox::Limits;
+use crate::tokenizer::tokenize;
+use std::path::PathBuf;
+
+// `sag run` でソースファイルを実行する。戻り値はプロセスの終了コード
+
+pub fn run_file_with_rc(
+    file_path: String,
+    debug: bool,
+    limits: Limits,
+) -> Result<i32, ExitError> {
+    let file = std::fs::read_to_string(&file_path).map_err(|e| ExitError::io(&file_path, e))?;
+
+    let tokens = tokenize(&file);
+    if debug {
+        println!("tokens: {:?}", tokens);
+    }
+
+    // 初期環境を作成
+    let mut rc_env = RcEnv::new();
+    rc_env.set_file_path(PathBuf::from(&file_path));
+    rc_env.set_limits(limits);
+
+    // ビルトイン関数を直接RcEnvに登録
+    let rc_builtins = register_rc_builtins(&mut rc_env);
+
+    // デバッグ用：登録されたビルトイン関数を表示
+    let mut parser = SagParser::new(tokens.to_vec(), rc_builtins.clone());
+    parser.set_file_path(PathBuf::from(&file_path));
+    let ast_nodes = parser
+        .parse_lines()
+        .map_err(|e| ExitError::parse(&e, &file))?;
+
+    if debug {
+        println!("ast: {:?}", ast_nodes);
+    }
+
+    // RcEnvとRcValueを直接使用して評価
+    let result = rc_evals(ast_nodes, &mut rc_env).and_then(|_| rc_call_main(&mut rc_env));
+
+    if debug {
+        println!("env: {:?}", rc_env);
+    }
+
+    exit_status(result.map(|value| value.to_value()), &file)
+}
+
+pub fn run_file(file_path: String, debug: bool, limits: Limits) -> Result<i32, ExitError> {
+    let file = std::fs::read_to_string(&file_path).map_err(|e| ExitError::io(&file_path, e))?;
+
+    let tokens = tokenize(&file);
+    if debug {
+        println!("tokens: {:?}", tokens);
+    }
+    let mut env = Env::new();
+    env.set_file_path(PathBuf::from(&file_path));
+    env.set_limits(limits);
+    let builtins = register_builtins(&mut env);
+    let mut parser = SagParser::new(tokens.to_vec(), builtins.clone());
+    parser.set_file_path(PathBuf::from(&file_path));
+    let ast_nodes = parser
+        .parse_lines()
+        .map_err(|e| ExitError::parse(&e, &file))?;
+    if debug {
+        println!("ast: {:?}", ast_nodes);
+    }
+    let result = evals(ast_nodes, &mut env).and_then(|_| call_main(&mut env));
+    if debug {
+        println!("env: {:?}", env);
+    }
+    exit_status(result, &file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exit_status::{IO_ERROR, PARSE_ERROR, RUNTIME_ERROR, TYPE_ERROR};
+
+    fn run(name: &str, source: &str) -> (Result<i32, ExitError>, Result<i32, ExitError>) {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, source).unwrap();
+        let path = path.display().to_string();
+        (
+            run_file(path.clone(), false, Limits::default()),
+            run_file_with_rc(path, false, Limits::default()),
+        )
+    }
+
+    fn code(result: Result<i32, ExitError>) -> i32 {
+        result.unwrap_or_else(|e| e.code)
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let (result, rc_result) = run("sag_exit.sag", "exit(7)\nprint(\"unreachable\")");
+        assert_eq!((code(result), code(rc_result)), (7, 7));
+
+        let source = "fun main(): number {\n    return 3\n}";
+        let (result, rc_result) = run("sag_exit_main.sag", source);
+        assert_eq!((code(result), code(rc_result)), (3, 3));
+
+        let (result, rc_result) = run("sag_exit_parse.sag", "val = 1");
+        assert_eq!((code(result), code(rc_result)), (PARSE_ERROR, PARSE_ERROR));
+
+        let (result, _) = run("sag_exit_type.sag", "val x: number = \"a\"");
+        assert_eq!(code(result), TYPE_ERROR);
+
+        let (result, rc_result) = run("sag_exit_runtime.sag", "val l = [1]\nl[5]");
+        assert_eq!(code(result), RUNTIME_ERROR);
+        assert_eq!(code(rc_result), RUNTIME_ERROR);
+
+        let missing = std::env::temp_dir().join("sag_exit_missing.sag");
+        let result = run_file(missing.display().to_string(), false, Limits::default());
+        assert_eq!(code(result), IO_ERROR);
+    }
+
+    #[test]
+    fn test_script_args_and_main() {
+        crate::builtin::set_script_args(vec!["a".to_string(), "bc".to_string()]);
+        let source = "fun main(args: List<string>): number {\n    \
+            assert_eq(args, args())\n    \
+            assert(env_var(\"SAG_SURELY_UNSET_VARIABLE\").is_none())\n    \
+            return len(args)\n}";
+        let (result, rc_result) = run("sag_exit_args.sag", source);
+        assert_eq!((result, rc_result), (Ok(2), Ok(2)));
+        crate::builtin::set_script_args(vec![]);
+    }
+}
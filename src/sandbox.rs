@@ -0,0 +1,317 @@
+use crate::value::Value;
+use fraction::{Fraction, ToPrimitive};
+use std::cell::Cell;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// 深い再帰でホストのメモリを使い切らないよう、呼び出しの深さだけは既定で制限する
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// 実行に掛ける上限。None の項目は制限しない
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    /// 評価する式の数。vm では命令の数
+    pub max_steps: Option<u64>,
+    /// 関数、メソッド、ラムダの呼び出しの深さ
+    pub max_call_depth: Option<usize>,
+    /// リストと辞書の要素数
+    pub max_collection_len: Option<usize>,
+    /// 文字列のバイト数
+    pub max_string_len: Option<usize>,
+    /// 評価を始めてからの経過時間。wasm では測れないので無視する
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            ..Limits::unlimited()
+        }
+    }
+}
+
+impl Limits {
+    pub fn unlimited() -> Self {
+        Limits {
+            max_steps: None,
+            max_call_depth: None,
+            max_collection_len: None,
+            max_string_len: None,
+            timeout: None,
+        }
+    }
+}
+
+/// 超えた上限とその値
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Steps(u64),
+    CallDepth(usize),
+    CollectionLen(usize),
+    StringLen(usize),
+    Timeout(Duration),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Steps(max) => write!(f, "step limit exceeded (max_steps = {})", max),
+            Limit::CallDepth(max) => {
+                write!(f, "call depth limit exceeded (max_call_depth = {})", max)
+            }
+            Limit::CollectionLen(max) => write!(
+                f,
+                "collection size limit exceeded (max_collection_len = {})",
+                max
+            ),
+            Limit::StringLen(max) => {
+                write!(f, "string length limit exceeded (max_string_len = {})", max)
+            }
+            Limit::Timeout(timeout) => {
+                write!(f, "time limit exceeded (timeout = {:?})", timeout)
+            }
+        }
+    }
+}
+
+/// 評価中に上限を確かめる。関数呼び出しで複製した環境どうしでも同じものを数える
+#[derive(Debug, Default)]
+pub struct Sandbox {
+    limits: Limits,
+    steps: Cell<u64>,
+    depth: Cell<usize>,
+    deadline: Cell<Option<Instant>>,
+}
+
+impl PartialEq for Sandbox {
+    fn eq(&self, other: &Self) -> bool {
+        self.limits == other.limits
+    }
+}
+
+impl Sandbox {
+    pub fn new(limits: Limits) -> Self {
+        Sandbox {
+            limits,
+            ..Sandbox::default()
+        }
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// ステップ数と経過時間を数え直す。REPL や埋め込み API では入力ごとに呼ぶ
+    pub fn restart(&self) {
+        self.steps.set(0);
+        self.deadline.set(None);
+    }
+
+    /// 式や命令を一つ評価する前に呼ぶ
+    pub fn step(&self) -> Result<(), Limit> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        match self.limits.max_steps {
+            Some(max) if steps > max => return Err(Limit::Steps(max)),
+            _ => (),
+        }
+        match (self.limits.timeout, self.deadline.get()) {
+            _ if cfg!(target_arch = "wasm32") => Ok(()),
+            (Some(timeout), None) => {
+                self.deadline.set(Some(Instant::now() + timeout));
+                Ok(())
+            }
+            // 時刻を読むのは軽くないので、数ステップごとに確かめる
+            (Some(timeout), Some(deadline))
+                if steps.is_multiple_of(64) && Instant::now() > deadline =>
+            {
+                Err(Limit::Timeout(timeout))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 関数本体を一段深く評価する。スタックが足りなくなればヒープに継ぎ足す
+    pub fn call<T>(&self, f: impl FnOnce() -> T) -> Result<T, Limit> {
+        let depth = self.depth.get();
+        match self.limits.max_call_depth {
+            Some(max) if depth >= max => return Err(Limit::CallDepth(max)),
+            _ => (),
+        }
+        self.depth.set(depth + 1);
+        let result = grow_stack(f);
+        self.depth.set(depth);
+        Ok(result)
+    }
+
+    pub fn check_len(&self, len: usize) -> Result<(), Limit> {
+        match self.limits.max_collection_len {
+            Some(max) if len > max => Err(Limit::CollectionLen(max)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_string(&self, len: usize) -> Result<(), Limit> {
+        match self.limits.max_string_len {
+            Some(max) if len > max => Err(Limit::StringLen(max)),
+            _ => Ok(()),
+        }
+    }
+
+    /// range(...) が作るリストの長さを、作る前に確かめる。
+    /// 上限を超える大きさのリストを確保してしまわないように、呼び出す前に使う
+    pub fn check_range(&self, args: &[Fraction]) -> Result<(), Limit> {
+        let bounds: Vec<i128> = args
+            .iter()
+            .map(|n| n.to_i64().unwrap_or(0) as i128)
+            .collect();
+        let (start, end, step) = match bounds.as_slice() {
+            [end] => (0, *end, 1),
+            [start, end] => (*start, *end, 1),
+            [start, end, step] => (*start, *end, *step),
+            _ => return Ok(()),
+        };
+        let (distance, step) = match step {
+            0 => (0, 1),
+            step if step > 0 => ((end - start).max(0), step),
+            step => ((start - end).max(0), -step),
+        };
+        let len = (distance + step - 1) / step;
+        self.check_len(usize::try_from(len).unwrap_or(usize::MAX))
+    }
+
+    /// 文字列の連結の結果の長さを、連結する前に確かめる
+    pub fn check_concat(&self, left: &str, right: &str) -> Result<(), Limit> {
+        self.check_string(left.len().saturating_add(right.len()))
+    }
+
+    /// 評価結果の大きさを確かめる
+    pub fn check_value(&self, value: &Value) -> Result<(), Limit> {
+        match value {
+            Value::List(values) => self.check_len(values.len()),
+            Value::Dict(entries) => self.check_len(entries.len()),
+            Value::String(s) => self.check_string(s.len()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// 1 回の呼び出しで使うスタックより十分大きく残っていなければ、新しいスタックで続ける
+#[cfg(not(target_arch = "wasm32"))]
+fn grow_stack<T>(f: impl FnOnce() -> T) -> T {
+    stacker::maybe_grow(1024 * 1024, 16 * 1024 * 1024, f)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn grow_stack<T>(f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repl_session::{EngineKind, EvalError, ReplSession};
+
+    const ENGINES: [EngineKind; 3] = [EngineKind::Tree, EngineKind::Rc, EngineKind::Vm];
+
+    fn run(engine: EngineKind, limits: Limits, source: &str) -> Option<Limit> {
+        let mut session = ReplSession::new(engine);
+        session.set_limits(limits);
+        match session.run(source) {
+            Err(EvalError::Runtime(e)) => e.limit,
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_runaway_recursion_is_stopped() {
+        let source = "fun f(n: number): number {\n    return f(n + 1)\n}\nf(0)\n";
+        for engine in ENGINES {
+            assert_eq!(
+                run(engine, Limits::default(), source),
+                Some(Limit::CallDepth(DEFAULT_MAX_CALL_DEPTH)),
+                "{}",
+                engine
+            );
+        }
+    }
+
+    #[test]
+    fn test_limits_in_all_engines() {
+        for engine in ENGINES {
+            let limits = Limits {
+                max_steps: Some(100),
+                ..Limits::default()
+            };
+            let source = "for i in range(1000) {\n    i\n}\n";
+            assert_eq!(run(engine, limits, source), Some(Limit::Steps(100)));
+
+            let limits = Limits {
+                max_collection_len: Some(3),
+                ..Limits::default()
+            };
+            assert_eq!(run(engine, limits.clone(), "[1, 2, 3]"), None, "{}", engine);
+            assert_eq!(
+                run(engine, limits, "[1, 2, 3, 4]"),
+                Some(Limit::CollectionLen(3))
+            );
+
+            let limits = Limits {
+                max_string_len: Some(3),
+                ..Limits::default()
+            };
+            assert_eq!(
+                run(engine, limits, "\"ab\" + \"cd\""),
+                Some(Limit::StringLen(3))
+            );
+        }
+    }
+
+    #[test]
+    fn test_huge_range_fails_before_allocating() {
+        let limits = Limits {
+            max_collection_len: Some(100),
+            timeout: Some(Duration::from_millis(100)),
+            ..Limits::default()
+        };
+        for engine in ENGINES {
+            let started = Instant::now();
+            assert_eq!(
+                run(engine, limits.clone(), "val xs = range(0, 50000000)"),
+                Some(Limit::CollectionLen(100)),
+                "{}",
+                engine
+            );
+            assert!(started.elapsed() < Duration::from_secs(1), "{}", engine);
+            assert_eq!(
+                run(engine, limits.clone(), "range(0, 100, 1)"),
+                None,
+                "{}",
+                engine
+            );
+            assert_eq!(
+                run(engine, limits.clone(), "range(200, 0, -1)"),
+                Some(Limit::CollectionLen(100)),
+                "{}",
+                engine
+            );
+        }
+    }
+
+    #[test]
+    fn test_timeout() {
+        let timeout = Duration::from_millis(20);
+        let limits = Limits {
+            timeout: Some(timeout),
+            ..Limits::unlimited()
+        };
+        let source = "for i in range(1000) {\n    for j in range(1000) {\n        j\n    }\n}\n";
+        for engine in ENGINES {
+            assert_eq!(
+                run(engine, limits.clone(), source),
+                Some(Limit::Timeout(timeout))
+            );
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::ast::ASTNode;
+use crate::builtin::register_builtins;
+use crate::environment::Env;
+use crate::evals::{eval, evals};
+use crate::package::manifest::{find_manifest_dir, Manifest};
+use crate::package::{PackageError, PACKAGE_DIR};
+use crate::parsers::Parser;
+use crate::tokenizer::tokenize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[allow(dead_code)]
+pub const TEST_PREFIX: &str = "test_";
+
+/// トップレベルの `fun test_*()` がひとつのテストになる
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    pub file: PathBuf,
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// 失敗したアサーションやエラーの位置
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestFailure {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub case: TestCase,
+    pub failure: Option<TestFailure>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TestReport {
+    pub results: Vec<TestResult>,
+    // 読み込めなかったファイルのエラー
+    pub errors: Vec<String>,
+    pub filtered_out: usize,
+}
+
+#[allow(dead_code)]
+impl TestReport {
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.failure.is_none()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0 && self.errors.is_empty()
+    }
+}
+
+impl fmt::Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            let status = if result.failure.is_some() {
+                "FAILED"
+            } else {
+                "ok"
+            };
+            writeln!(f, "test {} ... {}", result.case.name, status)?;
+        }
+        let failures = self
+            .results
+            .iter()
+            .filter_map(|r| r.failure.as_ref().map(|failure| (&r.case, failure)))
+            .collect::<Vec<_>>();
+        if !failures.is_empty() || !self.errors.is_empty() {
+            writeln!(f, "\nfailures:")?;
+            for (case, failure) in failures {
+                writeln!(
+                    f,
+                    "  {} at {}:{}:{}\n    {}",
+                    case.name,
+                    display_path(&case.file),
+                    failure.line,
+                    failure.column,
+                    failure.message
+                )?;
+            }
+            for error in &self.errors {
+                writeln!(f, "  {}", error)?;
+            }
+        }
+        write!(
+            f,
+            "\ntest result: {}. {} passed; {} failed; {} filtered out",
+            if self.is_success() { "ok" } else { "FAILED" },
+            self.passed(),
+            self.failed(),
+            self.filtered_out
+        )
+    }
+}
+
+/// テストを探すファイル。パスが指定されなければ、プロジェクトの tests/ と entry のディレクトリ、
+/// プロジェクトの外ならカレントディレクトリから探す
+#[allow(dead_code)]
+pub fn test_files(paths: &[PathBuf], current_dir: &Path) -> Result<Vec<PathBuf>, PackageError> {
+    let roots = if !paths.is_empty() {
+        paths.to_vec()
+    } else if let Some(dir) = find_manifest_dir(current_dir) {
+        let manifest = Manifest::load(&dir)?;
+        let mut roots = vec![dir.join("tests")];
+        let entry = dir.join(&manifest.entry);
+        match entry.parent() {
+            Some(entry_dir) if entry_dir != dir => roots.push(entry_dir.to_path_buf()),
+            _ => (),
+        }
+        roots
+    } else {
+        vec![current_dir.to_path_buf()]
+    };
+    let mut files = vec![];
+    for root in roots {
+        if root.is_file() {
+            files.push(root);
+        } else if root.is_dir() {
+            collect_sag_files(&root, &mut files)?;
+        } else if !paths.is_empty() {
+            return Err(PackageError::Io(format!("{} does not exist", root.display())));
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+#[allow(dead_code)]
+pub(crate) fn collect_sag_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), PackageError> {
+    let entries = fs::read_dir(dir).map_err(|e| PackageError::io("read", dir, e))?;
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            if entry.file_name() != PACKAGE_DIR && entry.file_name() != ".git" {
+                collect_sag_files(&path, files)?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == "sag") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// 各テストを新しい環境で実行する。ファイルのトップレベルはテストごとに評価し直すので、
+/// あるテストで変えた値が別のテストに残ることはない
+#[allow(dead_code)]
+pub fn run_tests(files: &[PathBuf], filter: Option<&str>) -> TestReport {
+    let mut report = TestReport::default();
+    for file in files {
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => {
+                report
+                    .errors
+                    .push(format!("cannot read {}: {}", display_path(file), e));
+                continue;
+            }
+        };
+        let (nodes, cases) = match parse_tests(file, &source) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                report.errors.push(e);
+                continue;
+            }
+        };
+        for case in cases {
+            if filter.is_some_and(|filter| !case.name.contains(filter)) {
+                report.filtered_out += 1;
+                continue;
+            }
+            let failure = run_test(&case, nodes.clone());
+            report.results.push(TestResult { case, failure });
+        }
+    }
+    report
+}
+
+#[allow(dead_code)]
+fn parse_tests(file: &Path, source: &str) -> Result<(Vec<ASTNode>, Vec<TestCase>), String> {
+    let tokens = tokenize(&source.to_string());
+    let builtins = register_builtins(&mut Env::new());
+    let mut parser = Parser::new(tokens, builtins);
+    parser.set_file_path(file.to_path_buf());
+    let nodes = parser.parse_lines().map_err(|e| {
+        format!(
+            "{}:{}:{}: parse error: {}",
+            display_path(file),
+            e.line,
+            e.column,
+            e.message
+        )
+    })?;
+    let cases = nodes
+        .iter()
+        .filter_map(|node| match node {
+            ASTNode::Function {
+                name,
+                arguments,
+                line,
+                column,
+                ..
+            } if name.starts_with(TEST_PREFIX) && arguments.is_empty() => Some(TestCase {
+                file: file.to_path_buf(),
+                name: name.clone(),
+                line: *line,
+                column: *column,
+            }),
+            _ => None,
+        })
+        .collect();
+    Ok((nodes, cases))
+}
+
+#[allow(dead_code)]
+fn run_test(case: &TestCase, nodes: Vec<ASTNode>) -> Option<TestFailure> {
+    let mut env = Env::new();
+    env.set_file_path(case.file.clone());
+    register_builtins(&mut env);
+    let call = ASTNode::FunctionCall {
+        name: case.name.clone(),
+        arguments: Box::new(ASTNode::FunctionCallArgs {
+            args: vec![],
+            line: case.line,
+            column: case.column,
+        }),
+        line: case.line,
+        column: case.column,
+    };
+    evals(nodes, &mut env)
+        .and_then(|_| eval(call, &mut env))
+        .err()
+        .map(|e| TestFailure {
+            message: e.message,
+            line: e.line,
+            column: e.column,
+        })
+}
+
+// カレントディレクトリからの相対パスで表示する
+#[allow(dead_code)]
+fn display_path(path: &Path) -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_tests_reports_failures_with_locations() {
+        let root = std::env::temp_dir().join("sag_test_runner");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("tests")).unwrap();
+        fs::write(
+            root.join("sag.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("tests/math.sag"),
+            "val mut count = 0\n\
+             fun test_add() {\n  count = count + 1\n  assert_eq(1 + 1, 2)\n  assert_eq(count, 1)\n}\n\
+             fun test_isolated() {\n  count = count + 1\n  assert_eq(count, 1, \"count\")\n}\n\
+             fun test_wrong() {\n  assert(1 > 2, \"bigger\")\n}\n\
+             fun test_fails() {\n  val list = [1]\n  assert_fail(\\|| => list[3])\n}\n\
+             fun helper() {\n  assert(false)\n}\n",
+        )
+        .unwrap();
+        fs::write(root.join("tests/broken.sag"), "val = 1").unwrap();
+
+        let files = test_files(&[], &root.join("tests")).unwrap();
+        assert_eq!(
+            files,
+            vec![root.join("tests/broken.sag"), root.join("tests/math.sag")]
+        );
+
+        let report = run_tests(&files, None);
+        assert_eq!(report.passed(), 3);
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.errors.len(), 1);
+        assert!(!report.is_success());
+        let failure = report.results[2].failure.clone().unwrap();
+        assert_eq!(report.results[2].case.name, "test_wrong");
+        assert_eq!(failure.message, "assertion failed: bigger");
+        assert_eq!(failure.line, 12);
+
+        let report = run_tests(&files[1..], Some("add"));
+        assert_eq!(report.passed(), 1);
+        assert_eq!(report.filtered_out, 3);
+        assert!(report.is_success());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
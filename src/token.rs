@@ -12,8 +12,10 @@ pub enum TokenKind {
     Immutable,
     Mutable,
     Colon,
+    DoubleColon,
     Identifier(String),
     String(String),
+    UnterminatedString(String), // 閉じる " がないまま入力が終わった文字列
     Number(Fraction),
     Bool(bool),
     Void,
@@ -57,6 +59,7 @@ pub enum TokenKind {
     Struct,
     Pub,
     Dot,
+    Question,
     Impl,
     CommentBlock(String),
     CommentLine(String),
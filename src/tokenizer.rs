@@ -3,6 +3,8 @@ use fraction::Fraction;
 
 struct Tokenizer {
     tokens: Vec<Token>,
+    // 構文には現れないコメント。フォーマッタやエディタ向けに位置とともに残す
+    comments: Vec<Token>,
     chars: Vec<char>,
     pos: usize,
     line: usize,
@@ -18,6 +20,7 @@ impl Tokenizer {
             line: 1,
             chars: line.chars().collect(),
             tokens: vec![],
+            comments: vec![],
             nesting_count: 0,
         }
     }
@@ -110,6 +113,7 @@ fn get_identifier(tokenizer: &mut Tokenizer) -> String {
             || c == '\\'
             || c == '['
             || c == ']'
+            || c == '?'
             || c == '\t'
         {
             break;
@@ -121,24 +125,33 @@ fn get_identifier(tokenizer: &mut Tokenizer) -> String {
     identifier
 }
 
-fn get_string(tokenizer: &mut Tokenizer) -> String {
+// 文字列と、閉じる " があったかどうか
+fn get_string(tokenizer: &mut Tokenizer) -> (String, bool) {
     let mut str = String::new();
     let mut pos = tokenizer.pos + 1;
-    loop {
+    let terminated = loop {
         let c = tokenizer.get_position_char(pos);
         if c == '"' {
             pos += 1;
             tokenizer.pos = pos;
-            break;
+            break true;
         }
         if c == '\0' {
-            break;
+            // 閉じていない文字列は入力の終わりまで読み、構文エラーにする
+            tokenizer.pos = pos;
+            break false;
         }
         str += &c.to_string();
         pos += 1;
+    };
+    match str.rfind('\n') {
+        Some(last) => {
+            tokenizer.line += str.matches('\n').count();
+            tokenizer.column = str[last + 1..].chars().count() + 2;
+        }
+        None => tokenizer.column += str.chars().count() + 2,
     }
-    tokenizer.column += str.len() + 2;
-    str
+    (str, terminated)
 }
 
 fn is_line_break(c: &char) -> bool {
@@ -149,6 +162,24 @@ fn is_colon(c: &char) -> bool {
     *c == ':'
 }
 
+fn is_double_colon(tokenizer: &mut Tokenizer) -> bool {
+    // `Point::new` のように識別子の直後に続く場合のみ `::` とみなす
+    // (`{::}` などの空の辞書リテラルと区別するため)
+    if !matches!(
+        tokenizer.tokens.last(),
+        Some(Token {
+            kind: TokenKind::Identifier(_),
+            ..
+        })
+    ) {
+        return false;
+    }
+    let next = tokenizer.get_position_char(tokenizer.pos + 2);
+    tokenizer.get_position_char(tokenizer.pos) == ':'
+        && tokenizer.get_position_char(tokenizer.pos + 1) == ':'
+        && (next.is_alphabetic() || next == '_')
+}
+
 fn is_comma(c: &char) -> bool {
     *c == ','
 }
@@ -209,7 +240,7 @@ fn get_line_comment_string(tokenizer: &mut Tokenizer) -> String {
         comment += &c.to_string();
         pos += 1;
     }
-    tokenizer.column += comment.len() + 2;
+    tokenizer.column += comment.chars().count() + 1;
     comment
 }
 
@@ -244,7 +275,14 @@ fn get_comment_string(tokenizer: &mut Tokenizer) -> String {
         comment += &c.to_string();
         pos += 1;
     }
-    tokenizer.column += comment.len() + 6;
+    // 複数行のコメントのあとも行と列がずれないようにする
+    match comment.rfind('\n') {
+        Some(last) => {
+            tokenizer.line += comment.matches('\n').count();
+            tokenizer.column = comment[last + 1..].chars().count() + 4;
+        }
+        None => tokenizer.column += comment.chars().count() + 6,
+    }
     comment
 }
 
@@ -545,6 +583,16 @@ fn is_else(tokenizer: &mut Tokenizer) -> bool {
 }
 
 pub fn tokenize(line: &String) -> Vec<Token> {
+    run(line).tokens
+}
+
+/// tokenize と同じトークンと、ソースに書かれた順のコメント
+pub fn tokenize_with_comments(source: &str) -> (Vec<Token>, Vec<Token>) {
+    let tokenizer = run(&source.to_string());
+    (tokenizer.tokens, tokenizer.comments)
+}
+
+fn run(line: &String) -> Tokenizer {
     let mut tokenizer = Tokenizer::new(&line);
     loop {
         let c = tokenizer.get_position_char(tokenizer.pos);
@@ -566,9 +614,14 @@ pub fn tokenize(line: &String) -> Vec<Token> {
                     column,
                 }),
             }
-            tokenizer.line += 1;
             tokenizer.pos += 1;
-            tokenizer.column = 1;
+            // `;` は文を区切るだけで、行は変わらない
+            if is_semicoron(&c) {
+                tokenizer.column += 1;
+            } else {
+                tokenizer.line += 1;
+                tokenizer.column = 1;
+            }
             continue;
         }
         if c == '\0' {
@@ -597,9 +650,13 @@ pub fn tokenize(line: &String) -> Vec<Token> {
 
         if is_string(&c) {
             let (line, column) = tokenizer.store_position();
-            let str = get_string(&mut tokenizer);
+            let (str, terminated) = get_string(&mut tokenizer);
             tokenizer.tokens.push(Token {
-                kind: TokenKind::String(str),
+                kind: if terminated {
+                    TokenKind::String(str)
+                } else {
+                    TokenKind::UnterminatedString(str)
+                },
                 line,
                 column,
             });
@@ -655,16 +712,26 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         }
 
         if is_line_comment(&mut tokenizer) {
-            let _comment = get_line_comment_string(&mut tokenizer);
-            //tokenizer.tokens.push(Token::CommentLine(comment));
-            tokenizer.pos += 1;
-            tokenizer.column += 1;
+            let (line, column) = tokenizer.store_position();
+            let comment = get_line_comment_string(&mut tokenizer);
+            // get_line_comment_string は 2 つ目の `/` から読む
+            tokenizer.comments.push(Token {
+                kind: TokenKind::CommentLine(comment[1..].to_string()),
+                line,
+                column,
+            });
+            // 行末は次の周回で Eof にする
             continue;
         }
 
         if is_comment_block(&mut tokenizer) {
-            let _comment = get_comment_string(&mut tokenizer);
-            //tokenizer.tokens.push(Token::CommentBlock(comment));
+            let (line, column) = tokenizer.store_position();
+            let comment = get_comment_string(&mut tokenizer);
+            tokenizer.comments.push(Token {
+                kind: TokenKind::CommentBlock(comment),
+                line,
+                column,
+            });
             continue;
         }
 
@@ -839,10 +906,11 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_and(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 5;
+            // 前後の空白ごと読むので、位置は単語の先頭にする
             tokenizer.tokens.push(Token {
                 kind: TokenKind::And,
                 line,
-                column,
+                column: column + 1,
             });
             tokenizer.pos += 5;
             continue;
@@ -850,10 +918,11 @@ pub fn tokenize(line: &String) -> Vec<Token> {
         if is_or(&mut tokenizer) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 4;
+            // 前後の空白ごと読むので、位置は単語の先頭にする
             tokenizer.tokens.push(Token {
                 kind: TokenKind::Or,
                 line,
-                column,
+                column: column + 1,
             });
             tokenizer.pos += 4;
             continue;
@@ -1051,6 +1120,18 @@ pub fn tokenize(line: &String) -> Vec<Token> {
             continue;
         }
 
+        if is_double_colon(&mut tokenizer) {
+            let (line, column) = tokenizer.store_position();
+            tokenizer.column += 2;
+            tokenizer.tokens.push(Token {
+                kind: TokenKind::DoubleColon,
+                line,
+                column,
+            });
+            tokenizer.pos += 2;
+            continue;
+        }
+
         if is_colon(&c) {
             let (line, column) = tokenizer.store_position();
             tokenizer.column += 1;
@@ -1140,6 +1221,11 @@ pub fn tokenize(line: &String) -> Vec<Token> {
                 line,
                 column,
             }),
+            '?' => tokenizer.tokens.push(Token {
+                kind: TokenKind::Question,
+                line,
+                column,
+            }),
             '\\' => tokenizer.tokens.push(Token {
                 kind: TokenKind::BackSlash,
                 line,
@@ -1174,9 +1260,8 @@ pub fn tokenize(line: &String) -> Vec<Token> {
                 column,
             }),
             _ => {
-                let (line, column) = tokenizer.store_position();
                 let value = get_identifier(&mut tokenizer);
-                tokenizer.column += value.len() - 1;
+                tokenizer.column += value.chars().count() - 1;
                 tokenizer.tokens.push(Token {
                     kind: TokenKind::Identifier(value),
                     line,
@@ -1204,7 +1289,65 @@ pub fn tokenize(line: &String) -> Vec<Token> {
             column,
         }),
     }
-    tokenizer.tokens
+    tokenizer
+}
+
+/// トークンと、同じ行でトークンが終わる列(その列は含まない)。
+/// トークンは長さを持たないので、次のトークンかコメントの手前から空白を除いた位置とする
+pub fn token_spans(source: &str) -> Vec<(Token, usize)> {
+    let lines: Vec<Vec<char>> = source.lines().map(|line| line.chars().collect()).collect();
+    let tokens = tokenize(&source.to_string());
+    tokens
+        .iter()
+        .enumerate()
+        .map(|(i, token)| {
+            let empty = vec![];
+            let line = token
+                .line
+                .checked_sub(1)
+                .and_then(|index| lines.get(index))
+                .unwrap_or(&empty);
+            let limit = match tokens.get(i + 1) {
+                Some(next) if next.line == token.line && next.column > token.column => next.column,
+                _ => line.len() + 1,
+            };
+            let end = match token.kind {
+                TokenKind::String(_) => string_end(line, token.column).min(limit),
+                _ => comment_start(line, token.column, limit),
+            };
+            (token.clone(), trim_end(line, token.column, end))
+        })
+        .collect()
+}
+
+// 閉じる引用符の次の列。閉じていなければ行末
+fn string_end(line: &[char], column: usize) -> usize {
+    let mut escaped = false;
+    for (index, c) in line.iter().enumerate().skip(column) {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return index + 2,
+            _ => escaped = false,
+        }
+    }
+    line.len() + 1
+}
+
+// column から limit までに始まるコメントの列。なければ limit
+fn comment_start(line: &[char], column: usize, limit: usize) -> usize {
+    (column..limit)
+        .find(|&c| {
+            let rest = &line[(c - 1).min(line.len())..];
+            rest.starts_with(&['/', '/']) || rest.starts_with(&['`', '`', '`'])
+        })
+        .unwrap_or(limit)
+}
+
+fn trim_end(line: &[char], column: usize, mut end: usize) -> usize {
+    while end > column && line.get(end - 2).is_some_and(|c| c.is_whitespace()) {
+        end -= 1;
+    }
+    end.max(column)
 }
 
 #[cfg(test)]
@@ -2025,4 +2168,78 @@ mod tests {
             assert_eq!(token.kind, result[i]);
         }
     }
+
+    #[test]
+    fn test_double_colon() {
+        let result = vec![
+            TokenKind::Identifier("Point".into()),
+            TokenKind::DoubleColon,
+            TokenKind::Identifier("new".into()),
+            TokenKind::LParen,
+            TokenKind::Number(Fraction::from(1)),
+            TokenKind::Comma,
+            TokenKind::Identifier("Point".into()),
+            TokenKind::DoubleColon,
+            TokenKind::Identifier("ORIGIN".into()),
+            TokenKind::RParen,
+            TokenKind::Eof,
+        ];
+        for (i, token) in tokenize(&"Point::new(1, Point::ORIGIN)".to_string())
+            .into_iter()
+            .enumerate()
+        {
+            assert_eq!(token.kind, result[i]);
+        }
+    }
+
+    #[test]
+    fn test_question() {
+        let result = vec![
+            TokenKind::Identifier("parse".into()),
+            TokenKind::LParen,
+            TokenKind::Identifier("x".into()),
+            TokenKind::RParen,
+            TokenKind::Question,
+            TokenKind::Plus,
+            TokenKind::Identifier("y".into()),
+            TokenKind::Question,
+            TokenKind::Eof,
+        ];
+        for (i, token) in tokenize(&"parse(x)? + y?".to_string())
+            .into_iter()
+            .enumerate()
+        {
+            assert_eq!(token.kind, result[i]);
+        }
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let spans = token_spans("val name = \"sag\"  // hi\nname")
+            .into_iter()
+            .map(|(token, end)| (token.line, token.column, end))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            vec![
+                (1, 1, 4),
+                (1, 5, 9),
+                (1, 10, 11),
+                (1, 12, 17),
+                (1, 24, 24),
+                (2, 1, 5),
+                (2, 5, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let tokens = tokenize(&"val s = \"abc\nprint(s)".to_string());
+        assert_eq!(
+            tokens[3].kind,
+            TokenKind::UnterminatedString("abc\nprint(s)".into())
+        );
+        assert_eq!((tokens[3].line, tokens[3].column), (1, 9));
+    }
 }
@@ -1,6 +1,5 @@
 use crate::ast::ASTNode;
-use crate::environment::Env;
-use crate::environment::{MethodInfo, ValueType};
+use crate::environment::{Captures, MethodInfo, ValueType};
 use fraction::Fraction;
 use std::collections::HashMap;
 use std::fmt;
@@ -14,6 +13,7 @@ pub enum Value {
     Bool(bool),
     Void,
     List(Vec<Value>),
+    Tuple(Vec<Value>),
     Dict(HashMap<String, Value>),
     Function,
     Return(Box<Value>),
@@ -39,7 +39,7 @@ pub enum Value {
     Lambda {
         arguments: Vec<ASTNode>,
         body: Box<ASTNode>,
-        env: Env,
+        captures: Captures,
     },
 }
 
@@ -66,7 +66,7 @@ impl Value {
                 } else {
                     ValueType::ResultType {
                         success: Box::new(ValueType::Void),
-                        failure: Box::new(v.as_ref().unwrap().value_type()),
+                        failure: Box::new(v.as_ref().unwrap_err().value_type()),
                     }
                 }
             }
@@ -127,6 +127,9 @@ impl Value {
                     ValueType::List(Box::new(value_type))
                 }
             }
+            Value::Tuple(values) => {
+                ValueType::Tuple(values.iter().map(|value| value.value_type()).collect())
+            }
             Value::Dict(dict) => {
                 let mut value_type = ValueType::Any;
                 for key in dict.keys() {
@@ -242,6 +245,16 @@ impl fmt::Display for Value {
                 }
                 write!(f, "[{}]", result)
             }
+            Value::Tuple(values) => {
+                let mut result = String::new();
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        result.push_str(", ");
+                    }
+                    result.push_str(&format!("{}", value));
+                }
+                write!(f, "({})", result)
+            }
             Value::Dict(dict) => {
                 let mut result = String::new();
                 for (i, (key, value)) in dict.iter().enumerate() {
@@ -1,50 +1,271 @@
-use crate::builtin::register_builtins;
+use crate::analysis::analyze;
+use crate::builtin::{register_builtins, with_output_sink, OutputSink};
 use crate::environment::Env;
 use crate::evals::evals;
+use crate::formatter;
 use crate::parsers::Parser;
-use crate::tokenizer::tokenize;
+use crate::parsers::parse_error::ParseErrorKind;
+use crate::repl_session::{EngineKind, EvalError, ReplSession};
+use crate::token::TokenKind;
+use crate::tokenizer::{token_spans, tokenize, tokenize_with_comments};
 use crate::value::Value;
+use serde::Serialize;
 use std::cell::RefCell;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 
-thread_local! {
-    pub(crate) static CONSOLE_OUTPUT: RefCell<String> = RefCell::new(String::new());
-}
-
+/// 出力と結果を `__ConsoleOutput__...__Result__...` の形で返す。新しいコードでは evaluate_json を使う
 #[wasm_bindgen]
 pub fn evaluate(input: &str) -> String {
-    CONSOLE_OUTPUT.with(|output| output.borrow_mut().clear());
+    // print の出力は改行でつないで結果と一緒に返す
+    let output = Rc::new(RefCell::new(String::new()));
+    let console = output.clone();
+    let sink: OutputSink = Rc::new(move |line: &str| {
+        let mut console = console.borrow_mut();
+        if !console.is_empty() {
+            console.push('\n');
+        }
+        console.push_str(line);
+    });
+    let result = with_output_sink(sink, || evaluate_source(input));
+    let output = output.borrow();
+    match result {
+        Ok(value) => format!(
+            "__ConsoleOutput__{}__Result__{}",
+            output.trim_end(),
+            value
+        ),
+        Err(error_message) => format!(
+            "__ConsoleOutput__{}__Result__{}",
+            error_message,
+            Value::Void
+        ),
+    }
+}
 
+fn evaluate_source(input: &str) -> Result<Value, String> {
     let tokens = tokenize(&input.to_string());
     let mut env = Env::new();
     let builtins = register_builtins(&mut env);
     let mut parser = Parser::new(tokens, builtins.clone());
-    let ast_nodes = parser.parse_lines();
-    if let Err(ref e) = ast_nodes {
-        let error_message = e.message_with_source(&input);
-        return format!(
-            "__ConsoleOutput__{}__Result__{}",
-            error_message,
-            Value::Void
-        );
+    let ast_nodes = parser
+        .parse_lines()
+        .map_err(|e| e.message_with_source(input))?;
+    let values = evals(ast_nodes, &mut env).map_err(|e| e.message_with_source(input))?;
+    Ok(values.last().cloned().unwrap_or(Value::Void))
+}
+
+/// evaluate_json の戻り値
+#[derive(Debug, Serialize)]
+struct Evaluation {
+    // print した行
+    stdout: Vec<String>,
+    // 最後の文の値と型。エラーなら null
+    value: Option<String>,
+    #[serde(rename = "type")]
+    value_type: Option<String>,
+    errors: Vec<Diagnostic>,
+}
+
+/// 位置つきのエラー。行と列は 1 始まりで、end_column の列は含まない。位置が分からなければ 0
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Diagnostic {
+    // parse, type, runtime, limit, exit のいずれか
+    kind: &'static str,
+    message: String,
+    line: usize,
+    column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+impl Diagnostic {
+    fn new(error: EvalError, source: &str) -> Self {
+        let (kind, message, line, column) = match error {
+            EvalError::Parse(e) => match e.kind {
+                ParseErrorKind::Syntax => ("parse", e.message, e.line, e.column),
+                ParseErrorKind::Type => ("type", e.message, e.line, e.column),
+            },
+            EvalError::Runtime(e) => match (e.exit_code, e.limit) {
+                (Some(code), _) => ("exit", format!("exit({}) called", code), 0, 0),
+                (None, Some(limit)) => ("limit", limit.to_string(), e.line, e.column),
+                (None, None) => ("runtime", e.message, e.line, e.column),
+            },
+        };
+        // エラーの位置から始まるトークンを範囲にする
+        let end_column = token_spans(source)
+            .into_iter()
+            .find(|(token, _)| token.line == line && token.column == column)
+            .map_or(column, |(_, end)| end);
+        Diagnostic {
+            kind,
+            message,
+            line,
+            column,
+            end_line: line,
+            end_column,
+        }
     }
-    let result = evals(ast_nodes.unwrap(), &mut env);
-    if let Err(ref e) = result {
-        let error_message = e.message_with_source(&input);
-        return format!(
-            "__ConsoleOutput__{}__Result__{}",
-            error_message,
-            Value::Void
-        );
+}
+
+/// プレイグラウンドのセッション。定義はセルをまたいで残る
+#[wasm_bindgen]
+pub struct Session {
+    session: ReplSession,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl Session {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Session {
+        Session {
+            session: ReplSession::new(EngineKind::Tree),
+        }
     }
 
-    let output = CONSOLE_OUTPUT.with(|output| output.borrow().clone());
-    let result_str = format!("{}", result.unwrap().last().unwrap_or(&Value::Void));
-    format!(
-        "__ConsoleOutput__{}__Result__{}",
-        output.trim_end(),
-        result_str
-    )
+    /// セルを評価し、evaluate_json と同じ形の JSON を返す
+    pub fn evaluate(&mut self, input: &str) -> String {
+        let stdout = Rc::new(RefCell::new(vec![]));
+        let lines = stdout.clone();
+        let sink: OutputSink = Rc::new(move |line: &str| lines.borrow_mut().push(line.to_string()));
+        let result = with_output_sink(sink, || self.session.run(input));
+        let stdout = stdout.take();
+        let evaluation = match result {
+            Ok(value) => Evaluation {
+                stdout,
+                value: Some(value.to_string()),
+                value_type: Some(value.value_type().to_string()),
+                errors: vec![],
+            },
+            Err(error) => Evaluation {
+                stdout,
+                value: None,
+                value_type: None,
+                errors: vec![Diagnostic::new(error, input)],
+            },
+        };
+        serde_json::to_string(&evaluation).unwrap_or_default()
+    }
+
+    /// 定義をすべて忘れる
+    pub fn reset(&mut self) {
+        self.session.reset();
+    }
+}
+
+/// ソースを評価し、出力、値、型、エラーを JSON で返す。
+/// `{"stdout": [..], "value": "3", "type": "number", "errors": [{"kind", "message", "line", "column", "endLine", "endColumn"}]}`
+#[wasm_bindgen]
+pub fn evaluate_json(input: &str) -> String {
+    Session::new().evaluate(input)
+}
+
+/// 構文の色分けに使うトークン
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Span {
+    // TokenKind の名前。Identifier、Number、CommentLine など
+    kind: String,
+    line: usize,
+    column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+/// ソースをトークンに分け、種類と範囲を JSON の配列で返す。コメントも含み、行の区切りは含まない。
+/// `[{"kind": "Immutable", "line": 1, "column": 1, "endLine": 1, "endColumn": 4}, ..]`
+#[wasm_bindgen]
+pub fn tokens(source: &str) -> String {
+    let (_, comments) = tokenize_with_comments(source);
+    let mut spans: Vec<Span> = token_spans(source)
+        .into_iter()
+        .filter(|(token, _)| token.kind != TokenKind::Eof)
+        .map(|(token, end_column)| Span {
+            kind: kind_name(&token.kind),
+            line: token.line,
+            column: token.column,
+            end_line: token.line,
+            end_column,
+        })
+        .collect();
+    for comment in comments {
+        // 範囲は `//` や ``` を含める
+        let (text, open, close) = match &comment.kind {
+            TokenKind::CommentLine(content) => (content, 2, 0),
+            TokenKind::CommentBlock(content) => (content, 3, 3),
+            _ => continue,
+        };
+        let lines = text.matches('\n').count();
+        let last = text.rsplit('\n').next().unwrap_or_default().chars().count();
+        let end_column = match lines {
+            0 => comment.column + open + last + close,
+            _ => 1 + last + close,
+        };
+        spans.push(Span {
+            kind: kind_name(&comment.kind),
+            line: comment.line,
+            column: comment.column,
+            end_line: comment.line + lines,
+            end_column,
+        });
+    }
+    spans.sort_by_key(|span| (span.line, span.column));
+    serde_json::to_string(&spans).unwrap_or_default()
+}
+
+fn kind_name(kind: &TokenKind) -> String {
+    let name = format!("{:?}", kind);
+    name.split('(').next().unwrap_or_default().to_string()
+}
+
+/// 実行せずに構文と型を検査し、Diagnostic の配列を JSON で返す。最初のエラーで止まる
+#[wasm_bindgen]
+pub fn diagnostics(source: &str) -> String {
+    let errors: Vec<Diagnostic> = analyze(source)
+        .error
+        .map(|e| Diagnostic::new(EvalError::Parse(e), source))
+        .into_iter()
+        .collect();
+    serde_json::to_string(&errors).unwrap_or_default()
+}
+
+/// 位置 (1 始まり) にある名前かリテラルの型。分からなければ undefined
+#[wasm_bindgen]
+pub fn type_at(source: &str, line: usize, column: usize) -> Option<String> {
+    analyze(source)
+        .type_at(line, column)
+        .map(|value_type| value_type.to_string())
+}
+
+/// format_source の戻り値
+#[derive(Debug, Serialize)]
+struct Formatted {
+    // 整形したソース。読めなければ null
+    formatted: Option<String>,
+    errors: Vec<Diagnostic>,
+}
+
+/// ソースを整形し、`{"formatted": "..", "errors": [..]}` を返す
+#[wasm_bindgen]
+pub fn format_source(source: &str) -> String {
+    let result = match formatter::format_source(source) {
+        Ok(formatted) => Formatted {
+            formatted: Some(formatted),
+            errors: vec![],
+        },
+        Err(e) => Formatted {
+            formatted: None,
+            errors: vec![Diagnostic::new(EvalError::Parse(e), source)],
+        },
+    };
+    serde_json::to_string(&result).unwrap_or_default()
 }
 
 #[cfg(test)]
@@ -107,4 +328,103 @@ fun f4(): number {
         let result = evaluate(input);
         assert_eq!(result, "__ConsoleOutput____Result__11/4");
     }
+
+    fn evaluate_value(input: &str) -> serde_json::Value {
+        serde_json::from_str(&evaluate_json(input)).unwrap()
+    }
+
+    #[test]
+    fn test_evaluate_json() {
+        let result = evaluate_value("print(\"__Result__\")\nprint(1, 2)\n1 + 2");
+        assert_eq!(
+            result,
+            serde_json::json!({
+                "stdout": ["__Result__", "1 2"],
+                "value": "3",
+                "type": "number",
+                "errors": [],
+            })
+        );
+
+        let result = evaluate_value("print(1)\nval x = 1\nx + missing");
+        assert_eq!(result["stdout"], serde_json::json!(["1"]));
+        assert_eq!(result["value"], serde_json::Value::Null);
+        assert_eq!(
+            result["errors"],
+            serde_json::json!([{
+                "kind": "runtime",
+                "message": "Variable not found: \"missing\"",
+                "line": 3,
+                "column": 5,
+                "endLine": 3,
+                "endColumn": 12,
+            }])
+        );
+
+        let result = evaluate_value("val = 1");
+        assert_eq!(result["stdout"], serde_json::json!([]));
+        assert_eq!(result["errors"][0]["kind"], "parse");
+        assert_eq!(result["errors"][0]["line"], 1);
+    }
+
+    #[test]
+    fn test_session_keeps_definitions() {
+        let mut session = Session::new();
+        session.evaluate("fun double(n: number): number {\n    return n * 2\n}\n");
+        let result: serde_json::Value =
+            serde_json::from_str(&session.evaluate("double(21)")).unwrap();
+        assert_eq!(result["value"], "42");
+        session.reset();
+        let result: serde_json::Value =
+            serde_json::from_str(&session.evaluate("double(21)")).unwrap();
+        assert_eq!(result["errors"][0]["kind"], "runtime");
+    }
+
+    #[test]
+    fn test_session_failure_value() {
+        let mut session = Session::new();
+        let result: serde_json::Value =
+            serde_json::from_str(&session.evaluate("Fail(\"bad\")")).unwrap();
+        assert_eq!(result["value"], "Fail(bad)");
+        assert_eq!(result["type"], "Result<void, string>");
+        assert_eq!(result["errors"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_editor_exports() {
+        let source = "val x = 1 // one\n```\nnote\n```\nval s: string = x\n";
+        let spans: serde_json::Value = serde_json::from_str(&tokens(source)).unwrap();
+        assert_eq!(
+            spans[0],
+            serde_json::json!({"kind": "Immutable", "line": 1, "column": 1, "endLine": 1, "endColumn": 4})
+        );
+        assert_eq!(
+            spans[4],
+            serde_json::json!({"kind": "CommentLine", "line": 1, "column": 11, "endLine": 1, "endColumn": 17})
+        );
+        assert_eq!(
+            spans[5],
+            serde_json::json!({"kind": "CommentBlock", "line": 2, "column": 1, "endLine": 4, "endColumn": 4})
+        );
+
+        let errors: serde_json::Value = serde_json::from_str(&diagnostics(source)).unwrap();
+        assert_eq!(errors.as_array().unwrap().len(), 1);
+        assert_eq!(errors[0]["kind"], "type");
+        assert_eq!(errors[0]["line"], 5);
+        assert_eq!(diagnostics("val x = 1"), "[]");
+
+        assert_eq!(type_at(source, 1, 5), Some("number".to_string()));
+        assert_eq!(type_at(source, 1, 7), None);
+
+        let result: serde_json::Value =
+            serde_json::from_str(&format_source("val  x=1\nfun f(): number {\nreturn x\n}\n"))
+                .unwrap();
+        assert_eq!(
+            result["formatted"],
+            "val x = 1\nfun f(): number {\n    return x\n}\n"
+        );
+        let result: serde_json::Value = serde_json::from_str(&format_source("val = 1")).unwrap();
+        assert_eq!(result["formatted"], serde_json::Value::Null);
+        assert_eq!(result["errors"][0]["kind"], "parse");
+    }
 }